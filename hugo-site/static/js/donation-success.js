@@ -132,20 +132,19 @@ async function generateAndSignCertificate(paymentIntentId) {
   console.log("Starting generateAndSignCertificate");
   try {
       const delegateCertificateBase64 = localStorage.getItem('delegate_certificate_base64');
+      const metadataBase64 = localStorage.getItem('ghost_key_metadata_base64') || undefined;
       
-    // Generate key pair and blind the public key using WebAssembly
+    // Generate key pair and blind the public key using WebAssembly, errors are thrown with a
+    // stable `code` property such as GK_BASE64_INVALID
     console.log("Generating key pair and blinding public key");
     const seed = crypto.getRandomValues(new Uint8Array(32));
-    const result = wasmModule.wasm_generate_keypair_and_blind(delegateCertificateBase64, seed);
-    
-    if (typeof result === 'string') {
-      throw new Error(result); // This is an error message
-    }
+    const result = wasmModule.wasm_generate_keypair_and_blind(delegateCertificateBase64, seed, metadataBase64);
 
     const publicKey = result.ec_verifying_key;
     const privateKey = result.ec_signing_key;
     const blindingSecret = result.blinding_secret;
-    const blindedPublicKey = result.blinded_signing_key;
+    // Names the delegate certificate the key was blinded for, so the server rejects a stale one
+    const issuanceRequest = result.issuance_request;
     console.log("Key pair generated and public key blinded");
 
     // Now send the blinded public key to the server for signing
@@ -163,7 +162,7 @@ async function generateAndSignCertificate(paymentIntentId) {
         },
         body: JSON.stringify({ 
           payment_intent_id: paymentIntentId, 
-          blinded_ghost_key_base64: blindedPublicKey
+          issuance_request_base64: issuanceRequest
         }),
         credentials: 'same-origin'
       });
@@ -192,18 +191,17 @@ async function generateAndSignCertificate(paymentIntentId) {
       signData.blind_signature_base64,
       blindingSecret,
       publicKey,
-      privateKey
+      privateKey,
+      metadataBase64
     );
 
-    if (ghostkeyCertResult instanceof Error) {
-      throw ghostkeyCertResult;
-    }
-
     console.log("Ghost Key certificate and signing key generated");
     displayCertificate(ghostkeyCertResult.armored_ghost_key_cert, ghostkeyCertResult.armored_ghost_key_signing_key);
+    displayRecoveryPhrase(result.mnemonic);
   } catch (error) {
     console.error("Error in generateAndSignCertificate:", error);
-    showError('Error generating certificate: ' + error.message);
+    const code = error.code ? ` [${error.code}]` : '';
+    showError('Error generating certificate' + code + ': ' + error.message);
   }
 }
 
@@ -269,6 +267,18 @@ function displayCertificate(armoredCertificate, armoredSigningKey) {
   }
 }
 
+// Shows the recovery phrase of the seed the ghost key was derived from
+function displayRecoveryPhrase(mnemonic) {
+  const section = document.getElementById('recoveryPhraseSection');
+  const phrase = document.getElementById('recoveryPhrase');
+  if (!section || !phrase || !mnemonic) {
+    console.error("Recovery phrase elements or phrase not found");
+    return;
+  }
+  phrase.textContent = mnemonic;
+  section.style.display = 'block';
+}
+
 // Verification is now handled by the WebAssembly module
 
 // MessagePack library is loaded globally, no need to require it
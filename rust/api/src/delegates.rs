@@ -1,35 +1,221 @@
 use std::path::PathBuf;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
 
-use blind_rsa_signatures::{BlindedMessage, BlindSignature, Options, SecretKey as RSASigningKey};
-use rand_core::OsRng;
+use blind_rsa_signatures::SecretKey as RSASigningKey;
+use ghostkey_lib::errors::GhostkeyError;
 
 use ghostkey_lib::armorable::*;
-use ghostkey_lib::delegate_certificate::DelegateCertificateV1;
+use ghostkey_lib::delegate_certificate::DelegateCertificate;
+use ghostkey_lib::delegate_key::PublicKey as DelegatePublicKey;
+use ghostkey_lib::encrypted_key::read_private_key;
+use ghostkey_lib::fingerprint::{Fingerprint, Fingerprinted};
+use ghostkey_lib::ghost_key_certificate::GhostkeyMetadata;
+use ghostkey_lib::issuance::BlindIssuer;
+use ghostkey_lib::revocation_list::DelegateRevocationListV1;
+use ghostkey_lib::trust_store::TrustStore;
+use ghostkey_lib::util::month_of;
 
 use crate::handle_sign_cert::CertificateError;
 
-pub(crate) fn get_delegate(amount: u64) -> Result<(DelegateCertificateV1, RSASigningKey), CertificateError> {
+/// File name suffix of the partially blind delegate that signs every amount, binding the amount
+/// into the metadata of each ghost key certificate instead.
+const ANY_AMOUNT: &str = "any";
+
+/// The delegate that signs ghost keys for an amount.
+pub(crate) struct Delegate {
+    pub certificate: DelegateCertificate,
+    pub signing_key: RSASigningKey,
+    /// Set for the partially blind delegate, which only signs ghost keys bound to the
+    /// [`ghost_key_metadata`] of the donation
+    pub binds_metadata: bool,
+}
+
+/// Loads `delegate_certificate_any.pem` if the delegate directory has one, otherwise the
+/// delegate for `amount` from `delegate_certificate_{amount}.pem`. An encrypted signing key is
+/// unlocked with the passphrase in [`PASSPHRASE`](crate::PASSPHRASE).
+pub(crate) fn get_delegate(amount: u64) -> Result<Delegate, CertificateError> {
     let delegate_dir = PathBuf::from(std::env::var("DELEGATE_DIR").map_err(|e| {
         log::error!("DELEGATE_DIR environment variable not set: {}", e);
         CertificateError::KeyError("DELEGATE_DIR environment variable not set".to_string())
     })?);
-    let cert_path = delegate_dir.join(format!("delegate_certificate_{}.pem", amount));
-    let cert = DelegateCertificateV1::from_file(&cert_path)
-        .map_err(|e| CertificateError::KeyError(format!("Unable to read certificate from {}: {}", cert_path.display(), e)))?;
+    let binds_metadata = delegate_dir.join(format!("delegate_certificate_{}.pem", ANY_AMOUNT)).is_file();
+    let suffix = if binds_metadata { ANY_AMOUNT.to_string() } else { amount.to_string() };
+    let cert_path = delegate_dir.join(format!("delegate_certificate_{}.pem", suffix));
+    let cert = DelegateCertificate::from_file(&cert_path).map_err(|e| {
+        log::error!("Unable to read certificate from {}: [{}] {}", cert_path.display(), e.code(), e);
+        CertificateError::GhostkeyError(e)
+    })?;
 
-    let signing_key_path = delegate_dir.join(format!("delegate_signing_key_{}.pem", amount));
-    let signing_key = RSASigningKey::from_file(&signing_key_path)
-        .map_err(|e| CertificateError::KeyError(format!("Unable to read signing key from {}: {}", signing_key_path.display(), e)))?;
-    Ok((cert, signing_key))
+    check_not_revoked(&cert)?;
+    if !binds_metadata {
+        check_amount(&cert, amount)?;
+    }
+
+    let signing_key_path = delegate_dir.join(format!("delegate_signing_key_{}.pem", suffix));
+    let signing_key = ArmoredBundle::from_file(&signing_key_path).and_then(|bundle| {
+        read_private_key::<RSASigningKey>(&bundle, delegate_key_passphrase)
+    }).map_err(|e| {
+        log::error!("Unable to read signing key from {}: [{}] {}", signing_key_path.display(), e.code(), e);
+        CertificateError::GhostkeyError(e)
+    })?;
+
+    let delegate_key_fingerprint = fingerprint("delegate key", cert.payload().delegate_verifying_key())?;
+    let signing_public_key = signing_key.public_key().map_err(|e| {
+        let e = GhostkeyError::RSAError("Failed to get delegate public key".to_string(), e.into());
+        log::error!("Unable to read public key of {}: [{}] {}", signing_key_path.display(), e.code(), e);
+        CertificateError::GhostkeyError(e)
+    })?;
+    let signing_key_fingerprint = fingerprint("signing key", &DelegatePublicKey::from(signing_public_key))?;
+    if signing_key_fingerprint != delegate_key_fingerprint {
+        log::error!(
+            "Signing key {} does not match delegate key {} of the certificate for amount {}",
+            signing_key_fingerprint, delegate_key_fingerprint, amount
+        );
+        return Err(CertificateError::KeyError(
+            "Delegate signing key does not match delegate certificate".to_string(),
+        ));
+    }
+    log::info!(
+        "Loaded delegate certificate {} with delegate key {} for amount {}{}",
+        fingerprint("delegate certificate", &cert)?, delegate_key_fingerprint, amount,
+        if binds_metadata { " (binds metadata)" } else { "" }
+    );
+    Ok(Delegate { certificate: cert, signing_key, binds_metadata })
 }
 
-pub(crate) fn sign_with_delegate_key(blinded_ghostkey: &BlindedMessage, amount_dollars: u64) -> Result<BlindSignature, CertificateError> {
-    let (_, delegate_signing_key) = get_delegate(amount_dollars)?;
+/// Reads the passphrase of an encrypted delegate signing key from the environment, as the CLI
+/// does for scripts.
+fn delegate_key_passphrase() -> Result<String, GhostkeyError> {
+    std::env::var(crate::PASSPHRASE).map_err(|_| {
+        GhostkeyError::InvalidInput(format!(
+            "Delegate signing key is encrypted but {} is not set",
+            crate::PASSPHRASE
+        ))
+    })
+}
 
-    let options = Options::default();
+/// The metadata a ghost key for a donation of `amount` dollars, created at Unix time `created`,
+/// is bound to when signed by a partially blind delegate.
+pub(crate) fn ghost_key_metadata(amount: u64, created: i64) -> GhostkeyMetadata {
+    GhostkeyMetadata {
+        amount,
+        currency: "USD".to_string(),
+        issued: month_of(created.max(0) as u64),
+        campaign: std::env::var(crate::CAMPAIGN).ok(),
+    }
+}
+
+/// Computes a fingerprint for logging, `name` describes what it is for the error message.
+fn fingerprint<T: Fingerprinted>(name: &str, value: &T) -> Result<Fingerprint, CertificateError> {
+    value.fingerprint().map_err(|e| {
+        log::error!("Unable to compute {} fingerprint: [{}] {}", name, e.code(), e);
+        CertificateError::GhostkeyError(e)
+    })
+}
+
+/// Loads the trust store configured with `--trust-store`, or the Freenet master verifying key.
+fn get_trust_store() -> Result<TrustStore, CertificateError> {
+    match std::env::var(crate::TRUST_STORE) {
+        Ok(path) => {
+            let path = PathBuf::from(path);
+            TrustStore::from_file(&path).map_err(|e| {
+                log::error!("Unable to read trust store from {}: [{}] {}", path.display(), e.code(), e);
+                CertificateError::GhostkeyError(e)
+            })
+        }
+        Err(_) => Ok(TrustStore::freenet()),
+    }
+}
 
-    let blind_sig = delegate_signing_key.blind_sign(&mut OsRng, blinded_ghostkey, &options)
-        .map_err(|e| CertificateError::MiscError(format!("Failed to blind sign: {}", e)))?;
+/// How often the revocation list is re-read, so a replaced list is picked up without a restart.
+const REVOCATION_LIST_RELOAD_INTERVAL: Duration = Duration::from_secs(5 * 60);
 
-    Ok(blind_sig)
-}
\ No newline at end of file
+/// The revocation list last read from disk, with the trust store it is verified against.
+struct LoadedRevocationList {
+    revocation_list: DelegateRevocationListV1,
+    trust_store: TrustStore,
+    loaded_at: Instant,
+}
+
+static REVOCATION_LIST_CACHE: Mutex<Option<LoadedRevocationList>> = Mutex::new(None);
+
+/// Reads the revocation list configured with `--revocation-list` and its trust store into the
+/// cache, called at startup so a missing or unreadable list stops the API before it serves.
+pub(crate) fn load_revocation_list() -> Result<(), CertificateError> {
+    let Ok(path) = std::env::var(crate::REVOCATION_LIST) else {
+        return Ok(());
+    };
+    let path = PathBuf::from(path);
+    let revocation_list = DelegateRevocationListV1::from_file(&path).map_err(|e| {
+        log::error!("Unable to read revocation list from {}: [{}] {}", path.display(), e.code(), e);
+        CertificateError::GhostkeyError(e)
+    })?;
+    let trust_store = get_trust_store()?;
+    log::info!(
+        "Loaded revocation list {} valid until {} (Unix time)",
+        path.display(), revocation_list.payload.next_update
+    );
+    *REVOCATION_LIST_CACHE.lock().unwrap_or_else(PoisonError::into_inner) = Some(LoadedRevocationList {
+        revocation_list,
+        trust_store,
+        loaded_at: Instant::now(),
+    });
+    Ok(())
+}
+
+/// Rejects the delegate if a revocation list was configured and the delegate appears in it.
+///
+/// The list is cached and re-read every [`REVOCATION_LIST_RELOAD_INTERVAL`]. If re-reading fails
+/// the cached list is kept, but checking fails closed: once the cached list is past its
+/// `next_update` every delegate is rejected until a current list is in place.
+fn check_not_revoked(cert: &DelegateCertificate) -> Result<(), CertificateError> {
+    if std::env::var(crate::REVOCATION_LIST).is_err() {
+        return Ok(());
+    }
+    let reload = match &*REVOCATION_LIST_CACHE.lock().unwrap_or_else(PoisonError::into_inner) {
+        Some(loaded) => loaded.loaded_at.elapsed() >= REVOCATION_LIST_RELOAD_INTERVAL,
+        None => true,
+    };
+    if reload {
+        if let Err(e) = load_revocation_list() {
+            log::error!("Unable to reload revocation list, keeping the cached one: {}", e);
+        }
+    }
+    let cache = REVOCATION_LIST_CACHE.lock().unwrap_or_else(PoisonError::into_inner);
+    let loaded = cache.as_ref().ok_or_else(|| {
+        CertificateError::KeyError("Revocation list is configured but could not be read".to_string())
+    })?;
+    loaded.revocation_list.verify_delegate_certificate(cert, &loaded.trust_store)
+        .map_err(|e| {
+            log::error!("Delegate certificate rejected: [{}] {}", e.code(), e);
+            CertificateError::GhostkeyError(e)
+        })?;
+    Ok(())
+}
+
+/// Rejects a delegate with typed details whose amount does not match the file it was loaded
+/// from, V1 delegates only carry an info string and are accepted as before.
+fn check_amount(cert: &DelegateCertificate, amount: u64) -> Result<(), CertificateError> {
+    if let Some(details) = cert.payload().delegate_info().details {
+        if details.amount != amount {
+            log::error!("Delegate certificate for amount {} is for {} {}", amount, details.amount, details.currency);
+            return Err(CertificateError::KeyError(format!(
+                "Delegate certificate amount {} does not match requested amount {}",
+                details.amount, amount
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Loads the delegate for `amount` as the issuer that blind signs ghost keys, and whether it
+/// binds metadata.
+pub(crate) fn get_issuer(amount: u64) -> Result<(BlindIssuer, bool), CertificateError> {
+    let delegate = get_delegate(amount)?;
+    let issuer = BlindIssuer::new(delegate.certificate, delegate.signing_key).map_err(|e| {
+        log::error!("Unable to create issuer for amount {}: [{}] {}", amount, e.code(), e);
+        CertificateError::GhostkeyError(e)
+    })?;
+    Ok((issuer, delegate.binds_metadata))
+}
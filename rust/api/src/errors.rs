@@ -30,7 +30,24 @@ impl std::fmt::Display for CertificateError {
     }
 }
 
-impl StdError for CertificateError {}
+impl CertificateError {
+    /// Stable error code of the underlying ghost key error, see [`GhostkeyError::code`].
+    pub fn code(&self) -> Option<&'static str> {
+        match self {
+            CertificateError::GhostkeyError(e) => Some(e.code()),
+            _ => None,
+        }
+    }
+}
+
+impl StdError for CertificateError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            CertificateError::GhostkeyError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
 
 impl From<stripe::StripeError> for CertificateError {
     fn from(error: stripe::StripeError) -> Self {
@@ -2,23 +2,41 @@ use std::collections::HashMap;
 use std::str::FromStr;
 
 use blind_rsa_signatures::BlindedMessage;
+use rand_core::OsRng;
 use serde::{Deserialize, Serialize};
 use stripe::{Client, PaymentIntent, PaymentIntentStatus};
 
 use ghostkey_lib::armorable::Armorable;
+use ghostkey_lib::errors::GhostkeyError;
+use ghostkey_lib::issuance::IssuanceRequest;
 
-use crate::delegates::sign_with_delegate_key;
+use crate::delegates::{get_issuer, ghost_key_metadata};
 pub use crate::errors::CertificateError;
 
+/// Either `issuance_request_base64` or, from pages that predate issuance requests,
+/// `blinded_ghost_key_base64` must be given.
 #[derive(Debug, Deserialize)]
 pub struct SignCertificateRequest {
     payment_intent_id: String,
-    blinded_ghost_key_base64: String,
+    #[serde(default)]
+    blinded_ghost_key_base64: Option<String>,
+    #[serde(default)]
+    issuance_request_base64: Option<String>,
+}
+
+/// The blinded ghost key to sign, decoded before the payment intent is marked as used so that a
+/// malformed request does not use up the payment.
+enum BlindedGhostKey {
+    Request(IssuanceRequest),
+    Legacy(BlindedMessage),
 }
 
 #[derive(Debug, Serialize)]
 pub struct SignCertificateResponse {
     pub blind_signature_base64: String,
+    /// Set when the request was an `issuance_request_base64`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub issuance_response_base64: Option<String>,
     pub delegate_certificate_base64: String,
     pub amount: u64,
 }
@@ -67,6 +85,15 @@ pub async fn sign_certificate(request: SignCertificateRequest) -> Result<SignCer
         return Err(CertificateError::CertificateAlreadySigned);
     }
 
+    let blinded_ghost_key = decode_blinded_ghost_key(&request)?;
+
+    // Check the delegate and the metadata before marking the payment intent, so a ghost key
+    // blinded with the wrong metadata does not use up the payment
+    let amount_cents = pi.amount as u64;
+    let amount_dollars = amount_cents / 100;
+    let (issuer, binds_metadata) = get_issuer(amount_dollars)?;
+    check_metadata(&blinded_ghost_key, binds_metadata, amount_dollars, pi.created)?;
+
     // Mark the payment intent as used for certificate signing
     let mut metadata = HashMap::new();
     metadata.insert("certificate_signed".to_string(), "true".to_string());
@@ -79,26 +106,103 @@ pub async fn sign_certificate(request: SignCertificateRequest) -> Result<SignCer
     // Sign the certificate
     log::info!("Payment intent verified successfully");
 
-    let blinded_ghostkey = BlindedMessage::from_base64(&request.blinded_ghost_key_base64)
-        .map_err(|e| {
-            log::error!("Error in from_base64: {:?}", e);
-            CertificateError::MiscError(e.to_string())
-        })?;
-
-    let amount_cents = pi.amount as u64;
-    let amount_dollars = amount_cents / 100;
-    let blind_signature = sign_with_delegate_key(&blinded_ghostkey, amount_dollars)
-        .map_err(|e| {
-            log::error!("Error in sign_with_delegate_key: {:?}", e);
-            e
-        })?;
+    let (blind_signature, issuance_response) = match blinded_ghost_key {
+        BlindedGhostKey::Request(issuance_request) => {
+            let issuance_response = issuer.sign(&issuance_request).map_err(|e| {
+                log::error!("Unable to sign issuance request: [{}] {}", e.code(), e);
+                CertificateError::GhostkeyError(e)
+            })?;
+            (issuance_response.blind_signature().clone(), Some(issuance_response.to_base64()?))
+        }
+        BlindedGhostKey::Legacy(blinded_message) => {
+            let blind_signature = issuer.sign_blinded(&mut OsRng, &blinded_message).map_err(|e| {
+                log::error!("Unable to sign blinded ghost key: [{}] {}", e.code(), e);
+                CertificateError::GhostkeyError(e)
+            })?;
+            (blind_signature, None)
+        }
+    };
+    log::info!(
+        "Blind signed ghost key for amount {} with delegate certificate {}",
+        amount_dollars,
+        issuer.delegate_fingerprint()
+    );
 
-    let (delegate_certificate, _) = crate::delegates::get_delegate(amount_dollars)?;
-    
     Ok(SignCertificateResponse {
-        blind_signature_base64: blind_signature.to_base64().map_err(|e| CertificateError::MiscError(e.to_string()))?,
+        blind_signature_base64: blind_signature.to_base64()?,
+        issuance_response_base64: issuance_response,
         // TODO: Shouldn't be needed if this is being stored in localstorage
-        delegate_certificate_base64: delegate_certificate.to_base64().map_err(|e| CertificateError::MiscError(e.to_string()))?,
+        delegate_certificate_base64: issuer.delegate_certificate().to_base64()?,
         amount: amount_cents,
     })
 }
+
+/// Checks that the ghost key is blinded with the metadata of the donation if the delegate binds
+/// metadata, as it would otherwise sign whatever amount the client asked for, and without
+/// metadata otherwise.
+fn check_metadata(
+    blinded_ghost_key: &BlindedGhostKey,
+    binds_metadata: bool,
+    amount: u64,
+    created: i64,
+) -> Result<(), CertificateError> {
+    let metadata = match blinded_ghost_key {
+        BlindedGhostKey::Request(issuance_request) => issuance_request.metadata(),
+        BlindedGhostKey::Legacy(_) => None,
+    };
+    if !binds_metadata {
+        return match metadata {
+            None => Ok(()),
+            Some(metadata) => {
+                log::error!("Ghost key has metadata {} but the delegate for amount {} does not bind metadata", metadata, amount);
+                Err(CertificateError::GhostkeyError(GhostkeyError::InvalidInput(
+                    "The delegate for this amount does not sign ghost keys with metadata".to_string(),
+                )))
+            }
+        };
+    }
+
+    let expected = ghost_key_metadata(amount, created);
+    match metadata {
+        Some(metadata) if *metadata == expected => {
+            log::info!("Ghost key metadata matches the donation: {}", metadata);
+            Ok(())
+        }
+        Some(metadata) => {
+            log::error!("Ghost key metadata {} does not match the donation: {}", metadata, expected);
+            Err(CertificateError::GhostkeyError(GhostkeyError::InvalidInput(format!(
+                "Ghost key metadata does not match the donation, expected {}",
+                expected
+            ))))
+        }
+        None => {
+            log::error!("Ghost key has no metadata, the delegate only signs ghost keys bound to {}", expected);
+            Err(CertificateError::GhostkeyError(GhostkeyError::InvalidInput(
+                "Ghost key metadata is required, please reload the donation page".to_string(),
+            )))
+        }
+    }
+}
+
+fn decode_blinded_ghost_key(request: &SignCertificateRequest) -> Result<BlindedGhostKey, CertificateError> {
+    match (&request.issuance_request_base64, &request.blinded_ghost_key_base64) {
+        (Some(encoded), _) => IssuanceRequest::from_base64(encoded)
+            .map(BlindedGhostKey::Request)
+            .map_err(|e| {
+                log::error!("Invalid issuance request: [{}] {}", e.code(), e);
+                CertificateError::GhostkeyError(e)
+            }),
+        (None, Some(encoded)) => BlindedMessage::from_base64(encoded)
+            .map(BlindedGhostKey::Legacy)
+            .map_err(|e| {
+                log::error!("Invalid blinded ghost key: [{}] {}", e.code(), e);
+                CertificateError::GhostkeyError(e)
+            }),
+        (None, None) => {
+            log::error!("Request has no issuance request or blinded ghost key");
+            Err(CertificateError::GhostkeyError(GhostkeyError::InvalidInput(
+                "Either issuance_request_base64 or blinded_ghost_key_base64 is required".to_string(),
+            )))
+        }
+    }
+}
@@ -21,6 +21,11 @@ mod delegates;
 mod errors;
 
 pub static DELEGATE_DIR: &str = "DELEGATE_DIR";
+pub static REVOCATION_LIST: &str = "REVOCATION_LIST";
+pub static TRUST_STORE: &str = "TRUST_STORE";
+pub static CAMPAIGN: &str = "CAMPAIGN";
+/// Passphrase of encrypted delegate signing keys, the variable the CLI reads it from too.
+pub static PASSPHRASE: &str = "GHOSTKEY_PASSPHRASE";
 
 async fn serve_http01_challenge(
     challenge_dir: Arc<Mutex<Option<PathBuf>>>,
@@ -59,8 +64,20 @@ async fn main() {
         .arg(Arg::new("delegate-dir")
             .long("delegate-dir")
             .value_name("DIR")
-            .help("Sets the delegate directory")
+            .help("Sets the delegate directory, encrypted signing keys in it are unlocked with the passphrase in GHOSTKEY_PASSPHRASE")
             .required(true))
+        .arg(Arg::new("revocation-list")
+            .long("revocation-list")
+            .value_name("FILE")
+            .help("Refuse to sign with delegates listed in this revocation list"))
+        .arg(Arg::new("trust-store")
+            .long("trust-store")
+            .value_name("FILE")
+            .help("Master verifying keys used to check the revocation list (defaults to the Freenet master key)"))
+        .arg(Arg::new("campaign")
+            .long("campaign")
+            .value_name("NAME")
+            .help("Campaign recorded in the metadata of ghost keys signed by a partially blind delegate"))
         .arg(Arg::new("tls-cert")
             .long("tls-cert")
             .value_name("FILE")
@@ -84,6 +101,15 @@ async fn main() {
     let user_port = matches.get_one::<u16>("port");
     let challenge_dir = matches.get_one::<String>("challenge-dir").map(PathBuf::from);
     env::set_var(DELEGATE_DIR, delegate_dir);
+    if let Some(revocation_list) = matches.get_one::<String>("revocation-list") {
+        env::set_var(REVOCATION_LIST, revocation_list);
+    }
+    if let Some(trust_store) = matches.get_one::<String>("trust-store") {
+        env::set_var(TRUST_STORE, trust_store);
+    }
+    if let Some(campaign) = matches.get_one::<String>("campaign") {
+        env::set_var(CAMPAIGN, campaign);
+    }
 
     env_logger::builder()
         .format_timestamp(Some(env_logger::TimestampPrecision::Millis))
@@ -99,6 +125,10 @@ async fn main() {
     }
 
     env::var("DELEGATE_DIR").expect("DELEGATE_DIR environment variable not set");
+    if let Err(e) = delegates::load_revocation_list() {
+        error!("Failed to load revocation list: {}", e);
+        std::process::exit(1);
+    }
     
     let challenge_dir = Arc::new(Mutex::new(challenge_dir));
 
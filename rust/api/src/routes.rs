@@ -12,14 +12,19 @@ use log::{error, info};
 use serde::{Deserialize, Serialize};
 use stripe::{Client, Currency, PaymentIntent, PaymentIntentId};
 use ghostkey_lib::armorable::Armorable;
+use ghostkey_lib::errors::GhostkeyError;
 
-use crate::delegates::get_delegate;
+use crate::delegates::{get_delegate, ghost_key_metadata};
 use crate::handle_sign_cert::{CertificateError, sign_certificate, SignCertificateRequest, SignCertificateResponse};
 
 #[derive(Serialize)]
 pub struct ErrorResponse {
     error: String,
     status: u16,
+    /// Stable ghost key error code, such as `GK_DELEGATE_REVOKED`, when the error came from
+    /// ghostkey_lib
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<&'static str>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -37,6 +42,9 @@ pub struct DonationResponse {
     pub client_secret: String,
     pub payment_intent_id: String,
     pub delegate_certificate_base64: String,
+    /// The metadata to blind the ghost key with, set when the delegate binds metadata
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ghost_key_metadata_base64: Option<String>,
 }
 
 async fn index() -> impl IntoResponse {
@@ -61,30 +69,49 @@ async fn sign_certificate_route(
             Ok(Json(response))
         },
         Err(e) => {
-            error!("Error signing certificate: {:?}", e);
+            error!("Error signing certificate: [{}] {:?}", e.code().unwrap_or("-"), e);
             match e {
                 CertificateError::PaymentNotSuccessful => {
                     Err((StatusCode::BAD_REQUEST, Json(ErrorResponse {
                         error: "Payment not successful. Please check your payment details and try again.".to_string(),
                         status: StatusCode::BAD_REQUEST.as_u16(),
+                        code: None,
                     })))
                 },
                 CertificateError::CertificateAlreadySigned => {
                     Err((StatusCode::CONFLICT, Json(ErrorResponse {
                         error: "Certificate has already been signed for this payment.".to_string(),
                         status: StatusCode::CONFLICT.as_u16(),
+                        code: None,
                     })))
                 },
                 CertificateError::KeyError(msg) => {
                     Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse {
                         error: format!("Key error: {}", msg),
                         status: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+                        code: None,
+                    })))
+                },
+                CertificateError::GhostkeyError(e) if is_request_error(&e) => {
+                    Err((StatusCode::BAD_REQUEST, Json(ErrorResponse {
+                        error: e.to_string(),
+                        status: StatusCode::BAD_REQUEST.as_u16(),
+                        code: Some(e.code()),
+                    })))
+                },
+                CertificateError::GhostkeyError(e) => {
+                    // The details were logged above, they may name files on the server
+                    Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse {
+                        error: "Unable to sign the certificate. Please try again later.".to_string(),
+                        status: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+                        code: Some(e.code()),
                     })))
                 },
                 _ => {
                     Err((StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse {
                         error: "An unexpected error occurred. Please try again later.".to_string(),
                         status: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+                        code: None,
                     })))
                 }
             }
@@ -92,6 +119,20 @@ async fn sign_certificate_route(
     }
 }
 
+/// Whether a ghost key error was caused by the request rather than by the server, such as a
+/// malformed blinded ghost key or metadata that doesn't match the donation.
+fn is_request_error(error: &GhostkeyError) -> bool {
+    matches!(
+        error,
+        GhostkeyError::InvalidInput(_)
+            | GhostkeyError::ValidationError(_)
+            | GhostkeyError::DecodingError(_)
+            | GhostkeyError::DeserializationError(..)
+            | GhostkeyError::Base64DecodeError(_)
+            | GhostkeyError::DelegateMismatchError(_)
+    )
+}
+
 #[derive(Debug)]
 pub enum DonationError {
     InvalidCurrency,
@@ -121,6 +162,7 @@ impl IntoResponse for DonationError {
         let body = Json(ErrorResponse {
             error: error_message.to_string(),
             status: status.as_u16(),
+            code: None,
         });
 
         (status, body).into_response()
@@ -188,17 +230,19 @@ async fn create_donation(
     
     let amount_dollars = request.amount / 100;
     
-    let (delegate_certificate, _) = get_delegate(amount_dollars as u64).map_err(|e| {
+    let delegate = get_delegate(amount_dollars as u64).map_err(|e| {
         error!("Error getting delegate: {:?}", e);
         DonationError::OtherError("Error getting delegate".to_string())
     })?;
+    let ghost_key_metadata_base64 = metadata_base64(delegate.binds_metadata, amount_dollars as u64, intent.created)?;
     
     match intent.client_secret {
         Some(secret) => {
             Ok(Json(DonationResponse {
                 client_secret: secret,
                 payment_intent_id: intent.id.to_string(),
-                delegate_certificate_base64: delegate_certificate.to_base64().unwrap(),
+                delegate_certificate_base64: delegate.certificate.to_base64().unwrap(),
+                ghost_key_metadata_base64,
             }))
         },
         None => {
@@ -237,18 +281,32 @@ async fn update_donation(
 
     let amount_dollars = request.amount / 100;
     
-    let (delegate_certificate, _) = get_delegate(amount_dollars as u64).map_err(|e| {
+    let delegate = get_delegate(amount_dollars as u64).map_err(|e| {
         error!("Error getting delegate: {:?}", e);
         DonationError::OtherError("Error getting delegate".to_string())
     })?;
+    let ghost_key_metadata_base64 = metadata_base64(delegate.binds_metadata, amount_dollars as u64, updated_intent.created)?;
     
     Ok(Json(DonationResponse {
         client_secret: updated_intent.client_secret.unwrap_or_default(),
         payment_intent_id: updated_intent.id.to_string(),
-        delegate_certificate_base64: delegate_certificate.to_base64().unwrap(),
+        delegate_certificate_base64: delegate.certificate.to_base64().unwrap(),
+        ghost_key_metadata_base64,
     }))
 }
 
+/// Encodes the metadata the sign-certificate route will expect for the donation, if the delegate
+/// binds metadata.
+fn metadata_base64(binds_metadata: bool, amount: u64, created: i64) -> Result<Option<String>, DonationError> {
+    if !binds_metadata {
+        return Ok(None);
+    }
+    ghost_key_metadata(amount, created).to_base64().map(Some).map_err(|e| {
+        error!("Error encoding ghost key metadata: [{}] {}", e.code(), e);
+        DonationError::OtherError("Error encoding ghost key metadata".to_string())
+    })
+}
+
 async fn check_payment_status_route(
     Path(payment_intent_id): Path<String>,
 ) -> Result<StatusCode, DonationError> {
@@ -7,10 +7,11 @@ DEFAULT_AMOUNTS=(1 5 20 50 100)
 TODAYS_DATE=$(date +%Y%m%d)
 DEFAULT_DELEGATE_DIR="$HOME/code/freenet/keys/mnt/ghostkey-${TODAYS_DATE}/delegates"
 OVERWRITE=false
+PARTIALLY_BLIND=false
 
 # Function to display usage information
 usage() {
-    echo "Usage: $0 --master-key <master_signing_key_file> [--delegate-dir <delegate_dir>] [--amounts <amount1> <amount2> ...] [--overwrite]" >&2
+    echo "Usage: $0 --master-key <master_signing_key_file> [--delegate-dir <delegate_dir>] [--amounts <amount1> <amount2> ... | --partially-blind] [--overwrite]" >&2
     exit 1
 }
 
@@ -40,6 +41,10 @@ while [ $# -gt 0 ]; do
             OVERWRITE=true
             shift
             ;;
+        --partially-blind)
+            PARTIALLY_BLIND=true
+            shift
+            ;;
         *)
             echo "Unknown option: $1" >&2
             usage
@@ -53,6 +58,18 @@ if [ -z "$MASTER_KEY_FILE" ]; then
     usage
 fi
 
+# A partially blind delegate binds the amount into each ghost key certificate, so a single
+# delegate named "any" replaces the per-amount delegates
+EXTRA_ARGS=()
+if [ "$PARTIALLY_BLIND" = true ]; then
+    if [ ${#AMOUNTS[@]} -ne 0 ]; then
+        echo "Error: --amounts cannot be used with --partially-blind." >&2
+        usage
+    fi
+    AMOUNTS=(any)
+    EXTRA_ARGS=(--partially-blind)
+fi
+
 # Use default amounts if not provided
 if [ ${#AMOUNTS[@]} -eq 0 ]; then
     AMOUNTS=("${DEFAULT_AMOUNTS[@]}")
@@ -72,9 +89,6 @@ chmod 700 "$DELEGATE_DIR"
 
 # Generate delegate keys for each amount
 for amount in "${AMOUNTS[@]}"; do
-    current_date=$(date -u +"%Y-%m-%d %H:%M:%S")
-    info="{\"action\":\"freenet-donation\",\"amount\":$amount,\"delegate-key-created\":\"$current_date\"}"
-    
     signing_key_file="$DELEGATE_DIR/delegate_signing_key_$amount.pem"
     cert_file="$DELEGATE_DIR/delegate_certificate_$amount.pem"
     
@@ -88,7 +102,10 @@ for amount in "${AMOUNTS[@]}"; do
     script_dir="$(cd "$(dirname "${BASH_SOURCE[0]}")" && pwd)"
     if ! cargo run --quiet --manifest-path "$script_dir/Cargo.toml" -- generate-delegate \
         --master-signing-key "$MASTER_KEY_FILE" \
-        --info "$info" \
+        --amount "${amount/any/0}" \
+        --currency USD \
+        --tier "freenet-donation" \
+        "${EXTRA_ARGS[@]}" \
         --output-dir "$DELEGATE_DIR" \
         --ignore-permissions >/dev/null 2>&1; then
         echo "Error: Failed to generate delegate key for amount $amount" >&2
@@ -1,14 +1,30 @@
 use blind_rsa_signatures::SecretKey as RSASigningKey;
-use clap::{Arg, ArgAction, Command};
+use clap::{Arg, ArgAction, ArgMatches, Command};
 use colored::Colorize;
 use ed25519_dalek::*;
-use ghostkey_lib::armorable::Armorable;
+use ghostkey_lib::armorable::{Armorable, ArmoredBundle};
 use ghostkey::commands::{
-    generate_delegate_cmd, generate_ghost_key_cmd, generate_master_key_cmd, verify_delegate_cmd,
-    verify_ghost_key_cmd, sign_message_cmd, verify_signed_message_cmd,
+    generate_delegate_cmd, generate_ghost_key_cmd, generate_master_key_cmd,
+    generate_revocation_list_cmd, add_trusted_key_cmd, change_passphrase_cmd, verify_delegate_cmd, verify_ghost_key_cmd, sign_message_cmd,
+    verify_signed_message_cmd, mint_subkey_cmd, verify_subkey_cmd, sign_message_with_subkey_cmd,
+    derive_pseudonym_cmd, sign_as_pseudonym_cmd, verify_pseudonym_cmd,
+    generate_master_key_shares_cmd, generate_delegate_request_cmd, threshold_commit_cmd,
+    threshold_package_cmd, threshold_sign_cmd, threshold_aggregate_cmd, restore_cmd, split_key_cmd,
+    combine_key_cmd, export_qr_cmd, import_qr_cmd, convert_cmd, export_ssh_key_cmd, ssh_sign_cmd,
+    ssh_verify_cmd,
 };
-use ghostkey_lib::delegate_certificate::DelegateCertificateV1;
-use ghostkey_lib::ghost_key_certificate::GhostkeyCertificateV1;
+use ghostkey::passphrase::{load_private_key, read_passphrase};
+use ghostkey_lib::encrypted_key::read_private_key;
+use ghostkey_lib::delegate_certificate::{DelegateCertificate, DelegateCertificateRequestV1, DelegateDetails};
+use ghostkey_lib::ghost_key_certificate::GhostkeyCertificate;
+use ghostkey_lib::pseudonym::{PseudonymCertificateV1, PseudonymMessageV1};
+use ghostkey_lib::revocation_list::DelegateRevocationListV1;
+use ghostkey_lib::secret_sharing::PrivateKeyShareV1;
+use ghostkey_lib::ssh::SshSignature;
+use ghostkey_lib::subkey::SubkeyCertificateV1;
+use ghostkey_lib::threshold::{MasterKeyShareV1, SignatureShareV1, SigningCommitmentV1, SigningNoncesV1, SigningPackageV1};
+use ghostkey_lib::trust_store::TrustStore;
+use ghostkey_lib::util::current_unix_time;
 use log::info;
 use std::path::Path;
 use std::process;
@@ -21,15 +37,89 @@ const CMD_GENERATE_GHOST_KEY: &str = "generate-ghost-key";
 const CMD_VERIFY_GHOST_KEY: &str = "verify-ghost-key";
 const CMD_SIGN_MESSAGE: &str = "sign-message";
 const CMD_VERIFY_SIGNED_MESSAGE: &str = "verify-signed-message";
+const CMD_GENERATE_REVOCATION_LIST: &str = "generate-revocation-list";
+const CMD_ADD_TRUSTED_KEY: &str = "add-trusted-key";
+const CMD_CHANGE_PASSPHRASE: &str = "change-passphrase";
+const CMD_MINT_SUBKEY: &str = "mint-subkey";
+const CMD_VERIFY_SUBKEY: &str = "verify-subkey";
+const CMD_DERIVE_PSEUDONYM: &str = "derive-pseudonym";
+const CMD_SIGN_AS_PSEUDONYM: &str = "sign-as-pseudonym";
+const CMD_VERIFY_PSEUDONYM: &str = "verify-pseudonym";
+const CMD_GENERATE_MASTER_KEY_SHARES: &str = "generate-master-key-shares";
+const CMD_THRESHOLD_COMMIT: &str = "threshold-commit";
+const CMD_THRESHOLD_PACKAGE: &str = "threshold-package";
+const CMD_THRESHOLD_SIGN: &str = "threshold-sign";
+const CMD_THRESHOLD_AGGREGATE: &str = "threshold-aggregate";
+const CMD_RESTORE: &str = "restore";
+const CMD_SPLIT_KEY: &str = "split-key";
+const CMD_COMBINE_KEY: &str = "combine-key";
+const CMD_EXPORT_QR: &str = "export-qr";
+const CMD_IMPORT_QR: &str = "import-qr";
+const CMD_CONVERT: &str = "convert";
+const CMD_EXPORT_SSH_KEY: &str = "export-ssh-key";
+const CMD_SSH_SIGN: &str = "ssh-sign";
+const CMD_SSH_VERIFY: &str = "ssh-verify";
 
 const ARG_OUTPUT_DIR: &str = "output-dir";
 const ARG_IGNORE_PERMISSIONS: &str = "ignore-permissions";
 const ARG_MASTER_SIGNING_KEY: &str = "master-signing-key";
 const ARG_INFO: &str = "info";
+const ARG_VALID_DAYS: &str = "valid-days";
+const ARG_AMOUNT: &str = "amount";
+const ARG_CURRENCY: &str = "currency";
+const ARG_TIER: &str = "tier";
+const ARG_ISSUER: &str = "issuer";
+const ARG_PARTIALLY_BLIND: &str = "partially-blind";
+const ARG_PURPOSE: &str = "purpose";
+const ARG_CONTENT_TYPE: &str = "content-type";
+const ARG_DETACHED: &str = "detached";
+const ARG_MESSAGE: &str = "message";
+
+/// Purpose of messages signed without `--purpose`
+const DEFAULT_PURPOSE: &str = "ghostkey message";
 const ARG_MASTER_VERIFYING_KEY: &str = "master-verifying-key";
 const ARG_DELEGATE_CERTIFICATE: &str = "delegate-certificate";
 const ARG_DELEGATE_DIR: &str = "delegate-dir";
 const ARG_GHOST_CERTIFICATE: &str = "ghost-certificate";
+const ARG_REVOCATION_LIST: &str = "revocation-list";
+const ARG_REVOKE: &str = "revoke";
+const ARG_REASON: &str = "reason";
+const ARG_NEXT_UPDATE_DAYS: &str = "next-update-days";
+const ARG_OUTPUT: &str = "output";
+const ARG_TRUST_STORE: &str = "trust-store";
+const ARG_NAME: &str = "name";
+const ARG_ENCRYPT: &str = "encrypt";
+const ARG_KEY: &str = "key";
+const ARG_REMOVE_ENCRYPTION: &str = "remove-encryption";
+const ARG_SUBKEY_CERTIFICATE: &str = "subkey-certificate";
+const ARG_SUBKEY_SIGNING_KEY: &str = "subkey-signing-key";
+const ARG_VALID_HOURS: &str = "valid-hours";
+const ARG_CONTEXT: &str = "context";
+const ARG_RING: &str = "ring";
+const ARG_PSEUDONYM_CERTIFICATE: &str = "pseudonym-certificate";
+const ARG_PSEUDONYM_SIGNING_KEY: &str = "pseudonym-signing-key";
+const ARG_SIGNED_MESSAGE: &str = "signed-message";
+const ARG_THRESHOLD: &str = "threshold";
+const ARG_SHARES: &str = "shares";
+const ARG_REQUEST: &str = "request";
+const ARG_KEY_SHARE: &str = "key-share";
+const ARG_NONCES: &str = "nonces";
+const ARG_COMMITMENT: &str = "commitment";
+const ARG_PACKAGE: &str = "package";
+const ARG_SIGNATURE_SHARE: &str = "signature-share";
+const ARG_SHOW_MNEMONIC: &str = "show-mnemonic";
+const ARG_MNEMONIC: &str = "mnemonic";
+const ARG_SHARE: &str = "share";
+const ARG_ELIDE_DELEGATE: &str = "elide-delegate";
+const ARG_PNG: &str = "png";
+const ARG_TEXT: &str = "text";
+const ARG_COMPACT: &str = "compact";
+const ARG_INPUT: &str = "input";
+const ARG_TO: &str = "to";
+const ARG_NAMESPACE: &str = "namespace";
+const ARG_SIGNATURE: &str = "signature";
+const ARG_PUBLIC_KEY: &str = "public-key";
+const ARG_ALLOW_V1: &str = "allow-v1";
 
 fn main() {
     let exit_code = run();
@@ -51,12 +141,27 @@ fn run() -> i32 {
                         .required(false)
                         .value_name("FILE"),
                 )
+                .arg(
+                    Arg::new(ARG_TRUST_STORE)
+                        .long(ARG_TRUST_STORE)
+                        .help("Optionally verify against the master verifying keys in this trust store")
+                        .required(false)
+                        .conflicts_with(ARG_MASTER_VERIFYING_KEY)
+                        .value_name("FILE"),
+                )
                 .arg(
                     Arg::new(ARG_GHOST_CERTIFICATE)
                         .long(ARG_GHOST_CERTIFICATE)
                         .help("The file containing the ghost certificate")
                         .required(true)
                         .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new(ARG_REVOCATION_LIST)
+                        .long(ARG_REVOCATION_LIST)
+                        .help("Optionally reject certificates issued by delegates in this revocation list")
+                        .required(false)
+                        .value_name("FILE"),
                 ),
         )
         .subcommand(
@@ -74,6 +179,52 @@ fn run() -> i32 {
                         .long(ARG_IGNORE_PERMISSIONS)
                         .help("Ignore file permission checks")
                         .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new(ARG_ENCRYPT)
+                        .long(ARG_ENCRYPT)
+                        .help("Encrypt the signing key with a passphrase (prompted, or read from GHOSTKEY_PASSPHRASE)")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new(CMD_GENERATE_MASTER_KEY_SHARES)
+                .about("Splits a master signing key into shares, any threshold of which sign together as the master key")
+                .arg(
+                    Arg::new(ARG_MASTER_SIGNING_KEY)
+                        .long(ARG_MASTER_SIGNING_KEY)
+                        .help("The master signing key to split (a new master key is generated if omitted)")
+                        .required(false)
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new(ARG_THRESHOLD)
+                        .long(ARG_THRESHOLD)
+                        .help("How many shares must cooperate to sign")
+                        .required(true)
+                        .value_parser(clap::value_parser!(u16))
+                        .value_name("M"),
+                )
+                .arg(
+                    Arg::new(ARG_SHARES)
+                        .long(ARG_SHARES)
+                        .help("How many shares to split the master key into")
+                        .required(true)
+                        .value_parser(clap::value_parser!(u16))
+                        .value_name("N"),
+                )
+                .arg(
+                    Arg::new(ARG_OUTPUT_DIR)
+                        .long(ARG_OUTPUT_DIR)
+                        .help("The directory to output the key shares and the master verifying key")
+                        .required(true)
+                        .value_name("DIR"),
+                )
+                .arg(
+                    Arg::new(ARG_ENCRYPT)
+                        .long(ARG_ENCRYPT)
+                        .help("Encrypt the key shares with a passphrase (prompted, or read from GHOSTKEY_PASSPHRASE)")
+                        .action(ArgAction::SetTrue),
                 ),
         )
         .subcommand(
@@ -83,16 +234,70 @@ fn run() -> i32 {
                     Arg::new(ARG_MASTER_SIGNING_KEY)
                         .long(ARG_MASTER_SIGNING_KEY)
                         .help("The file containing the master signing key")
-                        .required(true)
+                        .required_unless_present(ARG_REQUEST)
+                        .conflicts_with(ARG_REQUEST)
                         .value_name("FILE"),
                 )
+                .arg(
+                    Arg::new(ARG_REQUEST)
+                        .long(ARG_REQUEST)
+                        .help("Write a request for a V1 certificate, or V2 with --partially-blind, to be signed with master key shares (see threshold-commit) instead of signing with a master signing key")
+                        .action(ArgAction::SetTrue),
+                )
                 .arg(
                     Arg::new(ARG_INFO)
                         .long(ARG_INFO)
                         .help("The info string to be included in the delegate key certificate")
-                        .required(true)
+                        .required_unless_present(ARG_AMOUNT)
+                        .conflicts_with(ARG_AMOUNT)
                         .value_name("STRING"),
                 )
+                .arg(
+                    Arg::new(ARG_AMOUNT)
+                        .long(ARG_AMOUNT)
+                        .help("The donation amount the delegate signs for, creates a certificate with typed details instead of an info string")
+                        .value_parser(clap::value_parser!(u64))
+                        .value_name("AMOUNT"),
+                )
+                .arg(
+                    Arg::new(ARG_CURRENCY)
+                        .long(ARG_CURRENCY)
+                        .help("The currency of the donation amount")
+                        .requires(ARG_AMOUNT)
+                        .default_value("USD")
+                        .value_name("CODE"),
+                )
+                .arg(
+                    Arg::new(ARG_TIER)
+                        .long(ARG_TIER)
+                        .help("The name of the donation tier")
+                        .requires(ARG_AMOUNT)
+                        .default_value("donation")
+                        .value_name("NAME"),
+                )
+                .arg(
+                    Arg::new(ARG_ISSUER)
+                        .long(ARG_ISSUER)
+                        .help("The operator of the delegate")
+                        .requires(ARG_AMOUNT)
+                        .default_value("Freenet")
+                        .value_name("NAME"),
+                )
+                .arg(
+                    Arg::new(ARG_PARTIALLY_BLIND)
+                        .long(ARG_PARTIALLY_BLIND)
+                        .help("Generate a delegate key that binds metadata such as the donation amount into each ghost key certificate it signs, so one delegate serves every amount (key generation takes minutes)")
+                        .requires(ARG_AMOUNT)
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new(ARG_VALID_DAYS)
+                        .long(ARG_VALID_DAYS)
+                        .help("Number of days from now the delegate certificate remains valid (never expires if omitted)")
+                        .required(false)
+                        .value_parser(clap::value_parser!(u64))
+                        .value_name("DAYS"),
+                )
                 .arg(
                     Arg::new(ARG_OUTPUT_DIR)
                         .long(ARG_OUTPUT_DIR)
@@ -105,6 +310,120 @@ fn run() -> i32 {
                         .long(ARG_IGNORE_PERMISSIONS)
                         .help("Ignore file permission checks")
                         .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new(ARG_ENCRYPT)
+                        .long(ARG_ENCRYPT)
+                        .help("Encrypt the signing key with a passphrase (prompted, or read from GHOSTKEY_PASSPHRASE)")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new(CMD_THRESHOLD_COMMIT)
+                .about("First round of signing with a master key share: creates the nonces and the commitment to send to the coordinator")
+                .arg(
+                    Arg::new(ARG_KEY_SHARE)
+                        .long(ARG_KEY_SHARE)
+                        .help("The file containing the master key share")
+                        .required(true)
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new(ARG_OUTPUT_DIR)
+                        .long(ARG_OUTPUT_DIR)
+                        .help("The directory to output the signing nonces and commitment")
+                        .required(true)
+                        .value_name("DIR"),
+                )
+                .arg(
+                    Arg::new(ARG_ENCRYPT)
+                        .long(ARG_ENCRYPT)
+                        .help("Encrypt the signing nonces with a passphrase (prompted, or read from GHOSTKEY_PASSPHRASE)")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new(CMD_THRESHOLD_PACKAGE)
+                .about("Collects the signers' commitments for a delegate certificate request into a signing package")
+                .arg(
+                    Arg::new(ARG_REQUEST)
+                        .long(ARG_REQUEST)
+                        .help("The file containing the delegate certificate request")
+                        .required(true)
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new(ARG_COMMITMENT)
+                        .long(ARG_COMMITMENT)
+                        .help("A signing commitment file from threshold-commit, repeated for each signer")
+                        .required(true)
+                        .action(ArgAction::Append)
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new(ARG_OUTPUT)
+                        .long(ARG_OUTPUT)
+                        .help("The file to output the signing package")
+                        .required(true)
+                        .value_name("FILE"),
+                ),
+        )
+        .subcommand(
+            Command::new(CMD_THRESHOLD_SIGN)
+                .about("Second round of signing with a master key share: signs the delegate certificate request in a signing package")
+                .arg(
+                    Arg::new(ARG_KEY_SHARE)
+                        .long(ARG_KEY_SHARE)
+                        .help("The file containing the master key share")
+                        .required(true)
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new(ARG_NONCES)
+                        .long(ARG_NONCES)
+                        .help("The signing nonces file from threshold-commit, deleted once used")
+                        .required(true)
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new(ARG_PACKAGE)
+                        .long(ARG_PACKAGE)
+                        .help("The file containing the signing package")
+                        .required(true)
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new(ARG_OUTPUT_DIR)
+                        .long(ARG_OUTPUT_DIR)
+                        .help("The directory to output the signature share")
+                        .required(true)
+                        .value_name("DIR"),
+                ),
+        )
+        .subcommand(
+            Command::new(CMD_THRESHOLD_AGGREGATE)
+                .about("Combines the signature shares of a signing package into a delegate certificate")
+                .arg(
+                    Arg::new(ARG_PACKAGE)
+                        .long(ARG_PACKAGE)
+                        .help("The file containing the signing package")
+                        .required(true)
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new(ARG_SIGNATURE_SHARE)
+                        .long(ARG_SIGNATURE_SHARE)
+                        .help("A signature share file from threshold-sign, repeated for each signer")
+                        .required(true)
+                        .action(ArgAction::Append)
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new(ARG_OUTPUT_DIR)
+                        .long(ARG_OUTPUT_DIR)
+                        .help("The directory to output the delegate certificate")
+                        .required(true)
+                        .value_name("DIR"),
                 ),
         )
         .subcommand(
@@ -117,12 +436,27 @@ fn run() -> i32 {
                         .required(false)
                         .value_name("FILE"),
                 )
+                .arg(
+                    Arg::new(ARG_TRUST_STORE)
+                        .long(ARG_TRUST_STORE)
+                        .help("Optionally verify against the master verifying keys in this trust store")
+                        .required(false)
+                        .conflicts_with(ARG_MASTER_VERIFYING_KEY)
+                        .value_name("FILE"),
+                )
                 .arg(
                     Arg::new(ARG_DELEGATE_CERTIFICATE)
                         .long(ARG_DELEGATE_CERTIFICATE)
                         .help("The file containing the delegate certificate")
                         .required(true)
                         .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new(ARG_REVOCATION_LIST)
+                        .long(ARG_REVOCATION_LIST)
+                        .help("Optionally reject certificates issued by delegates in this revocation list")
+                        .required(false)
+                        .value_name("FILE"),
                 ),
         )
         .subcommand(
@@ -141,32 +475,119 @@ fn run() -> i32 {
                         .help("The directory to output the ghost key files")
                         .required(true)
                         .value_name("DIR"),
+                )
+                .arg(
+                    Arg::new(ARG_ENCRYPT)
+                        .long(ARG_ENCRYPT)
+                        .help("Encrypt the signing key with a passphrase (prompted, or read from GHOSTKEY_PASSPHRASE)")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new(ARG_SHOW_MNEMONIC)
+                        .long(ARG_SHOW_MNEMONIC)
+                        .help("Print the recovery phrase that restores the signing key with restore")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new(CMD_RESTORE)
+                .about("Restores a ghost signing key from its recovery phrase and ghost certificate")
+                .arg(
+                    Arg::new(ARG_GHOST_CERTIFICATE)
+                        .long(ARG_GHOST_CERTIFICATE)
+                        .help("The file containing the ghost certificate, the restored key must match it")
+                        .required(true)
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new(ARG_MNEMONIC)
+                        .long(ARG_MNEMONIC)
+                        .help("The recovery phrase, either a file path or the words (prompted if omitted)")
+                        .required(false)
+                        .value_name("PHRASE"),
+                )
+                .arg(
+                    Arg::new(ARG_OUTPUT_DIR)
+                        .long(ARG_OUTPUT_DIR)
+                        .help("The directory to output the ghost key files")
+                        .required(true)
+                        .value_name("DIR"),
+                )
+                .arg(
+                    Arg::new(ARG_ENCRYPT)
+                        .long(ARG_ENCRYPT)
+                        .help("Encrypt the signing key with a passphrase (prompted, or read from GHOSTKEY_PASSPHRASE)")
+                        .action(ArgAction::SetTrue),
                 ),
         )
         .subcommand(
             Command::new(CMD_SIGN_MESSAGE)
-                .about("Signs a message using a ghost key")
+                .about("Signs a message using a ghost key or a subkey")
+                .arg(
+                    Arg::new("ghost_key")
+                        .long("ghost-key")
+                        .help("A bundle file containing both the ghost certificate and the ghost signing key, such as the key copied from the donation page")
+                        .conflicts_with_all(["ghost_certificate", "ghost_signing_key", ARG_SUBKEY_CERTIFICATE])
+                        .value_name("FILE"),
+                )
                 .arg(
                     Arg::new("ghost_certificate")
                         .long("ghost-certificate")
                         .help("The file containing the ghost certificate")
-                        .required(true)
+                        .required_unless_present_any(["ghost_key", ARG_SUBKEY_CERTIFICATE])
+                        .conflicts_with(ARG_SUBKEY_CERTIFICATE)
                         .value_name("FILE"),
                 )
                 .arg(
                     Arg::new("ghost_signing_key")
                         .long("ghost-signing-key")
                         .help("The file containing the ghost signing key")
-                        .required(true)
+                        .required_unless_present_any(["ghost_key", ARG_SUBKEY_CERTIFICATE])
+                        .conflicts_with(ARG_SUBKEY_CERTIFICATE)
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new(ARG_SUBKEY_CERTIFICATE)
+                        .long(ARG_SUBKEY_CERTIFICATE)
+                        .help("Sign with a subkey instead of the ghost key, for the purpose of the subkey")
+                        .requires(ARG_SUBKEY_SIGNING_KEY)
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new(ARG_SUBKEY_SIGNING_KEY)
+                        .long(ARG_SUBKEY_SIGNING_KEY)
+                        .help("The file containing the subkey signing key")
+                        .requires(ARG_SUBKEY_CERTIFICATE)
                         .value_name("FILE"),
                 )
                 .arg(
-                    Arg::new("message")
-                        .long("message")
+                    Arg::new(ARG_MESSAGE)
+                        .long(ARG_MESSAGE)
                         .help("The message to sign (either a file path or a string)")
                         .required(true)
                         .value_name("MESSAGE"),
                 )
+                .arg(
+                    Arg::new(ARG_PURPOSE)
+                        .long(ARG_PURPOSE)
+                        .help("What the message is signed for, the signature is not valid for any other purpose")
+                        .default_value(DEFAULT_PURPOSE)
+                        .conflicts_with(ARG_SUBKEY_CERTIFICATE)
+                        .value_name("STRING"),
+                )
+                .arg(
+                    Arg::new(ARG_CONTENT_TYPE)
+                        .long(ARG_CONTENT_TYPE)
+                        .help("The media type of the message, such as text/plain")
+                        .required(false)
+                        .value_name("TYPE"),
+                )
+                .arg(
+                    Arg::new(ARG_DETACHED)
+                        .long(ARG_DETACHED)
+                        .help("Write only the signature, without the message")
+                        .action(ArgAction::SetTrue),
+                )
                 .arg(
                     Arg::new("output")
                         .long("output")
@@ -192,113 +613,862 @@ fn run() -> i32 {
                         .required(false)
                         .value_name("FILE"),
                 )
+                .arg(
+                    Arg::new(ARG_TRUST_STORE)
+                        .long(ARG_TRUST_STORE)
+                        .help("Optionally verify against the master verifying keys in this trust store")
+                        .required(false)
+                        .conflicts_with("master_verifying_key")
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new(ARG_MESSAGE)
+                        .long(ARG_MESSAGE)
+                        .help("The message a detached signature was made over (either a file path or a string)")
+                        .required(false)
+                        .value_name("MESSAGE"),
+                )
+                .arg(
+                    Arg::new(ARG_PURPOSE)
+                        .long(ARG_PURPOSE)
+                        .help("Fail unless the message was signed for this purpose")
+                        .required(false)
+                        .value_name("STRING"),
+                )
                 .arg(
                     Arg::new("output")
                         .long("output")
                         .help("The file to output the verified message (if not provided, the message will be printed to stdout)")
                         .required(false)
                         .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new(ARG_REVOCATION_LIST)
+                        .long(ARG_REVOCATION_LIST)
+                        .help("Optionally reject certificates issued by delegates in this revocation list")
+                        .required(false)
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new(ARG_ALLOW_V1)
+                        .long(ARG_ALLOW_V1)
+                        .help("Also accept V1 signed messages from earlier releases, whose signature any other signature by the ghost key over the same bytes passes as")
+                        .action(ArgAction::SetTrue),
                 ),
         )
-        .get_matches();
-
-    match matches.subcommand() {
-        Some((CMD_GENERATE_MASTER_KEY, sub_matches)) => {
-            let output_dir = Path::new(sub_matches.get_one::<String>(ARG_OUTPUT_DIR).unwrap());
-
-            if let Err(e) = std::fs::create_dir_all(output_dir) {
-                eprintln!("{} to create output directory: {}", "Failed".red(), e);
-                return 1;
-            }
-
-            let ignore_permissions = sub_matches.get_flag(ARG_IGNORE_PERMISSIONS);
-
-            let result = generate_master_key_cmd(output_dir, ignore_permissions);
-            if result == 0 {
-                println!(
-                    "{}",
-                    "Master key generation completed successfully.".green()
-                );
-            }
-            result
-        }
-        Some((CMD_GENERATE_DELEGATE, sub_matches)) => {
-            let master_signing_key_file = Path::new(
-                sub_matches
-                    .get_one::<String>(ARG_MASTER_SIGNING_KEY)
-                    .unwrap(),
-            );
-            let master_signing_key = match SigningKey::from_file(master_signing_key_file) {
-                Ok(key) => key,
-                Err(e) => {
-                    eprintln!("{} to read master signing key: {}", "Failed".red(), e);
-                    return 1;
-                }
-            };
-            let info = sub_matches.get_one::<String>(ARG_INFO).unwrap();
-            let output_dir = Path::new(sub_matches.get_one::<String>(ARG_OUTPUT_DIR).unwrap());
-            if let Err(e) = std::fs::create_dir_all(output_dir) {
-                eprintln!("{} to create output directory: {}", "Failed".red(), e);
-                return 1;
-            }
-
-            let ignore_permissions = sub_matches.get_flag(ARG_IGNORE_PERMISSIONS);
-
-            let result =
-                generate_delegate_cmd(&master_signing_key, info, output_dir, ignore_permissions);
-            if result == 0 {
-                println!(
-                    "{}",
-                    "Delegate key generation completed successfully.".green()
-                );
-            }
-            result
-        }
-        Some((CMD_VERIFY_DELEGATE, sub_matches)) => {
-            let master_verifying_key : Option<VerifyingKey> = if let Some(key_file) = sub_matches.get_one::<String>(ARG_MASTER_VERIFYING_KEY) {
-                match VerifyingKey::from_file(Path::new(key_file)) {
-                    Ok(key) => Some(key),
-                    Err(e) => {
-                        println!("{} to read master verifying key: {}", "Failed".red(), e);
-                        return 1;
-                    }
-                }
-            } else {
-                None
-            };
-            let delegate_certificate_file = Path::new(
-                sub_matches
-                    .get_one::<String>(ARG_DELEGATE_CERTIFICATE)
-                    .unwrap(),
+        .subcommand(
+            Command::new(CMD_MINT_SUBKEY)
+                .about("Mints a short-lived subkey certified by a ghost key, to sign messages for one purpose")
+                .arg(
+                    Arg::new("ghost_key")
+                        .long("ghost-key")
+                        .help("A bundle file containing both the ghost certificate and the ghost signing key, such as the key copied from the donation page")
+                        .conflicts_with_all(["ghost_certificate", "ghost_signing_key"])
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new("ghost_certificate")
+                        .long("ghost-certificate")
+                        .help("The file containing the ghost certificate")
+                        .required_unless_present("ghost_key")
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new("ghost_signing_key")
+                        .long("ghost-signing-key")
+                        .help("The file containing the ghost signing key")
+                        .required_unless_present("ghost_key")
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new(ARG_PURPOSE)
+                        .long(ARG_PURPOSE)
+                        .help("What the subkey may sign for, such as the server it is used with")
+                        .required(true)
+                        .value_name("STRING"),
+                )
+                .arg(
+                    Arg::new(ARG_VALID_HOURS)
+                        .long(ARG_VALID_HOURS)
+                        .help("Number of hours from now the subkey remains valid")
+                        .default_value("24")
+                        .value_parser(clap::value_parser!(u64))
+                        .value_name("HOURS"),
+                )
+                .arg(
+                    Arg::new(ARG_OUTPUT_DIR)
+                        .long(ARG_OUTPUT_DIR)
+                        .help("The directory to output the subkey certificate and signing key")
+                        .required(true)
+                        .value_name("DIR"),
+                )
+                .arg(
+                    Arg::new(ARG_ENCRYPT)
+                        .long(ARG_ENCRYPT)
+                        .help("Encrypt the signing key with a passphrase (prompted, or read from GHOSTKEY_PASSPHRASE)")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new(CMD_VERIFY_SUBKEY)
+                .about("Verifies a subkey certificate and the ghost key that certified it")
+                .arg(
+                    Arg::new(ARG_MASTER_VERIFYING_KEY)
+                        .long(ARG_MASTER_VERIFYING_KEY)
+                        .help("Optionally override the master verifying key")
+                        .required(false)
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new(ARG_TRUST_STORE)
+                        .long(ARG_TRUST_STORE)
+                        .help("Optionally verify against the master verifying keys in this trust store")
+                        .required(false)
+                        .conflicts_with(ARG_MASTER_VERIFYING_KEY)
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new(ARG_SUBKEY_CERTIFICATE)
+                        .long(ARG_SUBKEY_CERTIFICATE)
+                        .help("The file containing the subkey certificate")
+                        .required(true)
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new(ARG_PURPOSE)
+                        .long(ARG_PURPOSE)
+                        .help("Fail unless the subkey is for this purpose")
+                        .required(false)
+                        .value_name("STRING"),
+                )
+                .arg(
+                    Arg::new(ARG_REVOCATION_LIST)
+                        .long(ARG_REVOCATION_LIST)
+                        .help("Optionally reject certificates issued by delegates in this revocation list")
+                        .required(false)
+                        .value_name("FILE"),
+                ),
+        )
+        .subcommand(
+            Command::new(CMD_DERIVE_PSEUDONYM)
+                .about("Derives the pseudonym of a ghost key in a context, proven to belong to one of a ring of ghost keys without revealing which")
+                .arg(
+                    Arg::new("ghost_key")
+                        .long("ghost-key")
+                        .help("A bundle file containing both the ghost certificate and the ghost signing key, such as the key copied from the donation page")
+                        .conflicts_with_all(["ghost_certificate", "ghost_signing_key"])
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new("ghost_certificate")
+                        .long("ghost-certificate")
+                        .help("The file containing the ghost certificate")
+                        .required_unless_present("ghost_key")
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new("ghost_signing_key")
+                        .long("ghost-signing-key")
+                        .help("The file containing the ghost signing key")
+                        .required_unless_present("ghost_key")
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new(ARG_CONTEXT)
+                        .long(ARG_CONTEXT)
+                        .help("What the pseudonym is for, such as an app or a poll, the ghost key always has the same pseudonym in the same context")
+                        .required(true)
+                        .value_name("STRING"),
+                )
+                .arg(
+                    Arg::new(ARG_RING)
+                        .long(ARG_RING)
+                        .help("A file of ghost key certificates from the same delegate to hide the ghost key among, may be repeated")
+                        .required(true)
+                        .action(ArgAction::Append)
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new(ARG_OUTPUT_DIR)
+                        .long(ARG_OUTPUT_DIR)
+                        .help("The directory to output the pseudonym certificate and signing key")
+                        .required(true)
+                        .value_name("DIR"),
+                )
+                .arg(
+                    Arg::new(ARG_ENCRYPT)
+                        .long(ARG_ENCRYPT)
+                        .help("Encrypt the signing key with a passphrase (prompted, or read from GHOSTKEY_PASSPHRASE)")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new(CMD_SIGN_AS_PSEUDONYM)
+                .about("Signs a message with a pseudonym key")
+                .arg(
+                    Arg::new(ARG_PSEUDONYM_CERTIFICATE)
+                        .long(ARG_PSEUDONYM_CERTIFICATE)
+                        .help("The file containing the pseudonym certificate")
+                        .required(true)
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new(ARG_PSEUDONYM_SIGNING_KEY)
+                        .long(ARG_PSEUDONYM_SIGNING_KEY)
+                        .help("The file containing the pseudonym signing key")
+                        .required(true)
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new(ARG_MESSAGE)
+                        .long(ARG_MESSAGE)
+                        .help("The message to sign (either a file path or a string)")
+                        .required(true)
+                        .value_name("MESSAGE"),
+                )
+                .arg(
+                    Arg::new(ARG_OUTPUT)
+                        .long(ARG_OUTPUT)
+                        .help("The file to output the signed message")
+                        .required(true)
+                        .value_name("FILE"),
+                ),
+        )
+        .subcommand(
+            Command::new(CMD_VERIFY_PSEUDONYM)
+                .about("Verifies a pseudonym certificate, or a message signed by a pseudonym")
+                .arg(
+                    Arg::new(ARG_MASTER_VERIFYING_KEY)
+                        .long(ARG_MASTER_VERIFYING_KEY)
+                        .help("Optionally override the master verifying key")
+                        .required(false)
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new(ARG_TRUST_STORE)
+                        .long(ARG_TRUST_STORE)
+                        .help("Optionally verify against the master verifying keys in this trust store")
+                        .required(false)
+                        .conflicts_with(ARG_MASTER_VERIFYING_KEY)
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new(ARG_PSEUDONYM_CERTIFICATE)
+                        .long(ARG_PSEUDONYM_CERTIFICATE)
+                        .help("The file containing the pseudonym certificate")
+                        .required_unless_present(ARG_SIGNED_MESSAGE)
+                        .conflicts_with(ARG_SIGNED_MESSAGE)
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new(ARG_SIGNED_MESSAGE)
+                        .long(ARG_SIGNED_MESSAGE)
+                        .help("The file containing a message signed by a pseudonym")
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new(ARG_CONTEXT)
+                        .long(ARG_CONTEXT)
+                        .help("Fail unless the pseudonym is for this context")
+                        .required(false)
+                        .value_name("STRING"),
+                )
+                .arg(
+                    Arg::new(ARG_OUTPUT)
+                        .long(ARG_OUTPUT)
+                        .help("The file to output the verified message (if not provided, the message will be printed to stdout)")
+                        .required(false)
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new(ARG_REVOCATION_LIST)
+                        .long(ARG_REVOCATION_LIST)
+                        .help("Optionally reject certificates issued by delegates in this revocation list")
+                        .required(false)
+                        .value_name("FILE"),
+                ),
+        )
+        .subcommand(
+            Command::new(CMD_GENERATE_REVOCATION_LIST)
+                .about("Generates a revocation list of delegate certificates signed by the master signing key")
+                .arg(
+                    Arg::new(ARG_MASTER_SIGNING_KEY)
+                        .long(ARG_MASTER_SIGNING_KEY)
+                        .help("The file containing the master signing key")
+                        .required(true)
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new(ARG_REVOKE)
+                        .long(ARG_REVOKE)
+                        .help("A delegate certificate file to revoke, may be repeated")
+                        .required(false)
+                        .action(ArgAction::Append)
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new(ARG_REASON)
+                        .long(ARG_REASON)
+                        .help("The reason for revoking the delegates")
+                        .required(false)
+                        .default_value("unspecified")
+                        .value_name("STRING"),
+                )
+                .arg(
+                    Arg::new(ARG_REVOCATION_LIST)
+                        .long(ARG_REVOCATION_LIST)
+                        .help("An existing revocation list whose entries are carried over")
+                        .required(false)
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new(ARG_NEXT_UPDATE_DAYS)
+                        .long(ARG_NEXT_UPDATE_DAYS)
+                        .help("Number of days until the revocation list must be replaced")
+                        .required(false)
+                        .default_value("30")
+                        .value_parser(clap::value_parser!(u64))
+                        .value_name("DAYS"),
+                )
+                .arg(
+                    Arg::new(ARG_OUTPUT)
+                        .long(ARG_OUTPUT)
+                        .help("The file to output the revocation list")
+                        .required(true)
+                        .value_name("FILE"),
+                ),
+        )
+        .subcommand(
+            Command::new(CMD_ADD_TRUSTED_KEY)
+                .about("Adds a named master verifying key to a trust store, creating it if needed")
+                .arg(
+                    Arg::new(ARG_TRUST_STORE)
+                        .long(ARG_TRUST_STORE)
+                        .help("The trust store file to create or update")
+                        .required(true)
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new(ARG_NAME)
+                        .long(ARG_NAME)
+                        .help("The name of the key, e.g. current, next or test")
+                        .required(true)
+                        .value_name("STRING"),
+                )
+                .arg(
+                    Arg::new(ARG_MASTER_VERIFYING_KEY)
+                        .long(ARG_MASTER_VERIFYING_KEY)
+                        .help("The file containing the master verifying key")
+                        .required(true)
+                        .value_name("FILE"),
+                ),
+        )
+        .subcommand(
+            Command::new(CMD_CHANGE_PASSPHRASE)
+                .about("Encrypts a private key file with a new passphrase, or removes its encryption")
+                .arg(
+                    Arg::new(ARG_KEY)
+                        .long(ARG_KEY)
+                        .help("The private key file, encrypted or not")
+                        .required(true)
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new(ARG_REMOVE_ENCRYPTION)
+                        .long(ARG_REMOVE_ENCRYPTION)
+                        .help("Write the key back without encryption")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new(CMD_SPLIT_KEY)
+                .about("Splits a private key file into shares, any threshold of which rebuild it with combine-key")
+                .arg(
+                    Arg::new(ARG_KEY)
+                        .long(ARG_KEY)
+                        .help("The private key file, encrypted or not")
+                        .required(true)
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new(ARG_THRESHOLD)
+                        .long(ARG_THRESHOLD)
+                        .help("How many shares rebuild the key")
+                        .required(true)
+                        .value_parser(clap::value_parser!(u8))
+                        .value_name("K"),
+                )
+                .arg(
+                    Arg::new(ARG_SHARES)
+                        .long(ARG_SHARES)
+                        .help("How many shares to split the key into")
+                        .required(true)
+                        .value_parser(clap::value_parser!(u8))
+                        .value_name("N"),
+                )
+                .arg(
+                    Arg::new(ARG_OUTPUT_DIR)
+                        .long(ARG_OUTPUT_DIR)
+                        .help("The directory to output the key shares")
+                        .required(true)
+                        .value_name("DIR"),
+                ),
+        )
+        .subcommand(
+            Command::new(CMD_COMBINE_KEY)
+                .about("Rebuilds a private key file from shares written by split-key")
+                .arg(
+                    Arg::new(ARG_SHARE)
+                        .long(ARG_SHARE)
+                        .help("A key share file from split-key, repeated for each share")
+                        .required(true)
+                        .action(ArgAction::Append)
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new(ARG_OUTPUT)
+                        .long(ARG_OUTPUT)
+                        .help("The file to write the rebuilt key to")
+                        .required(true)
+                        .value_name("FILE"),
+                ),
+        )
+        .subcommand(
+            Command::new(CMD_EXPORT_QR)
+                .about("Shows a ghost key certificate as a QR code, in a compact encoding that import-qr reads back")
+                .arg(
+                    Arg::new(ARG_GHOST_CERTIFICATE)
+                        .long(ARG_GHOST_CERTIFICATE)
+                        .help("The file containing the ghost certificate")
+                        .required(true)
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new(ARG_ELIDE_DELEGATE)
+                        .long(ARG_ELIDE_DELEGATE)
+                        .help("Leave out the delegate certificate, for importers that already have it")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new(ARG_PNG)
+                        .long(ARG_PNG)
+                        .help("Write the QR code to a PNG image instead of the terminal")
+                        .required(false)
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new(ARG_TEXT)
+                        .long(ARG_TEXT)
+                        .help("Also write the text the QR code holds to a file")
+                        .required(false)
+                        .value_name("FILE"),
+                ),
+        )
+        .subcommand(
+            Command::new(CMD_IMPORT_QR)
+                .about("Rebuilds a full ghost key certificate from the text of a QR code made by export-qr")
+                .arg(
+                    Arg::new(ARG_COMPACT)
+                        .long(ARG_COMPACT)
+                        .help("The text of the QR code, either a file path or the text itself")
+                        .required(true)
+                        .value_name("TEXT"),
+                )
+                .arg(
+                    Arg::new(ARG_DELEGATE_CERTIFICATE)
+                        .long(ARG_DELEGATE_CERTIFICATE)
+                        .help("A delegate certificate to take the left out delegate from, may be repeated")
+                        .action(ArgAction::Append)
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new(ARG_OUTPUT)
+                        .long(ARG_OUTPUT)
+                        .help("The file to write the ghost certificate to")
+                        .required(true)
+                        .value_name("FILE"),
+                ),
+        )
+        .subcommand(
+            Command::new(CMD_CONVERT)
+                .about("Converts a key, certificate or signed message between armor, CBOR and JSON with JWK keys")
+                .arg(
+                    Arg::new(ARG_INPUT)
+                        .long(ARG_INPUT)
+                        .help("The file to convert, in any of the formats")
+                        .required(true)
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new(ARG_TO)
+                        .long(ARG_TO)
+                        .help("The format to convert to")
+                        .required(true)
+                        .value_parser(["json", "armor", "cbor"]),
+                )
+                .arg(
+                    Arg::new(ARG_OUTPUT)
+                        .long(ARG_OUTPUT)
+                        .help("The file to write the result to, stdout if not given")
+                        .required(false)
+                        .value_name("FILE"),
+                ),
+        )
+        .subcommand(
+            Command::new(CMD_EXPORT_SSH_KEY)
+                .about("Exports a ghost key as an OpenSSH key pair, for ssh-keygen -Y sign and git commit signing")
+                .arg(
+                    Arg::new("ghost_key")
+                        .long("ghost-key")
+                        .help("A bundle file containing both the ghost certificate and the ghost signing key, such as the key copied from the donation page")
+                        .conflicts_with_all(["ghost_certificate", "ghost_signing_key"])
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new("ghost_certificate")
+                        .long("ghost-certificate")
+                        .help("The file containing the ghost certificate")
+                        .required_unless_present("ghost_key")
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new("ghost_signing_key")
+                        .long("ghost-signing-key")
+                        .help("The file containing the ghost signing key")
+                        .required_unless_present("ghost_key")
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new(ARG_OUTPUT_DIR)
+                        .long(ARG_OUTPUT_DIR)
+                        .help("The directory to write ghost_key.pub and the unencrypted private key ghost_key to")
+                        .required(true)
+                        .value_name("DIR"),
+                ),
+        )
+        .subcommand(
+            Command::new(CMD_SSH_SIGN)
+                .about("Signs a message with a ghost key as ssh-keygen -Y sign does, writing an SSH signature")
+                .arg(
+                    Arg::new("ghost_key")
+                        .long("ghost-key")
+                        .help("A bundle file containing both the ghost certificate and the ghost signing key, such as the key copied from the donation page")
+                        .conflicts_with_all(["ghost_certificate", "ghost_signing_key"])
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new("ghost_certificate")
+                        .long("ghost-certificate")
+                        .help("The file containing the ghost certificate")
+                        .required_unless_present("ghost_key")
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new("ghost_signing_key")
+                        .long("ghost-signing-key")
+                        .help("The file containing the ghost signing key")
+                        .required_unless_present("ghost_key")
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new(ARG_NAMESPACE)
+                        .long(ARG_NAMESPACE)
+                        .help("What the signature is for, as in ssh-keygen -Y, such as git or file")
+                        .required(true)
+                        .value_name("STRING"),
+                )
+                .arg(
+                    Arg::new(ARG_MESSAGE)
+                        .long(ARG_MESSAGE)
+                        .help("The message to sign (either a file path or a string)")
+                        .required(true)
+                        .value_name("MESSAGE"),
+                )
+                .arg(
+                    Arg::new(ARG_OUTPUT)
+                        .long(ARG_OUTPUT)
+                        .help("The file to write the SSH signature to")
+                        .required(true)
+                        .value_name("FILE"),
+                ),
+        )
+        .subcommand(
+            Command::new(CMD_SSH_VERIFY)
+                .about("Verifies an SSH signature and the ghost key certificate carried by the signer's public key")
+                .arg(
+                    Arg::new(ARG_SIGNATURE)
+                        .long(ARG_SIGNATURE)
+                        .help("The file containing the SSH signature")
+                        .required(true)
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new(ARG_PUBLIC_KEY)
+                        .long(ARG_PUBLIC_KEY)
+                        .help("The ghost_key.pub file written by export-ssh-key")
+                        .required(true)
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new(ARG_NAMESPACE)
+                        .long(ARG_NAMESPACE)
+                        .help("What the signature is for, as in ssh-keygen -Y, such as git or file")
+                        .required(true)
+                        .value_name("STRING"),
+                )
+                .arg(
+                    Arg::new(ARG_MESSAGE)
+                        .long(ARG_MESSAGE)
+                        .help("The signed message (either a file path or a string)")
+                        .required(true)
+                        .value_name("MESSAGE"),
+                )
+                .arg(
+                    Arg::new(ARG_MASTER_VERIFYING_KEY)
+                        .long(ARG_MASTER_VERIFYING_KEY)
+                        .help("Optionally override the master verifying key")
+                        .required(false)
+                        .value_name("FILE"),
+                )
+                .arg(
+                    Arg::new(ARG_TRUST_STORE)
+                        .long(ARG_TRUST_STORE)
+                        .help("Optionally verify against the master verifying keys in this trust store")
+                        .required(false)
+                        .conflicts_with(ARG_MASTER_VERIFYING_KEY)
+                        .value_name("FILE"),
+                ),
+        )
+        .get_matches();
+
+    match matches.subcommand() {
+        Some((CMD_GENERATE_MASTER_KEY, sub_matches)) => {
+            let output_dir = Path::new(sub_matches.get_one::<String>(ARG_OUTPUT_DIR).unwrap());
+
+            if let Err(e) = std::fs::create_dir_all(output_dir) {
+                eprintln!("{} to create output directory: {}", "Failed".red(), e);
+                return 1;
+            }
+
+            let ignore_permissions = sub_matches.get_flag(ARG_IGNORE_PERMISSIONS);
+
+            let encrypt = sub_matches.get_flag(ARG_ENCRYPT);
+
+            let result = generate_master_key_cmd(output_dir, ignore_permissions, encrypt);
+            if result == 0 {
+                println!(
+                    "{}",
+                    "Master key generation completed successfully.".green()
+                );
+            }
+            result
+        }
+        Some((CMD_GENERATE_DELEGATE, sub_matches)) => {
+            let info = sub_matches.get_one::<String>(ARG_INFO);
+            let details = sub_matches.get_one::<u64>(ARG_AMOUNT).map(|amount| DelegateDetails {
+                amount: *amount,
+                currency: sub_matches.get_one::<String>(ARG_CURRENCY).unwrap().clone(),
+                tier: sub_matches.get_one::<String>(ARG_TIER).unwrap().clone(),
+                issuer: sub_matches.get_one::<String>(ARG_ISSUER).unwrap().clone(),
+                created: current_unix_time(),
+            });
+            let valid_days = sub_matches.get_one::<u64>(ARG_VALID_DAYS).copied();
+            let output_dir = Path::new(sub_matches.get_one::<String>(ARG_OUTPUT_DIR).unwrap());
+            if let Err(e) = std::fs::create_dir_all(output_dir) {
+                eprintln!("{} to create output directory: {}", "Failed".red(), e);
+                return 1;
+            }
+
+            if sub_matches.get_flag(ARG_REQUEST) {
+                return generate_delegate_request_cmd(
+                    info,
+                    details.as_ref(),
+                    sub_matches.get_flag(ARG_PARTIALLY_BLIND),
+                    valid_days,
+                    output_dir,
+                    sub_matches.get_flag(ARG_ENCRYPT),
+                );
+            }
+            let master_signing_key_file = Path::new(
+                sub_matches
+                    .get_one::<String>(ARG_MASTER_SIGNING_KEY)
+                    .unwrap(),
+            );
+            let master_signing_key = match load_private_key::<SigningKey>(master_signing_key_file) {
+                Ok(key) => key,
+                Err(e) => {
+                    eprintln!("{} to read master signing key: [{}] {}", "Failed".red(), e.code(), e);
+                    return 1;
+                }
+            };
+
+            let ignore_permissions = sub_matches.get_flag(ARG_IGNORE_PERMISSIONS);
+
+            let result =
+                generate_delegate_cmd(
+                    &master_signing_key,
+                    info,
+                    details.as_ref(),
+                    sub_matches.get_flag(ARG_PARTIALLY_BLIND),
+                    valid_days,
+                    output_dir,
+                    ignore_permissions,
+                    sub_matches.get_flag(ARG_ENCRYPT),
+                );
+            if result == 0 {
+                println!(
+                    "{}",
+                    "Delegate key generation completed successfully.".green()
+                );
+            }
+            result
+        }
+        Some((CMD_GENERATE_MASTER_KEY_SHARES, sub_matches)) => {
+            let master_signing_key = match sub_matches.get_one::<String>(ARG_MASTER_SIGNING_KEY) {
+                Some(file) => match load_private_key::<SigningKey>(Path::new(file)) {
+                    Ok(key) => Some(key),
+                    Err(e) => {
+                        eprintln!("{} to read master signing key: [{}] {}", "Failed".red(), e.code(), e);
+                        return 1;
+                    }
+                },
+                None => None,
+            };
+            let output_dir = Path::new(sub_matches.get_one::<String>(ARG_OUTPUT_DIR).unwrap());
+            if let Err(e) = std::fs::create_dir_all(output_dir) {
+                eprintln!("{} to create output directory: {}", "Failed".red(), e);
+                return 1;
+            }
+            generate_master_key_shares_cmd(
+                master_signing_key.as_ref(),
+                *sub_matches.get_one::<u16>(ARG_THRESHOLD).unwrap(),
+                *sub_matches.get_one::<u16>(ARG_SHARES).unwrap(),
+                output_dir,
+                sub_matches.get_flag(ARG_ENCRYPT),
+            )
+        }
+        Some((CMD_THRESHOLD_COMMIT, sub_matches)) => {
+            let key_share = match read_key_share(sub_matches) {
+                Ok(key_share) => key_share,
+                Err(code) => return code,
+            };
+            let output_dir = Path::new(sub_matches.get_one::<String>(ARG_OUTPUT_DIR).unwrap());
+            if let Err(e) = std::fs::create_dir_all(output_dir) {
+                eprintln!("{} to create output directory: {}", "Failed".red(), e);
+                return 1;
+            }
+            threshold_commit_cmd(&key_share, output_dir, sub_matches.get_flag(ARG_ENCRYPT))
+        }
+        Some((CMD_THRESHOLD_PACKAGE, sub_matches)) => {
+            let request_file = Path::new(sub_matches.get_one::<String>(ARG_REQUEST).unwrap());
+            let request = match DelegateCertificateRequestV1::from_file(request_file) {
+                Ok(request) => request,
+                Err(e) => {
+                    eprintln!("{} to read delegate certificate request: [{}] {}", "Failed".red(), e.code(), e);
+                    return 1;
+                }
+            };
+            let mut commitments = Vec::new();
+            for file in sub_matches.get_many::<String>(ARG_COMMITMENT).into_iter().flatten() {
+                match SigningCommitmentV1::from_file(Path::new(file)) {
+                    Ok(commitment) => commitments.push(commitment),
+                    Err(e) => {
+                        eprintln!("{} to read signing commitment {}: [{}] {}", "Failed".red(), file, e.code(), e);
+                        return 1;
+                    }
+                }
+            }
+            let output_file = Path::new(sub_matches.get_one::<String>(ARG_OUTPUT).unwrap());
+            threshold_package_cmd(&request, commitments, output_file)
+        }
+        Some((CMD_THRESHOLD_SIGN, sub_matches)) => {
+            let key_share = match read_key_share(sub_matches) {
+                Ok(key_share) => key_share,
+                Err(code) => return code,
+            };
+            let nonces_file = Path::new(sub_matches.get_one::<String>(ARG_NONCES).unwrap());
+            let nonces = match load_private_key::<SigningNoncesV1>(nonces_file) {
+                Ok(nonces) => nonces,
+                Err(e) => {
+                    eprintln!("{} to read signing nonces: [{}] {}", "Failed".red(), e.code(), e);
+                    return 1;
+                }
+            };
+            let (request, package) = match read_signing_package(sub_matches) {
+                Ok(package) => package,
+                Err(code) => return code,
+            };
+            let output_dir = Path::new(sub_matches.get_one::<String>(ARG_OUTPUT_DIR).unwrap());
+            if let Err(e) = std::fs::create_dir_all(output_dir) {
+                eprintln!("{} to create output directory: {}", "Failed".red(), e);
+                return 1;
+            }
+            threshold_sign_cmd(&key_share, nonces, nonces_file, &request, &package, output_dir)
+        }
+        Some((CMD_THRESHOLD_AGGREGATE, sub_matches)) => {
+            let (request, package) = match read_signing_package(sub_matches) {
+                Ok(package) => package,
+                Err(code) => return code,
+            };
+            let mut signature_shares = Vec::new();
+            for file in sub_matches.get_many::<String>(ARG_SIGNATURE_SHARE).into_iter().flatten() {
+                match SignatureShareV1::from_file(Path::new(file)) {
+                    Ok(signature_share) => signature_shares.push(signature_share),
+                    Err(e) => {
+                        eprintln!("{} to read signature share {}: [{}] {}", "Failed".red(), file, e.code(), e);
+                        return 1;
+                    }
+                }
+            }
+            let output_dir = Path::new(sub_matches.get_one::<String>(ARG_OUTPUT_DIR).unwrap());
+            if let Err(e) = std::fs::create_dir_all(output_dir) {
+                eprintln!("{} to create output directory: {}", "Failed".red(), e);
+                return 1;
+            }
+            threshold_aggregate_cmd(request, &package, &signature_shares, output_dir)
+        }
+        Some((CMD_VERIFY_DELEGATE, sub_matches)) => {
+            let trust_store = match read_trust_store(sub_matches, ARG_MASTER_VERIFYING_KEY) {
+                Ok(trust_store) => trust_store,
+                Err(code) => return code,
+            };
+            let delegate_certificate_file = Path::new(
+                sub_matches
+                    .get_one::<String>(ARG_DELEGATE_CERTIFICATE)
+                    .unwrap(),
             );
             let delegate_certificate =
-                match DelegateCertificateV1::from_file(delegate_certificate_file) {
+                match DelegateCertificate::from_file(delegate_certificate_file) {
                     Ok(cert) => cert,
                     Err(e) => {
                         println!("{} to read delegate certificate: {}", "Failed".red(), e);
                         return 1;
                     }
                 };
-            verify_delegate_cmd(&master_verifying_key, &delegate_certificate)
+            let revocation_list = match read_revocation_list(sub_matches) {
+                Ok(list) => list,
+                Err(code) => return code,
+            };
+            verify_delegate_cmd(&trust_store, &delegate_certificate, revocation_list.as_ref())
         }
         Some((CMD_GENERATE_GHOST_KEY, sub_matches)) => {
             let delegate_dir = sub_matches.get_one::<String>(ARG_DELEGATE_DIR).unwrap();
             let delegate_certificate_file =
                 Path::new(delegate_dir).join("delegate_certificate.pem");
             let delegate_certificate =
-                match DelegateCertificateV1::from_file(&delegate_certificate_file) {
+                match DelegateCertificate::from_file(&delegate_certificate_file) {
                     Ok(cert) => cert,
                     Err(e) => {
-                        eprintln!("{} to read delegate certificate: {}", "Failed".red(), e);
+                        eprintln!("{} to read delegate certificate: [{}] {}", "Failed".red(), e.code(), e);
                         return 1;
                     }
                 };
             let delegate_signing_key_file =
                 Path::new(delegate_dir).join("delegate_signing_key.pem");
-            let delegate_signing_key = match RSASigningKey::from_file(&delegate_signing_key_file) {
+            let delegate_signing_key = match load_private_key::<RSASigningKey>(&delegate_signing_key_file) {
                 Ok(key) => key,
                 Err(e) => {
-                    eprintln!("{} to read delegate signing key: {}", "Failed".red(), e);
+                    eprintln!("{} to read delegate signing key: [{}] {}", "Failed".red(), e.code(), e);
                     return 1;
                 }
             };
@@ -309,81 +1479,478 @@ fn run() -> i32 {
                 return 1;
             }
 
-            generate_ghost_key_cmd(&delegate_certificate, &delegate_signing_key, &output_dir)
+            let encrypt = sub_matches.get_flag(ARG_ENCRYPT);
+
+            generate_ghost_key_cmd(
+                &delegate_certificate,
+                &delegate_signing_key,
+                output_dir,
+                encrypt,
+                sub_matches.get_flag(ARG_SHOW_MNEMONIC),
+            )
         }
-        Some((CMD_VERIFY_GHOST_KEY, sub_matches)) => {
-            let master_verifying_key : Option<VerifyingKey> = if let Some(key_file) = sub_matches.get_one::<String>(ARG_MASTER_VERIFYING_KEY) {
-                match VerifyingKey::from_file(Path::new(key_file)) {
-                    Ok(key) => Some(key),
+        Some((CMD_RESTORE, sub_matches)) => {
+            let ghost_certificate_file = Path::new(sub_matches.get_one::<String>(ARG_GHOST_CERTIFICATE).unwrap());
+            let ghost_certificate = match GhostkeyCertificate::from_file(ghost_certificate_file) {
+                Ok(cert) => cert,
+                Err(e) => {
+                    eprintln!("{} to read ghost certificate: [{}] {}", "Failed".red(), e.code(), e);
+                    return 1;
+                }
+            };
+            let mnemonic = match sub_matches.get_one::<String>(ARG_MNEMONIC) {
+                Some(mnemonic) => match read_message(mnemonic) {
+                    Ok(content) => String::from_utf8_lossy(&content).into_owned(),
+                    Err(code) => return code,
+                },
+                None => match rpassword::prompt_password("Recovery phrase: ") {
+                    Ok(mnemonic) => mnemonic,
                     Err(e) => {
-                        eprintln!("{} to read master verifying key: {}", "Failed".red(), e);
+                        eprintln!("{} to read recovery phrase: {}", "Failed".red(), e);
                         return 1;
                     }
-                }
-            } else {
-                None
+                },
+            };
+            let output_dir = Path::new(sub_matches.get_one::<String>(ARG_OUTPUT_DIR).unwrap());
+            if let Err(e) = std::fs::create_dir_all(output_dir) {
+                eprintln!("{} to create output directory: {}", "Failed".red(), e);
+                return 1;
+            }
+            restore_cmd(&ghost_certificate, &mnemonic, output_dir, sub_matches.get_flag(ARG_ENCRYPT))
+        }
+        Some((CMD_VERIFY_GHOST_KEY, sub_matches)) => {
+            let trust_store = match read_trust_store(sub_matches, ARG_MASTER_VERIFYING_KEY) {
+                Ok(trust_store) => trust_store,
+                Err(code) => return code,
             };
             let ghost_certificate_file = Path::new(
                 sub_matches
                     .get_one::<String>(ARG_GHOST_CERTIFICATE)
                     .unwrap(),
             );
-            let ghost_certificate = match GhostkeyCertificateV1::from_file(ghost_certificate_file) {
+            let ghost_certificate = match GhostkeyCertificate::from_file(ghost_certificate_file) {
                 Ok(cert) => cert,
                 Err(e) => {
-                    eprintln!("{} to read ghost certificate: {}", "Failed".red(), e);
+                    eprintln!("{} to read ghost certificate: [{}] {}", "Failed".red(), e.code(), e);
                     return 1;
                 }
             };
-            verify_ghost_key_cmd(&master_verifying_key, &ghost_certificate)
+            let revocation_list = match read_revocation_list(sub_matches) {
+                Ok(list) => list,
+                Err(code) => return code,
+            };
+            verify_ghost_key_cmd(&trust_store, &ghost_certificate, revocation_list.as_ref())
         }
         Some((CMD_SIGN_MESSAGE, sub_matches)) => {
-            let ghost_certificate_file = Path::new(sub_matches.get_one::<String>("ghost_certificate").unwrap());
-            let ghost_certificate = match GhostkeyCertificateV1::from_file(ghost_certificate_file) {
+            let message_content = match read_message(sub_matches.get_one::<String>(ARG_MESSAGE).unwrap()) {
+                Ok(content) => content,
+                Err(code) => return code,
+            };
+            let output_file = Path::new(sub_matches.get_one::<String>("output").unwrap());
+            if let Some(subkey_certificate_file) = sub_matches.get_one::<String>(ARG_SUBKEY_CERTIFICATE) {
+                let subkey_certificate = match SubkeyCertificateV1::from_file(Path::new(subkey_certificate_file)) {
+                    Ok(cert) => cert,
+                    Err(e) => {
+                        eprintln!("{} to read subkey certificate: [{}] {}", "Failed".red(), e.code(), e);
+                        return 1;
+                    }
+                };
+                let subkey_signing_key_file = Path::new(sub_matches.get_one::<String>(ARG_SUBKEY_SIGNING_KEY).unwrap());
+                let subkey_signing_key = match load_private_key::<SigningKey>(subkey_signing_key_file) {
+                    Ok(key) => key,
+                    Err(e) => {
+                        eprintln!("{} to read subkey signing key: [{}] {}", "Failed".red(), e.code(), e);
+                        return 1;
+                    }
+                };
+                return sign_message_with_subkey_cmd(
+                    subkey_certificate,
+                    &subkey_signing_key,
+                    sub_matches.get_one::<String>(ARG_CONTENT_TYPE).map(|s| s.as_str()),
+                    &message_content,
+                    sub_matches.get_flag(ARG_DETACHED),
+                    output_file,
+                );
+            }
+            let (ghost_certificate, ghost_signing_key) = match read_ghost_key(sub_matches) {
+                Ok(ghost_key) => ghost_key,
+                Err(code) => return code,
+            };
+            sign_message_cmd(
+                ghost_certificate,
+                &ghost_signing_key,
+                sub_matches.get_one::<String>(ARG_PURPOSE).unwrap(),
+                sub_matches.get_one::<String>(ARG_CONTENT_TYPE).map(|s| s.as_str()),
+                &message_content,
+                sub_matches.get_flag(ARG_DETACHED),
+                output_file,
+            )
+        }
+        Some((CMD_VERIFY_SIGNED_MESSAGE, sub_matches)) => {
+            let signed_message_file = Path::new(sub_matches.get_one::<String>("signed_message").unwrap());
+            let trust_store = match read_trust_store(sub_matches, "master_verifying_key") {
+                Ok(trust_store) => trust_store,
+                Err(code) => return code,
+            };
+            let output_file = sub_matches.get_one::<String>("output").map(|s| Path::new(s));
+            let revocation_list = match read_revocation_list(sub_matches) {
+                Ok(list) => list,
+                Err(code) => return code,
+            };
+            let message = match sub_matches.get_one::<String>(ARG_MESSAGE).map(|message| read_message(message)) {
+                Some(Ok(content)) => Some(content),
+                Some(Err(code)) => return code,
+                None => None,
+            };
+            verify_signed_message_cmd(
+                signed_message_file,
+                &trust_store,
+                revocation_list.as_ref(),
+                message.as_deref(),
+                sub_matches.get_one::<String>(ARG_PURPOSE).map(|s| s.as_str()),
+                sub_matches.get_flag(ARG_ALLOW_V1),
+                output_file,
+            )
+        }
+        Some((CMD_GENERATE_REVOCATION_LIST, sub_matches)) => {
+            let master_signing_key_file = Path::new(
+                sub_matches
+                    .get_one::<String>(ARG_MASTER_SIGNING_KEY)
+                    .unwrap(),
+            );
+            let master_signing_key = match load_private_key::<SigningKey>(master_signing_key_file) {
+                Ok(key) => key,
+                Err(e) => {
+                    eprintln!("{} to read master signing key: [{}] {}", "Failed".red(), e.code(), e);
+                    return 1;
+                }
+            };
+            let existing_revocation_list = match read_revocation_list(sub_matches) {
+                Ok(list) => list,
+                Err(code) => return code,
+            };
+            let mut revoked_delegates = Vec::new();
+            if let Some(files) = sub_matches.get_many::<String>(ARG_REVOKE) {
+                for file in files {
+                    match DelegateCertificate::from_file(Path::new(file)) {
+                        Ok(cert) => revoked_delegates.push(cert),
+                        Err(e) => {
+                            eprintln!("{} to read delegate certificate {}: [{}] {}", "Failed".red(), file, e.code(), e);
+                            return 1;
+                        }
+                    }
+                }
+            }
+            let reason = sub_matches.get_one::<String>(ARG_REASON).unwrap();
+            let next_update_days = *sub_matches.get_one::<u64>(ARG_NEXT_UPDATE_DAYS).unwrap();
+            let output_file = Path::new(sub_matches.get_one::<String>(ARG_OUTPUT).unwrap());
+            generate_revocation_list_cmd(
+                &master_signing_key,
+                existing_revocation_list.as_ref(),
+                &revoked_delegates,
+                reason,
+                next_update_days,
+                output_file,
+            )
+        }
+        Some((CMD_MINT_SUBKEY, sub_matches)) => {
+            let (ghost_certificate, ghost_signing_key) = match read_ghost_key(sub_matches) {
+                Ok(ghost_key) => ghost_key,
+                Err(code) => return code,
+            };
+            let output_dir = Path::new(sub_matches.get_one::<String>(ARG_OUTPUT_DIR).unwrap());
+            if let Err(e) = std::fs::create_dir_all(output_dir) {
+                eprintln!("{} to create output directory: {}", "Failed".red(), e);
+                return 1;
+            }
+            let valid_hours = *sub_matches.get_one::<u64>(ARG_VALID_HOURS).unwrap();
+            mint_subkey_cmd(
+                ghost_certificate,
+                &ghost_signing_key,
+                sub_matches.get_one::<String>(ARG_PURPOSE).unwrap(),
+                valid_hours.saturating_mul(60 * 60),
+                output_dir,
+                sub_matches.get_flag(ARG_ENCRYPT),
+            )
+        }
+        Some((CMD_VERIFY_SUBKEY, sub_matches)) => {
+            let trust_store = match read_trust_store(sub_matches, ARG_MASTER_VERIFYING_KEY) {
+                Ok(trust_store) => trust_store,
+                Err(code) => return code,
+            };
+            let subkey_certificate_file = Path::new(sub_matches.get_one::<String>(ARG_SUBKEY_CERTIFICATE).unwrap());
+            let subkey_certificate = match SubkeyCertificateV1::from_file(subkey_certificate_file) {
+                Ok(cert) => cert,
+                Err(e) => {
+                    eprintln!("{} to read subkey certificate: [{}] {}", "Failed".red(), e.code(), e);
+                    return 1;
+                }
+            };
+            let revocation_list = match read_revocation_list(sub_matches) {
+                Ok(list) => list,
+                Err(code) => return code,
+            };
+            verify_subkey_cmd(
+                &trust_store,
+                &subkey_certificate,
+                revocation_list.as_ref(),
+                sub_matches.get_one::<String>(ARG_PURPOSE).map(|s| s.as_str()),
+            )
+        }
+        Some((CMD_DERIVE_PSEUDONYM, sub_matches)) => {
+            let (ghost_certificate, ghost_signing_key) = match read_ghost_key(sub_matches) {
+                Ok(ghost_key) => ghost_key,
+                Err(code) => return code,
+            };
+            let mut ring = Vec::new();
+            for file in sub_matches.get_many::<String>(ARG_RING).into_iter().flatten() {
+                let certificates = ArmoredBundle::from_file(Path::new(file))
+                    .and_then(|bundle| GhostkeyCertificate::all_from_bundle(&bundle));
+                match certificates {
+                    Ok(certificates) if certificates.is_empty() => {
+                        eprintln!("{}: No ghost key certificates in {}", "Error".red(), file);
+                        return 1;
+                    }
+                    Ok(certificates) => ring.extend(certificates),
+                    Err(e) => {
+                        eprintln!("{} to read ghost key certificates {}: [{}] {}", "Failed".red(), file, e.code(), e);
+                        return 1;
+                    }
+                }
+            }
+            if ring.iter().all(|member| member.verifying_key() == ghost_certificate.verifying_key()) {
+                eprintln!(
+                    "{}: The ring holds no ghost keys other than your own, the pseudonym would reveal your ghost key",
+                    "Error".red()
+                );
+                return 1;
+            }
+            let output_dir = Path::new(sub_matches.get_one::<String>(ARG_OUTPUT_DIR).unwrap());
+            if let Err(e) = std::fs::create_dir_all(output_dir) {
+                eprintln!("{} to create output directory: {}", "Failed".red(), e);
+                return 1;
+            }
+            derive_pseudonym_cmd(
+                ghost_certificate,
+                &ghost_signing_key,
+                sub_matches.get_one::<String>(ARG_CONTEXT).unwrap(),
+                &ring,
+                output_dir,
+                sub_matches.get_flag(ARG_ENCRYPT),
+            )
+        }
+        Some((CMD_SIGN_AS_PSEUDONYM, sub_matches)) => {
+            let pseudonym_certificate_file = Path::new(sub_matches.get_one::<String>(ARG_PSEUDONYM_CERTIFICATE).unwrap());
+            let pseudonym_certificate = match PseudonymCertificateV1::from_file(pseudonym_certificate_file) {
                 Ok(cert) => cert,
                 Err(e) => {
-                    eprintln!("{} to read ghost certificate: {}", "Failed".red(), e);
+                    eprintln!("{} to read pseudonym certificate: [{}] {}", "Failed".red(), e.code(), e);
                     return 1;
                 }
             };
-            let ghost_signing_key_file = Path::new(sub_matches.get_one::<String>("ghost_signing_key").unwrap());
-            let ghost_signing_key = match SigningKey::from_file(ghost_signing_key_file) {
+            let pseudonym_signing_key_file = Path::new(sub_matches.get_one::<String>(ARG_PSEUDONYM_SIGNING_KEY).unwrap());
+            let pseudonym_signing_key = match load_private_key::<SigningKey>(pseudonym_signing_key_file) {
                 Ok(key) => key,
                 Err(e) => {
-                    eprintln!("{} to read ghost signing key: {}", "Failed".red(), e);
+                    eprintln!("{} to read pseudonym signing key: [{}] {}", "Failed".red(), e.code(), e);
+                    return 1;
+                }
+            };
+            let message_content = match read_message(sub_matches.get_one::<String>(ARG_MESSAGE).unwrap()) {
+                Ok(content) => content,
+                Err(code) => return code,
+            };
+            let output_file = Path::new(sub_matches.get_one::<String>(ARG_OUTPUT).unwrap());
+            sign_as_pseudonym_cmd(pseudonym_certificate, &pseudonym_signing_key, &message_content, output_file)
+        }
+        Some((CMD_VERIFY_PSEUDONYM, sub_matches)) => {
+            let trust_store = match read_trust_store(sub_matches, ARG_MASTER_VERIFYING_KEY) {
+                Ok(trust_store) => trust_store,
+                Err(code) => return code,
+            };
+            let pseudonym_certificate = match sub_matches.get_one::<String>(ARG_PSEUDONYM_CERTIFICATE) {
+                Some(file) => match PseudonymCertificateV1::from_file(Path::new(file)) {
+                    Ok(cert) => Some(cert),
+                    Err(e) => {
+                        eprintln!("{} to read pseudonym certificate: [{}] {}", "Failed".red(), e.code(), e);
+                        return 1;
+                    }
+                },
+                None => None,
+            };
+            let pseudonym_message = match sub_matches.get_one::<String>(ARG_SIGNED_MESSAGE) {
+                Some(file) => match PseudonymMessageV1::from_file(Path::new(file)) {
+                    Ok(message) => Some(message),
+                    Err(e) => {
+                        eprintln!("{} to read signed message: [{}] {}", "Failed".red(), e.code(), e);
+                        return 1;
+                    }
+                },
+                None => None,
+            };
+            let revocation_list = match read_revocation_list(sub_matches) {
+                Ok(list) => list,
+                Err(code) => return code,
+            };
+            verify_pseudonym_cmd(
+                &trust_store,
+                pseudonym_certificate.as_ref(),
+                pseudonym_message.as_ref(),
+                revocation_list.as_ref(),
+                sub_matches.get_one::<String>(ARG_CONTEXT).map(|s| s.as_str()),
+                sub_matches.get_one::<String>(ARG_OUTPUT).map(Path::new),
+            )
+        }
+        Some((CMD_CHANGE_PASSPHRASE, sub_matches)) => {
+            let key_file = Path::new(sub_matches.get_one::<String>(ARG_KEY).unwrap());
+            change_passphrase_cmd(key_file, sub_matches.get_flag(ARG_REMOVE_ENCRYPTION))
+        }
+        Some((CMD_SPLIT_KEY, sub_matches)) => {
+            let key_file = Path::new(sub_matches.get_one::<String>(ARG_KEY).unwrap());
+            let output_dir = Path::new(sub_matches.get_one::<String>(ARG_OUTPUT_DIR).unwrap());
+            if let Err(e) = std::fs::create_dir_all(output_dir) {
+                eprintln!("{} to create output directory: {}", "Failed".red(), e);
+                return 1;
+            }
+            split_key_cmd(
+                key_file,
+                *sub_matches.get_one::<u8>(ARG_THRESHOLD).unwrap(),
+                *sub_matches.get_one::<u8>(ARG_SHARES).unwrap(),
+                output_dir,
+            )
+        }
+        Some((CMD_COMBINE_KEY, sub_matches)) => {
+            let mut key_shares = Vec::new();
+            for file in sub_matches.get_many::<String>(ARG_SHARE).into_iter().flatten() {
+                match PrivateKeyShareV1::from_file(Path::new(file)) {
+                    Ok(key_share) => key_shares.push(key_share),
+                    Err(e) => {
+                        eprintln!("{} to read key share {}: [{}] {}", "Failed".red(), file, e.code(), e);
+                        return 1;
+                    }
+                }
+            }
+            let output_file = Path::new(sub_matches.get_one::<String>(ARG_OUTPUT).unwrap());
+            combine_key_cmd(&key_shares, output_file)
+        }
+        Some((CMD_EXPORT_QR, sub_matches)) => {
+            let ghost_certificate_file = Path::new(sub_matches.get_one::<String>(ARG_GHOST_CERTIFICATE).unwrap());
+            let ghost_certificate = match GhostkeyCertificate::from_file(ghost_certificate_file) {
+                Ok(cert) => cert,
+                Err(e) => {
+                    eprintln!("{} to read ghost certificate: [{}] {}", "Failed".red(), e.code(), e);
                     return 1;
                 }
             };
-            let message = sub_matches.get_one::<String>("message").unwrap();
-            let message_content = if Path::new(message).is_file() {
-                match fs::read(message) {
-                    Ok(content) => content,
+            export_qr_cmd(
+                &ghost_certificate,
+                sub_matches.get_flag(ARG_ELIDE_DELEGATE),
+                sub_matches.get_one::<String>(ARG_PNG).map(Path::new),
+                sub_matches.get_one::<String>(ARG_TEXT).map(Path::new),
+            )
+        }
+        Some((CMD_IMPORT_QR, sub_matches)) => {
+            let text = match read_message(sub_matches.get_one::<String>(ARG_COMPACT).unwrap()) {
+                Ok(content) => String::from_utf8_lossy(&content).into_owned(),
+                Err(code) => return code,
+            };
+            let mut delegates = Vec::new();
+            for file in sub_matches.get_many::<String>(ARG_DELEGATE_CERTIFICATE).into_iter().flatten() {
+                match DelegateCertificate::from_file(Path::new(file)) {
+                    Ok(delegate) => delegates.push(delegate),
                     Err(e) => {
-                        eprintln!("{} to read message file: {}", "Failed".red(), e);
+                        eprintln!("{} to read delegate certificate {}: [{}] {}", "Failed".red(), file, e.code(), e);
                         return 1;
                     }
                 }
-            } else {
-                message.as_bytes().to_vec()
+            }
+            let output_file = Path::new(sub_matches.get_one::<String>(ARG_OUTPUT).unwrap());
+            import_qr_cmd(&text, &delegates, output_file)
+        }
+        Some((CMD_CONVERT, sub_matches)) => {
+            let input_file = Path::new(sub_matches.get_one::<String>(ARG_INPUT).unwrap());
+            let format = sub_matches.get_one::<String>(ARG_TO).unwrap();
+            convert_cmd(input_file, format, sub_matches.get_one::<String>(ARG_OUTPUT).map(Path::new))
+        }
+        Some((CMD_EXPORT_SSH_KEY, sub_matches)) => {
+            let (ghost_certificate, ghost_signing_key) = match read_ghost_key(sub_matches) {
+                Ok(ghost_key) => ghost_key,
+                Err(code) => return code,
             };
-            let output_file = Path::new(sub_matches.get_one::<String>("output").unwrap());
-            sign_message_cmd(ghost_certificate, &ghost_signing_key, &message_content, output_file)
+            let output_dir = Path::new(sub_matches.get_one::<String>(ARG_OUTPUT_DIR).unwrap());
+            if let Err(e) = std::fs::create_dir_all(output_dir) {
+                eprintln!("{} to create output directory: {}", "Failed".red(), e);
+                return 1;
+            }
+            export_ssh_key_cmd(&ghost_certificate, &ghost_signing_key, output_dir)
         }
-        Some((CMD_VERIFY_SIGNED_MESSAGE, sub_matches)) => {
-            let signed_message_file = Path::new(sub_matches.get_one::<String>("signed_message").unwrap());
-            let master_verifying_key = if let Some(key_file) = sub_matches.get_one::<String>("master_verifying_key") {
-                match VerifyingKey::from_file(Path::new(key_file)) {
-                    Ok(key) => Some(key),
+        Some((CMD_SSH_SIGN, sub_matches)) => {
+            let (ghost_certificate, ghost_signing_key) = match read_ghost_key(sub_matches) {
+                Ok(ghost_key) => ghost_key,
+                Err(code) => return code,
+            };
+            let message = match read_message(sub_matches.get_one::<String>(ARG_MESSAGE).unwrap()) {
+                Ok(content) => content,
+                Err(code) => return code,
+            };
+            ssh_sign_cmd(
+                &ghost_certificate,
+                &ghost_signing_key,
+                sub_matches.get_one::<String>(ARG_NAMESPACE).unwrap(),
+                &message,
+                Path::new(sub_matches.get_one::<String>(ARG_OUTPUT).unwrap()),
+            )
+        }
+        Some((CMD_SSH_VERIFY, sub_matches)) => {
+            let signature_file = sub_matches.get_one::<String>(ARG_SIGNATURE).unwrap();
+            let signature = match fs::read_to_string(signature_file) {
+                Ok(armored) => match SshSignature::from_armored_string(&armored) {
+                    Ok(signature) => signature,
                     Err(e) => {
-                        eprintln!("{} to read master verifying key: {}", "Failed".red(), e);
+                        eprintln!("{} to read SSH signature: [{}] {}", "Failed".red(), e.code(), e);
                         return 1;
                     }
+                },
+                Err(e) => {
+                    eprintln!("{} to read SSH signature: {}", "Failed".red(), e);
+                    return 1;
                 }
-            } else {
-                None
             };
-            let output_file = sub_matches.get_one::<String>("output").map(|s| Path::new(s));
-            verify_signed_message_cmd(signed_message_file, &master_verifying_key, output_file)
+            let public_key = match fs::read_to_string(sub_matches.get_one::<String>(ARG_PUBLIC_KEY).unwrap()) {
+                Ok(public_key) => public_key,
+                Err(e) => {
+                    eprintln!("{} to read public key: {}", "Failed".red(), e);
+                    return 1;
+                }
+            };
+            let trust_store = match read_trust_store(sub_matches, ARG_MASTER_VERIFYING_KEY) {
+                Ok(trust_store) => trust_store,
+                Err(code) => return code,
+            };
+            let message = match read_message(sub_matches.get_one::<String>(ARG_MESSAGE).unwrap()) {
+                Ok(content) => content,
+                Err(code) => return code,
+            };
+            ssh_verify_cmd(
+                &signature,
+                &public_key,
+                &trust_store,
+                sub_matches.get_one::<String>(ARG_NAMESPACE).unwrap(),
+                &message,
+            )
+        }
+        Some((CMD_ADD_TRUSTED_KEY, sub_matches)) => {
+            let trust_store_file = Path::new(sub_matches.get_one::<String>(ARG_TRUST_STORE).unwrap());
+            let name = sub_matches.get_one::<String>(ARG_NAME).unwrap();
+            let master_verifying_key_file = Path::new(sub_matches.get_one::<String>(ARG_MASTER_VERIFYING_KEY).unwrap());
+            let master_verifying_key = match VerifyingKey::from_file(master_verifying_key_file) {
+                Ok(key) => key,
+                Err(e) => {
+                    eprintln!("{} to read master verifying key: [{}] {}", "Failed".red(), e.code(), e);
+                    return 1;
+                }
+            };
+            add_trusted_key_cmd(trust_store_file, name, master_verifying_key)
         }
         _ => {
             info!("No valid subcommand provided. Use --help for usage information.");
@@ -391,3 +1958,104 @@ fn run() -> i32 {
         }
     }
 }
+
+/// Builds the trust store from `--trust-store` or the master verifying key argument, falling
+/// back to the Freenet master verifying key.
+fn read_trust_store(sub_matches: &ArgMatches, master_verifying_key_arg: &str) -> Result<TrustStore, i32> {
+    if let Some(file) = sub_matches.get_one::<String>(ARG_TRUST_STORE) {
+        return TrustStore::from_file(Path::new(file)).map_err(|e| {
+            eprintln!("{} to read trust store: [{}] {}", "Failed".red(), e.code(), e);
+            1
+        });
+    }
+    match sub_matches.get_one::<String>(master_verifying_key_arg) {
+        Some(key_file) => match VerifyingKey::from_file(Path::new(key_file)) {
+            Ok(key) => Ok(TrustStore::from(key)),
+            Err(e) => {
+                eprintln!("{} to read master verifying key: [{}] {}", "Failed".red(), e.code(), e);
+                Err(1)
+            }
+        },
+        None => Ok(TrustStore::freenet()),
+    }
+}
+
+fn read_revocation_list(sub_matches: &ArgMatches) -> Result<Option<DelegateRevocationListV1>, i32> {
+    match sub_matches.get_one::<String>(ARG_REVOCATION_LIST) {
+        Some(file) => match DelegateRevocationListV1::from_file(Path::new(file)) {
+            Ok(list) => Ok(Some(list)),
+            Err(e) => {
+                eprintln!("{} to read revocation list: [{}] {}", "Failed".red(), e.code(), e);
+                Err(1)
+            }
+        },
+        None => Ok(None),
+    }
+}
+
+/// Reads a message given on the command line, from the file it names if there is one.
+fn read_message(message: &str) -> Result<Vec<u8>, i32> {
+    if Path::new(message).is_file() {
+        fs::read(message).map_err(|e| {
+            eprintln!("{} to read message file: {}", "Failed".red(), e);
+            1
+        })
+    } else {
+        Ok(message.as_bytes().to_vec())
+    }
+}
+
+/// Reads the ghost certificate and signing key for `sign-message`, `mint-subkey` and
+/// `derive-pseudonym`, either from a single bundle file or from separate files.
+fn read_ghost_key(sub_matches: &ArgMatches) -> Result<(GhostkeyCertificate, SigningKey), i32> {
+    if let Some(bundle_file) = sub_matches.get_one::<String>("ghost_key") {
+        let bundle = ArmoredBundle::from_file(Path::new(bundle_file)).map_err(|e| {
+            eprintln!("{} to read ghost key bundle: [{}] {}", "Failed".red(), e.code(), e);
+            1
+        })?;
+        let ghost_certificate = GhostkeyCertificate::from_bundle(&bundle).map_err(|e| {
+            eprintln!("{} to read ghost certificate from bundle: [{}] {}", "Failed".red(), e.code(), e);
+            1
+        })?;
+        let ghost_signing_key = read_private_key::<SigningKey>(&bundle, || {
+            read_passphrase(&format!("Passphrase for {}: ", bundle_file))
+        })
+        .map_err(|e| {
+            eprintln!("{} to read ghost signing key from bundle: [{}] {}", "Failed".red(), e.code(), e);
+            1
+        })?;
+        return Ok((ghost_certificate, ghost_signing_key));
+    }
+
+    let ghost_certificate_file = Path::new(sub_matches.get_one::<String>("ghost_certificate").unwrap());
+    let ghost_certificate = GhostkeyCertificate::from_file(ghost_certificate_file).map_err(|e| {
+        eprintln!("{} to read ghost certificate: [{}] {}", "Failed".red(), e.code(), e);
+        1
+    })?;
+    let ghost_signing_key_file = Path::new(sub_matches.get_one::<String>("ghost_signing_key").unwrap());
+    let ghost_signing_key = load_private_key::<SigningKey>(ghost_signing_key_file).map_err(|e| {
+        eprintln!("{} to read ghost signing key: [{}] {}", "Failed".red(), e.code(), e);
+        1
+    })?;
+    Ok((ghost_certificate, ghost_signing_key))
+}
+
+fn read_key_share(sub_matches: &ArgMatches) -> Result<MasterKeyShareV1, i32> {
+    let key_share_file = Path::new(sub_matches.get_one::<String>(ARG_KEY_SHARE).unwrap());
+    load_private_key::<MasterKeyShareV1>(key_share_file).map_err(|e| {
+        eprintln!("{} to read master key share: [{}] {}", "Failed".red(), e.code(), e);
+        1
+    })
+}
+
+/// Reads the delegate certificate request and the signing package written by
+/// `threshold-package`.
+fn read_signing_package(sub_matches: &ArgMatches) -> Result<(DelegateCertificateRequestV1, SigningPackageV1), i32> {
+    let package_file = Path::new(sub_matches.get_one::<String>(ARG_PACKAGE).unwrap());
+    ArmoredBundle::from_file(package_file)
+        .and_then(|bundle| Ok((bundle.get::<DelegateCertificateRequestV1>()?, bundle.get::<SigningPackageV1>()?)))
+        .map_err(|e| {
+            eprintln!("{} to read signing package: [{}] {}", "Failed".red(), e.code(), e);
+            1
+        })
+}
@@ -1,23 +1,48 @@
 use ghostkey_lib::armorable::*;
-use ghostkey_lib::delegate_certificate::DelegateCertificateV1;
+use ghostkey_lib::delegate_certificate::{
+    DelegateCertificate, DelegateCertificateRequestV1, DelegateCertificateV2, DelegateDetails,
+    DelegateInfo,
+};
+use ghostkey_lib::delegate_key::PublicKey as DelegateVerifyingKey;
+use ghostkey_lib::encrypted_key::{read_private_key, EncryptedKeyV1, KdfParams};
 use ghostkey_lib::errors::GhostkeyError;
-use ghostkey_lib::ghost_key_certificate::GhostkeyCertificateV1;
-use ghostkey_lib::util::create_keypair;
+use ghostkey_lib::fingerprint::{private_key_fingerprint, Fingerprint, Fingerprinted};
+use ghostkey_lib::ghost_key_certificate::GhostkeyCertificate;
+use ghostkey_lib::issuance::{BlindIssuer, BlindingState};
+use ghostkey_lib::json::JsonEncodable;
+use ghostkey_lib::pseudonym::{PseudonymCertificateV1, PseudonymInfo, PseudonymMessageV1};
+use ghostkey_lib::revocation_list::{DelegateRevocationListV1, RevokedDelegate};
+use ghostkey_lib::secret_sharing::PrivateKeyShareV1;
+use ghostkey_lib::seed::GhostkeySeed;
+use ghostkey_lib::signed_message::{SignedMessage, SignedMessageV2, SignedMessageV3};
+use ghostkey_lib::ssh::{to_openssh_private_key, to_openssh_public_key, SshSignature};
+use ghostkey_lib::subkey::SubkeyCertificateV1;
+use ghostkey_lib::threshold::{
+    aggregate, MasterKeyShareV1, SignatureShareV1, SigningCommitmentV1, SigningNoncesV1,
+    SigningPackageV1,
+};
+use ghostkey_lib::trust_store::TrustStore;
+use ghostkey_lib::util::{create_keypair, current_unix_time};
 use blind_rsa_signatures::SecretKey as RSASigningKey;
 use colored::Colorize;
+use image::Luma;
+use qrcode::render::unicode;
+use qrcode::{EcLevel, QrCode};
 use ed25519_dalek::*;
 use log::info;
 use std::fs;
-use std::os::unix::fs::PermissionsExt;
+use std::io::Write;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
 use std::path::Path;
 use rand_core::OsRng;
-use crate::signed_message::SignedMessage;
+use zeroize::{Zeroize, Zeroizing};
+use crate::passphrase::{read_new_passphrase, read_passphrase, write_private_key};
 
-pub fn generate_master_key_cmd(output_dir: &Path, ignore_permissions: bool) -> i32 {
+pub fn generate_master_key_cmd(output_dir: &Path, ignore_permissions: bool, encrypt: bool) -> i32 {
     let (signing_key, verifying_key) = match create_keypair(&mut OsRng) {
         Ok(keypair) => keypair,
         Err(e) => {
-            eprintln!("{} to create keypair: {}", "Failed".red(), e);
+            eprintln!("{} to create keypair: [{}] {}", "Failed".red(), e.code(), e);
             return 1;
         }
     };
@@ -29,16 +54,8 @@ pub fn generate_master_key_cmd(output_dir: &Path, ignore_permissions: bool) -> i
         "Writing master signing key to {}",
         signing_key_file.display()
     );
-    if let Err(e) = signing_key.to_file(&signing_key_file) {
-        eprintln!("{} to write master signing key: {}", "Failed".red(), e);
-        return 1;
-    }
-    if let Err(e) = fs::set_permissions(&signing_key_file, fs::Permissions::from_mode(0o600)) {
-        eprintln!(
-            "{} to set permissions on master signing key file: {}",
-            "Failed".red(),
-            e
-        );
+    if let Err(e) = write_private_key(&signing_key, &signing_key_file, encrypt) {
+        eprintln!("{} to write master signing key: [{}] {}", "Failed".red(), e.code(), e);
         return 1;
     }
     println!(
@@ -47,12 +64,13 @@ pub fn generate_master_key_cmd(output_dir: &Path, ignore_permissions: bool) -> i
         "successfully".green(),
         signing_key_file.display().to_string().yellow()
     );
+    print_fingerprint("Master key fingerprint", verifying_key.fingerprint());
     info!(
         "Writing master verifying key to {}",
         verifying_key_file.display()
     );
     if let Err(e) = verifying_key.to_file(&verifying_key_file) {
-        eprintln!("{} to write master verifying key: {}", "Failed".red(), e);
+        eprintln!("{} to write master verifying key: [{}] {}", "Failed".red(), e.code(), e);
         return 1;
     }
     println!(
@@ -61,6 +79,7 @@ pub fn generate_master_key_cmd(output_dir: &Path, ignore_permissions: bool) -> i
         "successfully".green(),
         verifying_key_file.display().to_string().yellow()
     );
+    print_fingerprint("Master key fingerprint", verifying_key.fingerprint());
     if !ignore_permissions {
         if let Err(e) = require_strict_permissions(&signing_key_file) {
             eprintln!(
@@ -79,17 +98,113 @@ pub fn generate_master_key_cmd(output_dir: &Path, ignore_permissions: bool) -> i
     0
 }
 
+/// Splits a master signing key into `shares` key shares, any `threshold` of which sign together,
+/// writing `master_key_share_<n>.pem` for each and `master_verifying_key.pem` to `output_dir`.
+/// A new master key is generated unless `master_signing_key` is given.
+pub fn generate_master_key_shares_cmd(
+    master_signing_key: Option<&SigningKey>,
+    threshold: u16,
+    shares: u16,
+    output_dir: &Path,
+    encrypt: bool,
+) -> i32 {
+    let master_signing_key = match master_signing_key {
+        Some(key) => key.clone(),
+        None => match create_keypair(&mut OsRng) {
+            Ok((signing_key, _)) => signing_key,
+            Err(e) => {
+                eprintln!("{} to create keypair: [{}] {}", "Failed".red(), e.code(), e);
+                return 1;
+            }
+        },
+    };
+    let key_shares = match MasterKeyShareV1::split(&master_signing_key, threshold, shares) {
+        Ok(key_shares) => key_shares,
+        Err(e) => {
+            eprintln!("{} to split master signing key: [{}] {}", "Failed".red(), e.code(), e);
+            return 1;
+        }
+    };
+    for key_share in &key_shares {
+        let key_share_file = output_dir.join(format!("master_key_share_{}.pem", key_share.identifier));
+        info!("Writing master key share to {}", key_share_file.display());
+        if let Err(e) = write_private_key(key_share, &key_share_file, encrypt) {
+            eprintln!("{} to write master key share: [{}] {}", "Failed".red(), e.code(), e);
+            return 1;
+        }
+        println!(
+            "Master key share {} of {} written {}: {}",
+            key_share.identifier,
+            shares,
+            "successfully".green(),
+            key_share_file.display().to_string().yellow()
+        );
+    }
+    let verifying_key = master_signing_key.verifying_key();
+    let verifying_key_file = output_dir.join("master_verifying_key.pem");
+    info!("Writing master verifying key to {}", verifying_key_file.display());
+    if let Err(e) = verifying_key.to_file(&verifying_key_file) {
+        eprintln!("{} to write master verifying key: [{}] {}", "Failed".red(), e.code(), e);
+        return 1;
+    }
+    println!(
+        "Master verifying key written {}: {}",
+        "successfully".green(),
+        verifying_key_file.display().to_string().yellow()
+    );
+    print_fingerprint("Master key fingerprint", verifying_key.fingerprint());
+    println!("Any {} of the {} shares can sign for the master key", threshold, shares);
+    0
+}
+
+/// Generates a delegate certificate, with a typed payload if `details` are given and otherwise
+/// with the free-form `info` string. A `partially_blind` delegate issues V3 ghost key
+/// certificates binding their metadata.
+#[allow(clippy::too_many_arguments)]
 pub fn generate_delegate_cmd(
     master_signing_key: &SigningKey,
-    info: &String,
+    info: Option<&String>,
+    details: Option<&DelegateDetails>,
+    partially_blind: bool,
+    valid_days: Option<u64>,
     output_dir: &Path,
     ignore_permissions: bool,
+    encrypt: bool,
 ) -> i32 {
+    let validity = match validity_period(valid_days) {
+        Ok(validity) => validity,
+        Err(e) => {
+            eprintln!("{} to compute validity period: [{}] {}", "Failed".red(), e.code(), e);
+            return 1;
+        }
+    };
+    let created = match (details, info, validity) {
+        (Some(details), _, validity) if partially_blind => DelegateCertificateV2::new_partially_blind(
+            master_signing_key,
+            details,
+            validity.map(|(not_before, _)| not_before),
+            validity.map(|(_, not_after)| not_after),
+        ),
+        (Some(details), _, validity) => DelegateCertificateV2::new_with_details(
+            master_signing_key,
+            details,
+            validity.map(|(not_before, _)| not_before),
+            validity.map(|(_, not_after)| not_after),
+        ),
+        (None, Some(info), Some((not_before, not_after))) => {
+            DelegateCertificateV2::new_with_validity(master_signing_key, info, not_before, not_after)
+        }
+        (None, Some(info), None) => DelegateCertificateV2::new(master_signing_key, info),
+        (None, None, _) => {
+            eprintln!("{}: Either an info string or delegate details are required", "Error".red());
+            return 1;
+        }
+    };
     let (delegate_certificate, delegate_signing_key) =
-        match DelegateCertificateV1::new(&master_signing_key, &info) {
+        match created {
             Ok(result) => result,
             Err(e) => {
-                eprintln!("{} to create delegate certificate: {}", "Failed".red(), e);
+                eprintln!("{} to create delegate certificate: [{}] {}", "Failed".red(), e.code(), e);
                 return 1;
             }
         };
@@ -100,7 +215,7 @@ pub fn generate_delegate_cmd(
         delegate_certificate_file.display()
     );
     if let Err(e) = delegate_certificate.to_file(&delegate_certificate_file) {
-        eprintln!("{} to write delegate certificate: {}", "Failed".red(), e);
+        eprintln!("{} to write delegate certificate: [{}] {}", "Failed".red(), e.code(), e);
         return 1;
     }
     println!(
@@ -109,23 +224,17 @@ pub fn generate_delegate_cmd(
         "successfully".green(),
         delegate_certificate_file.display().to_string().yellow()
     );
+    print_fingerprint("Certificate fingerprint", delegate_certificate.fingerprint());
+    print_fingerprint(
+        "Delegate key fingerprint",
+        delegate_certificate.payload.delegate_verifying_key().fingerprint(),
+    );
     info!(
         "Writing delegate signing key to {}",
         delegate_signing_key_file.display()
     );
-    if let Err(e) = delegate_signing_key.to_file(&delegate_signing_key_file) {
-        eprintln!("{} to write delegate signing key: {}", "Failed".red(), e);
-        return 1;
-    }
-    if let Err(e) = fs::set_permissions(
-        &delegate_signing_key_file,
-        fs::Permissions::from_mode(0o600),
-    ) {
-        eprintln!(
-            "{} to set permissions on delegate signing key file: {}",
-            "Failed".red(),
-            e
-        );
+    if let Err(e) = write_private_key(&delegate_signing_key, &delegate_signing_key_file, encrypt) {
+        eprintln!("{} to write delegate signing key: [{}] {}", "Failed".red(), e.code(), e);
         return 1;
     }
     println!(
@@ -134,6 +243,10 @@ pub fn generate_delegate_cmd(
         "successfully".green(),
         delegate_signing_key_file.display().to_string().yellow()
     );
+    print_fingerprint(
+        "Delegate key fingerprint",
+        delegate_certificate.payload.delegate_verifying_key().fingerprint(),
+    );
     if !ignore_permissions {
         if let Err(e) = require_strict_permissions(&delegate_signing_key_file) {
             eprintln!(
@@ -156,118 +269,469 @@ pub fn generate_delegate_cmd(
     0
 }
 
+/// Generates a delegate signing key and a request for its certificate, to be signed with the
+/// shares of a threshold master key, writing `delegate_certificate_request.pem` and
+/// `delegate_signing_key.pem` to `output_dir`. The request is for a V1 certificate, or a V2
+/// certificate for a `partially_blind` delegate.
+pub fn generate_delegate_request_cmd(
+    info: Option<&String>,
+    details: Option<&DelegateDetails>,
+    partially_blind: bool,
+    valid_days: Option<u64>,
+    output_dir: &Path,
+    encrypt: bool,
+) -> i32 {
+    let validity = match validity_period(valid_days) {
+        Ok(validity) => validity,
+        Err(e) => {
+            eprintln!("{} to compute validity period: [{}] {}", "Failed".red(), e.code(), e);
+            return 1;
+        }
+    };
+    let not_before = validity.map(|(not_before, _)| not_before);
+    let not_after = validity.map(|(_, not_after)| not_after);
+    let created = match (details, info) {
+        (Some(details), _) => {
+            DelegateCertificateRequestV1::new_with_details(details, not_before, not_after, partially_blind)
+        }
+        (None, Some(info)) => DelegateCertificateRequestV1::new(info, not_before, not_after),
+        (None, None) => {
+            eprintln!("{}: Either an info string or delegate details are required", "Error".red());
+            return 1;
+        }
+    };
+    let (request, delegate_signing_key) = match created {
+        Ok(result) => result,
+        Err(e) => {
+            eprintln!("{} to create delegate certificate request: [{}] {}", "Failed".red(), e.code(), e);
+            return 1;
+        }
+    };
+    let request_file = output_dir.join("delegate_certificate_request.pem");
+    let delegate_signing_key_file = output_dir.join("delegate_signing_key.pem");
+    info!("Writing delegate certificate request to {}", request_file.display());
+    if let Err(e) = request.to_file(&request_file) {
+        eprintln!("{} to write delegate certificate request: [{}] {}", "Failed".red(), e.code(), e);
+        return 1;
+    }
+    println!(
+        "Delegate certificate request written {}: {}",
+        "successfully".green(),
+        request_file.display().to_string().yellow()
+    );
+    info!("Writing delegate signing key to {}", delegate_signing_key_file.display());
+    if let Err(e) = write_private_key(&delegate_signing_key, &delegate_signing_key_file, encrypt) {
+        eprintln!("{} to write delegate signing key: [{}] {}", "Failed".red(), e.code(), e);
+        return 1;
+    }
+    println!(
+        "Delegate signing key written {}: {}",
+        "successfully".green(),
+        delegate_signing_key_file.display().to_string().yellow()
+    );
+    print_fingerprint(
+        "Delegate key fingerprint",
+        request.payload.delegate_verifying_key().fingerprint(),
+    );
+    0
+}
+
+/// First round of threshold signing: creates nonces with the key share, writing the secret
+/// `signing_nonces.pem` and the `signing_commitment_<n>.pem` to send to the coordinator.
+pub fn threshold_commit_cmd(key_share: &MasterKeyShareV1, output_dir: &Path, encrypt: bool) -> i32 {
+    let nonces = match SigningNoncesV1::new(key_share) {
+        Ok(nonces) => nonces,
+        Err(e) => {
+            eprintln!("{} to create signing nonces: [{}] {}", "Failed".red(), e.code(), e);
+            return 1;
+        }
+    };
+    let nonces_file = output_dir.join("signing_nonces.pem");
+    let commitment_file = output_dir.join(format!("signing_commitment_{}.pem", key_share.identifier));
+    info!("Writing signing nonces to {}", nonces_file.display());
+    if let Err(e) = write_private_key(&nonces, &nonces_file, encrypt) {
+        eprintln!("{} to write signing nonces: [{}] {}", "Failed".red(), e.code(), e);
+        return 1;
+    }
+    println!(
+        "Signing nonces written {}: {}",
+        "successfully".green(),
+        nonces_file.display().to_string().yellow()
+    );
+    info!("Writing signing commitment to {}", commitment_file.display());
+    if let Err(e) = nonces.commitment.to_file(&commitment_file) {
+        eprintln!("{} to write signing commitment: [{}] {}", "Failed".red(), e.code(), e);
+        return 1;
+    }
+    println!(
+        "Signing commitment written {}: {}",
+        "successfully".green(),
+        commitment_file.display().to_string().yellow()
+    );
+    0
+}
+
+/// Collects the signing commitments for a delegate certificate request into a signing package,
+/// written to `output_file` together with the request for the signers to review.
+pub fn threshold_package_cmd(
+    request: &DelegateCertificateRequestV1,
+    commitments: Vec<SigningCommitmentV1>,
+    output_file: &Path,
+) -> i32 {
+    let package = match request
+        .signing_message()
+        .and_then(|message| SigningPackageV1::new(&message, commitments))
+    {
+        Ok(package) => package,
+        Err(e) => {
+            eprintln!("{} to create signing package: [{}] {}", "Failed".red(), e.code(), e);
+            return 1;
+        }
+    };
+    let mut bundle = ArmoredBundle::new();
+    if let Err(e) = bundle.add(request).and_then(|_| bundle.add(&package)) {
+        eprintln!("{} to encode signing package: [{}] {}", "Failed".red(), e.code(), e);
+        return 1;
+    }
+    info!("Writing signing package to {}", output_file.display());
+    if let Err(e) = bundle.to_file(output_file) {
+        eprintln!("{} to write signing package: [{}] {}", "Failed".red(), e.code(), e);
+        return 1;
+    }
+    println!(
+        "Signing package written {}: {}",
+        "successfully".green(),
+        output_file.display().to_string().yellow()
+    );
+    println!("Signers: {:?}", package.signers());
+    0
+}
+
+/// Second round of threshold signing: checks that the package signs the request it carries and
+/// signs it with the key share, writing `signature_share_<n>.pem` to `output_dir`. The nonces
+/// file is deleted before the share is written so the nonces are never used twice.
+pub fn threshold_sign_cmd(
+    key_share: &MasterKeyShareV1,
+    nonces: SigningNoncesV1,
+    nonces_file: &Path,
+    request: &DelegateCertificateRequestV1,
+    package: &SigningPackageV1,
+    output_dir: &Path,
+) -> i32 {
+    match request.signing_message() {
+        Ok(message) if package.message == message => {}
+        Ok(_) => {
+            eprintln!("{}: Signing package is not for the delegate certificate request", "Error".red());
+            return 1;
+        }
+        Err(e) => {
+            eprintln!("{} to encode delegate certificate request: [{}] {}", "Failed".red(), e.code(), e);
+            return 1;
+        }
+    }
+    println!("Signing V{} delegate certificate request:", request.certificate_version);
+    print_fingerprint(
+        "Delegate key fingerprint",
+        request.payload.delegate_verifying_key().fingerprint(),
+    );
+    print_delegate_info(&request.payload.delegate_info());
+    let signature_share = match SignatureShareV1::new(key_share, nonces, package) {
+        Ok(signature_share) => signature_share,
+        Err(e) => {
+            eprintln!("{} to sign: [{}] {}", "Failed".red(), e.code(), e);
+            return 1;
+        }
+    };
+    // The share must not be released unless the nonces are gone
+    if let Err(e) = fs::remove_file(nonces_file) {
+        eprintln!("{} to delete signing nonces: {}", "Failed".red(), e);
+        return 1;
+    }
+    let signature_share_file = output_dir.join(format!("signature_share_{}.pem", key_share.identifier));
+    info!("Writing signature share to {}", signature_share_file.display());
+    if let Err(e) = signature_share.to_file(&signature_share_file) {
+        eprintln!("{} to write signature share: [{}] {}", "Failed".red(), e.code(), e);
+        return 1;
+    }
+    println!(
+        "Signature share written {}: {}",
+        "successfully".green(),
+        signature_share_file.display().to_string().yellow()
+    );
+    0
+}
+
+/// Combines the signature shares into the master signature over the request, writing the
+/// verified `delegate_certificate.pem`, of the version the request is for, to `output_dir`.
+pub fn threshold_aggregate_cmd(
+    request: DelegateCertificateRequestV1,
+    package: &SigningPackageV1,
+    signature_shares: &[SignatureShareV1],
+    output_dir: &Path,
+) -> i32 {
+    let master_verifying_key = match package.group_verifying_key() {
+        Ok(key) => key,
+        Err(e) => {
+            eprintln!("{} to read master verifying key: [{}] {}", "Failed".red(), e.code(), e);
+            return 1;
+        }
+    };
+    let signature = match aggregate(package, signature_shares) {
+        Ok(signature) => signature,
+        Err(e) => {
+            eprintln!("{} to aggregate signature shares: [{}] {}", "Failed".red(), e.code(), e);
+            return 1;
+        }
+    };
+    let delegate_certificate = match request.certify(signature) {
+        Ok(delegate_certificate) => delegate_certificate,
+        Err(e) => {
+            eprintln!("{} to create delegate certificate: [{}] {}", "Failed".red(), e.code(), e);
+            return 1;
+        }
+    };
+    let info = match delegate_certificate.verify(&TrustStore::from(master_verifying_key)) {
+        Ok(info) => info,
+        Err(e) => {
+            eprintln!("{} to verify delegate certificate: [{}] {}", "Failed".red(), e.code(), e);
+            return 1;
+        }
+    };
+    let delegate_certificate_file = output_dir.join("delegate_certificate.pem");
+    info!("Writing delegate certificate to {}", delegate_certificate_file.display());
+    if let Err(e) = delegate_certificate.to_file(&delegate_certificate_file) {
+        eprintln!("{} to write delegate certificate: [{}] {}", "Failed".red(), e.code(), e);
+        return 1;
+    }
+    println!(
+        "Delegate certificate (V{}) written {}: {}",
+        delegate_certificate.version(),
+        "successfully".green(),
+        delegate_certificate_file.display().to_string().yellow()
+    );
+    print_fingerprint("Certificate fingerprint", delegate_certificate.fingerprint());
+    print_fingerprint("Master key fingerprint", master_verifying_key.fingerprint());
+    print_delegate_info(&info);
+    0
+}
+
 pub fn verify_delegate_cmd(
-    master_verifying_key: &Option<VerifyingKey>,
-    delegate_certificate: &DelegateCertificateV1,
+    trust_store: &TrustStore,
+    delegate_certificate: &DelegateCertificate,
+    revocation_list: Option<&DelegateRevocationListV1>,
 ) -> i32 {
-    match delegate_certificate.verify(master_verifying_key) {
+    let verification = match revocation_list {
+        Some(revocation_list) => {
+            revocation_list.verify_delegate_certificate(delegate_certificate, trust_store)
+        }
+        None => delegate_certificate.verify(trust_store),
+    };
+    match verification {
         Ok(info) => {
             println!("Delegate certificate {}", "verified".green());
-            println!("Info: {}", info.blue());
+            print_fingerprint("Certificate fingerprint", delegate_certificate.fingerprint());
+            print_fingerprint(
+                "Delegate key fingerprint",
+                delegate_certificate.payload().delegate_verifying_key().fingerprint(),
+            );
+            print_delegate_info(&info);
             0
         }
         Err(e) => {
-            eprintln!("{} to verify delegate certificate: {}", "Failed".red(), e);
+            eprintln!("{} to verify delegate certificate: [{}] {}", "Failed".red(), e.code(), e);
             1
         }
     }
 }
 
+/// Signs `message` for `purpose` with the ghost key, writing a signed message that embeds the
+/// message, or only the signature if `detached`.
 pub fn sign_message_cmd(
-    ghost_certificate: GhostkeyCertificateV1,
+    ghost_certificate: GhostkeyCertificate,
     ghost_signing_key: &SigningKey,
+    purpose: &str,
+    content_type: Option<&str>,
     message: &[u8],
+    detached: bool,
     output_file: &Path,
 ) -> i32 {
-    if ghost_signing_key.verifying_key() != ghost_certificate.verifying_key {
-        eprintln!("{}: Ghost signing key does not match ghost verifying key", "Error".red());
+    let ghost_key_fingerprint = ghost_certificate.verifying_key().fingerprint();
+    let mut signed_message = match SignedMessageV2::new(
+        ghost_certificate,
+        ghost_signing_key,
+        purpose,
+        content_type,
+        message,
+    ) {
+        Ok(signed_message) => signed_message,
+        Err(e) => {
+            eprintln!("{} to sign message: [{}] {}", "Failed".red(), e.code(), e);
+            return 1;
+        }
+    };
+    if detached {
+        signed_message.detach();
+    }
+    if write_signed_message(signed_message.into(), detached, output_file) != 0 {
         return 1;
     }
-    
-    let signature = ghost_signing_key.sign(message);
-    let signed_message = SignedMessage {
-        certificate: ghost_certificate,
-        message: message.to_vec(),
-        signature,
-    };
+    print_fingerprint("Signed by ghost key", ghost_key_fingerprint);
+    0
+}
+
+/// Signs `message` with a subkey, for the purpose of the subkey, writing a signed message that
+/// embeds the message, or only the signature if `detached`.
+pub fn sign_message_with_subkey_cmd(
+    subkey_certificate: SubkeyCertificateV1,
+    subkey_signing_key: &SigningKey,
+    content_type: Option<&str>,
+    message: &[u8],
+    detached: bool,
+    output_file: &Path,
+) -> i32 {
+    let subkey_fingerprint = subkey_certificate.payload.verifying_key.fingerprint();
+    let ghost_key_fingerprint = subkey_certificate.ghost_key_certificate().verifying_key().fingerprint();
+    let mut signed_message =
+        match SignedMessageV3::new(subkey_certificate, subkey_signing_key, content_type, message) {
+            Ok(signed_message) => signed_message,
+            Err(e) => {
+                eprintln!("{} to sign message: [{}] {}", "Failed".red(), e.code(), e);
+                return 1;
+            }
+        };
+    if detached {
+        signed_message.detach();
+    }
+    if write_signed_message(signed_message.into(), detached, output_file) != 0 {
+        return 1;
+    }
+    print_fingerprint("Signed by subkey", subkey_fingerprint);
+    print_fingerprint("Subkey certified by ghost key", ghost_key_fingerprint);
+    0
+}
 
+fn write_signed_message(signed_message: SignedMessage, detached: bool, output_file: &Path) -> i32 {
     match signed_message.to_file(output_file) {
         Ok(_) => {
             println!(
                 "{} written {}",
-                "Signed message",
+                if detached { "Detached signature" } else { "Signed message" },
                 "successfully".green()
             );
             0
         }
         Err(e) => {
-            eprintln!("{} to write signed message: {}", "Failed".red(), e);
+            eprintln!("{} to write signed message: [{}] {}", "Failed".red(), e.code(), e);
             1
         }
     }
 }
 
+/// Verifies a signed message, against `message` if its signature is detached, and checks that
+/// it was signed for `purpose` if given. V1 messages are only accepted with `allow_v1`.
+#[allow(clippy::too_many_arguments)]
 pub fn verify_signed_message_cmd(
     signed_message_file: &Path,
-    master_verifying_key: &Option<VerifyingKey>,
+    trust_store: &TrustStore,
+    revocation_list: Option<&DelegateRevocationListV1>,
+    message: Option<&[u8]>,
+    purpose: Option<&str>,
+    allow_v1: bool,
     output_file: Option<&Path>,
 ) -> i32 {
     let signed_message = match SignedMessage::from_file(signed_message_file) {
         Ok(sm) => sm,
         Err(e) => {
-            eprintln!("{} to read signed message: {}", "Failed".red(), e);
+            eprintln!("{} to read signed message: [{}] {}", "Failed".red(), e.code(), e);
             return 1;
         }
     };
 
-    match signed_message.certificate.verify(master_verifying_key) {
-        Ok(info) => {
-            println!("Ghost certificate {}", "verified".green());
-            println!("Info: {}", info.blue());
-
-            let verifying_key = signed_message.certificate.verifying_key;
-            match verifying_key.verify(&signed_message.message, &signed_message.signature) {
-                Ok(_) => {
-                    println!("Signature {}", "verified".green());
-                    match output_file {
-                        Some(file) => {
-                            if let Err(e) = fs::write(file, &signed_message.message) {
-                                eprintln!("{} to write message to file: {}", "Failed".red(), e);
-                                return 1;
-                            }
-                            println!("Message written to {}", file.display());
-                        }
-                        None => {
-                            println!("Message: {}", String::from_utf8_lossy(&signed_message.message));
-                        }
-                    }
-                    0
-                }
-                Err(e) => {
-                    eprintln!("{} to verify signature: {}", "Failed".red(), e);
-                    1
-                }
-            }
+    let verification = match (revocation_list, allow_v1) {
+        (Some(revocation_list), false) => {
+            revocation_list.verify_signed_message(&signed_message, trust_store, message)
         }
+        (Some(revocation_list), true) => {
+            revocation_list.verify_legacy_signed_message(&signed_message, trust_store, message)
+        }
+        (None, false) => signed_message.verify(trust_store, message),
+        (None, true) => signed_message.verify_legacy(trust_store, message),
+    };
+    let verified = match verification {
+        Ok(verified) => verified,
         Err(e) => {
-            eprintln!("{} to verify ghost certificate: {}", "Failed".red(), e);
-            1
+            eprintln!("{} to verify signed message: [{}] {}", "Failed".red(), e.code(), e);
+            return 1;
+        }
+    };
+    if let Some(purpose) = purpose {
+        if verified.purpose.as_deref() != Some(purpose) {
+            eprintln!(
+                "{}: Message was signed for purpose '{}', expected '{}'",
+                "Error".red(),
+                verified.purpose.as_deref().unwrap_or("none"),
+                purpose
+            );
+            return 1;
+        }
+    }
+
+    let ghost_certificate = signed_message.certificate();
+    println!("Ghost certificate {}", "verified".green());
+    print_ghost_key_fingerprints(&ghost_certificate);
+    print_delegate_info(&verified.delegate_info);
+    if let Some(metadata) = &verified.metadata {
+        println!("Metadata: {}", metadata.to_string().blue());
+    }
+    if let Some(subkey) = &verified.subkey {
+        println!("Subkey {}", "verified".green());
+        print_fingerprint("Subkey fingerprint", subkey.verifying_key.fingerprint());
+        println!("Subkey valid until: {} (Unix time)", subkey.not_after);
+    }
+    println!("Signature {}", "verified".green());
+    if let Some(purpose) = &verified.purpose {
+        println!("Purpose: {}", purpose.blue());
+    }
+    if let Some(created) = verified.created {
+        println!("Signed at: {} (Unix time)", created);
+    }
+    if let Some(content_type) = &verified.content_type {
+        println!("Content type: {}", content_type.blue());
+    }
+    match output_file {
+        Some(file) => {
+            if let Err(e) = fs::write(file, &verified.message) {
+                eprintln!("{} to write message to file: {}", "Failed".red(), e);
+                return 1;
+            }
+            println!("Message written to {}", file.display());
+        }
+        None => {
+            println!("Message: {}", String::from_utf8_lossy(&verified.message));
         }
     }
+    0
 }
 
+/// Issues a ghost key derived from a new seed, writing `ghost_key_certificate.pem` and
+/// `ghost_key_signing_key.pem` to `output_dir`. With `show_mnemonic` the recovery phrase of the
+/// seed is printed for [`restore_cmd`].
 pub fn generate_ghost_key_cmd(
-    delegate_certificate: &DelegateCertificateV1,
+    delegate_certificate: &DelegateCertificate,
     delegate_signing_key: &RSASigningKey,
     output_dir: &Path,
+    encrypt: bool,
+    show_mnemonic: bool,
 ) -> i32 {
-    if delegate_signing_key.public_key().unwrap() != delegate_certificate.payload.delegate_verifying_key {
-        eprintln!("{}: Delegate signing key does not match delegate verifying key", "Error".red());
-        return 1;
-    }
-    
+    let seed = GhostkeySeed::generate();
     let (ghost_key_certificate, ghost_key_signing_key) =
-        GhostkeyCertificateV1::new(delegate_certificate, delegate_signing_key);
+        match issue_ghost_key(delegate_certificate, delegate_signing_key, &seed) {
+            Ok(result) => result,
+            Err(e) => {
+                eprintln!("{} to generate ghost key: [{}] {}", "Failed".red(), e.code(), e);
+                return 1;
+            }
+        };
     let ghost_key_certificate_file = output_dir.join("ghost_key_certificate.pem");
     let ghost_key_signing_key_file = output_dir.join("ghost_key_signing_key.pem");
     info!(
@@ -275,7 +739,7 @@ pub fn generate_ghost_key_cmd(
         ghost_key_certificate_file.display()
     );
     if let Err(e) = ghost_key_certificate.to_file(&ghost_key_certificate_file) {
-        eprintln!("{} to write ghostkey certificate: {}", "Failed".red(), e);
+        eprintln!("{} to write ghostkey certificate: [{}] {}", "Failed".red(), e.code(), e);
         return 1;
     }
     println!(
@@ -284,23 +748,13 @@ pub fn generate_ghost_key_cmd(
         "successfully".green(),
         ghost_key_certificate_file.display().to_string().yellow()
     );
+    print_ghost_key_fingerprints(&ghost_key_certificate);
     info!(
         "Writing ghostkey signing key to {}",
         ghost_key_signing_key_file.display()
     );
-    if let Err(e) = ghost_key_signing_key.to_file(&ghost_key_signing_key_file) {
-        eprintln!("{} to write ghostkey signing key: {}", "Failed".red(), e);
-        return 1;
-    }
-    if let Err(e) = fs::set_permissions(
-        &ghost_key_signing_key_file,
-        fs::Permissions::from_mode(0o600),
-    ) {
-        eprintln!(
-            "{} to set permissions on ghostkey signing key file: {}",
-            "Failed".red(),
-            e
-        );
+    if let Err(e) = write_private_key(&ghost_key_signing_key, &ghost_key_signing_key_file, encrypt) {
+        eprintln!("{} to write ghostkey signing key: [{}] {}", "Failed".red(), e.code(), e);
         return 1;
     }
     println!(
@@ -309,28 +763,1085 @@ pub fn generate_ghost_key_cmd(
         "successfully".green(),
         ghost_key_signing_key_file.display().to_string().yellow()
     );
+    print_fingerprint("Ghost key fingerprint", ghost_key_certificate.verifying_key().fingerprint());
+    if show_mnemonic {
+        println!("Recovery phrase: {}", seed.to_mnemonic().yellow());
+        println!("Write the recovery phrase down and keep it secret, with the certificate it restores the signing key");
+    }
     0
 }
 
-pub fn verify_ghost_key_cmd(
-    master_verifying_key: &Option<VerifyingKey>,
-    ghost_certificate: &GhostkeyCertificateV1,
+/// Rebuilds the ghost signing key of `ghost_key_certificate` from its recovery phrase, writing
+/// `ghost_key_certificate.pem` and `ghost_key_signing_key.pem` to `output_dir`.
+pub fn restore_cmd(
+    ghost_key_certificate: &GhostkeyCertificate,
+    mnemonic: &str,
+    output_dir: &Path,
+    encrypt: bool,
 ) -> i32 {
-    match ghost_certificate.verify(&master_verifying_key.clone()) {
-        Ok(info) => {
-            println!("Ghost certificate {}", "verified".green());
-            println!("Info: {}", info.blue());
-            0
+    let ghost_key_signing_key = match GhostkeySeed::from_mnemonic(mnemonic)
+        .and_then(|seed| seed.restore(ghost_key_certificate))
+    {
+        Ok(key) => key,
+        Err(e) => {
+            eprintln!("{} to restore ghost signing key: [{}] {}", "Failed".red(), e.code(), e);
+            return 1;
+        }
+    };
+    let ghost_key_certificate_file = output_dir.join("ghost_key_certificate.pem");
+    let ghost_key_signing_key_file = output_dir.join("ghost_key_signing_key.pem");
+    info!("Writing ghostkey certificate to {}", ghost_key_certificate_file.display());
+    if let Err(e) = ghost_key_certificate.to_file(&ghost_key_certificate_file) {
+        eprintln!("{} to write ghostkey certificate: [{}] {}", "Failed".red(), e.code(), e);
+        return 1;
+    }
+    info!("Writing ghostkey signing key to {}", ghost_key_signing_key_file.display());
+    if let Err(e) = write_private_key(&ghost_key_signing_key, &ghost_key_signing_key_file, encrypt) {
+        eprintln!("{} to write ghostkey signing key: [{}] {}", "Failed".red(), e.code(), e);
+        return 1;
+    }
+    println!(
+        "Ghost signing key restored {}: {}",
+        "successfully".green(),
+        ghost_key_signing_key_file.display().to_string().yellow()
+    );
+    print_ghost_key_fingerprints(ghost_key_certificate);
+    0
+}
+
+/// Shows the compact encoding of a ghost key certificate as a QR code, printed to the terminal
+/// or written to `png_file`. With `elide_delegate` the delegate certificate is left out, so the
+/// importer must already have it. `text_file` receives the text the QR code holds, for
+/// `import-qr` without a camera.
+pub fn export_qr_cmd(
+    ghost_certificate: &GhostkeyCertificate,
+    elide_delegate: bool,
+    png_file: Option<&Path>,
+    text_file: Option<&Path>,
+) -> i32 {
+    let text = match ghost_certificate.to_compact_string(elide_delegate) {
+        Ok(text) => text,
+        Err(e) => {
+            eprintln!("{} to encode ghost certificate: [{}] {}", "Failed".red(), e.code(), e);
+            return 1;
         }
+    };
+    let code = match QrCode::with_error_correction_level(text.as_bytes(), EcLevel::L) {
+        Ok(code) => code,
         Err(e) => {
-            eprintln!("{} to verify ghost certificate: {}", "Failed".red(), e);
-            1
+            eprintln!("{} to create QR code: {}", "Failed".red(), e);
+            return 1;
+        }
+    };
+    match png_file {
+        Some(png_file) => {
+            info!("Writing QR code to {}", png_file.display());
+            if let Err(e) = code.render::<Luma<u8>>().build().save(png_file) {
+                eprintln!("{} to write QR code: {}", "Failed".red(), e);
+                return 1;
+            }
+            println!(
+                "QR code written {}: {}",
+                "successfully".green(),
+                png_file.display().to_string().yellow()
+            );
         }
+        None => {
+            // Light modules on a dark terminal background, as scanners expect dark on light
+            let rendered = code
+                .render::<unicode::Dense1x2>()
+                .dark_color(unicode::Dense1x2::Light)
+                .light_color(unicode::Dense1x2::Dark)
+                .build();
+            println!("{}", rendered);
+        }
+    }
+    if let Some(text_file) = text_file {
+        if let Err(e) = fs::write(text_file, &text) {
+            eprintln!("{} to write compact certificate: {}", "Failed".red(), e);
+            return 1;
+        }
+    }
+    println!(
+        "Compact certificate: {} characters{}",
+        text.len(),
+        if elide_delegate { ", without the delegate certificate" } else { "" }
+    );
+    print_ghost_key_fingerprints(ghost_certificate);
+    0
+}
+
+/// Rebuilds the full ghost key certificate from the text of a QR code made by [`export_qr_cmd`],
+/// taking the delegate certificate from `delegates` if it was left out.
+pub fn import_qr_cmd(text: &str, delegates: &[DelegateCertificate], output_file: &Path) -> i32 {
+    let ghost_certificate = match GhostkeyCertificate::from_compact_string(text, delegates) {
+        Ok(certificate) => certificate,
+        Err(e) => {
+            eprintln!("{} to decode compact certificate: [{}] {}", "Failed".red(), e.code(), e);
+            return 1;
+        }
+    };
+    info!("Writing ghostkey certificate to {}", output_file.display());
+    if let Err(e) = ghost_certificate.to_file(output_file) {
+        eprintln!("{} to write ghostkey certificate: [{}] {}", "Failed".red(), e.code(), e);
+        return 1;
+    }
+    println!(
+        "Ghost certificate imported {}: {}",
+        "successfully".green(),
+        output_file.display().to_string().yellow()
+    );
+    print_ghost_key_fingerprints(&ghost_certificate);
+    0
+}
+
+pub fn verify_ghost_key_cmd(
+    trust_store: &TrustStore,
+    ghost_certificate: &GhostkeyCertificate,
+    revocation_list: Option<&DelegateRevocationListV1>,
+) -> i32 {
+    let verification = match revocation_list {
+        Some(revocation_list) => {
+            revocation_list.verify_ghost_key_certificate(ghost_certificate, trust_store)
+        }
+        None => ghost_certificate.verify(trust_store),
+    };
+    match verification {
+        Ok(info) => {
+            println!("Ghost certificate {}", "verified".green());
+            print_ghost_key_fingerprints(ghost_certificate);
+            print_delegate_info(&info);
+            if let Some(metadata) = ghost_certificate.metadata() {
+                println!("Metadata: {}", metadata.to_string().blue());
+            }
+            0
+        }
+        Err(e) => {
+            eprintln!("{} to verify ghost certificate: [{}] {}", "Failed".red(), e.code(), e);
+            1
+        }
+    }
+}
+
+/// Mints a subkey for `purpose` valid for `valid_seconds`, certified by the ghost key, writing
+/// `subkey_certificate.pem` and `subkey_signing_key.pem` to `output_dir`.
+pub fn mint_subkey_cmd(
+    ghost_certificate: GhostkeyCertificate,
+    ghost_signing_key: &SigningKey,
+    purpose: &str,
+    valid_seconds: u64,
+    output_dir: &Path,
+    encrypt: bool,
+) -> i32 {
+    let (subkey_certificate, subkey_signing_key) =
+        match SubkeyCertificateV1::new(ghost_certificate, ghost_signing_key, purpose, valid_seconds) {
+            Ok(result) => result,
+            Err(e) => {
+                eprintln!("{} to mint subkey: [{}] {}", "Failed".red(), e.code(), e);
+                return 1;
+            }
+        };
+    let subkey_certificate_file = output_dir.join("subkey_certificate.pem");
+    let subkey_signing_key_file = output_dir.join("subkey_signing_key.pem");
+    info!("Writing subkey certificate to {}", subkey_certificate_file.display());
+    if let Err(e) = subkey_certificate.to_file(&subkey_certificate_file) {
+        eprintln!("{} to write subkey certificate: [{}] {}", "Failed".red(), e.code(), e);
+        return 1;
+    }
+    println!(
+        "Subkey certificate written {}: {}",
+        "successfully".green(),
+        subkey_certificate_file.display().to_string().yellow()
+    );
+    print_fingerprint("Certificate fingerprint", subkey_certificate.fingerprint());
+    print_fingerprint(
+        "Ghost key fingerprint",
+        subkey_certificate.ghost_key_certificate().verifying_key().fingerprint(),
+    );
+    info!("Writing subkey signing key to {}", subkey_signing_key_file.display());
+    if let Err(e) = write_private_key(&subkey_signing_key, &subkey_signing_key_file, encrypt) {
+        eprintln!("{} to write subkey signing key: [{}] {}", "Failed".red(), e.code(), e);
+        return 1;
+    }
+    println!(
+        "Subkey signing key written {}: {}",
+        "successfully".green(),
+        subkey_signing_key_file.display().to_string().yellow()
+    );
+    print_fingerprint("Subkey fingerprint", subkey_certificate.payload.verifying_key.fingerprint());
+    println!("Valid until: {} (Unix time)", subkey_certificate.payload.not_after);
+    0
+}
+
+/// Verifies a subkey certificate up to the master key, and checks that the subkey is for
+/// `purpose` if given.
+pub fn verify_subkey_cmd(
+    trust_store: &TrustStore,
+    subkey_certificate: &SubkeyCertificateV1,
+    revocation_list: Option<&DelegateRevocationListV1>,
+    purpose: Option<&str>,
+) -> i32 {
+    let verification = match revocation_list {
+        Some(revocation_list) => {
+            revocation_list.verify_subkey_certificate(subkey_certificate, trust_store)
+        }
+        None => subkey_certificate.verify(trust_store),
+    };
+    let info = match verification {
+        Ok(info) => info,
+        Err(e) => {
+            eprintln!("{} to verify subkey certificate: [{}] {}", "Failed".red(), e.code(), e);
+            return 1;
+        }
+    };
+    if let Some(purpose) = purpose {
+        if info.payload.purpose != purpose {
+            eprintln!(
+                "{}: Subkey is for purpose '{}', expected '{}'",
+                "Error".red(),
+                info.payload.purpose,
+                purpose
+            );
+            return 1;
+        }
+    }
+    println!("Subkey certificate {}", "verified".green());
+    print_ghost_key_fingerprints(&subkey_certificate.ghost_key_certificate());
+    print_delegate_info(&info.delegate_info);
+    if let Some(metadata) = &info.metadata {
+        println!("Metadata: {}", metadata.to_string().blue());
+    }
+    print_fingerprint("Subkey fingerprint", info.payload.verifying_key.fingerprint());
+    println!("Purpose: {}", info.payload.purpose.blue());
+    println!("Valid from: {} (Unix time)", info.payload.not_before);
+    println!("Valid until: {} (Unix time)", info.payload.not_after);
+    0
+}
+
+/// Derives the pseudonym of the ghost key in `context`, proven to belong to one of the ghost
+/// keys in `ring`, writing `pseudonym_certificate.pem` and `pseudonym_signing_key.pem` to
+/// `output_dir`.
+pub fn derive_pseudonym_cmd(
+    ghost_certificate: GhostkeyCertificate,
+    ghost_signing_key: &SigningKey,
+    context: &str,
+    ring: &[GhostkeyCertificate],
+    output_dir: &Path,
+    encrypt: bool,
+) -> i32 {
+    let (pseudonym_certificate, pseudonym_signing_key) =
+        match PseudonymCertificateV1::new(ghost_certificate, ghost_signing_key, context, ring) {
+            Ok(result) => result,
+            Err(e) => {
+                eprintln!("{} to derive pseudonym: [{}] {}", "Failed".red(), e.code(), e);
+                return 1;
+            }
+        };
+    let pseudonym_certificate_file = output_dir.join("pseudonym_certificate.pem");
+    let pseudonym_signing_key_file = output_dir.join("pseudonym_signing_key.pem");
+    info!("Writing pseudonym certificate to {}", pseudonym_certificate_file.display());
+    if let Err(e) = pseudonym_certificate.to_file(&pseudonym_certificate_file) {
+        eprintln!("{} to write pseudonym certificate: [{}] {}", "Failed".red(), e.code(), e);
+        return 1;
+    }
+    println!(
+        "Pseudonym certificate written {}: {}",
+        "successfully".green(),
+        pseudonym_certificate_file.display().to_string().yellow()
+    );
+    info!("Writing pseudonym signing key to {}", pseudonym_signing_key_file.display());
+    if let Err(e) = write_private_key(&pseudonym_signing_key, &pseudonym_signing_key_file, encrypt) {
+        eprintln!("{} to write pseudonym signing key: [{}] {}", "Failed".red(), e.code(), e);
+        return 1;
+    }
+    println!(
+        "Pseudonym signing key written {}: {}",
+        "successfully".green(),
+        pseudonym_signing_key_file.display().to_string().yellow()
+    );
+    print_fingerprint("Pseudonym", pseudonym_certificate.pseudonym_id());
+    print_fingerprint("Pseudonym key fingerprint", pseudonym_certificate.verifying_key.fingerprint());
+    println!("Ring size: {}", pseudonym_certificate.ring.len());
+    0
+}
+
+/// Signs `message` with a pseudonym key, embedding the pseudonym certificate.
+pub fn sign_as_pseudonym_cmd(
+    pseudonym_certificate: PseudonymCertificateV1,
+    pseudonym_signing_key: &SigningKey,
+    message: &[u8],
+    output_file: &Path,
+) -> i32 {
+    let pseudonym_id = pseudonym_certificate.pseudonym_id();
+    let pseudonym_message =
+        match PseudonymMessageV1::new(pseudonym_certificate, pseudonym_signing_key, message) {
+            Ok(pseudonym_message) => pseudonym_message,
+            Err(e) => {
+                eprintln!("{} to sign message: [{}] {}", "Failed".red(), e.code(), e);
+                return 1;
+            }
+        };
+    match pseudonym_message.to_file(output_file) {
+        Ok(_) => {
+            println!("Signed message written {}", "successfully".green());
+            print_fingerprint("Signed by pseudonym", pseudonym_id);
+            0
+        }
+        Err(e) => {
+            eprintln!("{} to write signed message: [{}] {}", "Failed".red(), e.code(), e);
+            1
+        }
+    }
+}
+
+/// Verifies a pseudonym certificate, or a message signed by a pseudonym, and checks that the
+/// pseudonym is for `context` if given. Writes the message to `output_file` if given, or prints
+/// it.
+pub fn verify_pseudonym_cmd(
+    trust_store: &TrustStore,
+    pseudonym_certificate: Option<&PseudonymCertificateV1>,
+    pseudonym_message: Option<&PseudonymMessageV1>,
+    revocation_list: Option<&DelegateRevocationListV1>,
+    context: Option<&str>,
+    output_file: Option<&Path>,
+) -> i32 {
+    let Some(certificate) = pseudonym_message.map(|message| &message.pseudonym).or(pseudonym_certificate) else {
+        eprintln!("{}: No pseudonym certificate or signed message given", "Error".red());
+        return 1;
+    };
+    let verification: Result<PseudonymInfo, GhostkeyError> = match (pseudonym_message, revocation_list) {
+        (Some(message), Some(revocation_list)) => revocation_list.verify_pseudonym_message(message, trust_store),
+        (Some(message), None) => message.verify(trust_store),
+        (None, Some(revocation_list)) => revocation_list.verify_pseudonym_certificate(certificate, trust_store),
+        (None, None) => certificate.verify(trust_store),
+    };
+    let info = match verification {
+        Ok(info) => info,
+        Err(e) => {
+            eprintln!("{} to verify pseudonym: [{}] {}", "Failed".red(), e.code(), e);
+            return 1;
+        }
+    };
+    if let Some(context) = context {
+        if info.context != context {
+            eprintln!(
+                "{}: Pseudonym is for context '{}', expected '{}'",
+                "Error".red(),
+                info.context,
+                context
+            );
+            return 1;
+        }
+    }
+    println!("Pseudonym {}", "verified".green());
+    println!("Context: {}", info.context.blue());
+    println!("Pseudonym: {} ({})", info.pseudonym_id.to_string().blue(), info.pseudonym_id.to_words());
+    print_fingerprint("Pseudonym key fingerprint", info.verifying_key.fingerprint());
+    println!("Ring size: {}", info.ring_size);
+    if let Some(member) = certificate.ring_certificates().first() {
+        print_fingerprint(
+            "Delegate key fingerprint",
+            member.delegate_payload().delegate_verifying_key().fingerprint(),
+        );
+    }
+    print_delegate_info(&info.delegate_info);
+    if let Some(pseudonym_message) = pseudonym_message {
+        println!("Signature {}", "verified".green());
+        println!("Signed at: {} (Unix time)", pseudonym_message.created);
+        match output_file {
+            Some(file) => {
+                if let Err(e) = fs::write(file, &pseudonym_message.message) {
+                    eprintln!("{} to write message to file: {}", "Failed".red(), e);
+                    return 1;
+                }
+                println!("Message written to {}", file.display());
+            }
+            None => {
+                println!("Message: {}", String::from_utf8_lossy(&pseudonym_message.message));
+            }
+        }
+    }
+    0
+}
+
+pub fn generate_revocation_list_cmd(
+    master_signing_key: &SigningKey,
+    existing_revocation_list: Option<&DelegateRevocationListV1>,
+    revoked_delegates: &[DelegateCertificate],
+    reason: &str,
+    next_update_days: u64,
+    output_file: &Path,
+) -> i32 {
+    let now = current_unix_time();
+    let mut newly_revoked = Vec::new();
+    let mut revoked: Vec<RevokedDelegate> = existing_revocation_list
+        .map(|list| list.payload.revoked.clone())
+        .unwrap_or_default();
+    for delegate_certificate in revoked_delegates {
+        let entry = match RevokedDelegate::new(delegate_certificate, now, reason) {
+            Ok(entry) => entry,
+            Err(e) => {
+                eprintln!("{} to fingerprint delegate certificate: [{}] {}", "Failed".red(), e.code(), e);
+                return 1;
+            }
+        };
+        if revoked.iter().any(|r| r.fingerprint == entry.fingerprint) {
+            info!("Delegate already revoked, skipping");
+            continue;
+        }
+        newly_revoked.push(entry.fingerprint);
+        revoked.push(entry);
+    }
+
+    let next_update = now + next_update_days * 24 * 60 * 60;
+    let revocation_list = match DelegateRevocationListV1::new(master_signing_key, revoked, now, next_update) {
+        Ok(list) => list,
+        Err(e) => {
+            eprintln!("{} to create revocation list: [{}] {}", "Failed".red(), e.code(), e);
+            return 1;
+        }
+    };
+
+    info!("Writing revocation list to {}", output_file.display());
+    if let Err(e) = revocation_list.to_file(output_file) {
+        eprintln!("{} to write revocation list: [{}] {}", "Failed".red(), e.code(), e);
+        return 1;
+    }
+    println!(
+        "Revocation list with {} revoked delegate(s) written {}: {}",
+        revocation_list.payload.revoked.len(),
+        "successfully".green(),
+        output_file.display().to_string().yellow()
+    );
+    for fingerprint in newly_revoked {
+        print_fingerprint("Revoked delegate key fingerprint", Ok(fingerprint));
+    }
+    0
+}
+
+pub fn add_trusted_key_cmd(
+    trust_store_file: &Path,
+    name: &str,
+    verifying_key: VerifyingKey,
+) -> i32 {
+    let mut trust_store = if trust_store_file.exists() {
+        match TrustStore::from_file(trust_store_file) {
+            Ok(trust_store) => trust_store,
+            Err(e) => {
+                eprintln!("{} to read trust store: [{}] {}", "Failed".red(), e.code(), e);
+                return 1;
+            }
+        }
+    } else {
+        TrustStore::new()
+    };
+    let fingerprint = verifying_key.fingerprint();
+    if let Err(e) = trust_store.add(name, verifying_key) {
+        eprintln!("{} to add key to trust store: [{}] {}", "Failed".red(), e.code(), e);
+        return 1;
+    }
+    info!("Writing trust store to {}", trust_store_file.display());
+    if let Err(e) = trust_store.to_file(trust_store_file) {
+        eprintln!("{} to write trust store: [{}] {}", "Failed".red(), e.code(), e);
+        return 1;
+    }
+    println!(
+        "Master verifying key '{}' added {}: {}",
+        name,
+        "successfully".green(),
+        trust_store_file.display().to_string().yellow()
+    );
+    print_fingerprint("Master key fingerprint", fingerprint);
+    0
+}
+
+/// Encrypts a private key file with a new passphrase, first decrypting it if it is already
+/// encrypted. With `remove_encryption` the key is written back in plain armor instead.
+pub fn change_passphrase_cmd(key_file: &Path, remove_encryption: bool) -> i32 {
+    let mut bundle = match ArmoredBundle::from_file(key_file) {
+        Ok(bundle) => bundle,
+        Err(e) => {
+            eprintln!("{} to read key file: [{}] {}", "Failed".red(), e.code(), e);
+            return 1;
+        }
+    };
+    let entry = match bundle.entries.as_slice() {
+        [entry] => entry,
+        _ => {
+            eprintln!("{}: Key file must contain exactly one key", "Error".red());
+            return 1;
+        }
+    };
+
+    let (key_type, plaintext) = if entry.label == EncryptedKeyV1::struct_name() {
+        let encrypted_key = match EncryptedKeyV1::from_bytes(&entry.block.data) {
+            Ok(key) => key,
+            Err(e) => {
+                eprintln!("{} to read encrypted key: [{}] {}", "Failed".red(), e.code(), e);
+                return 1;
+            }
+        };
+        let passphrase = match read_passphrase(&format!("Current passphrase for {}: ", key_file.display())) {
+            Ok(passphrase) => Zeroizing::new(passphrase),
+            Err(e) => {
+                eprintln!("{} to read passphrase: [{}] {}", "Failed".red(), e.code(), e);
+                return 1;
+            }
+        };
+        match encrypted_key.decrypt_bytes(&passphrase) {
+            Ok(plaintext) => (encrypted_key.key_type, Zeroizing::new(plaintext)),
+            Err(e) => {
+                eprintln!("{} to decrypt key: [{}] {}", "Failed".red(), e.code(), e);
+                return 1;
+            }
+        }
+    } else {
+        (entry.label.clone(), Zeroizing::new(entry.block.data.clone()))
+    };
+    for entry in &mut bundle.entries {
+        entry.block.data.zeroize();
+    }
+
+    let fingerprint = private_key_fingerprint(&key_type, &plaintext);
+
+    let mut output = ArmoredBundle::new();
+    if remove_encryption {
+        output.entries.push(BundleEntry {
+            label: key_type.clone(),
+            block: ArmoredBlock {
+                headers: vec![],
+                data: plaintext.to_vec(),
+            },
+        });
+    } else {
+        let encrypted = read_new_passphrase().map(Zeroizing::new).and_then(|passphrase| {
+            EncryptedKeyV1::encrypt_bytes(&key_type, &plaintext, &passphrase, KdfParams::new())
+        });
+        match encrypted {
+            Ok(encrypted) => {
+                if let Err(e) = output.add(&encrypted) {
+                    eprintln!("{} to encode encrypted key: [{}] {}", "Failed".red(), e.code(), e);
+                    return 1;
+                }
+            }
+            Err(e) => {
+                eprintln!("{} to encrypt key: [{}] {}", "Failed".red(), e.code(), e);
+                return 1;
+            }
+        }
+    }
+
+    let armored = output.to_armored_string().map(Zeroizing::new);
+    for entry in &mut output.entries {
+        entry.block.data.zeroize();
+    }
+    let armored = match armored {
+        Ok(armored) => armored,
+        Err(e) => {
+            eprintln!("{} to encode key file: [{}] {}", "Failed".red(), e.code(), e);
+            return 1;
+        }
+    };
+
+    // Write to a temporary file first so the key is not lost if writing fails. It is created
+    // readable only by its owner, since it may hold the decrypted key
+    let mut temporary_file = key_file.as_os_str().to_owned();
+    temporary_file.push(".tmp");
+    let temporary_file = Path::new(&temporary_file);
+    let written = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(temporary_file)
+        .and_then(|mut file| file.write_all(armored.as_bytes()).and_then(|_| file.sync_all()));
+    if let Err(e) = written {
+        eprintln!("{} to write {}: {}", "Failed".red(), temporary_file.display(), e);
+        if e.kind() != std::io::ErrorKind::AlreadyExists {
+            let _ = fs::remove_file(temporary_file);
+        }
+        return 1;
+    }
+    if let Err(e) = fs::rename(temporary_file, key_file) {
+        eprintln!("{} to replace key file: {}", "Failed".red(), e);
+        return 1;
+    }
+    println!(
+        "{} {} {}: {}",
+        key_type,
+        if remove_encryption { "decrypted" } else { "encrypted" },
+        "successfully".green(),
+        key_file.display().to_string().yellow()
+    );
+    if let Some(fingerprint) = fingerprint {
+        print_fingerprint("Key fingerprint", fingerprint);
+    }
+    0
+}
+
+/// Splits an armored private key into `shares` shares, any `threshold` of which rebuild it with
+/// [`combine_key_cmd`]. The key is split as it is stored, so the shares of an encrypted key
+/// rebuild the encrypted key and its passphrase is still needed.
+pub fn split_key_cmd(key_file: &Path, threshold: u8, shares: u8, output_dir: &Path) -> i32 {
+    let bundle = match ArmoredBundle::from_file(key_file) {
+        Ok(bundle) => bundle,
+        Err(e) => {
+            eprintln!("{} to read key file: [{}] {}", "Failed".red(), e.code(), e);
+            return 1;
+        }
+    };
+    let entry = match bundle.entries.as_slice() {
+        [entry] => entry,
+        _ => {
+            eprintln!("{}: Key file must contain exactly one key", "Error".red());
+            return 1;
+        }
+    };
+    let key_shares = match PrivateKeyShareV1::split(&entry.label, &entry.block.data, threshold, shares) {
+        Ok(key_shares) => key_shares,
+        Err(e) => {
+            eprintln!("{} to split key: [{}] {}", "Failed".red(), e.code(), e);
+            return 1;
+        }
+    };
+    for key_share in &key_shares {
+        let key_share_file = output_dir.join(format!("key_share_{}.pem", key_share.index));
+        info!("Writing key share to {}", key_share_file.display());
+        // The headers let custodians tell shares apart without the CLI
+        let comment = format!(
+            "Share {} of {} of a {}, any {} rebuild it",
+            key_share.index, shares, entry.label, threshold
+        );
+        let fingerprint = key_share.fingerprint.to_string();
+        let armored = key_share.to_armored_string_with_headers(&[
+            (HEADER_COMMENT, &comment),
+            (HEADER_FINGERPRINT, &fingerprint),
+        ]);
+        let written = armored.and_then(|armored| write_private_file(&key_share_file, armored.as_bytes()));
+        if let Err(e) = written {
+            eprintln!("{} to write key share: [{}] {}", "Failed".red(), e.code(), e);
+            return 1;
+        }
+        println!(
+            "Key share {} of {} written {}: {}",
+            key_share.index,
+            shares,
+            "successfully".green(),
+            key_share_file.display().to_string().yellow()
+        );
+    }
+    print_fingerprint("Key fingerprint", Ok(key_shares[0].fingerprint));
+    println!("Any {} of the {} shares rebuild the key", threshold, shares);
+    0
+}
+
+/// Rebuilds an armored private key from shares written by [`split_key_cmd`].
+pub fn combine_key_cmd(key_shares: &[PrivateKeyShareV1], output_file: &Path) -> i32 {
+    let (key_type, key) = match PrivateKeyShareV1::combine(key_shares) {
+        Ok(combined) => combined,
+        Err(e) => {
+            eprintln!("{} to combine key shares: [{}] {}", "Failed".red(), e.code(), e);
+            return 1;
+        }
+    };
+    let mut output = ArmoredBundle::new();
+    output.entries.push(BundleEntry {
+        label: key_type.clone(),
+        block: ArmoredBlock {
+            headers: vec![],
+            data: key,
+        },
+    });
+    info!("Writing key to {}", output_file.display());
+    if let Err(e) = output.to_private_file(output_file) {
+        eprintln!("{} to write key file: [{}] {}", "Failed".red(), e.code(), e);
+        return 1;
+    }
+    println!(
+        "{} rebuilt {}: {}",
+        key_type,
+        "successfully".green(),
+        output_file.display().to_string().yellow()
+    );
+    print_fingerprint("Key fingerprint", Ok(key_shares[0].fingerprint));
+    0
+}
+
+/// Writes the ghost key as an OpenSSH key pair to `output_dir`: `ghost_key.pub`, whose comment
+/// carries the ghost certificate, and the unencrypted private key `ghost_key`, which
+/// `ssh-keygen -Y sign` and git commit signing use.
+pub fn export_ssh_key_cmd(
+    ghost_certificate: &GhostkeyCertificate,
+    ghost_signing_key: &SigningKey,
+    output_dir: &Path,
+) -> i32 {
+    if ghost_signing_key.verifying_key() != *ghost_certificate.verifying_key() {
+        eprintln!("{}: The ghost signing key does not belong to the ghost certificate", "Error".red());
+        return 1;
+    }
+    let public_key = match ghost_certificate.to_openssh_public_key() {
+        Ok(public_key) => public_key,
+        Err(e) => {
+            eprintln!("{} to encode ghost certificate: [{}] {}", "Failed".red(), e.code(), e);
+            return 1;
+        }
+    };
+    let public_key_file = output_dir.join("ghost_key.pub");
+    let private_key_file = output_dir.join("ghost_key");
+    info!("Writing OpenSSH public key to {}", public_key_file.display());
+    if let Err(e) = fs::write(&public_key_file, format!("{}\n", public_key)) {
+        eprintln!("{} to write OpenSSH public key: {}", "Failed".red(), e);
+        return 1;
+    }
+    info!("Writing OpenSSH private key to {}", private_key_file.display());
+    // ssh-keygen refuses private keys others can read
+    let private_key = to_openssh_private_key(ghost_signing_key, "ghostkey");
+    if let Err(e) = write_private_file(&private_key_file, private_key.as_bytes()) {
+        eprintln!("{} to write OpenSSH private key: [{}] {}", "Failed".red(), e.code(), e);
+        return 1;
+    }
+    println!(
+        "OpenSSH keys written {}: {}, {}",
+        "successfully".green(),
+        public_key_file.display().to_string().yellow(),
+        private_key_file.display().to_string().yellow()
+    );
+    print_ghost_key_fingerprints(ghost_certificate);
+    println!(
+        "For an allowed_signers file: <principal> {}",
+        to_openssh_public_key(ghost_certificate.verifying_key(), "")
+    );
+    0
+}
+
+/// Signs `message` in `namespace` as `ssh-keygen -Y sign` does, writing the armored SSHSIG
+/// signature to `output_file`.
+pub fn ssh_sign_cmd(
+    ghost_certificate: &GhostkeyCertificate,
+    ghost_signing_key: &SigningKey,
+    namespace: &str,
+    message: &[u8],
+    output_file: &Path,
+) -> i32 {
+    if ghost_signing_key.verifying_key() != *ghost_certificate.verifying_key() {
+        eprintln!("{}: The ghost signing key does not belong to the ghost certificate", "Error".red());
+        return 1;
+    }
+    let signature = match SshSignature::sign(ghost_signing_key, namespace, message) {
+        Ok(signature) => signature,
+        Err(e) => {
+            eprintln!("{} to sign message: [{}] {}", "Failed".red(), e.code(), e);
+            return 1;
+        }
+    };
+    info!("Writing SSH signature to {}", output_file.display());
+    if let Err(e) = fs::write(output_file, signature.to_armored_string()) {
+        eprintln!("{} to write SSH signature: {}", "Failed".red(), e);
+        return 1;
+    }
+    println!(
+        "SSH signature written {}: {}",
+        "successfully".green(),
+        output_file.display().to_string().yellow()
+    );
+    print_fingerprint("Signed by ghost key", ghost_certificate.verifying_key().fingerprint());
+    0
+}
+
+/// Verifies an SSHSIG signature over `message` in `namespace` and the Freenet chain of the
+/// signing key, taken from the ghost certificate in the comment of `public_key`, a line written
+/// by [`export_ssh_key_cmd`].
+pub fn ssh_verify_cmd(
+    signature: &SshSignature,
+    public_key: &str,
+    trust_store: &TrustStore,
+    namespace: &str,
+    message: &[u8],
+) -> i32 {
+    let ghost_certificate = match GhostkeyCertificate::from_openssh_public_key(public_key) {
+        Ok(certificate) => certificate,
+        Err(e) => {
+            eprintln!("{} to read ghost certificate from public key: [{}] {}", "Failed".red(), e.code(), e);
+            return 1;
+        }
+    };
+    if signature.verifying_key != *ghost_certificate.verifying_key() {
+        eprintln!("{}: The SSH signature was not made by the ghost key of the public key", "Error".red());
+        return 1;
+    }
+    let info = match ghost_certificate.verify(trust_store) {
+        Ok(info) => info,
+        Err(e) => {
+            eprintln!("{} to verify ghost certificate: [{}] {}", "Failed".red(), e.code(), e);
+            return 1;
+        }
+    };
+    if let Err(e) = signature.verify(namespace, message) {
+        eprintln!("{} to verify SSH signature: [{}] {}", "Failed".red(), e.code(), e);
+        return 1;
+    }
+    println!("SSH signature {}", "verified".green());
+    print_ghost_key_fingerprints(&ghost_certificate);
+    print_delegate_info(&info);
+    0
+}
+
+/// Converts a key, certificate or signed message between armor, raw CBOR and JSON, see
+/// [`ghostkey_lib::json`]. The input format is detected: JSON starts with `{`, armor with
+/// `-----BEGIN` and anything else is taken as the CBOR of a certificate or signed message, since
+/// the CBOR of an Ed25519 signing key can't be told from that of a verifying key. The result is
+/// written to `output_file`, or to stdout without one.
+pub fn convert_cmd(input_file: &Path, format: &str, output_file: Option<&Path>) -> i32 {
+    let object = match Convertible::from_file(input_file) {
+        Ok(object) => object,
+        Err(e) => {
+            eprintln!("{} to read {}: [{}] {}", "Failed".red(), input_file.display(), e.code(), e);
+            return 1;
+        }
+    };
+    let converted = match format {
+        "json" => object.to_json().map(|json| format!("{}\n", json).into_bytes()),
+        "armor" => object.to_armored_string().map(String::into_bytes),
+        _ => object.to_bytes(),
+    };
+    let converted = match converted {
+        Ok(converted) => converted,
+        Err(e) => {
+            eprintln!("{} to encode {}: [{}] {}", "Failed".red(), object.name(), e.code(), e);
+            return 1;
+        }
+    };
+
+    let Some(output_file) = output_file else {
+        if let Err(e) = std::io::stdout().write_all(&converted) {
+            eprintln!("{} to write {}: {}", "Failed".red(), object.name(), e);
+            return 1;
+        }
+        return 0;
+    };
+    info!("Writing {} to {}", object.name(), output_file.display());
+    if matches!(object, Convertible::SigningKey(_)) {
+        if let Err(e) = write_private_file(output_file, &converted) {
+            eprintln!("{} to write {}: [{}] {}", "Failed".red(), object.name(), e.code(), e);
+            return 1;
+        }
+    } else if let Err(e) = fs::write(output_file, &converted) {
+        eprintln!("{} to write {}: {}", "Failed".red(), object.name(), e);
+        return 1;
+    }
+    println!(
+        "Converted {} to {} {}: {}",
+        object.name(),
+        format,
+        "successfully".green(),
+        output_file.display().to_string().yellow()
+    );
+    0
+}
+
+/// The objects [`convert_cmd`] converts.
+enum Convertible {
+    SigningKey(SigningKey),
+    VerifyingKey(VerifyingKey),
+    DelegateVerifyingKey(DelegateVerifyingKey),
+    DelegateCertificate(DelegateCertificate),
+    GhostkeyCertificate(GhostkeyCertificate),
+    SubkeyCertificate(SubkeyCertificateV1),
+    SignedMessage(SignedMessage),
+}
+
+impl Convertible {
+    fn from_file(input_file: &Path) -> Result<Self, GhostkeyError> {
+        let content = fs::read(input_file)
+            .map_err(|e| GhostkeyError::IOError(format!("Failed to read {}", input_file.display()), e))?;
+        match std::str::from_utf8(&content).map(str::trim_start) {
+            Ok(text) if text.starts_with('{') => Self::from_json(text),
+            Ok(text) if text.starts_with("-----BEGIN") => Self::from_armored_string(text, input_file),
+            _ => Self::from_bytes(&content),
+        }
+    }
+
+    /// Dispatches on the `type` of certificates and signed messages and the `kty` of keys.
+    fn from_json(json: &str) -> Result<Self, GhostkeyError> {
+        let value: serde_json::Value = serde_json::from_str(json)
+            .map_err(|e| GhostkeyError::DecodingError(format!("Invalid JSON: {}", e)))?;
+        let field = |name: &str| value.get(name).and_then(|field| field.as_str());
+        match (field("type"), field("kty")) {
+            (Some("delegate_certificate"), _) => DelegateCertificate::from_json(json).map(Self::DelegateCertificate),
+            (Some("ghost_key_certificate"), _) => GhostkeyCertificate::from_json(json).map(Self::GhostkeyCertificate),
+            (Some("subkey_certificate"), _) => SubkeyCertificateV1::from_json(json).map(Self::SubkeyCertificate),
+            (Some("signed_message"), _) => SignedMessage::from_json(json).map(Self::SignedMessage),
+            (None, Some("RSA")) => DelegateVerifyingKey::from_json(json).map(Self::DelegateVerifyingKey),
+            (None, Some("OKP")) if value.get("d").is_some() => SigningKey::from_json(json).map(Self::SigningKey),
+            (None, Some("OKP")) => VerifyingKey::from_json(json).map(Self::VerifyingKey),
+            _ => Err(GhostkeyError::InvalidInput(
+                "JSON is neither a ghostkey certificate or signed message nor a JWK".to_string(),
+            )),
+        }
+    }
+
+    /// Dispatches on the armor label, decrypting an encrypted signing key.
+    fn from_armored_string(armored: &str, input_file: &Path) -> Result<Self, GhostkeyError> {
+        let bundle = ArmoredBundle::from_armored_string(armored)?;
+        let label = match bundle.labels().as_slice() {
+            [label] => label.to_string(),
+            _ => {
+                return Err(GhostkeyError::InvalidInput(
+                    "Armored file must contain exactly one object".to_string(),
+                ))
+            }
+        };
+        if label == SigningKey::struct_name() || label == EncryptedKeyV1::struct_name() {
+            read_private_key(&bundle, || {
+                read_passphrase(&format!("Passphrase for {}: ", input_file.display()))
+            })
+            .map(Self::SigningKey)
+        } else if label == VerifyingKey::struct_name() {
+            bundle.get().map(Self::VerifyingKey)
+        } else if label == DelegateVerifyingKey::struct_name() {
+            bundle.get().map(Self::DelegateVerifyingKey)
+        } else if label == SubkeyCertificateV1::struct_name() {
+            bundle.get().map(Self::SubkeyCertificate)
+        } else if label.starts_with(DelegateCertificate::LABEL) {
+            DelegateCertificate::from_bundle(&bundle).map(Self::DelegateCertificate)
+        } else if label.starts_with(GhostkeyCertificate::LABEL) {
+            GhostkeyCertificate::from_bundle(&bundle).map(Self::GhostkeyCertificate)
+        } else if label.starts_with(SignedMessage::LABEL) {
+            SignedMessage::from_armored_string(armored).map(Self::SignedMessage)
+        } else {
+            Err(GhostkeyError::InvalidInput(format!("Can't convert {}", label)))
+        }
+    }
+
+    /// Tries each type in turn, those with more required fields first.
+    fn from_bytes(bytes: &[u8]) -> Result<Self, GhostkeyError> {
+        SignedMessage::from_bytes(bytes)
+            .map(Self::SignedMessage)
+            .or_else(|_| SubkeyCertificateV1::from_bytes(bytes).map(Self::SubkeyCertificate))
+            .or_else(|_| GhostkeyCertificate::from_bytes(bytes).map(Self::GhostkeyCertificate))
+            .or_else(|_| DelegateCertificate::from_bytes(bytes).map(Self::DelegateCertificate))
+            .map_err(|_| {
+                GhostkeyError::InvalidInput(
+                    "Input is neither JSON, armor nor the CBOR of a certificate or signed message".to_string(),
+                )
+            })
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Convertible::SigningKey(_) => "signing key",
+            Convertible::VerifyingKey(_) => "verifying key",
+            Convertible::DelegateVerifyingKey(_) => "delegate verifying key",
+            Convertible::DelegateCertificate(_) => "delegate certificate",
+            Convertible::GhostkeyCertificate(_) => "ghost certificate",
+            Convertible::SubkeyCertificate(_) => "subkey certificate",
+            Convertible::SignedMessage(_) => "signed message",
+        }
+    }
+
+    fn to_json(&self) -> Result<String, GhostkeyError> {
+        match self {
+            Convertible::SigningKey(key) => key.to_json(),
+            Convertible::VerifyingKey(key) => key.to_json(),
+            Convertible::DelegateVerifyingKey(key) => key.to_json(),
+            Convertible::DelegateCertificate(certificate) => certificate.to_json(),
+            Convertible::GhostkeyCertificate(certificate) => certificate.to_json(),
+            Convertible::SubkeyCertificate(certificate) => certificate.to_json(),
+            Convertible::SignedMessage(signed_message) => signed_message.to_json(),
+        }
+    }
+
+    fn to_armored_string(&self) -> Result<String, GhostkeyError> {
+        match self {
+            Convertible::SigningKey(key) => key.to_armored_string(),
+            Convertible::VerifyingKey(key) => key.to_armored_string(),
+            Convertible::DelegateVerifyingKey(key) => key.to_armored_string(),
+            Convertible::DelegateCertificate(certificate) => certificate.to_armored_string(),
+            Convertible::GhostkeyCertificate(certificate) => certificate.to_armored_string(),
+            Convertible::SubkeyCertificate(certificate) => certificate.to_armored_string(),
+            Convertible::SignedMessage(signed_message) => signed_message.to_armored_string(),
+        }
+    }
+
+    fn to_bytes(&self) -> Result<Vec<u8>, GhostkeyError> {
+        match self {
+            Convertible::SigningKey(key) => Armorable::to_bytes(key),
+            Convertible::VerifyingKey(key) => Armorable::to_bytes(key),
+            Convertible::DelegateVerifyingKey(key) => Armorable::to_bytes(key),
+            Convertible::DelegateCertificate(certificate) => certificate.to_bytes(),
+            Convertible::GhostkeyCertificate(certificate) => certificate.to_bytes(),
+            Convertible::SubkeyCertificate(certificate) => Armorable::to_bytes(certificate),
+            Convertible::SignedMessage(signed_message) => signed_message.to_bytes(),
+        }
+    }
+}
+
+/// Runs both sides of the blind issuance protocol, as the website and the API do, deriving the
+/// ghost key from `seed` as the website does.
+fn issue_ghost_key(
+    delegate_certificate: &DelegateCertificate,
+    delegate_signing_key: &RSASigningKey,
+    seed: &GhostkeySeed,
+) -> Result<(GhostkeyCertificate, SigningKey), GhostkeyError> {
+    let issuer = BlindIssuer::new(delegate_certificate.clone(), delegate_signing_key.clone())?;
+    let (blinding_state, issuance_request) =
+        BlindingState::new_with_rng(&mut seed.rng(), delegate_certificate.clone())?;
+    let issuance_response = issuer.sign(&issuance_request)?;
+    blinding_state.finalize(&issuance_response)
+}
+
+/// Returns the validity period of a certificate valid for `valid_days` from now, rejecting a
+/// number of days whose end time does not fit in a `u64`.
+fn validity_period(valid_days: Option<u64>) -> Result<Option<(u64, u64)>, GhostkeyError> {
+    let Some(days) = valid_days else {
+        return Ok(None);
+    };
+    let not_before = current_unix_time();
+    let not_after = days
+        .checked_mul(24 * 60 * 60)
+        .and_then(|seconds| not_before.checked_add(seconds))
+        .ok_or_else(|| GhostkeyError::InvalidInput(format!("Validity of {} days is too long", days)))?;
+    Ok(Some((not_before, not_after)))
+}
+
+/// Prints the key ID and word list form of a fingerprint, or why it could not be computed.
+fn print_fingerprint(name: &str, fingerprint: Result<Fingerprint, GhostkeyError>) {
+    match fingerprint {
+        Ok(fingerprint) => println!("{}: {} ({})", name, fingerprint.to_string().blue(), fingerprint.to_words()),
+        Err(e) => eprintln!("{} to compute {}: [{}] {}", "Failed".red(), name.to_lowercase(), e.code(), e),
+    }
+}
+
+fn print_ghost_key_fingerprints(ghost_certificate: &GhostkeyCertificate) {
+    print_fingerprint("Certificate fingerprint", ghost_certificate.fingerprint());
+    print_fingerprint("Ghost key fingerprint", ghost_certificate.verifying_key().fingerprint());
+    print_fingerprint(
+        "Delegate key fingerprint",
+        ghost_certificate.delegate_payload().delegate_verifying_key().fingerprint(),
+    );
+}
+
+fn print_delegate_info(info: &DelegateInfo) {
+    match &info.details {
+        Some(details) => {
+            println!("Amount: {} {}", details.amount.to_string().blue(), details.currency.blue());
+            println!("Tier: {}", details.tier.blue());
+            println!("Issuer: {}", details.issuer.blue());
+            println!("Created: {} (Unix time)", details.created);
+        }
+        None => println!("Info: {}", info.info.blue()),
+    }
+    if let Some(not_before) = info.not_before {
+        println!("Valid from: {} (Unix time)", not_before);
+    }
+    if let Some(not_after) = info.not_after {
+        println!("Valid until: {} (Unix time)", not_after);
     }
 }
 
 fn require_strict_permissions(file_path: &Path) -> Result<(), GhostkeyError> {
-    let metadata = fs::metadata(file_path).map_err(|e| GhostkeyError::IOError(e.to_string()))?;
+    let metadata = fs::metadata(file_path).map_err(|e| {
+        GhostkeyError::IOError(format!("Failed to read metadata of {}", file_path.display()), e)
+    })?;
     let permissions = metadata.permissions();
     let mode = permissions.mode();
 
@@ -1,2 +1,2 @@
 pub mod commands;
-pub mod signed_message;
+pub mod passphrase;
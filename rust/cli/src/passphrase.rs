@@ -0,0 +1,57 @@
+use ghostkey_lib::armorable::{write_private_file, Armorable, ArmoredBundle};
+use ghostkey_lib::encrypted_key::{read_private_key, EncryptedKeyV1};
+use ghostkey_lib::errors::GhostkeyError;
+use std::path::Path;
+
+/// Passphrase used to unlock encrypted keys, and to encrypt new keys unless
+/// [`NEW_PASSPHRASE_ENV`] is set. Intended for scripts, interactive users are prompted instead.
+pub const PASSPHRASE_ENV: &str = "GHOSTKEY_PASSPHRASE";
+/// Passphrase used to encrypt new keys, for example by `change-passphrase`.
+pub const NEW_PASSPHRASE_ENV: &str = "GHOSTKEY_NEW_PASSPHRASE";
+
+/// Reads the passphrase of an existing key from the environment or the terminal.
+pub fn read_passphrase(prompt: &str) -> Result<String, GhostkeyError> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+    rpassword::prompt_password(prompt)
+        .map_err(|e| GhostkeyError::IOError("Failed to read passphrase".to_string(), e))
+}
+
+/// Reads a passphrase for a new key from the environment, or prompts twice to confirm it.
+pub fn read_new_passphrase() -> Result<String, GhostkeyError> {
+    if let Ok(passphrase) = std::env::var(NEW_PASSPHRASE_ENV).or_else(|_| std::env::var(PASSPHRASE_ENV)) {
+        return Ok(passphrase);
+    }
+    let passphrase = rpassword::prompt_password("New passphrase: ")
+        .map_err(|e| GhostkeyError::IOError("Failed to read passphrase".to_string(), e))?;
+    let confirmation = rpassword::prompt_password("Repeat passphrase: ")
+        .map_err(|e| GhostkeyError::IOError("Failed to read passphrase".to_string(), e))?;
+    if passphrase != confirmation {
+        return Err(GhostkeyError::InvalidInput("Passphrases do not match".to_string()));
+    }
+    Ok(passphrase)
+}
+
+/// Loads a private key that may be encrypted, prompting for the passphrase only if it is.
+pub fn load_private_key<T: Armorable>(file_path: &Path) -> Result<T, GhostkeyError> {
+    let bundle = ArmoredBundle::from_file(file_path)?;
+    read_private_key(&bundle, || {
+        read_passphrase(&format!("Passphrase for {}: ", file_path.display()))
+    })
+}
+
+/// Writes a private key, encrypted with a new passphrase if `encrypt` is set. Either way the file
+/// is created readable by its owner only.
+pub fn write_private_key<T: Armorable>(
+    key: &T,
+    file_path: &Path,
+    encrypt: bool,
+) -> Result<(), GhostkeyError> {
+    let armored = if encrypt {
+        EncryptedKeyV1::encrypt(key, &read_new_passphrase()?)?.to_armored_string()?
+    } else {
+        key.to_armored_string()?
+    };
+    write_private_file(file_path, armored.as_bytes())
+}
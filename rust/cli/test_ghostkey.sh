@@ -59,6 +59,7 @@ check_files "$temp_dir/master-1" "master_signing_key.pem" "master_verifying_key.
 # Test generate-delegate
 run_test "Generate delegate" "cargo run --bin ghostkey -- generate-delegate --master-signing-key $temp_dir/master-1/master_signing_key.pem --info 'Test Delegate' --output-dir $temp_dir/delegate-1" 0
 check_files "$temp_dir/delegate-1" "delegate_certificate.pem" "delegate_signing_key.pem"
+run_test "New delegates are V2 certificates" "grep -q 'BEGIN DELEGATE_CERTIFICATE_V2' $temp_dir/delegate-1/delegate_certificate.pem" 0
 
 # Test verify-delegate (should succeed)
 run_test "Verify delegate with valid certificate" "cargo run --bin ghostkey -- verify-delegate --master-verifying-key $temp_dir/master-1/master_verifying_key.pem --delegate-certificate $temp_dir/delegate-1/delegate_certificate.pem" 0
@@ -67,12 +68,26 @@ run_test "Verify delegate with valid certificate" "cargo run --bin ghostkey -- v
 echo "Invalid certificate" > $temp_dir/INVALID_CERTIFICATE
 run_test "Verify delegate with invalid certificate (should fail)" "cargo run --bin ghostkey -- verify-delegate --master-verifying-key $temp_dir/master-1/master_verifying_key.pem --delegate-certificate $temp_dir/INVALID_CERTIFICATE" 1
 
+# Test generate-delegate with a validity period
+run_test "Generate delegate with validity period" "cargo run --bin ghostkey -- generate-delegate --master-signing-key $temp_dir/master-1/master_signing_key.pem --info 'Test Delegate' --valid-days 30 --output-dir $temp_dir/delegate-valid" 0
+run_test "Verify delegate with validity period" "cargo run --bin ghostkey -- verify-delegate --master-verifying-key $temp_dir/master-1/master_verifying_key.pem --delegate-certificate $temp_dir/delegate-valid/delegate_certificate.pem" 0
+
+# Test generate-delegate with typed details
+run_test "Generate delegate with details" "cargo run --bin ghostkey -- generate-delegate --master-signing-key $temp_dir/master-1/master_signing_key.pem --amount 20 --currency USD --tier supporter --output-dir $temp_dir/delegate-details" 0
+run_test "Verify delegate with details" "cargo run --bin ghostkey -- verify-delegate --master-verifying-key $temp_dir/master-1/master_verifying_key.pem --delegate-certificate $temp_dir/delegate-details/delegate_certificate.pem" 0
+run_test "Generate delegate with both info and amount (should fail)" "cargo run --bin ghostkey -- generate-delegate --master-signing-key $temp_dir/master-1/master_signing_key.pem --info 'Test Delegate' --amount 20 --output-dir $temp_dir/delegate-both" 2
+
 # Test generate-ghost-key
 run_test "Generate ghost key" "cargo run --bin ghostkey -- generate-ghost-key --delegate-dir $temp_dir/delegate-1 --output-dir $temp_dir/ghost-1" 0
+mkdir -p $temp_dir/delegate-mismatched
+cp $temp_dir/delegate-1/delegate_certificate.pem $temp_dir/delegate-valid/delegate_signing_key.pem $temp_dir/delegate-mismatched/
+run_test "Generate ghost key with another delegate's signing key (should fail)" "cargo run --bin ghostkey -- generate-ghost-key --delegate-dir $temp_dir/delegate-mismatched --output-dir $temp_dir/ghost-mismatched 2>&1 | grep -q GK_DELEGATE_MISMATCH" 0
 check_files "$temp_dir/ghost-1" "ghost_key_certificate.pem" "ghost_key_signing_key.pem"
+run_test "Ghost keys follow the delegate certificate version" "grep -q 'BEGIN GHOSTKEY_CERTIFICATE_V2' $temp_dir/ghost-1/ghost_key_certificate.pem" 0
 
 # Test verify-ghost-key
 run_test "Verify ghost key" "cargo run --bin ghostkey -- verify-ghost-key --master-verifying-key $temp_dir/master-1/master_verifying_key.pem --ghost-certificate $temp_dir/ghost-1/ghost_key_certificate.pem" 0
+run_test "Ghost key names the delegate key of its delegate certificate" "delegate_fingerprint=\$(cargo run --bin ghostkey -- verify-delegate --master-verifying-key $temp_dir/master-1/master_verifying_key.pem --delegate-certificate $temp_dir/delegate-1/delegate_certificate.pem 2>/dev/null | grep 'Delegate key fingerprint'); [ -n \"\$delegate_fingerprint\" ] && cargo run --bin ghostkey -- verify-ghost-key --master-verifying-key $temp_dir/master-1/master_verifying_key.pem --ghost-certificate $temp_dir/ghost-1/ghost_key_certificate.pem 2>/dev/null | grep -qF \"\$delegate_fingerprint\"" 0
 
 # Generate a second master key
 run_test "Generate second master key" "cargo run --bin ghostkey -- generate-master-key --output-dir $temp_dir/master-2" 0
@@ -83,6 +98,23 @@ run_test "Verify delegate with wrong master key (should fail)" "cargo run --bin
 
 # Test verify-ghost-key with wrong master key (should fail)
 run_test "Verify ghost key with wrong master key (should fail)" "cargo run --bin ghostkey -- verify-ghost-key --master-verifying-key $temp_dir/master-2/master_verifying_key.pem --ghost-certificate $temp_dir/ghost-1/ghost_key_certificate.pem" 1
+run_test "Wrong master key reports GK_SIG_DELEGATE_INVALID" "cargo run --bin ghostkey -- verify-ghost-key --master-verifying-key $temp_dir/master-2/master_verifying_key.pem --ghost-certificate $temp_dir/ghost-1/ghost_key_certificate.pem 2>&1 | grep -q GK_SIG_DELEGATE_INVALID" 0
+
+# Test trust stores with several master keys
+run_test "Add current key to trust store" "cargo run --bin ghostkey -- add-trusted-key --trust-store $temp_dir/trust_store.pem --name current --master-verifying-key $temp_dir/master-2/master_verifying_key.pem" 0
+run_test "Add next key to trust store" "cargo run --bin ghostkey -- add-trusted-key --trust-store $temp_dir/trust_store.pem --name next --master-verifying-key $temp_dir/master-1/master_verifying_key.pem" 0
+run_test "Add duplicate key name to trust store (should fail)" "cargo run --bin ghostkey -- add-trusted-key --trust-store $temp_dir/trust_store.pem --name next --master-verifying-key $temp_dir/master-1/master_verifying_key.pem" 1
+run_test "Verify ghost key with trust store" "cargo run --bin ghostkey -- verify-ghost-key --trust-store $temp_dir/trust_store.pem --ghost-certificate $temp_dir/ghost-1/ghost_key_certificate.pem" 0
+run_test "Add key to second trust store" "cargo run --bin ghostkey -- add-trusted-key --trust-store $temp_dir/other_trust_store.pem --name current --master-verifying-key $temp_dir/master-2/master_verifying_key.pem" 0
+run_test "Verify ghost key with untrusted master key (should fail)" "cargo run --bin ghostkey -- verify-ghost-key --trust-store $temp_dir/other_trust_store.pem --ghost-certificate $temp_dir/ghost-1/ghost_key_certificate.pem" 1
+
+# Test generate-revocation-list and verification against it
+run_test "Generate empty revocation list" "cargo run --bin ghostkey -- generate-revocation-list --master-signing-key $temp_dir/master-1/master_signing_key.pem --output $temp_dir/empty_revocation_list.pem" 0
+run_test "Verify ghost key with empty revocation list" "cargo run --bin ghostkey -- verify-ghost-key --master-verifying-key $temp_dir/master-1/master_verifying_key.pem --ghost-certificate $temp_dir/ghost-1/ghost_key_certificate.pem --revocation-list $temp_dir/empty_revocation_list.pem" 0
+run_test "Generate revocation list" "cargo run --bin ghostkey -- generate-revocation-list --master-signing-key $temp_dir/master-1/master_signing_key.pem --revoke $temp_dir/delegate-1/delegate_certificate.pem --reason 'Key leaked' --output $temp_dir/revocation_list.pem" 0
+run_test "Verify delegate with revocation list (should fail)" "cargo run --bin ghostkey -- verify-delegate --master-verifying-key $temp_dir/master-1/master_verifying_key.pem --delegate-certificate $temp_dir/delegate-1/delegate_certificate.pem --revocation-list $temp_dir/revocation_list.pem" 1
+run_test "Verify ghost key with revocation list (should fail)" "cargo run --bin ghostkey -- verify-ghost-key --master-verifying-key $temp_dir/master-1/master_verifying_key.pem --ghost-certificate $temp_dir/ghost-1/ghost_key_certificate.pem --revocation-list $temp_dir/revocation_list.pem" 1
+run_test "Verify revocation list with wrong master key (should fail)" "cargo run --bin ghostkey -- verify-ghost-key --master-verifying-key $temp_dir/master-2/master_verifying_key.pem --ghost-certificate $temp_dir/ghost-1/ghost_key_certificate.pem --revocation-list $temp_dir/empty_revocation_list.pem" 1
 
 # Test sign-message
 echo "Test message" > $temp_dir/test_message.txt
@@ -100,10 +132,143 @@ run_test "Verify signed message with output to file" "cargo run --bin ghostkey -
 # Verify the content of the output file
 run_test "Verify message content" "cmp -s \"$temp_dir/test_message.txt\" \"$temp_dir/verified_message.txt\"" 0
 
+# Test detached signatures and purposes
+run_test "Sign message with detached signature" "cargo run --bin ghostkey -- sign-message --ghost-certificate $temp_dir/ghost-1/ghost_key_certificate.pem --ghost-signing-key $temp_dir/ghost-1/ghost_key_signing_key.pem --message $temp_dir/test_message.txt --purpose 'test purpose' --content-type text/plain --detached --output $temp_dir/detached_signature.pem" 0
+run_test "Verify detached signature" "cargo run --bin ghostkey -- verify-signed-message --signed-message $temp_dir/detached_signature.pem --message $temp_dir/test_message.txt --purpose 'test purpose' --master-verifying-key $temp_dir/master-1/master_verifying_key.pem" 0
+run_test "Verify detached signature without message (should fail)" "cargo run --bin ghostkey -- verify-signed-message --signed-message $temp_dir/detached_signature.pem --master-verifying-key $temp_dir/master-1/master_verifying_key.pem" 1
+run_test "Verify detached signature with other message (should fail)" "cargo run --bin ghostkey -- verify-signed-message --signed-message $temp_dir/detached_signature.pem --message 'Other message' --master-verifying-key $temp_dir/master-1/master_verifying_key.pem" 1
+run_test "Verify signed message with other purpose (should fail)" "cargo run --bin ghostkey -- verify-signed-message --signed-message $temp_dir/detached_signature.pem --message $temp_dir/test_message.txt --purpose 'other purpose' --master-verifying-key $temp_dir/master-1/master_verifying_key.pem" 1
+
 # Test sign-message with mismatched ghost signing key (should fail)
 run_test "Generate another ghost key" "cargo run --bin ghostkey -- generate-ghost-key --delegate-dir $temp_dir/delegate-1 --output-dir $temp_dir/ghost-2" 0
 run_test "Sign message with mismatched ghost signing key" "cargo run --bin ghostkey -- sign-message --ghost-certificate $temp_dir/ghost-1/ghost_key_certificate.pem --ghost-signing-key $temp_dir/ghost-2/ghost_key_signing_key.pem --message $temp_dir/test_message.txt --output $temp_dir/signed_message_mismatched.pem" 1
 
+# Test sign-message with a bundle holding both the certificate and the signing key, as copied from the website
+cat $temp_dir/ghost-1/ghost_key_certificate.pem $temp_dir/ghost-1/ghost_key_signing_key.pem > $temp_dir/ghost_key_bundle.pem
+run_test "Sign message with ghost key bundle" "cargo run --bin ghostkey -- sign-message --ghost-key $temp_dir/ghost_key_bundle.pem --message $temp_dir/test_message.txt --output $temp_dir/signed_message_bundle.pem" 0
+run_test "Verify message signed with ghost key bundle" "cargo run --bin ghostkey -- verify-signed-message --signed-message $temp_dir/signed_message_bundle.pem --master-verifying-key $temp_dir/master-1/master_verifying_key.pem" 0
+cat $temp_dir/ghost-1/ghost_key_signing_key.pem $temp_dir/ghost-2/ghost_key_signing_key.pem > $temp_dir/ghost_key_bundle_no_cert.pem
+run_test "Sign message with bundle missing the certificate (should fail)" "cargo run --bin ghostkey -- sign-message --ghost-key $temp_dir/ghost_key_bundle_no_cert.pem --message $temp_dir/test_message.txt --output $temp_dir/signed_message_no_cert.pem" 1
+
+# Test subkeys
+run_test "Mint subkey" "cargo run --bin ghostkey -- mint-subkey --ghost-key $temp_dir/ghost_key_bundle.pem --purpose 'test server' --valid-hours 1 --output-dir $temp_dir/subkey-1" 0
+run_test "Verify subkey" "cargo run --bin ghostkey -- verify-subkey --subkey-certificate $temp_dir/subkey-1/subkey_certificate.pem --purpose 'test server' --master-verifying-key $temp_dir/master-1/master_verifying_key.pem" 0
+run_test "Verify subkey with wrong master key (should fail)" "cargo run --bin ghostkey -- verify-subkey --subkey-certificate $temp_dir/subkey-1/subkey_certificate.pem --master-verifying-key $temp_dir/master-2/master_verifying_key.pem" 1
+run_test "Sign message with subkey" "cargo run --bin ghostkey -- sign-message --subkey-certificate $temp_dir/subkey-1/subkey_certificate.pem --subkey-signing-key $temp_dir/subkey-1/subkey_signing_key.pem --message $temp_dir/test_message.txt --output $temp_dir/signed_message_subkey.pem" 0
+run_test "Verify message signed with subkey" "cargo run --bin ghostkey -- verify-signed-message --signed-message $temp_dir/signed_message_subkey.pem --purpose 'test server' --master-verifying-key $temp_dir/master-1/master_verifying_key.pem" 0
+run_test "Sign message with subkey for another purpose (should fail)" "cargo run --bin ghostkey -- sign-message --subkey-certificate $temp_dir/subkey-1/subkey_certificate.pem --subkey-signing-key $temp_dir/subkey-1/subkey_signing_key.pem --purpose 'other purpose' --message $temp_dir/test_message.txt --output $temp_dir/signed_message_subkey_other.pem" 2
+run_test "Sign message with ghost signing key as subkey (should fail)" "cargo run --bin ghostkey -- sign-message --subkey-certificate $temp_dir/subkey-1/subkey_certificate.pem --subkey-signing-key $temp_dir/ghost-1/ghost_key_signing_key.pem --message $temp_dir/test_message.txt --output $temp_dir/signed_message_subkey_wrong.pem" 1
+run_test "Mint subkey with mismatched ghost signing key (should fail)" "cargo run --bin ghostkey -- mint-subkey --ghost-certificate $temp_dir/ghost-1/ghost_key_certificate.pem --ghost-signing-key $temp_dir/ghost-2/ghost_key_signing_key.pem --purpose 'test server' --output-dir $temp_dir/subkey-2" 1
+
+# Test pseudonyms
+run_test "Generate third ghost key" "cargo run --bin ghostkey -- generate-ghost-key --delegate-dir $temp_dir/delegate-1 --output-dir $temp_dir/ghost-3" 0
+cat $temp_dir/ghost-2/ghost_key_certificate.pem $temp_dir/ghost-3/ghost_key_certificate.pem > $temp_dir/ring.pem
+run_test "Derive pseudonym" "cargo run --bin ghostkey -- derive-pseudonym --ghost-key $temp_dir/ghost_key_bundle.pem --context 'test poll' --ring $temp_dir/ring.pem --output-dir $temp_dir/pseudonym-1" 0
+run_test "Verify pseudonym" "cargo run --bin ghostkey -- verify-pseudonym --pseudonym-certificate $temp_dir/pseudonym-1/pseudonym_certificate.pem --context 'test poll' --master-verifying-key $temp_dir/master-1/master_verifying_key.pem" 0
+run_test "Verify pseudonym with wrong master key (should fail)" "cargo run --bin ghostkey -- verify-pseudonym --pseudonym-certificate $temp_dir/pseudonym-1/pseudonym_certificate.pem --master-verifying-key $temp_dir/master-2/master_verifying_key.pem" 1
+run_test "Verify pseudonym for another context (should fail)" "cargo run --bin ghostkey -- verify-pseudonym --pseudonym-certificate $temp_dir/pseudonym-1/pseudonym_certificate.pem --context 'other poll' --master-verifying-key $temp_dir/master-1/master_verifying_key.pem" 1
+run_test "Derive pseudonym again with a smaller ring" "cargo run --bin ghostkey -- derive-pseudonym --ghost-key $temp_dir/ghost_key_bundle.pem --context 'test poll' --output-dir $temp_dir/pseudonym-2" 0
+run_test "Pseudonym is stable within a context" "cmp -s $temp_dir/pseudonym-1/pseudonym_signing_key.pem $temp_dir/pseudonym-2/pseudonym_signing_key.pem" 0
+run_test "Sign message as pseudonym" "cargo run --bin ghostkey -- sign-as-pseudonym --pseudonym-certificate $temp_dir/pseudonym-1/pseudonym_certificate.pem --pseudonym-signing-key $temp_dir/pseudonym-1/pseudonym_signing_key.pem --message 'Yes' --output $temp_dir/pseudonym_message.pem" 0
+run_test "Verify message signed as pseudonym" "cargo run --bin ghostkey -- verify-pseudonym --signed-message $temp_dir/pseudonym_message.pem --master-verifying-key $temp_dir/master-1/master_verifying_key.pem" 0
+run_test "Sign message as pseudonym with ghost signing key (should fail)" "cargo run --bin ghostkey -- sign-as-pseudonym --pseudonym-certificate $temp_dir/pseudonym-1/pseudonym_certificate.pem --pseudonym-signing-key $temp_dir/ghost-1/ghost_key_signing_key.pem --message 'Yes' --output $temp_dir/pseudonym_message_wrong.pem" 1
+run_test "Generate ghost key from another delegate" "cargo run --bin ghostkey -- generate-ghost-key --delegate-dir $temp_dir/delegate-details --output-dir $temp_dir/ghost-details" 0
+run_test "Derive pseudonym with ring from another delegate (should fail)" "cargo run --bin ghostkey -- derive-pseudonym --ghost-key $temp_dir/ghost_key_bundle.pem --context 'test poll' --ring $temp_dir/ghost-details/ghost_key_certificate.pem --output-dir $temp_dir/pseudonym-3" 1
+
+# Test restoring a ghost key from its recovery phrase
+run_test "Generate ghost key with recovery phrase" "cargo run --bin ghostkey -- generate-ghost-key --delegate-dir $temp_dir/delegate-1 --output-dir $temp_dir/ghost-seed --show-mnemonic > $temp_dir/ghost_seed_output.txt" 0
+sed -n 's/\x1b\[[0-9;]*m//g; s/^Recovery phrase: //p' $temp_dir/ghost_seed_output.txt > $temp_dir/recovery_phrase.txt
+run_test "Recovery phrase has 24 words" "test \$(wc -w < $temp_dir/recovery_phrase.txt) -eq 24" 0
+run_test "Restore ghost key from recovery phrase" "cargo run --bin ghostkey -- restore --ghost-certificate $temp_dir/ghost-seed/ghost_key_certificate.pem --mnemonic $temp_dir/recovery_phrase.txt --output-dir $temp_dir/ghost-restored" 0
+run_test "Restored signing key matches the original" "cmp -s $temp_dir/ghost-seed/ghost_key_signing_key.pem $temp_dir/ghost-restored/ghost_key_signing_key.pem" 0
+run_test "Sign message with restored ghost key" "cargo run --bin ghostkey -- sign-message --ghost-certificate $temp_dir/ghost-restored/ghost_key_certificate.pem --ghost-signing-key $temp_dir/ghost-restored/ghost_key_signing_key.pem --message $temp_dir/test_message.txt --output $temp_dir/signed_message_restored.pem" 0
+run_test "Restore with another ghost certificate (should fail)" "cargo run --bin ghostkey -- restore --ghost-certificate $temp_dir/ghost-1/ghost_key_certificate.pem --mnemonic $temp_dir/recovery_phrase.txt --output-dir $temp_dir/ghost-restored-wrong" 1
+run_test "Restore with a mistyped recovery phrase (should fail)" "cargo run --bin ghostkey -- restore --ghost-certificate $temp_dir/ghost-seed/ghost_key_certificate.pem --mnemonic 'abandon abandon abandon' --output-dir $temp_dir/ghost-restored-wrong" 1
+
+# Test threshold signing with master key shares
+run_test "Split master key into shares" "cargo run --bin ghostkey -- generate-master-key-shares --master-signing-key $temp_dir/master-1/master_signing_key.pem --threshold 2 --shares 3 --output-dir $temp_dir/shares" 0
+check_files "$temp_dir/shares" "master_key_share_1.pem" "master_key_share_2.pem" "master_key_share_3.pem" "master_verifying_key.pem"
+run_test "Key shares sign for the master key" "cmp -s $temp_dir/master-1/master_verifying_key.pem $temp_dir/shares/master_verifying_key.pem" 0
+run_test "Generate delegate certificate request" "cargo run --bin ghostkey -- generate-delegate --request --info 'Threshold Delegate' --output-dir $temp_dir/delegate-threshold" 0
+run_test "Commit with key share 1" "cargo run --bin ghostkey -- threshold-commit --key-share $temp_dir/shares/master_key_share_1.pem --output-dir $temp_dir/signer-1" 0
+run_test "Commit with key share 3" "cargo run --bin ghostkey -- threshold-commit --key-share $temp_dir/shares/master_key_share_3.pem --output-dir $temp_dir/signer-3" 0
+run_test "Create signing package with one commitment (should fail)" "cargo run --bin ghostkey -- threshold-package --request $temp_dir/delegate-threshold/delegate_certificate_request.pem --commitment $temp_dir/signer-1/signing_commitment_1.pem --output $temp_dir/signing_package.pem" 1
+run_test "Create signing package" "cargo run --bin ghostkey -- threshold-package --request $temp_dir/delegate-threshold/delegate_certificate_request.pem --commitment $temp_dir/signer-1/signing_commitment_1.pem --commitment $temp_dir/signer-3/signing_commitment_3.pem --output $temp_dir/signing_package.pem" 0
+run_test "Sign with key share 2 outside the package (should fail)" "cargo run --bin ghostkey -- threshold-sign --key-share $temp_dir/shares/master_key_share_2.pem --nonces $temp_dir/signer-1/signing_nonces.pem --package $temp_dir/signing_package.pem --output-dir $temp_dir/signer-2" 1
+run_test "Sign with key share 1" "cargo run --bin ghostkey -- threshold-sign --key-share $temp_dir/shares/master_key_share_1.pem --nonces $temp_dir/signer-1/signing_nonces.pem --package $temp_dir/signing_package.pem --output-dir $temp_dir/signer-1" 0
+run_test "Signing nonces are deleted once used" "test -e $temp_dir/signer-1/signing_nonces.pem" 1
+run_test "Sign with key share 3" "cargo run --bin ghostkey -- threshold-sign --key-share $temp_dir/shares/master_key_share_3.pem --nonces $temp_dir/signer-3/signing_nonces.pem --package $temp_dir/signing_package.pem --output-dir $temp_dir/signer-3" 0
+run_test "Aggregate with one signature share (should fail)" "cargo run --bin ghostkey -- threshold-aggregate --package $temp_dir/signing_package.pem --signature-share $temp_dir/signer-1/signature_share_1.pem --output-dir $temp_dir/delegate-threshold" 1
+run_test "Aggregate signature shares" "cargo run --bin ghostkey -- threshold-aggregate --package $temp_dir/signing_package.pem --signature-share $temp_dir/signer-1/signature_share_1.pem --signature-share $temp_dir/signer-3/signature_share_3.pem --output-dir $temp_dir/delegate-threshold" 0
+run_test "Threshold-signed delegate is a V2 certificate" "grep -q 'BEGIN DELEGATE_CERTIFICATE_V2' $temp_dir/delegate-threshold/delegate_certificate.pem" 0
+run_test "Verify threshold-signed delegate" "cargo run --bin ghostkey -- verify-delegate --delegate-certificate $temp_dir/delegate-threshold/delegate_certificate.pem --master-verifying-key $temp_dir/master-1/master_verifying_key.pem" 0
+run_test "Generate ghost key from threshold-signed delegate" "cargo run --bin ghostkey -- generate-ghost-key --delegate-dir $temp_dir/delegate-threshold --output-dir $temp_dir/ghost-threshold" 0
+run_test "Verify ghost key from threshold-signed delegate" "cargo run --bin ghostkey -- verify-ghost-key --ghost-certificate $temp_dir/ghost-threshold/ghost_key_certificate.pem --master-verifying-key $temp_dir/master-1/master_verifying_key.pem" 0
+
+# Test passphrase-encrypted keys
+run_test "Generate encrypted master key" "GHOSTKEY_PASSPHRASE=secret cargo run --bin ghostkey -- generate-master-key --output-dir $temp_dir/master-enc --encrypt" 0
+run_test "Encrypted master key is armored as encrypted" "grep -q 'BEGIN ENCRYPTED_KEY_V1' $temp_dir/master-enc/master_signing_key.pem" 0
+run_test "Generate delegate with wrong passphrase (should fail)" "GHOSTKEY_PASSPHRASE=wrong cargo run --bin ghostkey -- generate-delegate --master-signing-key $temp_dir/master-enc/master_signing_key.pem --info 'Test Delegate' --output-dir $temp_dir/delegate-enc" 1
+run_test "Generate encrypted delegate" "GHOSTKEY_PASSPHRASE=secret cargo run --bin ghostkey -- generate-delegate --master-signing-key $temp_dir/master-enc/master_signing_key.pem --info 'Test Delegate' --output-dir $temp_dir/delegate-enc --encrypt" 0
+run_test "Generate encrypted ghost key" "GHOSTKEY_PASSPHRASE=secret cargo run --bin ghostkey -- generate-ghost-key --delegate-dir $temp_dir/delegate-enc --output-dir $temp_dir/ghost-enc --encrypt" 0
+run_test "Change ghost key passphrase" "GHOSTKEY_PASSPHRASE=secret GHOSTKEY_NEW_PASSPHRASE=other cargo run --bin ghostkey -- change-passphrase --key $temp_dir/ghost-enc/ghost_key_signing_key.pem" 0
+run_test "Sign message with old passphrase (should fail)" "GHOSTKEY_PASSPHRASE=secret cargo run --bin ghostkey -- sign-message --ghost-certificate $temp_dir/ghost-enc/ghost_key_certificate.pem --ghost-signing-key $temp_dir/ghost-enc/ghost_key_signing_key.pem --message $temp_dir/test_message.txt --output $temp_dir/signed_message_enc.pem" 1
+run_test "Sign message with encrypted ghost key" "GHOSTKEY_PASSPHRASE=other cargo run --bin ghostkey -- sign-message --ghost-certificate $temp_dir/ghost-enc/ghost_key_certificate.pem --ghost-signing-key $temp_dir/ghost-enc/ghost_key_signing_key.pem --message $temp_dir/test_message.txt --output $temp_dir/signed_message_enc.pem" 0
+run_test "Remove ghost key encryption" "GHOSTKEY_PASSPHRASE=other cargo run --bin ghostkey -- change-passphrase --key $temp_dir/ghost-enc/ghost_key_signing_key.pem --remove-encryption" 0
+run_test "Sign message with decrypted ghost key" "cargo run --bin ghostkey -- sign-message --ghost-certificate $temp_dir/ghost-enc/ghost_key_certificate.pem --ghost-signing-key $temp_dir/ghost-enc/ghost_key_signing_key.pem --message $temp_dir/test_message.txt --output $temp_dir/signed_message_dec.pem" 0
+
+# Test backing up a master key as secret shares
+run_test "Split master key for backup" "cargo run --bin ghostkey -- split-key --key $temp_dir/master-1/master_signing_key.pem --threshold 2 --shares 3 --output-dir $temp_dir/backup" 0
+check_files "$temp_dir/backup" "key_share_1.pem" "key_share_2.pem" "key_share_3.pem"
+run_test "Key shares name the master key" "master_fingerprint=\$(cargo run --bin ghostkey -- add-trusted-key --trust-store $temp_dir/backup_trust_store.pem --name backup --master-verifying-key $temp_dir/master-1/master_verifying_key.pem 2>/dev/null | sed -n 's/\x1b\[[0-9;]*m//g; s/^Master key fingerprint: \([0-9a-f ]*\) .*/\1/p'); [ -n \"\$master_fingerprint\" ] && grep -q \"Fingerprint: \$master_fingerprint\" $temp_dir/backup/key_share_2.pem" 0
+run_test "Combine one key share (should fail)" "cargo run --bin ghostkey -- combine-key --share $temp_dir/backup/key_share_2.pem --output $temp_dir/master_signing_key_rebuilt.pem" 1
+run_test "Combine the same key share twice (should fail)" "cargo run --bin ghostkey -- combine-key --share $temp_dir/backup/key_share_2.pem --share $temp_dir/backup/key_share_2.pem --output $temp_dir/master_signing_key_rebuilt.pem" 1
+run_test "Combine key shares" "cargo run --bin ghostkey -- combine-key --share $temp_dir/backup/key_share_3.pem --share $temp_dir/backup/key_share_1.pem --output $temp_dir/master_signing_key_rebuilt.pem" 0
+run_test "Rebuilt master key matches the original" "cmp -s $temp_dir/master-1/master_signing_key.pem $temp_dir/master_signing_key_rebuilt.pem" 0
+run_test "Split encrypted master key" "cargo run --bin ghostkey -- split-key --key $temp_dir/master-enc/master_signing_key.pem --threshold 2 --shares 2 --output-dir $temp_dir/backup-enc" 0
+run_test "Combine key shares of different keys (should fail)" "cargo run --bin ghostkey -- combine-key --share $temp_dir/backup/key_share_1.pem --share $temp_dir/backup-enc/key_share_2.pem --output $temp_dir/master_signing_key_mixed.pem" 1
+run_test "Combine encrypted key shares" "cargo run --bin ghostkey -- combine-key --share $temp_dir/backup-enc/key_share_1.pem --share $temp_dir/backup-enc/key_share_2.pem --output $temp_dir/master_signing_key_rebuilt_enc.pem" 0
+run_test "Generate delegate with rebuilt encrypted master key" "GHOSTKEY_PASSPHRASE=secret cargo run --bin ghostkey -- generate-delegate --master-signing-key $temp_dir/master_signing_key_rebuilt_enc.pem --info 'Test Delegate' --output-dir $temp_dir/delegate-rebuilt" 0
+
+# Test moving a ghost key certificate through a QR code
+run_test "Export ghost certificate as QR code" "cargo run --bin ghostkey -- export-qr --ghost-certificate $temp_dir/ghost-1/ghost_key_certificate.pem --text $temp_dir/compact.txt" 0
+run_test "Export ghost certificate without delegate as PNG" "cargo run --bin ghostkey -- export-qr --ghost-certificate $temp_dir/ghost-1/ghost_key_certificate.pem --elide-delegate --png $temp_dir/ghost_key_qr.png --text $temp_dir/compact_elided.txt" 0
+run_test "QR code is written as PNG" "head -c 4 $temp_dir/ghost_key_qr.png | grep -q PNG" 0
+run_test "Compact certificate without delegate is shorter" "test \$(wc -c < $temp_dir/compact_elided.txt) -lt \$(wc -c < $temp_dir/compact.txt)" 0
+run_test "Import compact certificate" "cargo run --bin ghostkey -- import-qr --compact $temp_dir/compact.txt --output $temp_dir/ghost_key_imported.pem" 0
+run_test "Imported certificate matches the original" "cmp -s $temp_dir/ghost-1/ghost_key_certificate.pem $temp_dir/ghost_key_imported.pem" 0
+run_test "Import compact certificate without its delegate (should fail)" "cargo run --bin ghostkey -- import-qr --compact $temp_dir/compact_elided.txt --delegate-certificate $temp_dir/delegate-details/delegate_certificate.pem --output $temp_dir/ghost_key_imported_elided.pem" 1
+run_test "Import compact certificate with its delegate" "cargo run --bin ghostkey -- import-qr --compact $temp_dir/compact_elided.txt --delegate-certificate $temp_dir/delegate-details/delegate_certificate.pem --delegate-certificate $temp_dir/delegate-1/delegate_certificate.pem --output $temp_dir/ghost_key_imported_elided.pem" 0
+run_test "Verify imported ghost key" "cargo run --bin ghostkey -- verify-ghost-key --master-verifying-key $temp_dir/master-1/master_verifying_key.pem --ghost-certificate $temp_dir/ghost_key_imported_elided.pem" 0
+
+# Test converting between armor, CBOR and JSON
+run_test "Convert ghost certificate to JSON" "cargo run --bin ghostkey -- convert --input $temp_dir/ghost-1/ghost_key_certificate.pem --to json --output $temp_dir/ghost_key_certificate.json" 0
+run_test "Ghost verifying key is an Ed25519 JWK" "grep -q '\"crv\": \"Ed25519\"' $temp_dir/ghost_key_certificate.json && grep -q '\"kty\": \"RSA\"' $temp_dir/ghost_key_certificate.json" 0
+run_test "Convert JSON ghost certificate to CBOR" "cargo run --bin ghostkey -- convert --input $temp_dir/ghost_key_certificate.json --to cbor --output $temp_dir/ghost_key_certificate.cbor" 0
+run_test "Convert CBOR ghost certificate to armor" "cargo run --bin ghostkey -- convert --input $temp_dir/ghost_key_certificate.cbor --to armor --output $temp_dir/ghost_key_certificate_converted.pem" 0
+run_test "Converted certificate matches the original" "cmp -s $temp_dir/ghost-1/ghost_key_certificate.pem $temp_dir/ghost_key_certificate_converted.pem" 0
+run_test "Convert signed message through JSON" "cargo run --bin ghostkey -- convert --input $temp_dir/signed_message.pem --to json --output $temp_dir/signed_message.json && cargo run --bin ghostkey -- convert --input $temp_dir/signed_message.json --to armor --output $temp_dir/signed_message_converted.pem" 0
+run_test "Verify converted signed message" "cargo run --bin ghostkey -- verify-signed-message --signed-message $temp_dir/signed_message_converted.pem --master-verifying-key $temp_dir/master-1/master_verifying_key.pem" 0
+run_test "Convert signing key to JWK" "cargo run --bin ghostkey -- convert --input $temp_dir/ghost-1/ghost_key_signing_key.pem --to json --output $temp_dir/ghost_key_signing_key.json" 0
+run_test "Signing key JWK is only readable by its owner" "test \$(stat -c %a $temp_dir/ghost_key_signing_key.json) = 600" 0
+run_test "Convert JWK back to signing key" "cargo run --bin ghostkey -- convert --input $temp_dir/ghost_key_signing_key.json --to armor | cmp -s - $temp_dir/ghost-1/ghost_key_signing_key.pem" 0
+run_test "Convert tampered JSON (should fail)" "sed 's/ghost_key_certificate/delegate_certificate/' $temp_dir/ghost_key_certificate.json > $temp_dir/tampered.json && cargo run --bin ghostkey -- convert --input $temp_dir/tampered.json --to armor" 1
+
+# Test OpenSSH keys and SSH signatures
+run_test "Export ghost key as OpenSSH keys" "cargo run --bin ghostkey -- export-ssh-key --ghost-certificate $temp_dir/ghost-1/ghost_key_certificate.pem --ghost-signing-key $temp_dir/ghost-1/ghost_key_signing_key.pem --output-dir $temp_dir/ssh" 0
+check_files "$temp_dir/ssh" "ghost_key.pub" "ghost_key"
+run_test "OpenSSH private key is only readable by its owner" "test \$(stat -c %a $temp_dir/ssh/ghost_key) = 600" 0
+run_test "Export another ghost key's signing key (should fail)" "cargo run --bin ghostkey -- export-ssh-key --ghost-certificate $temp_dir/ghost-1/ghost_key_certificate.pem --ghost-signing-key $temp_dir/ghost-enc/ghost_key_signing_key.pem --output-dir $temp_dir/ssh-mismatched" 1
+run_test "Sign message as SSH signature" "cargo run --bin ghostkey -- ssh-sign --ghost-certificate $temp_dir/ghost-1/ghost_key_certificate.pem --ghost-signing-key $temp_dir/ghost-1/ghost_key_signing_key.pem --namespace file --message $temp_dir/test_message.txt --output $temp_dir/test_message.sig" 0
+run_test "Verify SSH signature" "cargo run --bin ghostkey -- ssh-verify --signature $temp_dir/test_message.sig --public-key $temp_dir/ssh/ghost_key.pub --namespace file --message $temp_dir/test_message.txt --master-verifying-key $temp_dir/master-1/master_verifying_key.pem" 0
+run_test "Verify SSH signature in another namespace (should fail)" "cargo run --bin ghostkey -- ssh-verify --signature $temp_dir/test_message.sig --public-key $temp_dir/ssh/ghost_key.pub --namespace git --message $temp_dir/test_message.txt --master-verifying-key $temp_dir/master-1/master_verifying_key.pem" 1
+run_test "Verify SSH signature with wrong master key (should fail)" "cargo run --bin ghostkey -- ssh-verify --signature $temp_dir/test_message.sig --public-key $temp_dir/ssh/ghost_key.pub --namespace file --message $temp_dir/test_message.txt --master-verifying-key $temp_dir/master-2/master_verifying_key.pem" 1
+if command -v ssh-keygen > /dev/null; then
+    run_test "ssh-keygen reads the OpenSSH private key" "ssh-keygen -y -f $temp_dir/ssh/ghost_key | cut -d' ' -f2 | grep -qF \"\$(cut -d' ' -f2 $temp_dir/ssh/ghost_key.pub)\"" 0
+    run_test "ssh-keygen verifies the SSH signature" "echo \"ghost@example.com \$(cat $temp_dir/ssh/ghost_key.pub)\" > $temp_dir/allowed_signers && ssh-keygen -Y verify -f $temp_dir/allowed_signers -I ghost@example.com -n file -s $temp_dir/test_message.sig < $temp_dir/test_message.txt" 0
+    run_test "Verify SSH signature made by ssh-keygen" "ssh-keygen -Y sign -f $temp_dir/ssh/ghost_key -n git $temp_dir/test_message.txt && cargo run --bin ghostkey -- ssh-verify --signature $temp_dir/test_message.txt.sig --public-key $temp_dir/ssh/ghost_key.pub --namespace git --message $temp_dir/test_message.txt --master-verifying-key $temp_dir/master-1/master_verifying_key.pem" 0
+fi
+
 # Clean up
 echo "Cleaning up temporary directory"
 rm -rf "$temp_dir"
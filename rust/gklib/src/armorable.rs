@@ -1,6 +1,13 @@
-use std::any::type_name;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::any::type_name;
+#[cfg(feature = "fs")]
 use std::fs::File;
+#[cfg(feature = "fs")]
 use std::io::{Read, Write};
+#[cfg(feature = "fs")]
 use std::path::Path;
 
 use base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
@@ -9,12 +16,13 @@ use ciborium::{de::from_reader, ser::into_writer};
 use serde::{Deserialize, Serialize};
 
 use super::errors::GhostkeyError;
-use super::errors::GhostkeyError::Base64DecodeError;
 
 pub trait Armorable: Serialize + for<'de> Deserialize<'de> + 'static {
     fn to_bytes(&self) -> Result<Vec<u8>, GhostkeyError> {
         let mut buf = Vec::new();
-        into_writer(self, &mut buf).map_err(|e| GhostkeyError::IOError(e.to_string()))?;
+        into_writer(self, &mut buf).map_err(|e| {
+            GhostkeyError::SerializationError(format!("Failed to encode {}", Self::struct_name()), e.into())
+        })?;
         Ok(buf)
     }
 
@@ -22,7 +30,9 @@ pub trait Armorable: Serialize + for<'de> Deserialize<'de> + 'static {
     where
         Self: Sized,
     {
-        let object: Self = from_reader(bytes).map_err(|e| GhostkeyError::IOError(e.to_string()))?;
+        let object: Self = from_reader(bytes).map_err(|e| {
+            GhostkeyError::DeserializationError(format!("Failed to decode {}", Self::struct_name()), e.into())
+        })?;
         Ok(object)
     }
 
@@ -62,32 +72,28 @@ pub trait Armorable: Serialize + for<'de> Deserialize<'de> + 'static {
     }
 
     fn to_armored_string(&self) -> Result<String, GhostkeyError> {
-        let buf = self
-            .to_bytes()
-            .map_err(|e| GhostkeyError::IOError(e.to_string()))?;
-        let base64_encoded = BASE64_STANDARD.encode(&buf);
-        let wrapped = base64_encoded
-            .as_bytes()
-            .chunks(64)
-            .map(std::str::from_utf8)
-            .collect::<Result<Vec<&str>, _>>()
-            .map_err(|e| GhostkeyError::DecodingError(format!("UTF decoding error: {}", e)))?
-            .join("\n");
-
-        let struct_name = Self::struct_name();
-        let pem_content = format!(
-            "-----BEGIN {}-----\n{}\n-----END {}-----\n",
-            struct_name, wrapped, struct_name
-        );
+        self.to_armored_string_with_headers(&[])
+    }
 
-        Ok(pem_content)
+    /// Armors the object with `Key: value` header lines, such as [`HEADER_COMMENT`], before the
+    /// data and a CRC24 checksum line after it.
+    fn to_armored_string_with_headers(
+        &self,
+        headers: &[(&str, &str)],
+    ) -> Result<String, GhostkeyError> {
+        let buf = self.to_bytes()?;
+        armor(&Self::struct_name(), headers, &buf)
     }
 
+    #[cfg(feature = "fs")]
     fn to_file(&self, file_path: &Path) -> Result<(), GhostkeyError> {
         let pem_content = self.to_armored_string()?;
-        let mut file = File::create(file_path).map_err(|e| GhostkeyError::IOError(e.to_string()))?;
-        file.write_all(pem_content.as_bytes())
-            .map_err(|e| GhostkeyError::IOError(e.to_string()))?;
+        let mut file = File::create(file_path).map_err(|e| {
+            GhostkeyError::IOError(format!("Failed to create {}", file_path.display()), e)
+        })?;
+        file.write_all(pem_content.as_bytes()).map_err(|e| {
+            GhostkeyError::IOError(format!("Failed to write {}", file_path.display()), e)
+        })?;
         Ok(())
     }
 
@@ -101,56 +107,51 @@ pub trait Armorable: Serialize + for<'de> Deserialize<'de> + 'static {
             struct_name.trim_end_matches("_V1").to_string(),
         ];
 
+        let mut first_error = None;
         for label in possible_labels {
-            let begin_label = format!("-----BEGIN {}-----", label);
-            let end_label = format!("-----END {}-----", label);
-
-            if let Some(block) = armored_string.split(&begin_label).nth(1) {
-                if let Some(content) = block.split(&end_label).next() {
-                    let trimmed_content = content.trim();
-                    match Self::decode_block(trimmed_content) {
-                        Ok(result) => return Ok(result),
-                        Err(_) => continue, // Try the next label if decoding fails
+            if let Some(block) = find_block(armored_string, &label) {
+                match block.and_then(Self::decode_block) {
+                    Ok(result) => return Ok(result),
+                    // Try the next label if decoding fails, but report why this one failed
+                    Err(e) => {
+                        first_error.get_or_insert(e);
                     }
                 }
             }
         }
 
-        Err(GhostkeyError::DecodingError(format!(
-            "Failed to decode any matching block for {}",
-            struct_name
-        )))
+        Err(first_error.unwrap_or_else(|| {
+            GhostkeyError::DecodingError(format!(
+                "Failed to decode any matching block for {}",
+                struct_name
+            ))
+        }))
     }
 
     fn decode_block(block: &str) -> Result<Self, GhostkeyError>
     where
         Self: Sized,
     {
-        let base64_encoded = block
-            .lines()
-            .filter(|line| !line.starts_with("-----"))
-            .collect::<Vec<&str>>()
-            .join("");
-
-        let decoded = BASE64_STANDARD
-            .decode(&base64_encoded)
-            .map_err(|e| GhostkeyError::Base64DecodeError(e.to_string()))?;
-        Self::from_bytes(&decoded)
+        Self::from_bytes(&decode_block_contents(block)?)
     }
 
+    #[cfg(feature = "fs")]
     fn from_file(file_path: &Path) -> Result<Self, GhostkeyError>
     where
         Self: Sized,
     {
-        let mut file = File::open(file_path).map_err(|e| GhostkeyError::IOError(e.to_string()))?;
+        let mut file = File::open(file_path).map_err(|e| {
+            GhostkeyError::IOError(format!("Failed to open {}", file_path.display()), e)
+        })?;
         let mut armored_content = String::new();
-        file.read_to_string(&mut armored_content)
-            .map_err(|e| GhostkeyError::IOError(e.to_string()))?;
+        file.read_to_string(&mut armored_content).map_err(|e| {
+            GhostkeyError::IOError(format!("Failed to read {}", file_path.display()), e)
+        })?;
 
         Self::from_armored_string(&armored_content)
     }
 
-    fn to_base64(&self) -> Result<String, Box<dyn std::error::Error>> {
+    fn to_base64(&self) -> Result<String, GhostkeyError> {
         let buf = self.to_bytes()?;
         Ok(BASE64_STANDARD.encode(&buf))
     }
@@ -159,15 +160,388 @@ pub trait Armorable: Serialize + for<'de> Deserialize<'de> + 'static {
     where
         Self: Sized,
     {
-        let decoded = BASE64_STANDARD
-            .decode(encoded)
-            .map_err(|e| Base64DecodeError(e.to_string()))?;
+        let decoded = BASE64_STANDARD.decode(encoded)?;
         Self::from_bytes(&decoded)
     }
 }
 
 impl<T: Serialize + for<'de> Deserialize<'de> + 'static> Armorable for T {}
 
+/// Header naming the software that wrote the armor.
+pub const HEADER_VERSION: &str = "Version";
+/// Free-form header for humans, ignored when decoding.
+pub const HEADER_COMMENT: &str = "Comment";
+/// Header carrying the fingerprint of the armored key or certificate.
+pub const HEADER_FINGERPRINT: &str = "Fingerprint";
+
+/// The contents of an armored block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArmoredBlock {
+    pub headers: Vec<(String, String)>,
+    pub data: Vec<u8>,
+}
+
+impl ArmoredBlock {
+    pub fn header(&self, key: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(key))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Computes the OpenPGP CRC24 checksum (RFC 4880, section 6.1).
+pub fn crc24(data: &[u8]) -> u32 {
+    let mut crc: u32 = 0xB704CE;
+    for byte in data {
+        crc ^= (*byte as u32) << 16;
+        for _ in 0..8 {
+            crc <<= 1;
+            if crc & 0x1000000 != 0 {
+                crc ^= 0x1864CFB;
+            }
+        }
+    }
+    crc & 0xFFFFFF
+}
+
+fn encode_checksum(data: &[u8]) -> String {
+    let crc = crc24(data);
+    BASE64_STANDARD.encode([(crc >> 16) as u8, (crc >> 8) as u8, crc as u8])
+}
+
+/// Armors `data` under `label` with header lines and a CRC24 checksum line.
+pub fn armor(label: &str, headers: &[(&str, &str)], data: &[u8]) -> Result<String, GhostkeyError> {
+    let base64_encoded = BASE64_STANDARD.encode(data);
+    let wrapped = base64_encoded
+        .as_bytes()
+        .chunks(64)
+        .map(core::str::from_utf8)
+        .collect::<Result<Vec<&str>, _>>()
+        .map_err(|e| GhostkeyError::DecodingError(format!("UTF decoding error: {}", e)))?
+        .join("\n");
+
+    let mut header_lines = String::new();
+    for (key, value) in headers {
+        if key.is_empty() || key.contains([':', '\n', '\r']) || value.contains(['\n', '\r']) {
+            return Err(GhostkeyError::ArmorError(format!(
+                "Invalid armor header '{}'",
+                key
+            )));
+        }
+        header_lines.push_str(&format!("{}: {}\n", key, value));
+    }
+    if !header_lines.is_empty() {
+        header_lines.push('\n');
+    }
+
+    Ok(format!(
+        "-----BEGIN {}-----\n{}{}\n={}\n-----END {}-----\n",
+        label,
+        header_lines,
+        wrapped,
+        encode_checksum(data),
+        label
+    ))
+}
+
+/// Returns the text between the BEGIN and END lines for `label`, or `None` if there is no BEGIN
+/// line. A BEGIN line without a matching END line is reported as a truncated block.
+fn find_block<'a>(
+    armored_string: &'a str,
+    label: &str,
+) -> Option<Result<&'a str, GhostkeyError>> {
+    let begin_label = format!("-----BEGIN {}-----", label);
+    let end_label = format!("-----END {}-----", label);
+
+    let mut blocks = armored_string.split(&begin_label).skip(1);
+    let block = blocks.next()?;
+    if blocks.next().is_some() {
+        return Some(Err(GhostkeyError::ArmorError(format!(
+            "Found more than one {} block, use an ArmoredBundle to read several objects",
+            label
+        ))));
+    }
+    Some(match block.split_once(&end_label) {
+        Some((content, _)) => Ok(content.trim()),
+        None => Err(GhostkeyError::TruncatedBlockError(format!(
+            "{} block has no END line, it may have been cut off while copying",
+            label
+        ))),
+    })
+}
+
+/// Parses the text between the BEGIN and END lines of an armored block: optional `Key: value`
+/// headers, the base64 data and an optional `=XXXX` CRC24 checksum line. Armor written before
+/// checksums were introduced has neither headers nor a checksum and is still accepted.
+pub fn parse_block(block: &str) -> Result<ArmoredBlock, GhostkeyError> {
+    let mut headers = Vec::new();
+    let mut base64_encoded = String::new();
+    let mut checksum = None;
+
+    for line in block.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with("-----") {
+            continue;
+        }
+        if checksum.is_some() {
+            return Err(GhostkeyError::ArmorError(
+                "Unexpected data after the checksum line".to_string(),
+            ));
+        }
+        if let Some((key, value)) = line.split_once(':') {
+            if !base64_encoded.is_empty() {
+                return Err(GhostkeyError::ArmorError(format!(
+                    "Header '{}' found after the armored data",
+                    key
+                )));
+            }
+            headers.push((key.trim().to_string(), value.trim().to_string()));
+        } else if line.len() == 5 && line.starts_with('=') {
+            checksum = Some(&line[1..]);
+        } else {
+            base64_encoded.push_str(line);
+        }
+    }
+
+    if base64_encoded.is_empty() {
+        return Err(GhostkeyError::TruncatedBlockError(
+            "Armored block contains no data".to_string(),
+        ));
+    }
+
+    let data = BASE64_STANDARD.decode(&base64_encoded).map_err(|e| {
+        if checksum.is_none() && !base64_encoded.len().is_multiple_of(4) {
+            GhostkeyError::TruncatedBlockError(format!(
+                "Armored data has an incomplete final group, it may have been cut off: {}",
+                e
+            ))
+        } else {
+            GhostkeyError::Base64DecodeError(e)
+        }
+    })?;
+
+    if let Some(checksum) = checksum {
+        let expected = encode_checksum(&data);
+        if checksum != expected {
+            return Err(GhostkeyError::ChecksumMismatchError(format!(
+                "Armor checksum is ={} but the data has checksum ={}, the block was altered or \
+                 damaged",
+                checksum, expected
+            )));
+        }
+    }
+
+    Ok(ArmoredBlock { headers, data })
+}
+
+/// Decodes the data of an armored block into bytes, verifying its checksum if present.
+pub fn decode_block_contents(block: &str) -> Result<Vec<u8>, GhostkeyError> {
+    Ok(parse_block(block)?.data)
+}
+
+/// Finds the armored block labelled `base_label`, or `base_label` followed by a `_V<n>` version
+/// suffix, and returns the labelled version (if any) with the decoded contents of the block.
+/// Used to read types that exist in several versions without knowing which was written.
+pub fn read_versioned_block(
+    armored_string: &str,
+    base_label: &str,
+) -> Result<(Option<u32>, Vec<u8>), GhostkeyError> {
+    let bundle = ArmoredBundle::from_armored_string(armored_string)?;
+    let (version, entry) = bundle.find_versioned(base_label)?;
+    Ok((version, entry.block.data.clone()))
+}
+
+/// A labelled block within an [`ArmoredBundle`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct BundleEntry {
+    pub label: String,
+    pub block: ArmoredBlock,
+}
+
+/// Several armored objects in one string, such as a ghost key certificate together with its
+/// signing key. Text between blocks is ignored, so bundles can carry comments for humans.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ArmoredBundle {
+    pub entries: Vec<BundleEntry>,
+}
+
+impl ArmoredBundle {
+    pub fn new() -> Self {
+        ArmoredBundle { entries: Vec::new() }
+    }
+
+    /// Adds an object under its armor label.
+    pub fn add<T: Armorable>(&mut self, object: &T) -> Result<(), GhostkeyError> {
+        self.add_with_headers(object, &[])
+    }
+
+    pub fn add_with_headers<T: Armorable>(
+        &mut self,
+        object: &T,
+        headers: &[(&str, &str)],
+    ) -> Result<(), GhostkeyError> {
+        self.entries.push(BundleEntry {
+            label: T::struct_name(),
+            block: ArmoredBlock {
+                headers: headers
+                    .iter()
+                    .map(|(key, value)| (key.to_string(), value.to_string()))
+                    .collect(),
+                data: object.to_bytes()?,
+            },
+        });
+        Ok(())
+    }
+
+    /// The labels of the blocks in the bundle, in order.
+    pub fn labels(&self) -> Vec<&str> {
+        self.entries.iter().map(|entry| entry.label.as_str()).collect()
+    }
+
+    /// Returns the entries with exactly this label.
+    pub fn find(&self, label: &str) -> Vec<&BundleEntry> {
+        self.entries.iter().filter(|entry| entry.label == label).collect()
+    }
+
+    /// Extracts the single object of type `T`, matching its versioned or unversioned label.
+    pub fn get<T: Armorable>(&self) -> Result<T, GhostkeyError> {
+        let struct_name = T::struct_name();
+        let unversioned = struct_name.trim_end_matches("_V1");
+        let entries: Vec<&BundleEntry> = self
+            .entries
+            .iter()
+            .filter(|entry| entry.label == struct_name || entry.label == unversioned)
+            .collect();
+        match entries.as_slice() {
+            [entry] => T::from_bytes(&entry.block.data),
+            [] => Err(GhostkeyError::DecodingError(format!(
+                "Bundle contains no {} block",
+                struct_name
+            ))),
+            _ => Err(GhostkeyError::ArmorError(format!(
+                "Bundle contains {} {} blocks, expected one",
+                entries.len(),
+                struct_name
+            ))),
+        }
+    }
+
+    /// Returns the single entry labelled `base_label` or `base_label` with a `_V<n>` suffix,
+    /// together with the labelled version.
+    pub fn find_versioned(
+        &self,
+        base_label: &str,
+    ) -> Result<(Option<u32>, &BundleEntry), GhostkeyError> {
+        let versioned_prefix = format!("{}_V", base_label);
+        let mut found = None;
+        for entry in &self.entries {
+            let version = if entry.label == base_label {
+                None
+            } else {
+                match entry.label.strip_prefix(&versioned_prefix).map(str::parse::<u32>) {
+                    Some(Ok(version)) => Some(version),
+                    _ => continue,
+                }
+            };
+            if found.is_some() {
+                return Err(GhostkeyError::ArmorError(format!(
+                    "Bundle contains more than one {} block",
+                    base_label
+                )));
+            }
+            found = Some((version, entry));
+        }
+        found.ok_or_else(|| {
+            GhostkeyError::DecodingError(format!("Failed to find a {} block", base_label))
+        })
+    }
+
+    pub fn to_armored_string(&self) -> Result<String, GhostkeyError> {
+        let mut armored_blocks = Vec::with_capacity(self.entries.len());
+        for entry in &self.entries {
+            let headers: Vec<(&str, &str)> = entry
+                .block
+                .headers
+                .iter()
+                .map(|(key, value)| (key.as_str(), value.as_str()))
+                .collect();
+            armored_blocks.push(armor(&entry.label, &headers, &entry.block.data)?);
+        }
+        Ok(armored_blocks.join("\n"))
+    }
+
+    /// Parses every armored block in the string. A block without an END line is reported as
+    /// truncated rather than skipped.
+    pub fn from_armored_string(armored_string: &str) -> Result<Self, GhostkeyError> {
+        let mut entries = Vec::new();
+        let mut rest = armored_string;
+        while let Some(begin_index) = rest.find("-----BEGIN ") {
+            let after_begin = &rest[begin_index + "-----BEGIN ".len()..];
+            let (label, after_label) = after_begin.split_once("-----").ok_or_else(|| {
+                GhostkeyError::TruncatedBlockError("BEGIN line is incomplete".to_string())
+            })?;
+            let end_label = format!("-----END {}-----", label);
+            let (content, after_end) = after_label.split_once(&end_label).ok_or_else(|| {
+                GhostkeyError::TruncatedBlockError(format!(
+                    "{} block has no END line, it may have been cut off while copying",
+                    label
+                ))
+            })?;
+            entries.push(BundleEntry {
+                label: label.to_string(),
+                block: parse_block(content.trim())?,
+            });
+            rest = after_end;
+        }
+        Ok(ArmoredBundle { entries })
+    }
+
+    #[cfg(feature = "fs")]
+    pub fn to_file(&self, file_path: &Path) -> Result<(), GhostkeyError> {
+        std::fs::write(file_path, self.to_armored_string()?).map_err(|e| {
+            GhostkeyError::IOError(format!("Failed to write {}", file_path.display()), e)
+        })
+    }
+
+    /// Writes the bundle with [`write_private_file`], for bundles holding private keys.
+    #[cfg(feature = "fs")]
+    pub fn to_private_file(&self, file_path: &Path) -> Result<(), GhostkeyError> {
+        write_private_file(file_path, self.to_armored_string()?.as_bytes())
+    }
+
+    #[cfg(feature = "fs")]
+    pub fn from_file(file_path: &Path) -> Result<Self, GhostkeyError> {
+        let armored_string = std::fs::read_to_string(file_path).map_err(|e| {
+            GhostkeyError::IOError(format!("Failed to read {}", file_path.display()), e)
+        })?;
+        Self::from_armored_string(&armored_string)
+    }
+}
+
+/// Writes `contents` to a file only its owner can access. On unix the file is created with mode
+/// 0600, and an existing file is restricted to 0600 before anything is written to it, so a
+/// private key is never readable by others, not even briefly.
+#[cfg(feature = "fs")]
+pub fn write_private_file(file_path: &Path, contents: &[u8]) -> Result<(), GhostkeyError> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(file_path).map_err(|e| {
+        GhostkeyError::IOError(format!("Failed to create {}", file_path.display()), e)
+    })?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600)).map_err(|e| {
+            GhostkeyError::IOError(format!("Failed to set permissions on {}", file_path.display()), e)
+        })?;
+    }
+    file.write_all(contents).map_err(|e| {
+        GhostkeyError::IOError(format!("Failed to write {}", file_path.display()), e)
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -302,6 +676,27 @@ mod tests {
         assert_eq!(test_struct_v1, decoded_struct_v1);
     }
 
+    #[test]
+    fn test_read_versioned_block() {
+        let test_struct_v2 = TestStructV2 {
+            field1: "Hello".to_string(),
+            field2: 42,
+        };
+
+        let armored = test_struct_v2.to_armored_string().unwrap();
+        let (version, bytes) = read_versioned_block(&armored, "TEST_STRUCT").unwrap();
+        assert_eq!(version, Some(2));
+        assert_eq!(TestStructV2::from_bytes(&bytes).unwrap(), test_struct_v2);
+
+        let armored_no_version = armored.replace("TEST_STRUCT_V2", "TEST_STRUCT");
+        let (version, _) = read_versioned_block(&armored_no_version, "TEST_STRUCT").unwrap();
+        assert_eq!(version, None);
+
+        // Labels that merely share a prefix are not matched
+        let other = armored.replace("TEST_STRUCT_V2", "TEST_STRUCT_LIST_V1");
+        assert!(read_versioned_block(&other, "TEST_STRUCT").is_err());
+    }
+
     #[test]
     fn test_struct_v1_label_with_version() {
         let test_struct_v1 = TestStruct {
@@ -314,4 +709,200 @@ mod tests {
         let decoded_struct_v1 = TestStruct::from_armored_string(&armored).unwrap();
         assert_eq!(test_struct_v1, decoded_struct_v1);
     }
+
+    #[test]
+    fn test_crc24() {
+        assert_eq!(crc24(b""), 0xB704CE);
+        assert_eq!(crc24(b"123456789"), 0x21CF02);
+    }
+
+    #[test]
+    fn test_armor_has_checksum() {
+        let test_struct = TestStruct {
+            field1: "Hello".to_string(),
+            field2: 42,
+        };
+
+        let armored = test_struct.to_armored_string().unwrap();
+        let checksum_line = armored.lines().rev().nth(1).unwrap();
+        assert_eq!(checksum_line, format!("={}", encode_checksum(&test_struct.to_bytes().unwrap())));
+    }
+
+    #[test]
+    fn test_armor_without_checksum() {
+        let test_struct = TestStruct {
+            field1: "Hello".to_string(),
+            field2: 42,
+        };
+
+        // Armor as written before checksums were added
+        let armored = format!(
+            "-----BEGIN TEST_STRUCT_V1-----\n{}\n-----END TEST_STRUCT_V1-----\n",
+            test_struct.to_base64().unwrap()
+        );
+        assert_eq!(TestStruct::from_armored_string(&armored).unwrap(), test_struct);
+    }
+
+    #[test]
+    fn test_armor_checksum_mismatch() {
+        let test_struct = TestStruct {
+            field1: "Hello".to_string(),
+            field2: 42,
+        };
+
+        let armored = test_struct.to_armored_string().unwrap();
+        let tampered = armored.replace(
+            &test_struct.to_base64().unwrap(),
+            &TestStruct {
+                field1: "Hellp".to_string(),
+                field2: 42,
+            }
+            .to_base64()
+            .unwrap(),
+        );
+        assert!(matches!(
+            TestStruct::from_armored_string(&tampered),
+            Err(GhostkeyError::ChecksumMismatchError(_))
+        ));
+    }
+
+    #[test]
+    fn test_truncated_armor() {
+        let test_struct = TestStruct {
+            field1: "Hello".to_string(),
+            field2: 42,
+        };
+
+        let armored = test_struct.to_armored_string().unwrap();
+        let truncated = &armored[..armored.len() / 2];
+        assert!(matches!(
+            TestStruct::from_armored_string(truncated),
+            Err(GhostkeyError::TruncatedBlockError(_))
+        ));
+    }
+
+    #[test]
+    fn test_armor_headers() {
+        let test_struct = TestStruct {
+            field1: "Hello".to_string(),
+            field2: 42,
+        };
+
+        let armored = test_struct
+            .to_armored_string_with_headers(&[
+                (HEADER_VERSION, "ghostkey_lib 0.2.0"),
+                (HEADER_COMMENT, "Keep this safe: it is your ghost key"),
+            ])
+            .unwrap();
+        assert!(armored.contains("\nVersion: ghostkey_lib 0.2.0\n"));
+        assert_eq!(TestStruct::from_armored_string(&armored).unwrap(), test_struct);
+
+        let block = armored.split("-----").nth(2).unwrap();
+        let parsed = parse_block(block).unwrap();
+        assert_eq!(parsed.header("comment"), Some("Keep this safe: it is your ghost key"));
+        assert_eq!(parsed.data, test_struct.to_bytes().unwrap());
+
+        assert!(matches!(
+            test_struct.to_armored_string_with_headers(&[(HEADER_COMMENT, "two\nlines")]),
+            Err(GhostkeyError::ArmorError(_))
+        ));
+    }
+
+    #[test]
+    fn test_bundle_round_trip() {
+        let test_struct = TestStruct {
+            field1: "Hello".to_string(),
+            field2: 42,
+        };
+        let test_struct_v2 = TestStructV2 {
+            field1: "World".to_string(),
+            field2: 7,
+        };
+
+        let mut bundle = ArmoredBundle::new();
+        bundle.add(&test_struct).unwrap();
+        bundle
+            .add_with_headers(&test_struct_v2, &[(HEADER_COMMENT, "Second object")])
+            .unwrap();
+
+        let armored = bundle.to_armored_string().unwrap();
+        let decoded = ArmoredBundle::from_armored_string(&armored).unwrap();
+        assert_eq!(decoded, bundle);
+        assert_eq!(decoded.labels(), vec!["TEST_STRUCT_V1", "TEST_STRUCT_V2"]);
+        assert_eq!(decoded.get::<TestStruct>().unwrap(), test_struct);
+        assert_eq!(decoded.get::<TestStructV2>().unwrap(), test_struct_v2);
+        assert_eq!(
+            decoded.find("TEST_STRUCT_V2")[0].block.header(HEADER_COMMENT),
+            Some("Second object")
+        );
+
+        // Each object can still be read from the bundle on its own
+        assert_eq!(TestStruct::from_armored_string(&armored).unwrap(), test_struct);
+    }
+
+    #[test]
+    fn test_bundle_duplicate_blocks() {
+        let test_struct = TestStruct {
+            field1: "Hello".to_string(),
+            field2: 42,
+        };
+
+        let mut bundle = ArmoredBundle::new();
+        bundle.add(&test_struct).unwrap();
+        bundle.add(&test_struct).unwrap();
+        assert!(matches!(
+            bundle.get::<TestStruct>(),
+            Err(GhostkeyError::ArmorError(_))
+        ));
+
+        // Extra blocks are reported rather than silently ignored
+        let armored = bundle.to_armored_string().unwrap();
+        assert!(matches!(
+            TestStruct::from_armored_string(&armored),
+            Err(GhostkeyError::ArmorError(_))
+        ));
+    }
+
+    #[test]
+    fn test_bundle_truncated_block() {
+        let test_struct = TestStruct {
+            field1: "Hello".to_string(),
+            field2: 42,
+        };
+
+        let mut bundle = ArmoredBundle::new();
+        bundle.add(&test_struct).unwrap();
+        bundle.add(&TestStructV2 { field1: "World".to_string(), field2: 7 }).unwrap();
+        let armored = bundle.to_armored_string().unwrap();
+        let truncated = armored.trim_end().trim_end_matches("-----END TEST_STRUCT_V2-----");
+        assert!(matches!(
+            ArmoredBundle::from_armored_string(truncated),
+            Err(GhostkeyError::TruncatedBlockError(_))
+        ));
+    }
+
+    #[cfg(all(unix, feature = "fs"))]
+    #[test]
+    fn test_bundle_to_private_file() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mut bundle = ArmoredBundle::new();
+        bundle.add(&TestStruct { field1: "Hello".to_string(), field2: 42 }).unwrap();
+        let file_path = std::env::temp_dir()
+            .join(format!("ghostkey_test_private_file_{}.pem", std::process::id()));
+
+        // An existing file readable by others is restricted before the bundle is written
+        std::fs::write(&file_path, "old").unwrap();
+        std::fs::set_permissions(&file_path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        bundle.to_private_file(&file_path).unwrap();
+        let mode = std::fs::metadata(&file_path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(ArmoredBundle::from_file(&file_path).unwrap(), bundle);
+
+        std::fs::remove_file(&file_path).unwrap();
+        bundle.to_private_file(&file_path).unwrap();
+        let mode = std::fs::metadata(&file_path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        std::fs::remove_file(&file_path).unwrap();
+    }
 }
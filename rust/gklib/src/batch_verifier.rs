@@ -0,0 +1,355 @@
+use super::delegate_certificate::{DelegateCertificateV2, DelegateInfo, DELEGATE_CERTIFICATE_V2_CONTEXT};
+use super::errors::GhostkeyError;
+use super::fingerprint::{Fingerprint, Fingerprinted};
+use super::ghost_key_certificate::GhostkeyCertificate;
+use super::trust_store::TrustStore;
+use super::util::context_hash;
+#[cfg(feature = "std")]
+use super::util::current_unix_time;
+use alloc::collections::{BTreeMap, BTreeSet, VecDeque};
+use alloc::vec;
+use alloc::vec::Vec;
+use ed25519_dalek::{verify_batch, Signature, Verifier};
+use crate::armorable::Armorable;
+
+/// Verifies many ghost key certificates against a trust store. Ghost keys issued by the same
+/// delegate share its certificate, so the master signature of each distinct delegate
+/// certificate is checked once per batch rather than once per ghost key, and the master
+/// signatures of all distinct delegates are checked together with Ed25519 batch verification.
+/// The delegate signature over each ghost key and the validity period of each delegate are
+/// still checked for every certificate.
+///
+/// Ed25519 batch verification checks the cofactored verification equation, which accepts
+/// slightly more than the cofactorless check of an individual [`GhostkeyCertificate::verify_at`]:
+/// a master signature deliberately built with a small-order component can pass in a batch yet
+/// fail on its own. Only the holder of the master signing key can build one, so a batch never
+/// accepts a delegate that a trusted master key did not sign.
+///
+/// A verifier created with [`with_cache`](Self::with_cache) also remembers delegates whose
+/// master signature verified in earlier batches, so a long-running service skips them entirely.
+pub struct BatchVerifier {
+    trust_store: TrustStore,
+    cache: Option<DelegateCache>,
+}
+
+impl BatchVerifier {
+    /// Creates a verifier that keeps nothing between batches.
+    pub fn new(trust_store: TrustStore) -> Self {
+        BatchVerifier {
+            trust_store,
+            cache: None,
+        }
+    }
+
+    /// Creates a verifier that remembers up to `capacity` verified delegate certificates,
+    /// forgetting the oldest once full.
+    pub fn with_cache(trust_store: TrustStore, capacity: usize) -> Self {
+        BatchVerifier {
+            trust_store,
+            cache: Some(DelegateCache::new(capacity)),
+        }
+    }
+
+    pub fn trust_store(&self) -> &TrustStore {
+        &self.trust_store
+    }
+
+    /// Number of delegate certificates currently in the cache.
+    pub fn cached_delegates(&self) -> usize {
+        self.cache.as_ref().map_or(0, |cache| cache.order.len())
+    }
+
+    /// Verifies the certificates at the current time, see [`verify_at`](Self::verify_at).
+    #[cfg(feature = "std")]
+    pub fn verify(
+        &mut self,
+        certificates: &[GhostkeyCertificate],
+    ) -> Vec<Result<DelegateInfo, GhostkeyError>> {
+        self.verify_at(current_unix_time(), certificates)
+    }
+
+    /// Verifies the certificates, checking validity periods against `time` (seconds since the
+    /// Unix epoch). Returns one result per certificate, in order, each the same as
+    /// [`GhostkeyCertificate::verify_at`] would return for it, except for a master signature
+    /// crafted to verify only under the cofactored equation, as described on [`BatchVerifier`].
+    pub fn verify_at(
+        &mut self,
+        time: u64,
+        certificates: &[GhostkeyCertificate],
+    ) -> Vec<Result<DelegateInfo, GhostkeyError>> {
+        let mut verified: BTreeMap<Fingerprint, bool> = BTreeMap::new();
+        let mut pending = Vec::new();
+        for certificate in certificates {
+            let Ok(fingerprint) = delegate_fingerprint(certificate) else {
+                continue;
+            };
+            if verified.contains_key(&fingerprint) {
+                continue;
+            }
+            let cached = self
+                .cache
+                .as_ref()
+                .is_some_and(|cache| cache.contains(&fingerprint));
+            verified.insert(fingerprint, cached);
+            if !cached {
+                if let Some(message) = delegate_signed_message(certificate) {
+                    pending.push((fingerprint, message, delegate_signature(certificate)));
+                }
+            }
+        }
+
+        for fingerprint in self.verify_master_signatures(&pending) {
+            verified.insert(fingerprint, true);
+            if let Some(cache) = &mut self.cache {
+                cache.insert(fingerprint);
+            }
+        }
+
+        certificates
+            .iter()
+            .map(|certificate| {
+                let delegate_verified = delegate_fingerprint(certificate)
+                    .ok()
+                    .and_then(|fingerprint| verified.get(&fingerprint).copied())
+                    .unwrap_or(false);
+                let delegate_verification = if delegate_verified {
+                    let payload = certificate.delegate_payload();
+                    payload
+                        .check_validity(time)
+                        .map(|_| payload.delegate_info())
+                } else {
+                    // Verify the delegate on its own, for the same error as an individual check
+                    match certificate {
+                        GhostkeyCertificate::V1(certificate) => {
+                            certificate.delegate.verify_at(time, &self.trust_store)
+                        }
+                        GhostkeyCertificate::V2(certificate) => {
+                            certificate.delegate.verify_at(time, &self.trust_store)
+                        }
+                        GhostkeyCertificate::V3(certificate) => {
+                            certificate.delegate.verify_at(time, &self.trust_store)
+                        }
+                    }
+                };
+                certificate.verify_with_delegate(delegate_verification)
+            })
+            .collect()
+    }
+
+    /// Returns the fingerprints of the pending delegates whose master signature verifies.
+    fn verify_master_signatures(
+        &self,
+        pending: &[(Fingerprint, Vec<u8>, Signature)],
+    ) -> Vec<Fingerprint> {
+        if pending.is_empty() {
+            return Vec::new();
+        }
+
+        // Delegates are normally all signed by the first key of the trust store, so check them
+        // together and only fall back to checking each one if that fails. A passing batch is
+        // cofactored, see the documentation of BatchVerifier
+        if let Some(key) = self.trust_store.keys.first() {
+            let messages: Vec<&[u8]> = pending.iter().map(|(_, message, _)| message.as_slice()).collect();
+            let signatures: Vec<Signature> = pending.iter().map(|(_, _, signature)| *signature).collect();
+            let verifying_keys = vec![key.verifying_key; pending.len()];
+            if verify_batch(&messages, &signatures, &verifying_keys).is_ok() {
+                return pending.iter().map(|(fingerprint, _, _)| *fingerprint).collect();
+            }
+        }
+
+        pending
+            .iter()
+            .filter(|(_, message, signature)| {
+                self.trust_store
+                    .keys
+                    .iter()
+                    .any(|key| key.verifying_key.verify(message, signature).is_ok())
+            })
+            .map(|(fingerprint, _, _)| *fingerprint)
+            .collect()
+    }
+}
+
+fn delegate_fingerprint(
+    certificate: &GhostkeyCertificate,
+) -> Result<Fingerprint, GhostkeyError> {
+    match certificate {
+        GhostkeyCertificate::V1(certificate) => certificate.delegate.fingerprint(),
+        GhostkeyCertificate::V2(certificate) => certificate.delegate.fingerprint(),
+        GhostkeyCertificate::V3(certificate) => certificate.delegate.fingerprint(),
+    }
+}
+
+/// The message the master key signed for the delegate certificate, as checked by its
+/// `verify_at`. `None` if the certificate can't be valid, its `verify_at` then reports why.
+fn delegate_signed_message(certificate: &GhostkeyCertificate) -> Option<Vec<u8>> {
+    match certificate {
+        GhostkeyCertificate::V1(certificate) => {
+            Armorable::to_bytes(&certificate.delegate.payload).ok()
+        }
+        GhostkeyCertificate::V2(certificate) => v2_delegate_signed_message(&certificate.delegate),
+        GhostkeyCertificate::V3(certificate) => v2_delegate_signed_message(&certificate.delegate),
+    }
+}
+
+fn v2_delegate_signed_message(delegate: &DelegateCertificateV2) -> Option<Vec<u8>> {
+    if delegate.context != DELEGATE_CERTIFICATE_V2_CONTEXT {
+        return None;
+    }
+    context_hash(DELEGATE_CERTIFICATE_V2_CONTEXT, &delegate.payload)
+        .ok()
+        .map(|hash| hash.to_vec())
+}
+
+fn delegate_signature(certificate: &GhostkeyCertificate) -> Signature {
+    match certificate {
+        GhostkeyCertificate::V1(certificate) => certificate.delegate.signature,
+        GhostkeyCertificate::V2(certificate) => certificate.delegate.signature,
+        GhostkeyCertificate::V3(certificate) => certificate.delegate.signature,
+    }
+}
+
+/// Fingerprints of delegate certificates whose master signature has verified against the
+/// verifier's trust store, which can't change, so an entry never needs to be re-checked.
+struct DelegateCache {
+    capacity: usize,
+    order: VecDeque<Fingerprint>,
+    fingerprints: BTreeSet<Fingerprint>,
+}
+
+impl DelegateCache {
+    fn new(capacity: usize) -> Self {
+        DelegateCache {
+            capacity,
+            order: VecDeque::new(),
+            fingerprints: BTreeSet::new(),
+        }
+    }
+
+    fn contains(&self, fingerprint: &Fingerprint) -> bool {
+        self.fingerprints.contains(fingerprint)
+    }
+
+    fn insert(&mut self, fingerprint: Fingerprint) {
+        if self.capacity == 0 || !self.fingerprints.insert(fingerprint) {
+            return;
+        }
+        self.order.push_back(fingerprint);
+        if self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.fingerprints.remove(&oldest);
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "issue"))]
+mod tests {
+    use super::*;
+    use crate::delegate_certificate::{
+        DelegateCertificate, DelegateCertificateV1, DelegateCertificateV2, DelegatePayload,
+    };
+    use crate::util::create_keypair;
+    use rand_core::OsRng;
+
+    #[test]
+    fn test_batch_matches_individual_verification() {
+        let (master_signing_key, master_verifying_key) = create_keypair(&mut OsRng).unwrap();
+        let (other_master_signing_key, _) = create_keypair(&mut OsRng).unwrap();
+        let trust_store = TrustStore::from(master_verifying_key);
+
+        let info = "Test Delegate".to_string();
+        let (delegate_v1, delegate_v1_signing_key) =
+            DelegateCertificateV1::new(&master_signing_key, &info).unwrap();
+        let (delegate_v2, delegate_v2_signing_key) =
+            DelegateCertificateV2::new(&master_signing_key, &info).unwrap();
+        let (untrusted_delegate, untrusted_delegate_signing_key) =
+            DelegateCertificateV1::new(&other_master_signing_key, &info).unwrap();
+
+        let mut certificates = Vec::new();
+        for _ in 0..3 {
+            certificates.push(
+                GhostkeyCertificate::new(&DelegateCertificate::V1(delegate_v1.clone()), &delegate_v1_signing_key).unwrap().0,
+            );
+            certificates.push(
+                GhostkeyCertificate::new(&DelegateCertificate::V2(delegate_v2.clone()), &delegate_v2_signing_key).unwrap().0,
+            );
+        }
+        certificates.push(
+            GhostkeyCertificate::new(&DelegateCertificate::V1(untrusted_delegate), &untrusted_delegate_signing_key).unwrap().0,
+        );
+        // A ghost key that was not signed by its delegate
+        let mut forged = certificates[0].clone();
+        if let GhostkeyCertificate::V1(certificate) = &mut forged {
+            certificate.verifying_key = create_keypair(&mut OsRng).unwrap().1;
+        }
+        certificates.push(forged);
+
+        let results = BatchVerifier::new(trust_store.clone()).verify_at(1000, &certificates);
+        assert_eq!(results.len(), certificates.len());
+        for (certificate, result) in certificates.iter().zip(&results) {
+            match (certificate.verify_at(1000, &trust_store), result) {
+                (Ok(expected), Ok(info)) => assert_eq!(&expected, info),
+                (Err(expected), Err(error)) => assert_eq!(expected.code(), error.code()),
+                (expected, actual) => panic!("expected {:?}, got {:?}", expected, actual),
+            }
+        }
+        assert!(results[..6].iter().all(|result| result.is_ok()));
+        assert_eq!(results[6].as_ref().unwrap_err().code(), "GK_SIG_DELEGATE_INVALID");
+        assert_eq!(results[7].as_ref().unwrap_err().code(), "GK_SIG_GHOST_KEY_INVALID");
+    }
+
+    #[test]
+    fn test_delegates_signed_by_other_trusted_key() {
+        let (current_signing_key, current_verifying_key) = create_keypair(&mut OsRng).unwrap();
+        let (next_signing_key, next_verifying_key) = create_keypair(&mut OsRng).unwrap();
+        let mut trust_store = TrustStore::single("current", current_verifying_key);
+        trust_store.add("next", next_verifying_key).unwrap();
+
+        let info = "Test Delegate".to_string();
+        let (current_delegate, current_delegate_signing_key) =
+            DelegateCertificateV1::new(&current_signing_key, &info).unwrap();
+        let (next_delegate, next_delegate_signing_key) =
+            DelegateCertificateV1::new(&next_signing_key, &info).unwrap();
+        let certificates = vec![
+            GhostkeyCertificate::new(&DelegateCertificate::V1(current_delegate), &current_delegate_signing_key).unwrap().0,
+            GhostkeyCertificate::new(&DelegateCertificate::V1(next_delegate), &next_delegate_signing_key).unwrap().0,
+        ];
+
+        let results = BatchVerifier::new(trust_store).verify_at(1000, &certificates);
+        assert!(results.iter().all(|result| result.is_ok()));
+    }
+
+    #[test]
+    fn test_cache_is_bounded_and_keyed_by_whole_certificate() {
+        let (master_signing_key, master_verifying_key) = create_keypair(&mut OsRng).unwrap();
+        let info = "Test Delegate".to_string();
+        let (first_delegate, first_delegate_signing_key) =
+            DelegateCertificateV1::new(&master_signing_key, &info).unwrap();
+        let (second_delegate, second_delegate_signing_key) =
+            DelegateCertificateV1::new(&master_signing_key, &info).unwrap();
+        let first = GhostkeyCertificate::new(&DelegateCertificate::V1(first_delegate), &first_delegate_signing_key).unwrap().0;
+        let second = GhostkeyCertificate::new(&DelegateCertificate::V1(second_delegate), &second_delegate_signing_key).unwrap().0;
+
+        let mut verifier = BatchVerifier::with_cache(TrustStore::from(master_verifying_key), 1);
+        assert!(verifier.verify_at(1000, core::slice::from_ref(&first))[0].is_ok());
+        assert_eq!(verifier.cached_delegates(), 1);
+
+        // Altering the payload of a cached delegate changes its fingerprint, so the cache does
+        // not vouch for it
+        let mut tampered = first.clone();
+        if let GhostkeyCertificate::V1(certificate) = &mut tampered {
+            if let DelegatePayload::V1(payload) = &mut certificate.delegate.payload {
+                payload.info = "Tampered Info".to_string();
+            }
+        }
+        assert_eq!(
+            verifier.verify_at(1000, &[tampered])[0].as_ref().unwrap_err().code(),
+            "GK_SIG_DELEGATE_INVALID"
+        );
+
+        assert!(verifier.verify_at(1000, &[second])[0].is_ok());
+        assert_eq!(verifier.cached_delegates(), 1);
+        assert!(!verifier.cache.as_ref().unwrap().contains(&delegate_fingerprint(&first).unwrap()));
+    }
+}
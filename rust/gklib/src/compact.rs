@@ -0,0 +1,275 @@
+//! Compact binary encoding of ghost key certificates, small enough for a QR code. The CBOR
+//! encoding names every field and embeds the whole delegate certificate, RSA public key
+//! included, so a ghost key takes several kilobytes of armor. The compact encoding is a fixed
+//! layout that leaves out the context strings, which are constants, and can leave out the
+//! delegate certificate too, naming it by its [`Fingerprint`] for a verifier that already has it:
+//!
+//! | Bytes    | Contents                                                                  |
+//! |----------|---------------------------------------------------------------------------|
+//! | 1        | Encoding version, [`COMPACT_VERSION`]                                     |
+//! | 1        | Certificate version, plus [`DELEGATE_ELIDED`] if the delegate is left out |
+//! | 32       | Ghost verifying key                                                       |
+//! | 2 + n    | Big-endian length and the delegate signature                              |
+//! | 2 + n    | V3 only: big-endian length and the canonical CBOR of the metadata         |
+//! | 32 or n  | Delegate certificate fingerprint if elided, otherwise its CBOR            |
+//!
+//! As text, such as in a QR code, the bytes are written in unpadded URL-safe base64.
+
+use super::armorable::Armorable;
+use super::delegate_certificate::{DelegateCertificate, DelegateCertificateV1, DelegateCertificateV2};
+use super::delegate_key::Signature as RSASignature;
+use super::errors::GhostkeyError;
+use super::errors::GhostkeyError::{DecodingError, DelegateMismatchError, InvalidInput};
+use super::fingerprint::{Fingerprint, Fingerprinted};
+use super::ghost_key_certificate::{
+    GhostkeyCertificate, GhostkeyCertificateV1, GhostkeyCertificateV2, GhostkeyCertificateV3,
+    GhostkeyMetadata, GHOSTKEY_CERTIFICATE_V2_CONTEXT, GHOSTKEY_CERTIFICATE_V3_CONTEXT,
+};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use ciborium::de::from_reader;
+use ed25519_dalek::VerifyingKey;
+
+/// Version of the compact encoding, its first byte.
+pub const COMPACT_VERSION: u8 = 1;
+/// Flag in the certificate version byte marking a certificate without its delegate
+/// certificate.
+pub const DELEGATE_ELIDED: u8 = 0x80;
+
+impl GhostkeyCertificate {
+    /// Encodes the certificate compactly, leaving out the delegate certificate if
+    /// `elide_delegate` is set. An elided certificate can only be decoded by someone who has the
+    /// delegate certificate, see [`from_compact`](Self::from_compact).
+    pub fn to_compact(&self, elide_delegate: bool) -> Result<Vec<u8>, GhostkeyError> {
+        let (verifying_key, signature, metadata, delegate) = match self {
+            GhostkeyCertificate::V1(certificate) => (
+                &certificate.verifying_key,
+                &certificate.signature,
+                None,
+                DelegateCertificate::V1(certificate.delegate.clone()),
+            ),
+            GhostkeyCertificate::V2(certificate) => (
+                &certificate.verifying_key,
+                &certificate.signature,
+                None,
+                DelegateCertificate::V2(certificate.delegate.clone()),
+            ),
+            GhostkeyCertificate::V3(certificate) => (
+                &certificate.verifying_key,
+                &certificate.signature,
+                Some(&certificate.metadata),
+                DelegateCertificate::V2(certificate.delegate.clone()),
+            ),
+        };
+
+        let mut bytes = Vec::new();
+        bytes.push(COMPACT_VERSION);
+        let version = self.version() as u8;
+        bytes.push(if elide_delegate { version | DELEGATE_ELIDED } else { version });
+        bytes.extend_from_slice(verifying_key.as_bytes());
+        push_with_length(&mut bytes, &signature.0)?;
+        if let Some(metadata) = metadata {
+            push_with_length(&mut bytes, &metadata.to_bytes()?)?;
+        }
+        if elide_delegate {
+            bytes.extend_from_slice(delegate.fingerprint()?.as_bytes());
+        } else {
+            bytes.extend_from_slice(&delegate.to_bytes()?);
+        }
+        Ok(bytes)
+    }
+
+    /// Decodes a compact certificate. If its delegate certificate was left out it is looked up
+    /// by fingerprint among `delegates`, failing if none matches. The certificate still has to
+    /// be verified.
+    pub fn from_compact(bytes: &[u8], delegates: &[DelegateCertificate]) -> Result<Self, GhostkeyError> {
+        let mut reader = Reader(bytes);
+        let encoding_version = reader.take(1)?[0];
+        if encoding_version != COMPACT_VERSION {
+            return Err(DecodingError(format!(
+                "Unsupported compact encoding version {}",
+                encoding_version
+            )));
+        }
+        let flags = reader.take(1)?[0];
+        let version = flags & !DELEGATE_ELIDED;
+        if !(1..=3).contains(&version) {
+            return Err(DecodingError(format!("Unsupported ghost key certificate version {}", version)));
+        }
+        let verifying_key_bytes: [u8; 32] = reader.take(32)?.try_into().expect("32 bytes");
+        let verifying_key = VerifyingKey::from_bytes(&verifying_key_bytes)
+            .map_err(|e| DecodingError(format!("Invalid ghost verifying key: {}", e)))?;
+        let signature = RSASignature(reader.take_with_length()?.to_vec());
+        let metadata = match version {
+            3 => Some(<GhostkeyMetadata as Armorable>::from_bytes(reader.take_with_length()?)?),
+            _ => None,
+        };
+
+        let delegate = if flags & DELEGATE_ELIDED != 0 {
+            let fingerprint = Fingerprint(reader.take(32)?.try_into().expect("32 bytes"));
+            if !reader.0.is_empty() {
+                return Err(DecodingError(format!(
+                    "{} unexpected bytes after the delegate fingerprint",
+                    reader.0.len()
+                )));
+            }
+            let known = delegates.iter().find(|delegate| delegate.fingerprint().ok() == Some(fingerprint));
+            known.cloned().ok_or_else(|| {
+                DelegateMismatchError(format!(
+                    "The ghost key was issued by delegate certificate {}, which was not given",
+                    fingerprint
+                ))
+            })?
+        } else {
+            let delegate = match version {
+                1 => DelegateCertificate::V1(reader.take_cbor::<DelegateCertificateV1>()?),
+                _ => DelegateCertificate::V2(reader.take_cbor::<DelegateCertificateV2>()?),
+            };
+            if !reader.0.is_empty() {
+                return Err(DecodingError(format!(
+                    "{} unexpected bytes after the delegate certificate",
+                    reader.0.len()
+                )));
+            }
+            delegate
+        };
+
+        match (version, delegate) {
+            (1, DelegateCertificate::V1(delegate)) => Ok(GhostkeyCertificate::V1(GhostkeyCertificateV1 {
+                delegate,
+                verifying_key,
+                signature,
+            })),
+            (2, DelegateCertificate::V2(delegate)) => Ok(GhostkeyCertificate::V2(GhostkeyCertificateV2 {
+                delegate,
+                verifying_key,
+                context: GHOSTKEY_CERTIFICATE_V2_CONTEXT.to_string(),
+                signature,
+            })),
+            (3, DelegateCertificate::V2(delegate)) => Ok(GhostkeyCertificate::V3(GhostkeyCertificateV3 {
+                delegate,
+                verifying_key,
+                metadata: metadata.expect("decoded for version 3"),
+                context: GHOSTKEY_CERTIFICATE_V3_CONTEXT.to_string(),
+                signature,
+            })),
+            _ => Err(DelegateMismatchError(format!(
+                "A V{} ghost key certificate can't be issued by this delegate certificate version",
+                version
+            ))),
+        }
+    }
+
+    /// The compact encoding as unpadded URL-safe base64, the text put in QR codes.
+    pub fn to_compact_string(&self, elide_delegate: bool) -> Result<String, GhostkeyError> {
+        Ok(URL_SAFE_NO_PAD.encode(self.to_compact(elide_delegate)?))
+    }
+
+    /// Decodes the text of [`to_compact_string`](Self::to_compact_string), ignoring whitespace.
+    pub fn from_compact_string(text: &str, delegates: &[DelegateCertificate]) -> Result<Self, GhostkeyError> {
+        let text: String = text.split_whitespace().collect();
+        Self::from_compact(&URL_SAFE_NO_PAD.decode(text)?, delegates)
+    }
+}
+
+fn push_with_length(bytes: &mut Vec<u8>, data: &[u8]) -> Result<(), GhostkeyError> {
+    let length = u16::try_from(data.len())
+        .map_err(|_| InvalidInput(format!("{} bytes is too long for the compact encoding", data.len())))?;
+    bytes.extend_from_slice(&length.to_be_bytes());
+    bytes.extend_from_slice(data);
+    Ok(())
+}
+
+/// Reads the fields of a compact certificate in order.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], GhostkeyError> {
+        if self.0.len() < length {
+            return Err(DecodingError("Compact ghost key certificate is truncated".to_string()));
+        }
+        let (field, rest) = self.0.split_at(length);
+        self.0 = rest;
+        Ok(field)
+    }
+
+    fn take_with_length(&mut self) -> Result<&'a [u8], GhostkeyError> {
+        let length = u16::from_be_bytes(self.take(2)?.try_into().expect("2 bytes"));
+        self.take(length as usize)
+    }
+
+    /// Decodes one CBOR item from the front, leaving whatever follows it.
+    fn take_cbor<T: Armorable>(&mut self) -> Result<T, GhostkeyError> {
+        from_reader(&mut self.0).map_err(|e| {
+            GhostkeyError::DeserializationError(format!("Failed to decode {}", T::struct_name()), e.into())
+        })
+    }
+}
+
+#[cfg(all(test, feature = "issue"))]
+mod tests {
+    use super::*;
+    use crate::trust_store::TrustStore;
+    use crate::util::create_keypair;
+    use rand_core::OsRng;
+
+    #[test]
+    fn test_compact_round_trip() {
+        let (master_signing_key, master_verifying_key) = create_keypair(&mut OsRng).unwrap();
+        let trust_store = TrustStore::from(master_verifying_key);
+        let (delegate_certificate, delegate_signing_key) =
+            DelegateCertificateV2::new(&master_signing_key, "Test Delegate").unwrap();
+        let delegate_certificate = DelegateCertificate::V2(delegate_certificate);
+        let (ghost_key_certificate, _) =
+            GhostkeyCertificate::new(&delegate_certificate, &delegate_signing_key).unwrap();
+
+        let full = ghost_key_certificate.to_compact(false).unwrap();
+        let elided = ghost_key_certificate.to_compact(true).unwrap();
+        assert!(full.len() < ghost_key_certificate.to_bytes().unwrap().len());
+        assert!(elided.len() < full.len());
+
+        let decoded = GhostkeyCertificate::from_compact(&full, &[]).unwrap();
+        assert_eq!(decoded.to_bytes().unwrap(), ghost_key_certificate.to_bytes().unwrap());
+        decoded.verify(&trust_store).unwrap();
+
+        let text = ghost_key_certificate.to_compact_string(true).unwrap();
+        let decoded = GhostkeyCertificate::from_compact_string(&text, core::slice::from_ref(&delegate_certificate)).unwrap();
+        assert_eq!(decoded.to_bytes().unwrap(), ghost_key_certificate.to_bytes().unwrap());
+
+        // The delegate certificate is needed to rebuild an elided certificate
+        assert_eq!(
+            GhostkeyCertificate::from_compact(&elided, &[]).err().map(|e| e.code()),
+            Some("GK_DELEGATE_MISMATCH")
+        );
+        assert_eq!(
+            GhostkeyCertificate::from_compact(&full[..40], &[]).err().map(|e| e.code()),
+            Some("GK_DECODE_FAILED")
+        );
+    }
+
+    #[test]
+    fn test_compact_rejects_trailing_bytes() {
+        let (master_signing_key, _) = create_keypair(&mut OsRng).unwrap();
+        let (delegate_certificate, delegate_signing_key) =
+            DelegateCertificateV2::new(&master_signing_key, "Test Delegate").unwrap();
+        let delegate_certificate = DelegateCertificate::V2(delegate_certificate);
+        let (ghost_key_certificate, _) =
+            GhostkeyCertificate::new(&delegate_certificate, &delegate_signing_key).unwrap();
+
+        let mut elided = ghost_key_certificate.to_compact(true).unwrap();
+        elided.push(0);
+        assert_eq!(
+            GhostkeyCertificate::from_compact(&elided, core::slice::from_ref(&delegate_certificate))
+                .err()
+                .map(|e| e.code()),
+            Some("GK_DECODE_FAILED")
+        );
+
+        let mut full = ghost_key_certificate.to_compact(false).unwrap();
+        full.push(0);
+        assert_eq!(GhostkeyCertificate::from_compact(&full, &[]).err().map(|e| e.code()), Some("GK_DECODE_FAILED"));
+    }
+}
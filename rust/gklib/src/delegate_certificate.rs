@@ -1,13 +1,30 @@
-use super::errors::GhostkeyError;
-use super::util::{sign_with_hash, verify_with_hash};
-use blind_rsa_signatures::{
-    KeyPair as RSAKeyPair, PublicKey as RSAVerifyingKey, SecretKey as RSASigningKey,
-};
+use super::armorable::{read_versioned_block, Armorable, ArmoredBundle};
+use super::delegate_key::PublicKey as RSAVerifyingKey;
+use super::errors::{GhostkeyError, SignatureKind};
+use super::trust_store::TrustStore;
+use super::ghost_key_certificate::GhostkeyCertificateV2;
+#[cfg(feature = "std")]
+use super::util::current_unix_time;
+#[cfg(feature = "issue")]
+use super::partially_blind;
+use super::util::context_hash;
+#[cfg(feature = "issue")]
+use super::util::{sign_with_context, sign_with_hash};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
+use base64::Engine;
+#[cfg(feature = "issue")]
+use blind_rsa_signatures::{KeyPair as RSAKeyPair, SecretKey as RSASigningKey};
+use core::fmt;
+use core::ops::RangeInclusive;
 use ed25519_dalek::*;
-use rand_core::OsRng;
+#[cfg(feature = "issue")]
+use rand_core::{CryptoRng, OsRng, RngCore};
 use serde::{Deserialize, Serialize};
-use crate::armorable::Armorable;
-use crate::FREENET_MASTER_VERIFYING_KEY_BASE64;
+#[cfg(feature = "fs")]
+use std::path::Path;
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct DelegateCertificateV1 {
@@ -16,56 +33,872 @@ pub struct DelegateCertificateV1 {
     pub signature: Signature,
 }
 
+/// BLAKE3 key derivation context under which V2 delegate certificates are signed.
+pub const DELEGATE_CERTIFICATE_V2_CONTEXT: &str =
+    "freenet.org ghostkey 2024-10 delegate certificate v2";
+
+/// A delegate certificate whose payload is signed with
+/// [`sign_with_context`](crate::util::sign_with_context), a BLAKE3 hash of its canonical CBOR
+/// encoding under [`DELEGATE_CERTIFICATE_V2_CONTEXT`], so the signature is independent of the
+/// encoder and can't be confused with a signature over any other type.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DelegateCertificateV2 {
+    pub payload: DelegatePayload,
+    /// Always [`DELEGATE_CERTIFICATE_V2_CONTEXT`], stored so the certificate is self-describing
+    /// and its CBOR shape differs from V1
+    pub context: String,
+    /// The hash of the payload signed by the master signing key
+    pub signature: Signature,
+}
+
+/// The signed payload of a delegate certificate. Versions are distinguished by their CBOR
+/// shape, so V1 payloads serialize exactly as they did before V2 was introduced and their
+/// signatures continue to verify. Neither version accepts unknown fields, so a payload can only
+/// decode as the version whose fields it has.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum DelegatePayload {
+    V2(DelegatePayloadV2),
+    V1(DelegatePayloadV1),
+}
+
+/// The original payload, describing the delegate with a free-form info string.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct DelegatePayloadV1 {
+    pub delegate_verifying_key: RSAVerifyingKey,
+    pub info: String,
+    /// Unix time (seconds) before which the delegate is not valid, unbounded if absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub not_before: Option<u64>,
+    /// Unix time (seconds) after which the delegate is no longer valid, unbounded if absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub not_after: Option<u64>,
+}
+
+/// A payload describing the delegate with typed fields.
 #[derive(Serialize, Deserialize, Clone)]
-pub struct DelegatePayload {
+#[serde(deny_unknown_fields)]
+pub struct DelegatePayloadV2 {
     pub delegate_verifying_key: RSAVerifyingKey,
+    pub details: DelegateDetails,
+    /// Unix time (seconds) before which the delegate is not valid, unbounded if absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub not_before: Option<u64>,
+    /// Unix time (seconds) after which the delegate is no longer valid, unbounded if absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub not_after: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct DelegateDetails {
+    /// The donation amount in whole units of `currency`
+    pub amount: u64,
+    /// ISO 4217 currency code, e.g. "USD"
+    pub currency: String,
+    /// Name of the donation tier the delegate signs for
+    pub tier: String,
+    /// Who operates the delegate, e.g. "Freenet"
+    pub issuer: String,
+    /// Unix time (seconds) at which the delegate was created
+    pub created: u64,
+}
+
+impl fmt::Display for DelegateDetails {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} tier, {} {}, issued by {} at {}",
+            self.tier, self.amount, self.currency, self.issuer, self.created
+        )
+    }
+}
+
+/// The verified contents of a delegate certificate, returned by `verify`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DelegateInfo {
+    /// Payload version the certificate was signed with
+    pub version: u8,
+    /// The info string of a V1 payload, or a description of the details of a V2 payload
     pub info: String,
+    /// Typed fields, only present for V2 payloads
+    pub details: Option<DelegateDetails>,
+    pub not_before: Option<u64>,
+    pub not_after: Option<u64>,
+}
+
+impl fmt::Display for DelegateInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.info)
+    }
+}
+
+impl DelegatePayload {
+    pub fn delegate_verifying_key(&self) -> &RSAVerifyingKey {
+        match self {
+            DelegatePayload::V1(payload) => &payload.delegate_verifying_key,
+            DelegatePayload::V2(payload) => &payload.delegate_verifying_key,
+        }
+    }
+
+    pub fn not_before(&self) -> Option<u64> {
+        match self {
+            DelegatePayload::V1(payload) => payload.not_before,
+            DelegatePayload::V2(payload) => payload.not_before,
+        }
+    }
+
+    pub fn not_after(&self) -> Option<u64> {
+        match self {
+            DelegatePayload::V1(payload) => payload.not_after,
+            DelegatePayload::V2(payload) => payload.not_after,
+        }
+    }
+
+    pub fn delegate_info(&self) -> DelegateInfo {
+        match self {
+            DelegatePayload::V1(payload) => DelegateInfo {
+                version: 1,
+                info: payload.info.clone(),
+                details: None,
+                not_before: payload.not_before,
+                not_after: payload.not_after,
+            },
+            DelegatePayload::V2(payload) => DelegateInfo {
+                version: 2,
+                info: payload.details.to_string(),
+                details: Some(payload.details.clone()),
+                not_before: payload.not_before,
+                not_after: payload.not_after,
+            },
+        }
+    }
+
+    /// Checks that `time` (seconds since the Unix epoch) falls inside the validity period.
+    pub fn check_validity(&self, time: u64) -> Result<(), GhostkeyError> {
+        if let Some(not_before) = self.not_before() {
+            if time < not_before {
+                return Err(GhostkeyError::ValidityPeriodError(format!(
+                    "Delegate certificate is not valid before {} (time is {})",
+                    not_before, time
+                )));
+            }
+        }
+        if let Some(not_after) = self.not_after() {
+            if time > not_after {
+                return Err(GhostkeyError::ValidityPeriodError(format!(
+                    "Delegate certificate expired at {} (time is {})",
+                    not_after, time
+                )));
+            }
+        }
+        Ok(())
+    }
 }
 
 impl DelegateCertificateV1 {
+    /// Creates a delegate certificate with a V1 payload and no validity period, it will be
+    /// valid until revoked.
+    #[cfg(feature = "issue")]
     pub fn new(
         master_signing_key: &SigningKey,
-        info: &String,
-    ) -> Result<(Self, RSASigningKey), Box<GhostkeyError>> {
-        let delegate_keypair = RSAKeyPair::generate(&mut OsRng, 2048)
-            .map_err(|e| GhostkeyError::RSAError(e.to_string()))?;
-
-        let payload = DelegatePayload {
-            delegate_verifying_key: delegate_keypair.pk,
-            info: info.clone(),
-        };
+        info: &str,
+    ) -> Result<(Self, RSASigningKey), GhostkeyError> {
+        Self::new_with_rng(&mut OsRng, DEFAULT_DELEGATE_KEY_BITS, master_signing_key, info, None, None)
+    }
+
+    /// Creates a delegate certificate with a V1 payload that is only valid between `not_before`
+    /// and `not_after` (inclusive, in seconds since the Unix epoch).
+    #[cfg(feature = "issue")]
+    pub fn new_with_validity(
+        master_signing_key: &SigningKey,
+        info: &str,
+        not_before: u64,
+        not_after: u64,
+    ) -> Result<(Self, RSASigningKey), GhostkeyError> {
+        Self::new_with_rng(
+            &mut OsRng,
+            DEFAULT_DELEGATE_KEY_BITS,
+            master_signing_key,
+            info,
+            Some(not_before),
+            Some(not_after),
+        )
+    }
 
-        let signature = sign_with_hash(&master_signing_key, &payload)?;
+    /// Creates a delegate certificate with a typed V2 payload and an optional validity period.
+    #[cfg(feature = "issue")]
+    pub fn new_with_details(
+        master_signing_key: &SigningKey,
+        details: &DelegateDetails,
+        not_before: Option<u64>,
+        not_after: Option<u64>,
+    ) -> Result<(Self, RSASigningKey), GhostkeyError> {
+        Self::new_with_details_and_rng(
+            &mut OsRng,
+            DEFAULT_DELEGATE_KEY_BITS,
+            master_signing_key,
+            details,
+            not_before,
+            not_after,
+        )
+    }
 
-        let certificate = DelegateCertificateV1 {
-            payload,
-            signature: Signature::from(signature),
-        };
+    /// Creates a delegate certificate with a V1 payload and an optional validity period,
+    /// generating a delegate key with a `key_bits` modulus from `rng`.
+    #[cfg(feature = "issue")]
+    pub fn new_with_rng<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        key_bits: usize,
+        master_signing_key: &SigningKey,
+        info: &str,
+        not_before: Option<u64>,
+        not_after: Option<u64>,
+    ) -> Result<(Self, RSASigningKey), GhostkeyError> {
+        let (payload, delegate_signing_key) = new_delegate_payload(
+            rng,
+            key_bits,
+            DelegateKeyKind::Blind,
+            DelegateDescription::Info(info),
+            not_before,
+            not_after,
+        )?;
+        Ok((Self::certify(master_signing_key, payload)?, delegate_signing_key))
+    }
+
+    /// Creates a delegate certificate with a typed V2 payload and an optional validity period,
+    /// generating a delegate key with a `key_bits` modulus from `rng`.
+    #[cfg(feature = "issue")]
+    pub fn new_with_details_and_rng<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        key_bits: usize,
+        master_signing_key: &SigningKey,
+        details: &DelegateDetails,
+        not_before: Option<u64>,
+        not_after: Option<u64>,
+    ) -> Result<(Self, RSASigningKey), GhostkeyError> {
+        let (payload, delegate_signing_key) = new_delegate_payload(
+            rng,
+            key_bits,
+            DelegateKeyKind::Blind,
+            DelegateDescription::Details(details),
+            not_before,
+            not_after,
+        )?;
+        Ok((Self::certify(master_signing_key, payload)?, delegate_signing_key))
+    }
 
-        Ok((certificate, delegate_keypair.sk))
+    #[cfg(feature = "issue")]
+    fn certify(
+        master_signing_key: &SigningKey,
+        payload: DelegatePayload,
+    ) -> Result<Self, GhostkeyError> {
+        let signature = sign_with_hash(master_signing_key, &payload)?;
+
+        Ok(DelegateCertificateV1 { payload, signature })
     }
 
-    /// Verifies the delegate certificate using the master verifying key. If the verification is
-    /// successful, the info field of the payload is returned. Uses Freenet master verifying key
-    /// if no key is provided.
+    /// Verifies the delegate certificate against the master verifying keys in the trust store.
+    /// If the verification is successful, the contents of the payload are returned.
+    #[cfg(feature = "std")]
     pub fn verify(
         &self,
-        &master_verifying_key: &Option<VerifyingKey>,
-    ) -> Result<String, Box<GhostkeyError>> {
-        let master_verifying_key = master_verifying_key.unwrap_or(VerifyingKey::from_base64(FREENET_MASTER_VERIFYING_KEY_BASE64).unwrap());
-        
-        let verification = verify_with_hash(&master_verifying_key, &self.payload, &self.signature)?;
-        if verification {
-            Ok(self.payload.info.clone())
-        } else {
-            Err(Box::new(GhostkeyError::SignatureVerificationError(
+        trust_store: &TrustStore,
+    ) -> Result<DelegateInfo, GhostkeyError> {
+        self.verify_at(current_unix_time(), trust_store)
+    }
+
+    /// Like [`verify`](Self::verify), but checks the validity period against `time` (seconds
+    /// since the Unix epoch) rather than the current time.
+    pub fn verify_at(
+        &self,
+        time: u64,
+        trust_store: &TrustStore,
+    ) -> Result<DelegateInfo, GhostkeyError> {
+        if trust_store.find_signer(&self.payload, &self.signature)?.is_none() {
+            return Err(GhostkeyError::SignatureVerificationError(
+                SignatureKind::Delegate,
+                "Failed to verify delegate certificate".to_string(),
+            ));
+        }
+
+        self.payload.check_validity(time)?;
+        Ok(self.payload.delegate_info())
+    }
+}
+
+impl DelegateCertificateV2 {
+    /// Creates a V2 delegate certificate for an info string with no validity period, it will be
+    /// valid until revoked.
+    #[cfg(feature = "issue")]
+    pub fn new(
+        master_signing_key: &SigningKey,
+        info: &str,
+    ) -> Result<(Self, RSASigningKey), GhostkeyError> {
+        Self::new_with_rng(&mut OsRng, DEFAULT_DELEGATE_KEY_BITS, master_signing_key, info, None, None)
+    }
+
+    /// Creates a V2 delegate certificate for an info string that is only valid between
+    /// `not_before` and `not_after` (inclusive, in seconds since the Unix epoch).
+    #[cfg(feature = "issue")]
+    pub fn new_with_validity(
+        master_signing_key: &SigningKey,
+        info: &str,
+        not_before: u64,
+        not_after: u64,
+    ) -> Result<(Self, RSASigningKey), GhostkeyError> {
+        Self::new_with_rng(
+            &mut OsRng,
+            DEFAULT_DELEGATE_KEY_BITS,
+            master_signing_key,
+            info,
+            Some(not_before),
+            Some(not_after),
+        )
+    }
+
+    /// Creates a V2 delegate certificate for typed details with an optional validity period.
+    #[cfg(feature = "issue")]
+    pub fn new_with_details(
+        master_signing_key: &SigningKey,
+        details: &DelegateDetails,
+        not_before: Option<u64>,
+        not_after: Option<u64>,
+    ) -> Result<(Self, RSASigningKey), GhostkeyError> {
+        Self::new_with_details_and_rng(
+            &mut OsRng,
+            DEFAULT_DELEGATE_KEY_BITS,
+            master_signing_key,
+            details,
+            not_before,
+            not_after,
+        )
+    }
+
+    /// Creates a V2 delegate certificate for an info string with an optional validity period,
+    /// generating a delegate key with a `key_bits` modulus from `rng`.
+    #[cfg(feature = "issue")]
+    pub fn new_with_rng<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        key_bits: usize,
+        master_signing_key: &SigningKey,
+        info: &str,
+        not_before: Option<u64>,
+        not_after: Option<u64>,
+    ) -> Result<(Self, RSASigningKey), GhostkeyError> {
+        let (payload, delegate_signing_key) = new_delegate_payload(
+            rng,
+            key_bits,
+            DelegateKeyKind::Blind,
+            DelegateDescription::Info(info),
+            not_before,
+            not_after,
+        )?;
+        Ok((Self::certify(master_signing_key, payload)?, delegate_signing_key))
+    }
+
+    /// Creates a V2 delegate certificate for typed details with an optional validity period,
+    /// generating a delegate key with a `key_bits` modulus from `rng`.
+    #[cfg(feature = "issue")]
+    pub fn new_with_details_and_rng<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        key_bits: usize,
+        master_signing_key: &SigningKey,
+        details: &DelegateDetails,
+        not_before: Option<u64>,
+        not_after: Option<u64>,
+    ) -> Result<(Self, RSASigningKey), GhostkeyError> {
+        let (payload, delegate_signing_key) = new_delegate_payload(
+            rng,
+            key_bits,
+            DelegateKeyKind::Blind,
+            DelegateDescription::Details(details),
+            not_before,
+            not_after,
+        )?;
+        Ok((Self::certify(master_signing_key, payload)?, delegate_signing_key))
+    }
+
+    /// Like [`new_with_details`](Self::new_with_details), but generates a delegate key for
+    /// [`partially_blind`](crate::partially_blind) signing, which can issue V3 ghost key
+    /// certificates binding any metadata. Generating the key takes minutes for the default size,
+    /// see [`partially_blind::generate_signing_key`].
+    #[cfg(feature = "issue")]
+    pub fn new_partially_blind(
+        master_signing_key: &SigningKey,
+        details: &DelegateDetails,
+        not_before: Option<u64>,
+        not_after: Option<u64>,
+    ) -> Result<(Self, RSASigningKey), GhostkeyError> {
+        Self::new_partially_blind_with_rng(
+            &mut OsRng,
+            DEFAULT_DELEGATE_KEY_BITS,
+            master_signing_key,
+            details,
+            not_before,
+            not_after,
+        )
+    }
+
+    /// Like [`new_partially_blind`](Self::new_partially_blind), generating a delegate key with
+    /// a `key_bits` modulus from `rng`.
+    #[cfg(feature = "issue")]
+    pub fn new_partially_blind_with_rng<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        key_bits: usize,
+        master_signing_key: &SigningKey,
+        details: &DelegateDetails,
+        not_before: Option<u64>,
+        not_after: Option<u64>,
+    ) -> Result<(Self, RSASigningKey), GhostkeyError> {
+        let (payload, delegate_signing_key) = new_delegate_payload(
+            rng,
+            key_bits,
+            DelegateKeyKind::PartiallyBlind,
+            DelegateDescription::Details(details),
+            not_before,
+            not_after,
+        )?;
+        Ok((Self::certify(master_signing_key, payload)?, delegate_signing_key))
+    }
+
+    #[cfg(feature = "issue")]
+    fn certify(
+        master_signing_key: &SigningKey,
+        payload: DelegatePayload,
+    ) -> Result<Self, GhostkeyError> {
+        let signature =
+            sign_with_context(master_signing_key, DELEGATE_CERTIFICATE_V2_CONTEXT, &payload)?;
+
+        Ok(DelegateCertificateV2 {
+            payload,
+            context: DELEGATE_CERTIFICATE_V2_CONTEXT.to_string(),
+            signature,
+        })
+    }
+
+    /// Verifies the delegate certificate against the master verifying keys in the trust store.
+    /// If the verification is successful, the contents of the payload are returned.
+    #[cfg(feature = "std")]
+    pub fn verify(
+        &self,
+        trust_store: &TrustStore,
+    ) -> Result<DelegateInfo, GhostkeyError> {
+        self.verify_at(current_unix_time(), trust_store)
+    }
+
+    /// Like [`verify`](Self::verify), but checks the validity period against `time` (seconds
+    /// since the Unix epoch) rather than the current time.
+    pub fn verify_at(
+        &self,
+        time: u64,
+        trust_store: &TrustStore,
+    ) -> Result<DelegateInfo, GhostkeyError> {
+        if self.context != DELEGATE_CERTIFICATE_V2_CONTEXT {
+            return Err(GhostkeyError::SignatureVerificationError(SignatureKind::Delegate, format!(
+                "Unexpected delegate certificate context '{}'",
+                self.context
+            )));
+        }
+
+        if trust_store
+            .find_signer_with_context(DELEGATE_CERTIFICATE_V2_CONTEXT, &self.payload, &self.signature)?
+            .is_none()
+        {
+            return Err(GhostkeyError::SignatureVerificationError(
+                SignatureKind::Delegate,
                 "Failed to verify delegate certificate".to_string(),
-            )))
+            ));
+        }
+
+        self.payload.check_validity(time)?;
+        Ok(self.payload.delegate_info())
+    }
+}
+
+/// The payload of a delegate certificate awaiting the master signature, for master keys that
+/// don't sign in one place such as the shares of a [`threshold`](crate::threshold) master key.
+/// `V1` is the version of the request, [`certify`](Self::certify) produces the certificate
+/// version named in it.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DelegateCertificateRequestV1 {
+    pub payload: DelegatePayload,
+    /// The version of the certificate the request is for, 1 or 2. Requests that don't name one
+    /// are for V2 certificates, the only version they were made for before.
+    #[serde(default = "default_request_certificate_version")]
+    pub certificate_version: u32,
+}
+
+fn default_request_certificate_version() -> u32 {
+    2
+}
+
+impl DelegateCertificateRequestV1 {
+    /// Generates a delegate key and a request for a V1 certificate with a V1 payload and an
+    /// optional validity period.
+    #[cfg(feature = "issue")]
+    pub fn new(
+        info: &str,
+        not_before: Option<u64>,
+        not_after: Option<u64>,
+    ) -> Result<(Self, RSASigningKey), GhostkeyError> {
+        let (payload, delegate_signing_key) = new_delegate_payload(
+            &mut OsRng,
+            DEFAULT_DELEGATE_KEY_BITS,
+            DelegateKeyKind::Blind,
+            DelegateDescription::Info(info),
+            not_before,
+            not_after,
+        )?;
+        Ok((Self { payload, certificate_version: 1 }, delegate_signing_key))
+    }
+
+    /// Generates a delegate key and a request for a certificate with a typed V2 payload and an
+    /// optional validity period, see [`DelegateCertificateV2::new_partially_blind`] for
+    /// `partially_blind`. The request is for a V1 certificate, or a V2 certificate if
+    /// `partially_blind` is set since only V2 delegates issue V3 ghost key certificates.
+    #[cfg(feature = "issue")]
+    pub fn new_with_details(
+        details: &DelegateDetails,
+        not_before: Option<u64>,
+        not_after: Option<u64>,
+        partially_blind: bool,
+    ) -> Result<(Self, RSASigningKey), GhostkeyError> {
+        let key_kind = if partially_blind {
+            DelegateKeyKind::PartiallyBlind
+        } else {
+            DelegateKeyKind::Blind
+        };
+        let (payload, delegate_signing_key) = new_delegate_payload(
+            &mut OsRng,
+            DEFAULT_DELEGATE_KEY_BITS,
+            key_kind,
+            DelegateDescription::Details(details),
+            not_before,
+            not_after,
+        )?;
+        let certificate_version = if partially_blind { 2 } else { 1 };
+        Ok((Self { payload, certificate_version }, delegate_signing_key))
+    }
+
+    /// The message the master key signs: the CBOR encoding of the payload for a V1 certificate,
+    /// as [`DelegateCertificateV1::verify`] checks it, and for a V2 certificate the hash its
+    /// signature covers.
+    pub fn signing_message(&self) -> Result<Vec<u8>, GhostkeyError> {
+        match self.certificate_version {
+            1 => Armorable::to_bytes(&self.payload),
+            2 => Ok(context_hash(DELEGATE_CERTIFICATE_V2_CONTEXT, &self.payload)?.to_vec()),
+            version => Err(GhostkeyError::InvalidInput(format!(
+                "Unsupported delegate certificate version {}",
+                version
+            ))),
+        }
+    }
+
+    /// Completes the certificate with the master signature over the
+    /// [`signing_message`](Self::signing_message). The signature is not checked, verify the
+    /// certificate against the master verifying key before using it.
+    pub fn certify(self, signature: Signature) -> Result<DelegateCertificate, GhostkeyError> {
+        match self.certificate_version {
+            1 => Ok(DelegateCertificate::V1(DelegateCertificateV1 {
+                payload: self.payload,
+                signature,
+            })),
+            2 => Ok(DelegateCertificate::V2(DelegateCertificateV2 {
+                payload: self.payload,
+                context: DELEGATE_CERTIFICATE_V2_CONTEXT.to_string(),
+                signature,
+            })),
+            version => Err(GhostkeyError::InvalidInput(format!(
+                "Unsupported delegate certificate version {}",
+                version
+            ))),
+        }
+    }
+}
+
+/// Size in bits of the RSA modulus of delegate keys created without an explicit size.
+pub const DEFAULT_DELEGATE_KEY_BITS: usize = 2048;
+
+/// Smallest and largest RSA modulus sizes, in bits, accepted for new delegate keys.
+pub const DELEGATE_KEY_BITS_RANGE: RangeInclusive<usize> = 2048..=4096;
+
+/// Which signatures a new delegate key is generated for.
+#[cfg(feature = "issue")]
+enum DelegateKeyKind {
+    /// Blind signatures over ghost keys
+    Blind,
+    /// Partially blind signatures over ghost keys and their metadata, needing safe primes
+    PartiallyBlind,
+}
+
+/// What a new delegate certificate says about the delegate.
+#[cfg(feature = "issue")]
+enum DelegateDescription<'a> {
+    /// A free-form info string, certified in a V1 payload
+    Info(&'a str),
+    /// Typed details, certified in a V2 payload
+    Details(&'a DelegateDetails),
+}
+
+#[cfg(feature = "issue")]
+impl DelegateDescription<'_> {
+    fn payload(
+        self,
+        delegate_verifying_key: RSAVerifyingKey,
+        not_before: Option<u64>,
+        not_after: Option<u64>,
+    ) -> DelegatePayload {
+        match self {
+            DelegateDescription::Info(info) => DelegatePayload::V1(DelegatePayloadV1 {
+                delegate_verifying_key,
+                info: info.to_string(),
+                not_before,
+                not_after,
+            }),
+            DelegateDescription::Details(details) => DelegatePayload::V2(DelegatePayloadV2 {
+                delegate_verifying_key,
+                details: details.clone(),
+                not_before,
+                not_after,
+            }),
+        }
+    }
+}
+
+/// Generates a new delegate keypair and builds the payload certifying its verifying key. Every
+/// certificate version and [`DelegateCertificateRequestV1`] is created from such a payload.
+#[cfg(feature = "issue")]
+fn new_delegate_payload<R: RngCore + CryptoRng>(
+    rng: &mut R,
+    key_bits: usize,
+    key_kind: DelegateKeyKind,
+    description: DelegateDescription<'_>,
+    not_before: Option<u64>,
+    not_after: Option<u64>,
+) -> Result<(DelegatePayload, RSASigningKey), GhostkeyError> {
+    check_validity_period(not_before, not_after)?;
+    if !DELEGATE_KEY_BITS_RANGE.contains(&key_bits) {
+        return Err(GhostkeyError::InvalidInput(format!(
+            "Delegate key size must be between {} and {} bits, got {}",
+            DELEGATE_KEY_BITS_RANGE.start(),
+            DELEGATE_KEY_BITS_RANGE.end(),
+            key_bits
+        )));
+    }
+
+    let delegate_signing_key = match key_kind {
+        DelegateKeyKind::Blind => {
+            RSAKeyPair::generate(rng, key_bits)
+                .map_err(|e| {
+                    GhostkeyError::RSAError("Failed to generate delegate key".to_string(), e.into())
+                })?
+                .sk
+        }
+        DelegateKeyKind::PartiallyBlind => partially_blind::generate_signing_key(rng, key_bits)?,
+    };
+
+    let delegate_verifying_key = RSAVerifyingKey(delegate_signing_key.0.to_public_key());
+    Ok((
+        description.payload(delegate_verifying_key, not_before, not_after),
+        delegate_signing_key,
+    ))
+}
+
+/// A delegate certificate for the fixed partially blind test key, see
+/// [`partially_blind::test_signing_key`].
+#[cfg(all(test, feature = "issue"))]
+pub(crate) fn test_partially_blind_delegate(
+    master_signing_key: &SigningKey,
+    details: &DelegateDetails,
+) -> (DelegateCertificateV2, RSASigningKey) {
+    let delegate_signing_key = partially_blind::test_signing_key();
+    let payload = DelegateDescription::Details(details).payload(
+        RSAVerifyingKey(delegate_signing_key.0.to_public_key()),
+        None,
+        None,
+    );
+    let certificate = DelegateCertificateV2::certify(master_signing_key, payload).unwrap();
+    (certificate, delegate_signing_key)
+}
+
+/// Any supported version of a delegate certificate. Decoding picks the version from the armor
+/// label, falling back to the CBOR shape (newest version first) for unversioned labels and raw
+/// bytes, so that certificates written by older releases keep loading after a format change.
+#[derive(Clone)]
+pub enum DelegateCertificate {
+    V1(DelegateCertificateV1),
+    V2(DelegateCertificateV2),
+}
+
+impl DelegateCertificate {
+    /// Armor label without the `_V<n>` version suffix.
+    pub const LABEL: &'static str = "DELEGATE_CERTIFICATE";
+
+    pub fn version(&self) -> u32 {
+        match self {
+            DelegateCertificate::V1(_) => 1,
+            DelegateCertificate::V2(_) => 2,
+        }
+    }
+
+    pub fn payload(&self) -> &DelegatePayload {
+        match self {
+            DelegateCertificate::V1(certificate) => &certificate.payload,
+            DelegateCertificate::V2(certificate) => &certificate.payload,
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub fn verify(&self, trust_store: &TrustStore) -> Result<DelegateInfo, GhostkeyError> {
+        match self {
+            DelegateCertificate::V1(certificate) => certificate.verify(trust_store),
+            DelegateCertificate::V2(certificate) => certificate.verify(trust_store),
+        }
+    }
+
+    pub fn verify_at(
+        &self,
+        time: u64,
+        trust_store: &TrustStore,
+    ) -> Result<DelegateInfo, GhostkeyError> {
+        match self {
+            DelegateCertificate::V1(certificate) => certificate.verify_at(time, trust_store),
+            DelegateCertificate::V2(certificate) => certificate.verify_at(time, trust_store),
+        }
+    }
+
+    /// Re-signs a V1 certificate as a [`DelegateCertificateV2`] with the same payload and
+    /// delegate key, V2 certificates are returned as is. `master_signing_key` must be the key
+    /// that signed the V1 certificate.
+    #[cfg(feature = "issue")]
+    pub fn upgrade(self, master_signing_key: &SigningKey) -> Result<Self, GhostkeyError> {
+        match self {
+            DelegateCertificate::V1(certificate) => {
+                let signer = TrustStore::from(master_signing_key.verifying_key());
+                if signer.find_signer(&certificate.payload, &certificate.signature)?.is_none() {
+                    return Err(GhostkeyError::InvalidInput(
+                        "Delegate certificate was not signed by this master signing key".to_string(),
+                    ));
+                }
+                Ok(DelegateCertificate::V2(DelegateCertificateV2::certify(
+                    master_signing_key,
+                    certificate.payload,
+                )?))
+            }
+            DelegateCertificate::V2(certificate) => Ok(DelegateCertificate::V2(certificate)),
         }
     }
+
+    /// Returns the message the delegate signs to certify `ghost_verifying_key`, which depends
+    /// on the version of this certificate: V1 signs the CBOR encoding of the key, V2 the
+    /// message defined by [`GhostkeyCertificateV2::message`].
+    pub fn ghost_key_message(
+        &self,
+        ghost_verifying_key: &VerifyingKey,
+    ) -> Result<Vec<u8>, GhostkeyError> {
+        match self {
+            DelegateCertificate::V1(_) => Armorable::to_bytes(ghost_verifying_key),
+            DelegateCertificate::V2(_) => GhostkeyCertificateV2::message(ghost_verifying_key),
+        }
+    }
+
+    /// Decodes CBOR bytes of any supported version, trying the newest version first.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, GhostkeyError> {
+        DelegateCertificateV2::from_bytes(bytes)
+            .map(DelegateCertificate::V2)
+            .or_else(|_| DelegateCertificateV1::from_bytes(bytes).map(DelegateCertificate::V1))
+    }
+
+    fn from_versioned_bytes(version: Option<u32>, bytes: &[u8]) -> Result<Self, GhostkeyError> {
+        match version {
+            None => Self::from_bytes(bytes),
+            Some(1) => DelegateCertificateV1::from_bytes(bytes).map(DelegateCertificate::V1),
+            Some(2) => DelegateCertificateV2::from_bytes(bytes).map(DelegateCertificate::V2),
+            Some(version) => Err(GhostkeyError::DecodingError(format!(
+                "Unsupported delegate certificate version {}",
+                version
+            ))),
+        }
+    }
+
+    pub fn from_armored_string(armored_string: &str) -> Result<Self, GhostkeyError> {
+        let (version, bytes) = read_versioned_block(armored_string, Self::LABEL)?;
+        Self::from_versioned_bytes(version, &bytes)
+    }
+
+    /// Extracts the certificate from a bundle holding other objects, such as its signing key.
+    pub fn from_bundle(bundle: &ArmoredBundle) -> Result<Self, GhostkeyError> {
+        let (version, entry) = bundle.find_versioned(Self::LABEL)?;
+        Self::from_versioned_bytes(version, &entry.block.data)
+    }
+
+    #[cfg(feature = "fs")]
+    pub fn from_file(file_path: &Path) -> Result<Self, GhostkeyError> {
+        let armored_string = std::fs::read_to_string(file_path).map_err(|e| {
+            GhostkeyError::IOError(format!("Failed to read {}", file_path.display()), e)
+        })?;
+        Self::from_armored_string(&armored_string)
+    }
+
+    pub fn from_base64(encoded: &str) -> Result<Self, GhostkeyError> {
+        let bytes = BASE64_STANDARD.decode(encoded)?;
+        Self::from_bytes(&bytes)
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, GhostkeyError> {
+        match self {
+            DelegateCertificate::V1(certificate) => certificate.to_bytes(),
+            DelegateCertificate::V2(certificate) => certificate.to_bytes(),
+        }
+    }
+
+    pub fn to_base64(&self) -> Result<String, GhostkeyError> {
+        Ok(BASE64_STANDARD.encode(self.to_bytes()?))
+    }
+
+    pub fn to_armored_string(&self) -> Result<String, GhostkeyError> {
+        match self {
+            DelegateCertificate::V1(certificate) => certificate.to_armored_string(),
+            DelegateCertificate::V2(certificate) => certificate.to_armored_string(),
+        }
+    }
+
+    #[cfg(feature = "fs")]
+    pub fn to_file(&self, file_path: &Path) -> Result<(), GhostkeyError> {
+        match self {
+            DelegateCertificate::V1(certificate) => certificate.to_file(file_path),
+            DelegateCertificate::V2(certificate) => certificate.to_file(file_path),
+        }
+    }
+}
+
+impl From<DelegateCertificateV1> for DelegateCertificate {
+    fn from(certificate: DelegateCertificateV1) -> Self {
+        DelegateCertificate::V1(certificate)
+    }
+}
+
+impl From<DelegateCertificateV2> for DelegateCertificate {
+    fn from(certificate: DelegateCertificateV2) -> Self {
+        DelegateCertificate::V2(certificate)
+    }
+}
+
+#[cfg(feature = "issue")]
+fn check_validity_period(not_before: Option<u64>, not_after: Option<u64>) -> Result<(), GhostkeyError> {
+    if let (Some(not_before), Some(not_after)) = (not_before, not_after) {
+        if not_after < not_before {
+            return Err(GhostkeyError::InvalidInput(format!(
+                "not_after ({}) must not be earlier than not_before ({})",
+                not_after, not_before
+            )));
+        }
+    }
+    Ok(())
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "issue"))]
 mod tests {
     use crate::util::create_keypair;
     use super::*;
@@ -81,8 +914,10 @@ mod tests {
             DelegateCertificateV1::new(&master_signing_key, &info).unwrap();
 
         // Verify the certificate
-        let verified_info = certificate.verify(&Some(master_verifying_key)).unwrap();
-        assert_eq!(verified_info, info);
+        let verified_info = certificate.verify(&TrustStore::from(master_verifying_key)).unwrap();
+        assert_eq!(verified_info.info, info);
+        assert_eq!(verified_info.version, 1);
+        assert_eq!(verified_info.details, None);
     }
 
     #[test]
@@ -97,11 +932,11 @@ mod tests {
             DelegateCertificateV1::new(&master_signing_key, &info).unwrap();
 
         // Try to verify with the wrong key
-        let result = certificate.verify(&Some(wrong_verifying_key));
+        let result = certificate.verify(&TrustStore::from(wrong_verifying_key));
         assert!(result.is_err());
         assert!(matches!(
-            result.unwrap_err().as_ref(),
-            GhostkeyError::SignatureVerificationError(_)
+            result.unwrap_err(),
+            GhostkeyError::SignatureVerificationError(..)
         ));
     }
 
@@ -116,17 +951,331 @@ mod tests {
             DelegateCertificateV1::new(&master_signing_key, &info).unwrap();
 
         // Verify the original certificate
-        assert!(certificate.verify(&Some(master_verifying_key)).is_ok());
+        assert!(certificate.verify(&TrustStore::from(master_verifying_key)).is_ok());
 
         // Tamper with the payload
-        certificate.payload.info = "Tampered Info".to_string();
+        if let DelegatePayload::V1(payload) = &mut certificate.payload {
+            payload.info = "Tampered Info".to_string();
+        }
 
         // Verify the tampered certificate
-        let result = certificate.verify(&Some(master_verifying_key));
+        let result = certificate.verify(&TrustStore::from(master_verifying_key));
         assert!(result.is_err());
         assert!(matches!(
-            result.unwrap_err().as_ref(),
-            GhostkeyError::SignatureVerificationError(_)
+            result.unwrap_err(),
+            GhostkeyError::SignatureVerificationError(..)
+        ));
+    }
+
+    #[test]
+    fn test_delegate_certificate_validity_period() {
+        let (master_signing_key, master_verifying_key) = create_keypair(&mut OsRng).unwrap();
+
+        let info = "Test Delegate".to_string();
+        let (certificate, _delegate_signing_key) =
+            DelegateCertificateV1::new_with_validity(&master_signing_key, &info, 1000, 2000)
+                .unwrap();
+
+        // Inside the validity period
+        let verified_info = certificate
+            .verify_at(1500, &TrustStore::from(master_verifying_key))
+            .unwrap();
+        assert_eq!(verified_info.info, info);
+        assert_eq!(verified_info.not_before, Some(1000));
+        assert_eq!(verified_info.not_after, Some(2000));
+
+        // Not yet valid
+        let result = certificate.verify_at(999, &TrustStore::from(master_verifying_key));
+        assert!(matches!(
+            result.unwrap_err(),
+            GhostkeyError::ValidityPeriodError(_)
+        ));
+
+        // Expired
+        let result = certificate.verify_at(2001, &TrustStore::from(master_verifying_key));
+        assert!(matches!(
+            result.unwrap_err(),
+            GhostkeyError::ValidityPeriodError(_)
+        ));
+    }
+
+    #[test]
+    fn test_delegate_certificate_validity_period_is_signed() {
+        let (master_signing_key, master_verifying_key) = create_keypair(&mut OsRng).unwrap();
+
+        let info = "Test Delegate".to_string();
+        let (mut certificate, _delegate_signing_key) =
+            DelegateCertificateV1::new_with_validity(&master_signing_key, &info, 1000, 2000)
+                .unwrap();
+
+        // Extending the expiry must invalidate the signature
+        if let DelegatePayload::V1(payload) = &mut certificate.payload {
+            payload.not_after = Some(3000);
+        }
+        let result = certificate.verify_at(2500, &TrustStore::from(master_verifying_key));
+        assert!(matches!(
+            result.unwrap_err(),
+            GhostkeyError::SignatureVerificationError(..)
+        ));
+    }
+
+    #[test]
+    fn test_delegate_certificate_invalid_validity_period() {
+        let (master_signing_key, _) = create_keypair(&mut OsRng).unwrap();
+
+        let info = "Test Delegate".to_string();
+        let result = DelegateCertificateV1::new_with_validity(&master_signing_key, &info, 2000, 1000);
+        assert!(matches!(
+            result.err().unwrap(),
+            GhostkeyError::InvalidInput(_)
+        ));
+    }
+
+    #[test]
+    fn test_delegate_key_size() {
+        let (master_signing_key, _) = create_keypair(&mut OsRng).unwrap();
+
+        let info = "Test Delegate".to_string();
+        for key_bits in [1024, 8192] {
+            let result = DelegateCertificateV2::new_with_rng(&mut OsRng, key_bits, &master_signing_key, &info, None, None);
+            assert!(matches!(
+                result.err().unwrap(),
+                GhostkeyError::InvalidInput(_)
+            ));
+        }
+    }
+
+    fn test_details() -> DelegateDetails {
+        DelegateDetails {
+            amount: 20,
+            currency: "USD".to_string(),
+            tier: "supporter".to_string(),
+            issuer: "Freenet".to_string(),
+            created: 1000,
+        }
+    }
+
+    #[test]
+    fn test_delegate_certificate_v2_payload() {
+        let (master_signing_key, master_verifying_key) = create_keypair(&mut OsRng).unwrap();
+
+        let details = test_details();
+        let (certificate, _delegate_signing_key) =
+            DelegateCertificateV1::new_with_details(&master_signing_key, &details, Some(1000), None)
+                .unwrap();
+
+        let verified_info = certificate
+            .verify_at(1500, &TrustStore::from(master_verifying_key))
+            .unwrap();
+        assert_eq!(verified_info.version, 2);
+        assert_eq!(verified_info.details, Some(details));
+        assert_eq!(verified_info.not_before, Some(1000));
+        assert_eq!(verified_info.not_after, None);
+    }
+
+    #[test]
+    fn test_delegate_certificate_v2_payload_integrity() {
+        let (master_signing_key, master_verifying_key) = create_keypair(&mut OsRng).unwrap();
+
+        let (mut certificate, _delegate_signing_key) =
+            DelegateCertificateV1::new_with_details(&master_signing_key, &test_details(), None, None)
+                .unwrap();
+
+        // Raising the amount must invalidate the signature
+        if let DelegatePayload::V2(payload) = &mut certificate.payload {
+            payload.details.amount = 1000;
+        }
+        let result = certificate.verify(&TrustStore::from(master_verifying_key));
+        assert!(matches!(
+            result.unwrap_err(),
+            GhostkeyError::SignatureVerificationError(..)
+        ));
+    }
+
+    #[test]
+    fn test_delegate_payload_versions_decode() {
+        let (master_signing_key, master_verifying_key) = create_keypair(&mut OsRng).unwrap();
+        let trust_store = TrustStore::from(master_verifying_key);
+
+        let info = "Test Delegate".to_string();
+        let (v1_certificate, _) = DelegateCertificateV1::new(&master_signing_key, &info).unwrap();
+        let (v2_certificate, _) =
+            DelegateCertificateV1::new_with_details(&master_signing_key, &test_details(), None, None)
+                .unwrap();
+
+        let decoded_v1 = DelegateCertificateV1::from_bytes(&v1_certificate.to_bytes().unwrap()).unwrap();
+        assert!(matches!(decoded_v1.payload, DelegatePayload::V1(_)));
+        assert_eq!(decoded_v1.verify(&trust_store).unwrap().info, info);
+
+        let decoded_v2 = DelegateCertificateV1::from_bytes(&v2_certificate.to_bytes().unwrap()).unwrap();
+        assert!(matches!(decoded_v2.payload, DelegatePayload::V2(_)));
+        assert_eq!(decoded_v2.verify(&trust_store).unwrap().details, Some(test_details()));
+    }
+
+    #[test]
+    fn test_delegate_payload_rejects_unknown_fields() {
+        use ciborium::value::Value;
+
+        let (master_signing_key, _) = create_keypair(&mut OsRng).unwrap();
+        let (v1_certificate, _) = DelegateCertificateV1::new(&master_signing_key, "Test Delegate").unwrap();
+        let (v2_certificate, _) =
+            DelegateCertificateV1::new_with_details(&master_signing_key, &test_details(), None, None)
+                .unwrap();
+
+        // Re-encodes the certificate after editing the fields of its payload
+        fn edit_payload<F: FnOnce(&mut Vec<(Value, Value)>)>(certificate: &DelegateCertificateV1, edit: F) -> Vec<u8> {
+            let mut value: Value = ciborium::de::from_reader(certificate.to_bytes().unwrap().as_slice()).unwrap();
+            let Value::Map(fields) = &mut value else { panic!("certificate is a map") };
+            let Some((_, Value::Map(payload))) = fields.iter_mut().find(|(key, _)| key.as_text() == Some("payload"))
+            else {
+                panic!("certificate has a payload map")
+            };
+            edit(payload);
+            let mut bytes = Vec::new();
+            ciborium::ser::into_writer(&value, &mut bytes).unwrap();
+            bytes
+        }
+
+        // A V2 payload with a malformed field and an info string must not decode as V1
+        let bytes = edit_payload(&v2_certificate, |payload| {
+            for (key, value) in payload.iter_mut() {
+                if key.as_text() == Some("details") {
+                    *value = Value::Text("Gold tier".to_string());
+                }
+            }
+            payload.push((Value::Text("info".to_string()), Value::Text("Test Delegate".to_string())));
+        });
+        assert!(DelegateCertificateV1::from_bytes(&bytes).is_err());
+
+        // Nor may a V1 payload carry fields it doesn't sign for
+        let bytes = edit_payload(&v1_certificate, |payload| {
+            payload.push((Value::Text("details".to_string()), Value::Text("Gold tier".to_string())));
+        });
+        assert!(DelegateCertificateV1::from_bytes(&bytes).is_err());
+
+        // Unedited payloads still decode as their own version
+        let bytes = edit_payload(&v2_certificate, |_| {});
+        assert!(matches!(DelegateCertificateV1::from_bytes(&bytes).unwrap().payload, DelegatePayload::V2(_)));
+    }
+
+    #[test]
+    fn test_delegate_certificate_versioned_decode() {
+        let (master_signing_key, master_verifying_key) = create_keypair(&mut OsRng).unwrap();
+        let trust_store = TrustStore::from(master_verifying_key);
+
+        let info = "Test Delegate".to_string();
+        let (certificate, _) = DelegateCertificateV1::new(&master_signing_key, &info).unwrap();
+        let armored = certificate.to_armored_string().unwrap();
+
+        let decoded = DelegateCertificate::from_armored_string(&armored).unwrap();
+        assert_eq!(decoded.version(), 1);
+        assert_eq!(decoded.verify(&trust_store).unwrap().info, info);
+
+        // Unversioned labels are decoded by shape
+        let unversioned = armored.replace("DELEGATE_CERTIFICATE_V1", "DELEGATE_CERTIFICATE");
+        let decoded = DelegateCertificate::from_armored_string(&unversioned).unwrap();
+        assert_eq!(decoded.version(), 1);
+
+        let unsupported = armored.replace("DELEGATE_CERTIFICATE_V1", "DELEGATE_CERTIFICATE_V99");
+        assert!(matches!(
+            DelegateCertificate::from_armored_string(&unsupported),
+            Err(GhostkeyError::DecodingError(_))
+        ));
+    }
+
+    #[test]
+    fn test_delegate_certificate_upgrade() {
+        let (master_signing_key, master_verifying_key) = create_keypair(&mut OsRng).unwrap();
+        let trust_store = TrustStore::from(master_verifying_key);
+
+        let info = "Test Delegate".to_string();
+        let (certificate, _) = DelegateCertificateV1::new(&master_signing_key, &info).unwrap();
+        let certificate = DelegateCertificate::from(certificate);
+
+        let upgraded = certificate.clone().upgrade(&master_signing_key).unwrap();
+        assert_eq!(upgraded.version(), 2);
+        assert_eq!(upgraded.verify(&trust_store).unwrap().info, info);
+        assert_eq!(
+            Armorable::to_bytes(upgraded.payload()).unwrap(),
+            Armorable::to_bytes(certificate.payload()).unwrap()
+        );
+        assert!(upgraded.to_armored_string().unwrap().contains("DELEGATE_CERTIFICATE_V2"));
+
+        // V2 certificates are already current
+        let signature = match &upgraded {
+            DelegateCertificate::V2(certificate) => certificate.signature,
+            DelegateCertificate::V1(_) => unreachable!(),
+        };
+        match upgraded.upgrade(&master_signing_key).unwrap() {
+            DelegateCertificate::V2(certificate) => assert_eq!(certificate.signature, signature),
+            DelegateCertificate::V1(_) => panic!("Expected a V2 certificate"),
+        }
+
+        // Only the master key that signed the certificate can upgrade it
+        let (other_signing_key, _) = create_keypair(&mut OsRng).unwrap();
+        assert!(matches!(
+            certificate.upgrade(&other_signing_key),
+            Err(GhostkeyError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_delegate_certificate_v2() {
+        let (master_signing_key, master_verifying_key) = create_keypair(&mut OsRng).unwrap();
+        let trust_store = TrustStore::from(master_verifying_key);
+
+        let (certificate, _) = DelegateCertificateV2::new_with_details(
+            &master_signing_key,
+            &test_details(),
+            Some(1000),
+            Some(2000),
+        )
+        .unwrap();
+        assert_eq!(certificate.context, DELEGATE_CERTIFICATE_V2_CONTEXT);
+        assert_eq!(
+            certificate.verify_at(1500, &trust_store).unwrap().details,
+            Some(test_details())
+        );
+
+        // Unversioned armor and raw bytes are recognised as V2 by their shape
+        let armored = certificate.to_armored_string().unwrap();
+        assert!(armored.contains("DELEGATE_CERTIFICATE_V2"));
+        let unversioned = armored.replace("DELEGATE_CERTIFICATE_V2", "DELEGATE_CERTIFICATE");
+        let decoded = DelegateCertificate::from_armored_string(&unversioned).unwrap();
+        assert_eq!(decoded.version(), 2);
+        assert!(decoded.verify_at(1500, &trust_store).is_ok());
+
+        let decoded = DelegateCertificate::from_base64(
+            &DelegateCertificate::from(certificate).to_base64().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(decoded.version(), 2);
+    }
+
+    #[test]
+    fn test_delegate_certificate_v2_signature_is_domain_separated() {
+        let (master_signing_key, master_verifying_key) = create_keypair(&mut OsRng).unwrap();
+        let trust_store = TrustStore::from(master_verifying_key);
+
+        // A V1 signature over the same payload is not a valid V2 signature
+        let info = "Test Delegate".to_string();
+        let (v1_certificate, _) = DelegateCertificateV1::new(&master_signing_key, &info).unwrap();
+        let forged = DelegateCertificateV2 {
+            payload: v1_certificate.payload.clone(),
+            context: DELEGATE_CERTIFICATE_V2_CONTEXT.to_string(),
+            signature: v1_certificate.signature,
+        };
+        assert!(matches!(
+            forged.verify(&trust_store).unwrap_err(),
+            GhostkeyError::SignatureVerificationError(..)
+        ));
+
+        // Nor is a V2 certificate valid under another context
+        let (mut certificate, _) = DelegateCertificateV2::new(&master_signing_key, &info).unwrap();
+        certificate.context = "some other context".to_string();
+        assert!(matches!(
+            certificate.verify(&trust_store).unwrap_err(),
+            GhostkeyError::SignatureVerificationError(..)
         ));
     }
 }
@@ -0,0 +1,106 @@
+use super::partially_blind;
+use alloc::vec::Vec;
+use rsa::sha2::{Digest, Sha384};
+use rsa::{PublicKey as _, Pss, RsaPublicKey};
+use serde::{Deserialize, Serialize};
+
+/// The RSA verifying key of a delegate. Encoded exactly like `blind_rsa_signatures::PublicKey`
+/// and armored under the same `PUBLIC_KEY_V1` label, but verifying with it needs neither std nor
+/// a random number generator. With the `issue` feature it converts to and from the
+/// blind-rsa-signatures key for blinding and signing.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PublicKey(pub RsaPublicKey);
+
+/// A delegate signature over a ghost key, encoded exactly like `blind_rsa_signatures::Signature`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Signature(pub Vec<u8>);
+
+impl PublicKey {
+    /// Checks an RSASSA-PSS signature with SHA-384, the scheme of signatures finalized by
+    /// blind-rsa-signatures with the default options and no message randomizer.
+    pub fn verify(&self, signature: &Signature, message: &[u8]) -> bool {
+        let hashed = Sha384::digest(message);
+        self.0
+            .verify(Pss::new::<Sha384>(), &hashed, &signature.0)
+            .is_ok()
+    }
+
+    /// Checks a partially blind signature over `message` that binds `metadata`, see
+    /// [`partially_blind`](crate::partially_blind).
+    pub fn verify_with_metadata(&self, signature: &Signature, metadata: &[u8], message: &[u8]) -> bool {
+        match partially_blind::derive_public_key(self, metadata) {
+            Ok(derived_key) => {
+                derived_key.verify(signature, &partially_blind::metadata_message(metadata, message))
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+#[cfg(feature = "issue")]
+impl PublicKey {
+    /// The key as a blind-rsa-signatures key, for blinding messages and finalizing signatures.
+    pub fn to_blind_rsa(&self) -> blind_rsa_signatures::PublicKey {
+        blind_rsa_signatures::PublicKey(self.0.clone())
+    }
+}
+
+#[cfg(feature = "issue")]
+impl From<blind_rsa_signatures::PublicKey> for PublicKey {
+    fn from(key: blind_rsa_signatures::PublicKey) -> Self {
+        PublicKey(key.0)
+    }
+}
+
+#[cfg(feature = "issue")]
+impl From<blind_rsa_signatures::Signature> for Signature {
+    fn from(signature: blind_rsa_signatures::Signature) -> Self {
+        Signature(signature.0)
+    }
+}
+
+#[cfg(feature = "issue")]
+impl From<Signature> for blind_rsa_signatures::Signature {
+    fn from(signature: Signature) -> Self {
+        blind_rsa_signatures::Signature(signature.0)
+    }
+}
+
+#[cfg(all(test, feature = "issue"))]
+mod tests {
+    use super::*;
+    use crate::armorable::Armorable;
+    use crate::util::unblinded_rsa_sign;
+    use blind_rsa_signatures::KeyPair;
+    use rand_core::OsRng;
+
+    #[test]
+    fn test_verifies_blind_rsa_signatures() {
+        let keypair = KeyPair::generate(&mut OsRng, 2048).unwrap();
+        let signature = Signature::from(unblinded_rsa_sign(&mut OsRng, &keypair, b"test").unwrap());
+        let public_key = PublicKey::from(keypair.pk.clone());
+
+        assert!(public_key.verify(&signature, b"test"));
+        assert!(!public_key.verify(&signature, b"other"));
+    }
+
+    #[test]
+    fn test_encoding_matches_blind_rsa_signatures() {
+        let keypair = KeyPair::generate(&mut OsRng, 2048).unwrap();
+        let public_key = PublicKey::from(keypair.pk.clone());
+        assert_eq!(
+            Armorable::to_bytes(&public_key).unwrap(),
+            Armorable::to_bytes(&keypair.pk).unwrap()
+        );
+        assert_eq!(
+            public_key.to_armored_string().unwrap(),
+            keypair.pk.to_armored_string().unwrap()
+        );
+
+        let signature = unblinded_rsa_sign(&mut OsRng, &keypair, b"test").unwrap();
+        assert_eq!(
+            Armorable::to_bytes(&Signature::from(signature.clone())).unwrap(),
+            Armorable::to_bytes(&signature).unwrap()
+        );
+    }
+}
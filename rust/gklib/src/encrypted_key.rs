@@ -0,0 +1,295 @@
+use super::armorable::{Armorable, ArmoredBundle};
+use super::errors::GhostkeyError;
+use super::errors::GhostkeyError::EncryptionError;
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 24;
+/// Upper bound on the KDF memory accepted when decrypting, so a crafted file can't exhaust memory.
+const MAX_MEMORY_KIB: u32 = 1024 * 1024;
+/// Upper bound on the KDF passes accepted when decrypting, so a crafted file can't hang it.
+const MAX_ITERATIONS: u32 = 64;
+/// Upper bound on the KDF lanes accepted when decrypting.
+const MAX_PARALLELISM: u32 = 16;
+
+/// A private key (master signing key, delegate RSA secret key or ghost signing key) encrypted
+/// with a passphrase. The key is derived with Argon2id and the CBOR encoding of the private key
+/// is sealed with XChaCha20-Poly1305. Armored as an `ENCRYPTED_KEY_V1` block.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct EncryptedKeyV1 {
+    /// Armor label of the encrypted key, e.g. `SIGNING_KEY_V1`, authenticated with the
+    /// ciphertext so a key can't be decrypted as a different type
+    pub key_type: String,
+    pub kdf: KdfParams,
+    #[serde(with = "serde_bytes")]
+    pub nonce: Vec<u8>,
+    #[serde(with = "serde_bytes")]
+    pub ciphertext: Vec<u8>,
+}
+
+/// Argon2id parameters used to derive the encryption key from the passphrase.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct KdfParams {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+    #[serde(with = "serde_bytes")]
+    pub salt: Vec<u8>,
+}
+
+impl KdfParams {
+    /// 64 MiB of memory and 3 passes, which takes well under a second on a laptop.
+    pub fn new() -> Self {
+        Self::with_cost(64 * 1024, 3)
+    }
+
+    /// Parameters with a fresh random salt and the given cost.
+    pub fn with_cost(memory_kib: u32, iterations: u32) -> Self {
+        let mut salt = vec![0u8; SALT_LENGTH];
+        OsRng.fill_bytes(&mut salt);
+        KdfParams {
+            memory_kib,
+            iterations,
+            parallelism: 1,
+            salt,
+        }
+    }
+
+    fn derive_key(&self, passphrase: &str) -> Result<[u8; 32], GhostkeyError> {
+        if self.memory_kib > MAX_MEMORY_KIB {
+            return Err(EncryptionError(format!(
+                "KDF memory of {} KiB exceeds the limit of {} KiB",
+                self.memory_kib, MAX_MEMORY_KIB
+            )));
+        }
+        if self.iterations > MAX_ITERATIONS {
+            return Err(EncryptionError(format!(
+                "KDF iterations of {} exceed the limit of {}",
+                self.iterations, MAX_ITERATIONS
+            )));
+        }
+        if self.parallelism > MAX_PARALLELISM {
+            return Err(EncryptionError(format!(
+                "KDF parallelism of {} exceeds the limit of {}",
+                self.parallelism, MAX_PARALLELISM
+            )));
+        }
+        let params = Params::new(self.memory_kib, self.iterations, self.parallelism, Some(32))
+            .map_err(|e| EncryptionError(format!("Invalid KDF parameters: {}", e)))?;
+        let mut key = [0u8; 32];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), &self.salt, &mut key)
+            .map_err(|e| EncryptionError(format!("Failed to derive key: {}", e)))?;
+        Ok(key)
+    }
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EncryptedKeyV1 {
+    /// Encrypts a private key with the default KDF parameters.
+    pub fn encrypt<T: Armorable>(key: &T, passphrase: &str) -> Result<Self, GhostkeyError> {
+        Self::encrypt_with_params(key, passphrase, KdfParams::new())
+    }
+
+    pub fn encrypt_with_params<T: Armorable>(
+        key: &T,
+        passphrase: &str,
+        kdf: KdfParams,
+    ) -> Result<Self, GhostkeyError> {
+        Self::encrypt_bytes(&T::struct_name(), &key.to_bytes()?, passphrase, kdf)
+    }
+
+    /// Encrypts the CBOR encoding of a key whose armor label is `key_type`.
+    pub fn encrypt_bytes(
+        key_type: &str,
+        plaintext: &[u8],
+        passphrase: &str,
+        kdf: KdfParams,
+    ) -> Result<Self, GhostkeyError> {
+        if passphrase.is_empty() {
+            return Err(GhostkeyError::InvalidInput(
+                "Passphrase must not be empty".to_string(),
+            ));
+        }
+
+        let mut nonce = vec![0u8; NONCE_LENGTH];
+        OsRng.fill_bytes(&mut nonce);
+
+        let key = kdf.derive_key(passphrase)?;
+        let ciphertext = XChaCha20Poly1305::new(Key::from_slice(&key))
+            .encrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: plaintext,
+                    aad: key_type.as_bytes(),
+                },
+            )
+            .map_err(|_| EncryptionError("Failed to encrypt key".to_string()))?;
+
+        Ok(EncryptedKeyV1 {
+            key_type: key_type.to_string(),
+            kdf,
+            nonce,
+            ciphertext,
+        })
+    }
+
+    /// Decrypts the key, failing if the passphrase is wrong, the data was altered or the key is
+    /// not of type `T`.
+    pub fn decrypt<T: Armorable>(&self, passphrase: &str) -> Result<T, GhostkeyError> {
+        let expected_type = T::struct_name();
+        if self.key_type != expected_type {
+            return Err(GhostkeyError::InvalidInput(format!(
+                "Encrypted key is a {}, expected a {}",
+                self.key_type, expected_type
+            )));
+        }
+        T::from_bytes(&self.decrypt_bytes(passphrase)?)
+    }
+
+    /// Decrypts the CBOR encoding of the key, see [`key_type`](Self::key_type) for its type.
+    pub fn decrypt_bytes(&self, passphrase: &str) -> Result<Vec<u8>, GhostkeyError> {
+        if self.nonce.len() != NONCE_LENGTH {
+            return Err(EncryptionError(format!(
+                "Nonce must be {} bytes, got {}",
+                NONCE_LENGTH,
+                self.nonce.len()
+            )));
+        }
+
+        let key = self.kdf.derive_key(passphrase)?;
+        XChaCha20Poly1305::new(Key::from_slice(&key))
+            .decrypt(
+                XNonce::from_slice(&self.nonce),
+                Payload {
+                    msg: &self.ciphertext,
+                    aad: self.key_type.as_bytes(),
+                },
+            )
+            .map_err(|_| {
+                EncryptionError("Wrong passphrase, or the encrypted key is damaged".to_string())
+            })
+    }
+}
+
+/// Reads a private key of type `T` from a bundle holding it either in plain or encrypted armor.
+/// `passphrase` is only called if the key is encrypted, so callers can prompt lazily.
+pub fn read_private_key<T: Armorable>(
+    bundle: &ArmoredBundle,
+    passphrase: impl FnOnce() -> Result<String, GhostkeyError>,
+) -> Result<T, GhostkeyError> {
+    let key_type = T::struct_name();
+    let encrypted_key_type = EncryptedKeyV1::struct_name();
+    let is_encrypted = bundle.labels().contains(&encrypted_key_type.as_str())
+        && !bundle.labels().contains(&key_type.as_str());
+    if !is_encrypted {
+        return bundle.get::<T>();
+    }
+
+    let encrypted_key = bundle.get::<EncryptedKeyV1>()?;
+    encrypted_key.decrypt(&passphrase()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::create_keypair;
+    use ed25519_dalek::SigningKey;
+
+    // Cheap parameters so the tests run quickly
+    fn test_params() -> KdfParams {
+        KdfParams::with_cost(64, 1)
+    }
+
+    #[test]
+    fn test_encrypt_and_decrypt() {
+        let (signing_key, _) = create_keypair(&mut OsRng).unwrap();
+
+        let encrypted =
+            EncryptedKeyV1::encrypt_with_params(&signing_key, "correct horse", test_params())
+                .unwrap();
+        assert_eq!(encrypted.key_type, "SIGNING_KEY_V1");
+
+        let armored = encrypted.to_armored_string().unwrap();
+        assert!(armored.contains("ENCRYPTED_KEY_V1"));
+
+        let decoded = EncryptedKeyV1::from_armored_string(&armored).unwrap();
+        let decrypted: SigningKey = decoded.decrypt("correct horse").unwrap();
+        assert_eq!(decrypted.to_bytes(), signing_key.to_bytes());
+    }
+
+    #[test]
+    fn test_wrong_passphrase() {
+        let (signing_key, _) = create_keypair(&mut OsRng).unwrap();
+
+        let encrypted =
+            EncryptedKeyV1::encrypt_with_params(&signing_key, "correct horse", test_params())
+                .unwrap();
+        assert!(matches!(
+            encrypted.decrypt::<SigningKey>("battery staple"),
+            Err(EncryptionError(_))
+        ));
+
+        // The key type is authenticated
+        let mut relabelled = encrypted.clone();
+        relabelled.key_type = "SECRET_KEY_V1".to_string();
+        assert!(matches!(
+            relabelled.decrypt_bytes("correct horse"),
+            Err(EncryptionError(_))
+        ));
+    }
+
+    #[test]
+    fn test_oversized_kdf_params() {
+        let (signing_key, _) = create_keypair(&mut OsRng).unwrap();
+        let encrypted =
+            EncryptedKeyV1::encrypt_with_params(&signing_key, "correct horse", test_params())
+                .unwrap();
+
+        // Rejected before any work is done, whatever the passphrase
+        let mut crafted = encrypted.clone();
+        crafted.kdf.memory_kib = MAX_MEMORY_KIB + 1;
+        assert!(matches!(crafted.decrypt_bytes("correct horse"), Err(EncryptionError(_))));
+
+        let mut crafted = encrypted.clone();
+        crafted.kdf.iterations = u32::MAX;
+        assert!(matches!(crafted.decrypt_bytes("correct horse"), Err(EncryptionError(_))));
+
+        let mut crafted = encrypted.clone();
+        crafted.kdf.parallelism = MAX_PARALLELISM + 1;
+        let armored = crafted.to_armored_string().unwrap();
+        let decoded = EncryptedKeyV1::from_armored_string(&armored).unwrap();
+        assert!(matches!(decoded.decrypt::<SigningKey>("correct horse"), Err(EncryptionError(_))));
+    }
+
+    #[test]
+    fn test_read_private_key() {
+        let (signing_key, _) = create_keypair(&mut OsRng).unwrap();
+
+        let mut plain = ArmoredBundle::new();
+        plain.add(&signing_key).unwrap();
+        let key: SigningKey =
+            read_private_key(&plain, || panic!("Plain keys need no passphrase")).unwrap();
+        assert_eq!(key.to_bytes(), signing_key.to_bytes());
+
+        let mut encrypted = ArmoredBundle::new();
+        encrypted
+            .add(
+                &EncryptedKeyV1::encrypt_with_params(&signing_key, "correct horse", test_params())
+                    .unwrap(),
+            )
+            .unwrap();
+        let key: SigningKey =
+            read_private_key(&encrypted, || Ok("correct horse".to_string())).unwrap();
+        assert_eq!(key.to_bytes(), signing_key.to_bytes());
+    }
+}
@@ -1,38 +1,140 @@
+use alloc::boxed::Box;
+use alloc::string::String;
 use GhostkeyError::*;
 
-#[derive(Debug, PartialEq)]
+/// Underlying error carried by variants that wrap a failure from another crate.
+pub type ErrorSource = Box<dyn core::error::Error + Send + Sync>;
+
+/// Errors returned by ghostkey_lib. Every variant has a stable machine-readable
+/// [`code`](GhostkeyError::code) that tools can match on instead of the message text, and
+/// variants wrapping a failure from another crate expose it through
+/// [`source`](core::error::Error::source). Variants only some features can produce, such as
+/// [`IOError`](GhostkeyError::IOError), exist only with those features.
+#[derive(Debug)]
+#[non_exhaustive]
 pub enum GhostkeyError {
     KeyCreationError(String),
     SignatureError(String),
-    RSAError(String),
-    SignatureVerificationError(String),
-    Base64DecodeError(String),
-    SerializationError(String),
-    DeserializationError(String),
+    /// A blind RSA operation failed, the source is a `blind_rsa_signatures::Error`
+    RSAError(String, ErrorSource),
+    /// A signature in a certificate chain or signed object did not verify
+    SignatureVerificationError(SignatureKind, String),
+    Base64DecodeError(base64::DecodeError),
+    /// Encoding to CBOR failed
+    SerializationError(String, ErrorSource),
+    /// Decoding CBOR failed, the data is corrupt or not of the expected type
+    DeserializationError(String, ErrorSource),
     InvalidInput(String),
     ArmorError(String),
     ValidationError(String),
     DecodingError(String),
-    IOError(String),
+    #[cfg(feature = "std")]
+    IOError(String, std::io::Error),
+    ValidityPeriodError(String),
+    DelegateRevokedError(String),
+    ChecksumMismatchError(String),
+    TruncatedBlockError(String),
+    EncryptionError(String),
+    /// A delegate signing key or blinded ghost key belongs to another delegate certificate
+    DelegateMismatchError(String),
 }
 
-impl std::error::Error for GhostkeyError {}
+/// Which signature failed to verify in a [`SignatureVerificationError`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureKind {
+    /// The master signature over a delegate certificate, it is not signed by a trusted master
+    /// key or the delegate payload was altered
+    Delegate,
+    /// The delegate signature over a ghost key, the ghost verifying key was altered or signed
+    /// by another delegate
+    GhostKey,
+    /// The master signature over a revocation list
+    RevocationList,
+    /// The ghost key or subkey signature over a signed message
+    Message,
+    /// The ghost key signature over a subkey certificate
+    Subkey,
+    /// The ring proof of a pseudonym certificate, or the pseudonym signature over a message
+    Pseudonym,
+    /// One signer's share of a threshold master signature, or the signature they add up to
+    SignatureShare,
+}
 
-impl std::fmt::Display for GhostkeyError {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl GhostkeyError {
+    /// Stable machine-readable code identifying the kind of error. Codes are never reused or
+    /// changed once released, new failure modes get new codes.
+    pub fn code(&self) -> &'static str {
+        match self {
+            KeyCreationError(_) => "GK_KEY_CREATION_FAILED",
+            SignatureError(_) => "GK_SIGNING_FAILED",
+            RSAError(..) => "GK_RSA_FAILED",
+            SignatureVerificationError(SignatureKind::Delegate, _) => "GK_SIG_DELEGATE_INVALID",
+            SignatureVerificationError(SignatureKind::GhostKey, _) => "GK_SIG_GHOST_KEY_INVALID",
+            SignatureVerificationError(SignatureKind::RevocationList, _) => {
+                "GK_SIG_REVOCATION_LIST_INVALID"
+            }
+            SignatureVerificationError(SignatureKind::Message, _) => "GK_SIG_MESSAGE_INVALID",
+            SignatureVerificationError(SignatureKind::Subkey, _) => "GK_SIG_SUBKEY_INVALID",
+            SignatureVerificationError(SignatureKind::Pseudonym, _) => "GK_SIG_PSEUDONYM_INVALID",
+            SignatureVerificationError(SignatureKind::SignatureShare, _) => "GK_SIG_SHARE_INVALID",
+            Base64DecodeError(_) => "GK_BASE64_INVALID",
+            SerializationError(..) => "GK_CBOR_ENCODE_FAILED",
+            DeserializationError(..) => "GK_CBOR_DECODE_FAILED",
+            InvalidInput(_) => "GK_INVALID_INPUT",
+            ArmorError(_) => "GK_ARMOR_INVALID",
+            ValidationError(_) => "GK_VALIDATION_FAILED",
+            DecodingError(_) => "GK_DECODE_FAILED",
+            #[cfg(feature = "std")]
+            IOError(..) => "GK_IO_FAILED",
+            ValidityPeriodError(_) => "GK_VALIDITY_PERIOD",
+            DelegateRevokedError(_) => "GK_DELEGATE_REVOKED",
+            ChecksumMismatchError(_) => "GK_ARMOR_CHECKSUM_MISMATCH",
+            TruncatedBlockError(_) => "GK_ARMOR_TRUNCATED",
+            EncryptionError(_) => "GK_ENCRYPTION_FAILED",
+            DelegateMismatchError(_) => "GK_DELEGATE_MISMATCH",
+        }
+    }
+}
+
+impl core::error::Error for GhostkeyError {
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            RSAError(_, source) => Some(source.as_ref()),
+            // base64 only implements Error with std
+            #[cfg(feature = "std")]
+            Base64DecodeError(source) => Some(source),
+            SerializationError(_, source) | DeserializationError(_, source) => Some(source.as_ref()),
+            #[cfg(feature = "std")]
+            IOError(_, source) => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl core::fmt::Display for GhostkeyError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         match self {
             KeyCreationError(msg) => write!(f, "Key Creation Error: {}", msg),
             SignatureError(msg) => write!(f, "Signature Error: {}", msg),
-            SignatureVerificationError(msg) => write!(f, "Signature Verification Error: {}", msg),
-            RSAError(msg) => write!(f, "Blind RSA Error: {}", msg),
-            Base64DecodeError(msg) => write!(f, "Base64 Decode Error: {}", msg),
-            SerializationError(msg) => write!(f, "Serialization Error: {}", msg),
-            DeserializationError(msg) => write!(f, "Deserialization Error: {}", msg),
+            SignatureVerificationError(_, msg) => write!(f, "Signature Verification Error: {}", msg),
+            RSAError(msg, source) => write!(f, "Blind RSA Error: {}: {}", msg, source),
+            Base64DecodeError(source) => write!(f, "Base64 Decode Error: {}", source),
+            SerializationError(msg, source) => write!(f, "Serialization Error: {}: {}", msg, source),
+            DeserializationError(msg, source) => {
+                write!(f, "Deserialization Error: {}: {}", msg, source)
+            }
             InvalidInput(msg) => write!(f, "Invalid Input: {}", msg),
             ArmorError(msg) => write!(f, "Armor Error: {}", msg),
             ValidationError(msg) => write!(f, "Validation Error: {}", msg),
             DecodingError(msg) => write!(f, "Decoding Error: {}", msg),
-            IOError(msg) => write!(f, "IO Error: {}", msg),
+            #[cfg(feature = "std")]
+            IOError(msg, source) => write!(f, "IO Error: {}: {}", msg, source),
+            ValidityPeriodError(msg) => write!(f, "Validity Period Error: {}", msg),
+            DelegateRevokedError(msg) => write!(f, "Delegate Revoked Error: {}", msg),
+            ChecksumMismatchError(msg) => write!(f, "Checksum Mismatch Error: {}", msg),
+            TruncatedBlockError(msg) => write!(f, "Truncated Block Error: {}", msg),
+            EncryptionError(msg) => write!(f, "Encryption Error: {}", msg),
+            DelegateMismatchError(msg) => write!(f, "Delegate Mismatch Error: {}", msg),
         }
     }
 }
@@ -42,3 +144,33 @@ impl From<String> for GhostkeyError {
         GhostkeyError::InvalidInput(error)
     }
 }
+
+impl From<base64::DecodeError> for GhostkeyError {
+    fn from(error: base64::DecodeError) -> Self {
+        GhostkeyError::Base64DecodeError(error)
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use std::error::Error;
+
+    #[test]
+    fn test_codes_distinguish_signature_failures() {
+        let delegate = SignatureVerificationError(SignatureKind::Delegate, "bad".to_string());
+        let ghost_key = SignatureVerificationError(SignatureKind::GhostKey, "bad".to_string());
+        assert_eq!(delegate.code(), "GK_SIG_DELEGATE_INVALID");
+        assert_eq!(ghost_key.code(), "GK_SIG_GHOST_KEY_INVALID");
+        assert_eq!(delegate.to_string(), ghost_key.to_string());
+    }
+
+    #[test]
+    fn test_source_is_exposed() {
+        let io_error = std::io::Error::new(std::io::ErrorKind::NotFound, "missing");
+        let error = IOError("Failed to open key.pem".to_string(), io_error);
+        assert_eq!(error.code(), "GK_IO_FAILED");
+        assert_eq!(error.source().unwrap().to_string(), "missing");
+        assert!(InvalidInput("bad".to_string()).source().is_none());
+    }
+}
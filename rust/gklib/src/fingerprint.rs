@@ -0,0 +1,232 @@
+use super::delegate_certificate::{DelegateCertificate, DelegateCertificateV1, DelegateCertificateV2};
+use super::delegate_key::PublicKey as RSAVerifyingKey;
+use super::errors::GhostkeyError;
+use super::ghost_key_certificate::{
+    GhostkeyCertificate, GhostkeyCertificateV1, GhostkeyCertificateV2, GhostkeyCertificateV3,
+};
+use super::subkey::SubkeyCertificateV1;
+use super::util::context_hash;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use ed25519_dalek::VerifyingKey;
+use serde::{Deserialize, Serialize};
+
+/// BLAKE3 key derivation context under which fingerprints are computed.
+pub const FINGERPRINT_CONTEXT: &str = "freenet.org ghostkey 2024-10 fingerprint";
+
+/// Number of leading bytes shown in the key ID.
+const KEY_ID_LENGTH: usize = 8;
+/// Number of words in the word list form, each encoding 11 bits.
+const WORD_COUNT: usize = 6;
+
+/// A BLAKE3 hash over the canonical CBOR encoding of a key or certificate, so the same key or
+/// certificate has the same fingerprint whichever armor label or release wrote it.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Fingerprint(pub [u8; 32]);
+
+impl Fingerprint {
+    /// Computes the fingerprint of `data`, with `kind` naming its type so that objects of
+    /// different types never share a fingerprint.
+    pub fn compute<T: Serialize>(kind: &str, data: &T) -> Result<Self, GhostkeyError> {
+        Ok(Fingerprint(context_hash(FINGERPRINT_CONTEXT, &(kind, data))?))
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// All 32 bytes as lowercase hex.
+    pub fn to_hex(&self) -> String {
+        self.0.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    /// The first 8 bytes as hex in groups of four digits, e.g. `3f2a 9c01 77de 4b10`. Short
+    /// enough to read out, and what the CLI and API print to name a key.
+    pub fn key_id(&self) -> String {
+        self.0[..KEY_ID_LENGTH]
+            .chunks(2)
+            .map(|pair| format!("{:02x}{:02x}", pair[0], pair[1]))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// The first 66 bits as six words of the BIP-39 English word list, for comparing
+    /// fingerprints at a glance or over the phone.
+    pub fn to_words(&self) -> String {
+        let mut bits: u128 = 0;
+        for byte in &self.0[..9] {
+            bits = (bits << 8) | *byte as u128;
+        }
+        let words = bip39::Language::English.word_list();
+        (0..WORD_COUNT)
+            .map(|i| words[((bits >> (72 - 11 * (i + 1))) & 0x7ff) as usize])
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.key_id())
+    }
+}
+
+/// Keys and certificates that have a [`Fingerprint`]. A certificate's fingerprint covers its
+/// signatures, the key it certifies has a fingerprint of its own.
+pub trait Fingerprinted {
+    fn fingerprint(&self) -> Result<Fingerprint, GhostkeyError>;
+}
+
+impl Fingerprinted for VerifyingKey {
+    fn fingerprint(&self) -> Result<Fingerprint, GhostkeyError> {
+        Fingerprint::compute("ed25519 verifying key", self)
+    }
+}
+
+impl Fingerprinted for RSAVerifyingKey {
+    fn fingerprint(&self) -> Result<Fingerprint, GhostkeyError> {
+        Fingerprint::compute("delegate verifying key", self)
+    }
+}
+
+impl Fingerprinted for DelegateCertificateV1 {
+    fn fingerprint(&self) -> Result<Fingerprint, GhostkeyError> {
+        Fingerprint::compute("delegate certificate v1", self)
+    }
+}
+
+impl Fingerprinted for DelegateCertificateV2 {
+    fn fingerprint(&self) -> Result<Fingerprint, GhostkeyError> {
+        Fingerprint::compute("delegate certificate v2", self)
+    }
+}
+
+impl Fingerprinted for DelegateCertificate {
+    fn fingerprint(&self) -> Result<Fingerprint, GhostkeyError> {
+        match self {
+            DelegateCertificate::V1(certificate) => certificate.fingerprint(),
+            DelegateCertificate::V2(certificate) => certificate.fingerprint(),
+        }
+    }
+}
+
+impl Fingerprinted for GhostkeyCertificateV1 {
+    fn fingerprint(&self) -> Result<Fingerprint, GhostkeyError> {
+        Fingerprint::compute("ghost key certificate v1", self)
+    }
+}
+
+impl Fingerprinted for GhostkeyCertificateV2 {
+    fn fingerprint(&self) -> Result<Fingerprint, GhostkeyError> {
+        Fingerprint::compute("ghost key certificate v2", self)
+    }
+}
+
+impl Fingerprinted for GhostkeyCertificateV3 {
+    fn fingerprint(&self) -> Result<Fingerprint, GhostkeyError> {
+        Fingerprint::compute("ghost key certificate v3", self)
+    }
+}
+
+impl Fingerprinted for GhostkeyCertificate {
+    fn fingerprint(&self) -> Result<Fingerprint, GhostkeyError> {
+        match self {
+            GhostkeyCertificate::V1(certificate) => certificate.fingerprint(),
+            GhostkeyCertificate::V2(certificate) => certificate.fingerprint(),
+            GhostkeyCertificate::V3(certificate) => certificate.fingerprint(),
+        }
+    }
+}
+
+impl Fingerprinted for SubkeyCertificateV1 {
+    fn fingerprint(&self) -> Result<Fingerprint, GhostkeyError> {
+        Fingerprint::compute("subkey certificate v1", self)
+    }
+}
+
+/// The fingerprint of the public key of an armored private key, given its armor label and
+/// contents, so a private key is named like the public key the CLI prints for it. `None` for
+/// keys of other types, including encrypted keys.
+#[cfg(feature = "issue")]
+pub fn private_key_fingerprint(key_type: &str, key: &[u8]) -> Option<Result<Fingerprint, GhostkeyError>> {
+    use super::armorable::Armorable;
+    use alloc::string::ToString;
+    use blind_rsa_signatures::SecretKey as RSASigningKey;
+    use ed25519_dalek::SigningKey;
+
+    if key_type == SigningKey::struct_name() {
+        Some(<SigningKey as Armorable>::from_bytes(key).and_then(|key| key.verifying_key().fingerprint()))
+    } else if key_type == RSASigningKey::struct_name() {
+        Some(<RSASigningKey as Armorable>::from_bytes(key).and_then(|key| {
+            let public_key = key.public_key().map_err(|e| {
+                GhostkeyError::RSAError("Failed to derive public key".to_string(), e.into())
+            })?;
+            RSAVerifyingKey::from(public_key).fingerprint()
+        }))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::armorable::Armorable;
+    use crate::FREENET_MASTER_VERIFYING_KEY_BASE64;
+
+    #[test]
+    fn test_fingerprint_forms() {
+        let verifying_key = VerifyingKey::from_base64(FREENET_MASTER_VERIFYING_KEY_BASE64).unwrap();
+        let fingerprint = verifying_key.fingerprint().unwrap();
+
+        // Fingerprints of existing keys must never change
+        assert_eq!(
+            fingerprint.to_hex(),
+            "ef271ead6231a0e5f2a20b31d0baa455b306ff397c065f85d3056e1f8deb8ebc"
+        );
+        assert_eq!(fingerprint.key_id(), "ef27 1ead 6231 a0e5");
+        assert_eq!(fingerprint.to_string(), "ef27 1ead 6231 a0e5");
+        assert_eq!(fingerprint.to_words(), "upset decline prize settle borrow indicate");
+    }
+
+    #[cfg(feature = "issue")]
+    #[test]
+    fn test_certificate_fingerprints() {
+        use crate::util::create_keypair;
+        use rand_core::OsRng;
+
+        let (master_signing_key, _) = create_keypair(&mut OsRng).unwrap();
+        let info = "Test Delegate".to_string();
+        let (delegate_certificate, delegate_signing_key) =
+            DelegateCertificateV1::new(&master_signing_key, &info).unwrap();
+        let (ghost_key_certificate, _) =
+            GhostkeyCertificateV1::new(&delegate_certificate, &delegate_signing_key).unwrap();
+
+        // The version enums share the fingerprint of the certificate they hold
+        assert_eq!(
+            DelegateCertificate::V1(delegate_certificate.clone()).fingerprint().unwrap(),
+            delegate_certificate.fingerprint().unwrap()
+        );
+        assert_eq!(
+            GhostkeyCertificate::V1(ghost_key_certificate.clone()).fingerprint().unwrap(),
+            ghost_key_certificate.fingerprint().unwrap()
+        );
+
+        // A certificate and the key it certifies are named differently
+        let delegate_key = delegate_certificate.payload.delegate_verifying_key();
+        assert_ne!(
+            delegate_certificate.fingerprint().unwrap(),
+            delegate_key.fingerprint().unwrap()
+        );
+        assert_eq!(
+            delegate_key.fingerprint().unwrap(),
+            RSAVerifyingKey::from(delegate_signing_key.public_key().unwrap()).fingerprint().unwrap()
+        );
+        assert_ne!(
+            ghost_key_certificate.fingerprint().unwrap(),
+            ghost_key_certificate.verifying_key.fingerprint().unwrap()
+        );
+    }
+}
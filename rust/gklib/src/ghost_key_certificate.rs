@@ -1,14 +1,39 @@
-use super::delegate_certificate::DelegateCertificateV1;
+use super::armorable::{read_versioned_block, ArmoredBundle};
+use super::delegate_certificate::{
+    DelegateCertificate, DelegateCertificateV1, DelegateCertificateV2, DelegateInfo,
+    DelegatePayload,
+};
+#[cfg(feature = "issue")]
+use super::delegate_key::PublicKey as RSAVerifyingKey;
+use super::delegate_key::Signature as RSASignature;
 use super::errors::GhostkeyError;
-use super::errors::GhostkeyError::{RSAError, SignatureVerificationError};
+use super::errors::GhostkeyError::SignatureVerificationError;
+use super::errors::SignatureKind;
+use super::trust_store::TrustStore;
+use super::util::{canonical_cbor, context_hash};
+#[cfg(feature = "std")]
+use super::util::current_unix_time;
+#[cfg(feature = "issue")]
+use super::issuance;
+#[cfg(feature = "issue")]
+use super::partially_blind;
+#[cfg(feature = "issue")]
 use super::util::{create_keypair, unblinded_rsa_sign};
-use blind_rsa_signatures::{
-    KeyPair, Options, SecretKey as RSASigningKey, Signature as RSASignature,
-};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+#[cfg(feature = "issue")]
+use blind_rsa_signatures::{KeyPair, SecretKey as RSASigningKey};
+use core::fmt;
 use ed25519_dalek::*;
-use rand_core::OsRng;
+#[cfg(feature = "issue")]
+use rand_core::{CryptoRng, OsRng, RngCore};
 use serde::{Deserialize, Serialize};
+#[cfg(feature = "fs")]
+use std::path::Path;
 use crate::armorable::Armorable;
+use base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
+use base64::Engine;
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct GhostkeyCertificateV1 {
@@ -19,65 +44,655 @@ pub struct GhostkeyCertificateV1 {
 }
 
 impl GhostkeyCertificateV1 {
+    /// Creates a new ghost key and a certificate for it signed by the delegate signing key.
+    #[cfg(feature = "issue")]
     pub fn new(
         delegate_certificate: &DelegateCertificateV1,
         delegate_signing_key: &RSASigningKey,
-    ) -> (Self, SigningKey) {
-        let delegate_keypair = KeyPair::new(
-            delegate_signing_key.public_key().unwrap(),
-            delegate_signing_key.clone(),
-        );
-        let (ghost_signing_key, ghost_verifying_key) = create_keypair(&mut OsRng).unwrap();
-        let ghost_signing_key = SigningKey::from(ghost_signing_key);
-        let ghost_verifying_key = VerifyingKey::from(ghost_verifying_key);
+    ) -> Result<(Self, SigningKey), GhostkeyError> {
+        Self::new_with_rng(&mut OsRng, delegate_certificate, delegate_signing_key)
+    }
+
+    /// Like [`new`](Self::new), drawing the ghost key and the signature randomness from `rng`.
+    #[cfg(feature = "issue")]
+    pub fn new_with_rng<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        delegate_certificate: &DelegateCertificateV1,
+        delegate_signing_key: &RSASigningKey,
+    ) -> Result<(Self, SigningKey), GhostkeyError> {
+        let delegate_keypair = delegate_keypair(delegate_signing_key)?;
+        let (ghost_signing_key, ghost_verifying_key) = create_keypair(rng)?;
+        let message = Armorable::to_bytes(&ghost_verifying_key)?;
 
-        (
+        Ok((
             Self {
                 delegate: delegate_certificate.clone(),
-                verifying_key: ghost_verifying_key.clone(),
-                signature: unblinded_rsa_sign(&delegate_keypair, &Armorable::to_bytes(&ghost_verifying_key).unwrap())
-                    .unwrap(),
+                verifying_key: ghost_verifying_key,
+                signature: unblinded_rsa_sign(rng, &delegate_keypair, &message)?.into(),
             },
-            ghost_signing_key.clone(),
+            ghost_signing_key,
+        ))
+    }
+
+    /// Verifies the certificate chain against the master verifying keys in the trust store,
+    /// returning the delegate info on success.
+    #[cfg(feature = "std")]
+    pub fn verify(
+        &self,
+        trust_store: &TrustStore,
+    ) -> Result<DelegateInfo, GhostkeyError> {
+        self.verify_at(current_unix_time(), trust_store)
+    }
+
+    /// Verifies the full certificate chain, checking the delegate's validity period against
+    /// `time` (seconds since the Unix epoch) rather than the current time.
+    pub fn verify_at(
+        &self,
+        time: u64,
+        trust_store: &TrustStore,
+    ) -> Result<DelegateInfo, GhostkeyError> {
+        verify_chain(
+            self.delegate.verify_at(time, trust_store),
+            &self.delegate.payload,
+            &self.signature,
+            None,
+            Armorable::to_bytes(&self.verifying_key)?,
         )
     }
+}
+
+/// BLAKE3 key derivation context under which delegates sign the verifying key of a V2 ghost key
+/// certificate.
+pub const GHOSTKEY_CERTIFICATE_V2_CONTEXT: &str =
+    "freenet.org ghostkey 2024-10 ghost key certificate v2";
+
+/// A ghost key certificate issued by a [`DelegateCertificateV2`]. The delegate signs the
+/// [`context_hash`] of the ghost verifying key under [`GHOSTKEY_CERTIFICATE_V2_CONTEXT`] rather
+/// than its raw CBOR encoding.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct GhostkeyCertificateV2 {
+    pub delegate: DelegateCertificateV2,
+    pub verifying_key: VerifyingKey,
+    /// Always [`GHOSTKEY_CERTIFICATE_V2_CONTEXT`], stored so the certificate is self-describing
+    /// and its CBOR shape differs from V1
+    pub context: String,
+    /// Hash of verifying_key signed by the delegate signing key
+    pub signature: RSASignature,
+}
+
+impl GhostkeyCertificateV2 {
+    /// Creates a new ghost key and a certificate for it signed by the delegate signing key.
+    #[cfg(feature = "issue")]
+    pub fn new(
+        delegate_certificate: &DelegateCertificateV2,
+        delegate_signing_key: &RSASigningKey,
+    ) -> Result<(Self, SigningKey), GhostkeyError> {
+        Self::new_with_rng(&mut OsRng, delegate_certificate, delegate_signing_key)
+    }
+
+    /// Like [`new`](Self::new), drawing the ghost key and the signature randomness from `rng`.
+    #[cfg(feature = "issue")]
+    pub fn new_with_rng<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        delegate_certificate: &DelegateCertificateV2,
+        delegate_signing_key: &RSASigningKey,
+    ) -> Result<(Self, SigningKey), GhostkeyError> {
+        let delegate_keypair = delegate_keypair(delegate_signing_key)?;
+        let (ghost_signing_key, ghost_verifying_key) = create_keypair(rng)?;
+        let message = Self::message(&ghost_verifying_key)?;
+
+        Ok((
+            Self {
+                delegate: delegate_certificate.clone(),
+                verifying_key: ghost_verifying_key,
+                context: GHOSTKEY_CERTIFICATE_V2_CONTEXT.to_string(),
+                signature: unblinded_rsa_sign(rng, &delegate_keypair, &message)?.into(),
+            },
+            ghost_signing_key,
+        ))
+    }
 
+    /// The message the delegate signs, blindly or not, to certify `verifying_key`.
+    pub fn message(verifying_key: &VerifyingKey) -> Result<Vec<u8>, GhostkeyError> {
+        Ok(context_hash(GHOSTKEY_CERTIFICATE_V2_CONTEXT, verifying_key)?.to_vec())
+    }
+
+    /// Verifies the certificate chain against the master verifying keys in the trust store,
+    /// returning the delegate info on success.
+    #[cfg(feature = "std")]
     pub fn verify(
         &self,
-        master_verifying_key: &Option<VerifyingKey>,
-    ) -> Result<String, Box<GhostkeyError>> {
-        // Verify delegate certificate
-        let info = self
-            .delegate
-            .verify(master_verifying_key)
-            .map_err(|e| SignatureVerificationError(format!("Failed to verify delegate: {}", e)))?;
-
-        // Verify ghostkey certificate
-        let verification = self
-            .delegate
-            .payload
-            .delegate_verifying_key
-            .verify(
-                &self.signature,
+        trust_store: &TrustStore,
+    ) -> Result<DelegateInfo, GhostkeyError> {
+        self.verify_at(current_unix_time(), trust_store)
+    }
+
+    /// Verifies the full certificate chain, checking the delegate's validity period against
+    /// `time` (seconds since the Unix epoch) rather than the current time.
+    pub fn verify_at(
+        &self,
+        time: u64,
+        trust_store: &TrustStore,
+    ) -> Result<DelegateInfo, GhostkeyError> {
+        check_context(&self.context, GHOSTKEY_CERTIFICATE_V2_CONTEXT)?;
+
+        verify_chain(
+            self.delegate.verify_at(time, trust_store),
+            &self.delegate.payload,
+            &self.signature,
+            None,
+            Self::message(&self.verifying_key)?,
+        )
+    }
+}
+
+/// BLAKE3 key derivation context under which delegates sign the verifying key of a V3 ghost key
+/// certificate.
+pub const GHOSTKEY_CERTIFICATE_V3_CONTEXT: &str =
+    "freenet.org ghostkey 2024-10 ghost key certificate v3";
+
+/// Public metadata that the delegate signature of a [`GhostkeyCertificateV3`] binds to the ghost
+/// key. Unlike the verifying key, the issuer sees it when signing, so it must not identify the
+/// donor.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct GhostkeyMetadata {
+    /// The donation amount in whole units of `currency`
+    pub amount: u64,
+    /// ISO 4217 currency code, e.g. "USD"
+    pub currency: String,
+    /// UTC month in which the ghost key was issued, as `YYYY-MM`, see
+    /// [`month_of`](crate::util::month_of)
+    pub issued: String,
+    /// Campaign the donation was made for, absent if none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub campaign: Option<String>,
+}
+
+impl GhostkeyMetadata {
+    /// The canonical CBOR encoding, which is the metadata the partially blind signature binds.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, GhostkeyError> {
+        canonical_cbor(self)
+    }
+}
+
+impl fmt::Display for GhostkeyMetadata {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}, issued {}", self.amount, self.currency, self.issued)?;
+        if let Some(campaign) = &self.campaign {
+            write!(f, ", campaign {}", campaign)?;
+        }
+        Ok(())
+    }
+}
+
+/// A ghost key certificate whose delegate signature also covers [`GhostkeyMetadata`]. The
+/// delegate signs the [`context_hash`] of the ghost verifying key under
+/// [`GHOSTKEY_CERTIFICATE_V3_CONTEXT`] with a [`partially_blind`](crate::partially_blind)
+/// signature bound to the metadata, so one delegate can issue ghost keys for any amount or
+/// month, and the metadata can't be changed without invalidating the signature.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct GhostkeyCertificateV3 {
+    pub delegate: DelegateCertificateV2,
+    pub verifying_key: VerifyingKey,
+    pub metadata: GhostkeyMetadata,
+    /// Always [`GHOSTKEY_CERTIFICATE_V3_CONTEXT`], stored so the certificate is self-describing
+    pub context: String,
+    /// Hash of verifying_key signed by the delegate signing key, bound to metadata
+    pub signature: RSASignature,
+}
+
+impl GhostkeyCertificateV3 {
+    /// Creates a new ghost key and a certificate for it signed by the delegate signing key,
+    /// which must have been generated for partially blind signing.
+    #[cfg(feature = "issue")]
+    pub fn new(
+        delegate_certificate: &DelegateCertificateV2,
+        delegate_signing_key: &RSASigningKey,
+        metadata: &GhostkeyMetadata,
+    ) -> Result<(Self, SigningKey), GhostkeyError> {
+        Self::new_with_rng(&mut OsRng, delegate_certificate, delegate_signing_key, metadata)
+    }
+
+    /// Like [`new`](Self::new), drawing the ghost key and the signature randomness from `rng`.
+    #[cfg(feature = "issue")]
+    pub fn new_with_rng<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        delegate_certificate: &DelegateCertificateV2,
+        delegate_signing_key: &RSASigningKey,
+        metadata: &GhostkeyMetadata,
+    ) -> Result<(Self, SigningKey), GhostkeyError> {
+        let metadata_bytes = metadata.to_bytes()?;
+        let delegate_keypair = partially_blind::derive_key_pair(delegate_signing_key, &metadata_bytes)?;
+        let (ghost_signing_key, ghost_verifying_key) = create_keypair(rng)?;
+        let message =
+            partially_blind::metadata_message(&metadata_bytes, &Self::message(&ghost_verifying_key)?);
+
+        let (blinded_message, secret) = issuance::blind(rng, &delegate_keypair.pk, &message)?;
+        let blind_signature = issuance::blind_sign(rng, &delegate_keypair.sk, &blinded_message)?;
+        let signature =
+            issuance::finalize(&delegate_keypair.pk, &blind_signature, &secret, &message)?;
+
+        Ok((
+            Self {
+                delegate: delegate_certificate.clone(),
+                verifying_key: ghost_verifying_key,
+                metadata: metadata.clone(),
+                context: GHOSTKEY_CERTIFICATE_V3_CONTEXT.to_string(),
+                signature: signature.into(),
+            },
+            ghost_signing_key,
+        ))
+    }
+
+    /// The message the delegate signs, blindly or not, to certify `verifying_key`. The
+    /// signature binds the metadata on top of it.
+    pub fn message(verifying_key: &VerifyingKey) -> Result<Vec<u8>, GhostkeyError> {
+        Ok(context_hash(GHOSTKEY_CERTIFICATE_V3_CONTEXT, verifying_key)?.to_vec())
+    }
+
+    /// Verifies the certificate chain against the master verifying keys in the trust store,
+    /// returning the delegate info on success. The metadata is only valid if this succeeds.
+    #[cfg(feature = "std")]
+    pub fn verify(
+        &self,
+        trust_store: &TrustStore,
+    ) -> Result<DelegateInfo, GhostkeyError> {
+        self.verify_at(current_unix_time(), trust_store)
+    }
+
+    /// Verifies the full certificate chain, checking the delegate's validity period against
+    /// `time` (seconds since the Unix epoch) rather than the current time.
+    pub fn verify_at(
+        &self,
+        time: u64,
+        trust_store: &TrustStore,
+    ) -> Result<DelegateInfo, GhostkeyError> {
+        check_context(&self.context, GHOSTKEY_CERTIFICATE_V3_CONTEXT)?;
+
+        verify_chain(
+            self.delegate.verify_at(time, trust_store),
+            &self.delegate.payload,
+            &self.signature,
+            Some(&self.metadata),
+            Self::message(&self.verifying_key)?,
+        )
+    }
+}
+
+/// Pairs the delegate signing key with its public key, failing on a malformed key rather than
+/// panicking.
+#[cfg(feature = "issue")]
+fn delegate_keypair(delegate_signing_key: &RSASigningKey) -> Result<KeyPair, GhostkeyError> {
+    let delegate_verifying_key = delegate_signing_key.public_key().map_err(|e| {
+        GhostkeyError::RSAError("Failed to derive delegate verifying key".to_string(), e.into())
+    })?;
+    Ok(KeyPair::new(delegate_verifying_key, delegate_signing_key.clone()))
+}
+
+/// Rejects a certificate whose stored context is not the one of its version.
+fn check_context(context: &str, expected: &str) -> Result<(), GhostkeyError> {
+    if context != expected {
+        return Err(SignatureVerificationError(SignatureKind::GhostKey, format!(
+            "Unexpected ghost key certificate context '{}'",
+            context
+        )));
+    }
+    Ok(())
+}
+
+/// Completes the verification of a ghost key certificate: passes on the error from verifying the
+/// delegate certificate unchanged, so its code identifies the failing link, then checks the
+/// delegate's signature over `message`, bound to `metadata` if given.
+fn verify_chain(
+    delegate_verification: Result<DelegateInfo, GhostkeyError>,
+    delegate_payload: &DelegatePayload,
+    signature: &RSASignature,
+    metadata: Option<&GhostkeyMetadata>,
+    message: Vec<u8>,
+) -> Result<DelegateInfo, GhostkeyError> {
+    // Verify delegate certificate
+    let info = delegate_verification?;
+
+    // Verify ghostkey certificate
+    let delegate_verifying_key = delegate_payload.delegate_verifying_key();
+    let verified = match metadata {
+        Some(metadata) => {
+            delegate_verifying_key.verify_with_metadata(signature, &metadata.to_bytes()?, &message)
+        }
+        None => delegate_verifying_key.verify(signature, &message),
+    };
+    if verified {
+        Ok(info)
+    } else {
+        Err(SignatureVerificationError(
+            SignatureKind::GhostKey,
+            "Failed to verify ghostkey certificate".to_string(),
+        ))
+    }
+}
+
+/// Any supported version of a ghost key certificate. Decoding picks the version from the armor
+/// label, falling back to the CBOR shape (newest version first) for unversioned labels and raw
+/// bytes, so that ghost keys issued by older releases keep loading after a format change.
+#[derive(Clone)]
+pub enum GhostkeyCertificate {
+    V1(GhostkeyCertificateV1),
+    V2(GhostkeyCertificateV2),
+    V3(GhostkeyCertificateV3),
+}
+
+impl GhostkeyCertificate {
+    /// Armor label without the `_V<n>` version suffix.
+    pub const LABEL: &'static str = "GHOSTKEY_CERTIFICATE";
+
+    /// Creates a ghost key certificate of the version matching the delegate certificate.
+    #[cfg(feature = "issue")]
+    pub fn new(
+        delegate_certificate: &DelegateCertificate,
+        delegate_signing_key: &RSASigningKey,
+    ) -> Result<(Self, SigningKey), GhostkeyError> {
+        Self::new_with_rng(&mut OsRng, delegate_certificate, delegate_signing_key)
+    }
+
+    /// Like [`new`](Self::new), drawing the ghost key and the signature randomness from `rng`.
+    #[cfg(feature = "issue")]
+    pub fn new_with_rng<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        delegate_certificate: &DelegateCertificate,
+        delegate_signing_key: &RSASigningKey,
+    ) -> Result<(Self, SigningKey), GhostkeyError> {
+        match delegate_certificate {
+            DelegateCertificate::V1(delegate) => {
+                let (certificate, signing_key) =
+                    GhostkeyCertificateV1::new_with_rng(rng, delegate, delegate_signing_key)?;
+                Ok((GhostkeyCertificate::V1(certificate), signing_key))
+            }
+            DelegateCertificate::V2(delegate) => {
+                let (certificate, signing_key) =
+                    GhostkeyCertificateV2::new_with_rng(rng, delegate, delegate_signing_key)?;
+                Ok((GhostkeyCertificate::V2(certificate), signing_key))
+            }
+        }
+    }
+
+    /// Assembles a certificate from a delegate signature over
+    /// [`DelegateCertificate::ghost_key_message`], such as an unblinded blind signature.
+    pub fn from_parts(
+        delegate_certificate: DelegateCertificate,
+        verifying_key: VerifyingKey,
+        signature: RSASignature,
+    ) -> Self {
+        match delegate_certificate {
+            DelegateCertificate::V1(delegate) => GhostkeyCertificate::V1(GhostkeyCertificateV1 {
+                delegate,
+                verifying_key,
+                signature,
+            }),
+            DelegateCertificate::V2(delegate) => GhostkeyCertificate::V2(GhostkeyCertificateV2 {
+                delegate,
+                verifying_key,
+                context: GHOSTKEY_CERTIFICATE_V2_CONTEXT.to_string(),
+                signature,
+            }),
+        }
+    }
+
+    /// Re-issues a V1 certificate as a [`GhostkeyCertificateV2`] for the same ghost key, under
+    /// `delegate_certificate`, the issuing delegate's certificate upgraded with
+    /// [`DelegateCertificate::upgrade`]. The delegate signs the ghost verifying key directly, as
+    /// the V1 certificate already made it public. V2 and V3 certificates are returned as is.
+    #[cfg(feature = "issue")]
+    pub fn upgrade(
+        self,
+        delegate_certificate: &DelegateCertificate,
+        delegate_signing_key: &RSASigningKey,
+    ) -> Result<Self, GhostkeyError> {
+        let certificate = match self {
+            GhostkeyCertificate::V1(certificate) => certificate,
+            current => return Ok(current),
+        };
+        let delegate = match delegate_certificate {
+            DelegateCertificate::V2(delegate) => delegate,
+            DelegateCertificate::V1(_) => {
+                return Err(GhostkeyError::InvalidInput(
+                    "Delegate certificate must be upgraded to V2 first".to_string(),
+                ))
+            }
+        };
+        let delegate_verifying_key = certificate.delegate.payload.delegate_verifying_key();
+        if delegate.payload.delegate_verifying_key() != delegate_verifying_key {
+            return Err(GhostkeyError::DelegateMismatchError(
+                "Delegate certificate is not for the delegate that issued the ghost key".to_string(),
+            ));
+        }
+        let delegate_keypair = delegate_keypair(delegate_signing_key)?;
+        if RSAVerifyingKey::from(delegate_keypair.pk.clone()) != *delegate_verifying_key {
+            return Err(GhostkeyError::DelegateMismatchError(
+                "Delegate signing key does not match the delegate certificate".to_string(),
+            ));
+        }
+        // Only ghost keys the delegate really certified are re-issued
+        let message = Armorable::to_bytes(&certificate.verifying_key)?;
+        if !delegate_verifying_key.verify(&certificate.signature, &message) {
+            return Err(SignatureVerificationError(
+                SignatureKind::GhostKey,
+                "Failed to verify ghostkey certificate".to_string(),
+            ));
+        }
+
+        let message = GhostkeyCertificateV2::message(&certificate.verifying_key)?;
+        Ok(GhostkeyCertificate::V2(GhostkeyCertificateV2 {
+            delegate: delegate.clone(),
+            verifying_key: certificate.verifying_key,
+            context: GHOSTKEY_CERTIFICATE_V2_CONTEXT.to_string(),
+            signature: unblinded_rsa_sign(&mut OsRng, &delegate_keypair, &message)?.into(),
+        }))
+    }
+
+    pub fn version(&self) -> u32 {
+        match self {
+            GhostkeyCertificate::V1(_) => 1,
+            GhostkeyCertificate::V2(_) => 2,
+            GhostkeyCertificate::V3(_) => 3,
+        }
+    }
+
+    /// The ghost verifying key certified by the delegate.
+    pub fn verifying_key(&self) -> &VerifyingKey {
+        match self {
+            GhostkeyCertificate::V1(certificate) => &certificate.verifying_key,
+            GhostkeyCertificate::V2(certificate) => &certificate.verifying_key,
+            GhostkeyCertificate::V3(certificate) => &certificate.verifying_key,
+        }
+    }
+
+    /// The payload of the delegate certificate that issued the ghost key.
+    pub fn delegate_payload(&self) -> &DelegatePayload {
+        match self {
+            GhostkeyCertificate::V1(certificate) => &certificate.delegate.payload,
+            GhostkeyCertificate::V2(certificate) => &certificate.delegate.payload,
+            GhostkeyCertificate::V3(certificate) => &certificate.delegate.payload,
+        }
+    }
+
+    /// The metadata bound into a V3 certificate, `None` for earlier versions. Only meaningful
+    /// once the certificate has been verified.
+    pub fn metadata(&self) -> Option<&GhostkeyMetadata> {
+        match self {
+            GhostkeyCertificate::V3(certificate) => Some(&certificate.metadata),
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub fn verify(&self, trust_store: &TrustStore) -> Result<DelegateInfo, GhostkeyError> {
+        match self {
+            GhostkeyCertificate::V1(certificate) => certificate.verify(trust_store),
+            GhostkeyCertificate::V2(certificate) => certificate.verify(trust_store),
+            GhostkeyCertificate::V3(certificate) => certificate.verify(trust_store),
+        }
+    }
+
+    pub fn verify_at(
+        &self,
+        time: u64,
+        trust_store: &TrustStore,
+    ) -> Result<DelegateInfo, GhostkeyError> {
+        match self {
+            GhostkeyCertificate::V1(certificate) => certificate.verify_at(time, trust_store),
+            GhostkeyCertificate::V2(certificate) => certificate.verify_at(time, trust_store),
+            GhostkeyCertificate::V3(certificate) => certificate.verify_at(time, trust_store),
+        }
+    }
+
+    /// Checks the delegate's signature over the ghost verifying key, given the result of
+    /// verifying the delegate certificate, so that callers can verify the delegate themselves.
+    pub(crate) fn verify_with_delegate(
+        &self,
+        delegate_verification: Result<DelegateInfo, GhostkeyError>,
+    ) -> Result<DelegateInfo, GhostkeyError> {
+        match self {
+            GhostkeyCertificate::V1(certificate) => verify_chain(
+                delegate_verification,
+                &certificate.delegate.payload,
+                &certificate.signature,
                 None,
-                Armorable::to_bytes(&self.verifying_key).unwrap(),
-                &Options::default(),
-            )
-            .map_err(|e| RSAError(format!("Failed to verify ghostkey: {}", e)));
+                Armorable::to_bytes(&certificate.verifying_key)?,
+            ),
+            GhostkeyCertificate::V2(certificate) => {
+                check_context(&certificate.context, GHOSTKEY_CERTIFICATE_V2_CONTEXT)?;
+                verify_chain(
+                    delegate_verification,
+                    &certificate.delegate.payload,
+                    &certificate.signature,
+                    None,
+                    GhostkeyCertificateV2::message(&certificate.verifying_key)?,
+                )
+            }
+            GhostkeyCertificate::V3(certificate) => {
+                check_context(&certificate.context, GHOSTKEY_CERTIFICATE_V3_CONTEXT)?;
+                verify_chain(
+                    delegate_verification,
+                    &certificate.delegate.payload,
+                    &certificate.signature,
+                    Some(&certificate.metadata),
+                    GhostkeyCertificateV3::message(&certificate.verifying_key)?,
+                )
+            }
+        }
+    }
+
+    /// Decodes CBOR bytes of any supported version, trying the newest version first.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, GhostkeyError> {
+        GhostkeyCertificateV3::from_bytes(bytes)
+            .map(GhostkeyCertificate::V3)
+            .or_else(|_| GhostkeyCertificateV2::from_bytes(bytes).map(GhostkeyCertificate::V2))
+            .or_else(|_| GhostkeyCertificateV1::from_bytes(bytes).map(GhostkeyCertificate::V1))
+    }
 
-        match verification {
-            Ok(_) => Ok(info),
-            Err(e) => Err(Box::new(SignatureVerificationError(
-                format!("Failed to verify ghostkey certificate: {}", e),
+    fn from_versioned_bytes(version: Option<u32>, bytes: &[u8]) -> Result<Self, GhostkeyError> {
+        match version {
+            None => Self::from_bytes(bytes),
+            Some(1) => GhostkeyCertificateV1::from_bytes(bytes).map(GhostkeyCertificate::V1),
+            Some(2) => GhostkeyCertificateV2::from_bytes(bytes).map(GhostkeyCertificate::V2),
+            Some(3) => GhostkeyCertificateV3::from_bytes(bytes).map(GhostkeyCertificate::V3),
+            Some(version) => Err(GhostkeyError::DecodingError(format!(
+                "Unsupported ghost key certificate version {}",
+                version
             ))),
         }
     }
+
+    pub fn from_armored_string(armored_string: &str) -> Result<Self, GhostkeyError> {
+        let (version, bytes) = read_versioned_block(armored_string, Self::LABEL)?;
+        Self::from_versioned_bytes(version, &bytes)
+    }
+
+    /// Extracts the certificate from a bundle holding other objects, such as its signing key.
+    pub fn from_bundle(bundle: &ArmoredBundle) -> Result<Self, GhostkeyError> {
+        let (version, entry) = bundle.find_versioned(Self::LABEL)?;
+        Self::from_versioned_bytes(version, &entry.block.data)
+    }
+
+    /// Extracts every certificate from a bundle, such as the ring of a pseudonym.
+    pub fn all_from_bundle(bundle: &ArmoredBundle) -> Result<Vec<Self>, GhostkeyError> {
+        let versioned_prefix = format!("{}_V", Self::LABEL);
+        let mut certificates = Vec::new();
+        for entry in &bundle.entries {
+            let version = if entry.label == Self::LABEL {
+                None
+            } else {
+                match entry.label.strip_prefix(&versioned_prefix).map(str::parse::<u32>) {
+                    Some(Ok(version)) => Some(version),
+                    _ => continue,
+                }
+            };
+            certificates.push(Self::from_versioned_bytes(version, &entry.block.data)?);
+        }
+        Ok(certificates)
+    }
+
+    #[cfg(feature = "fs")]
+    pub fn from_file(file_path: &Path) -> Result<Self, GhostkeyError> {
+        let armored_string = std::fs::read_to_string(file_path).map_err(|e| {
+            GhostkeyError::IOError(format!("Failed to read {}", file_path.display()), e)
+        })?;
+        Self::from_armored_string(&armored_string)
+    }
+
+    pub fn from_base64(encoded: &str) -> Result<Self, GhostkeyError> {
+        let bytes = BASE64_STANDARD.decode(encoded)?;
+        Self::from_bytes(&bytes)
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, GhostkeyError> {
+        match self {
+            GhostkeyCertificate::V1(certificate) => certificate.to_bytes(),
+            GhostkeyCertificate::V2(certificate) => certificate.to_bytes(),
+            GhostkeyCertificate::V3(certificate) => certificate.to_bytes(),
+        }
+    }
+
+    pub fn to_armored_string(&self) -> Result<String, GhostkeyError> {
+        match self {
+            GhostkeyCertificate::V1(certificate) => certificate.to_armored_string(),
+            GhostkeyCertificate::V2(certificate) => certificate.to_armored_string(),
+            GhostkeyCertificate::V3(certificate) => certificate.to_armored_string(),
+        }
+    }
+
+    #[cfg(feature = "fs")]
+    pub fn to_file(&self, file_path: &Path) -> Result<(), GhostkeyError> {
+        match self {
+            GhostkeyCertificate::V1(certificate) => certificate.to_file(file_path),
+            GhostkeyCertificate::V2(certificate) => certificate.to_file(file_path),
+            GhostkeyCertificate::V3(certificate) => certificate.to_file(file_path),
+        }
+    }
 }
 
+impl From<GhostkeyCertificateV1> for GhostkeyCertificate {
+    fn from(certificate: GhostkeyCertificateV1) -> Self {
+        GhostkeyCertificate::V1(certificate)
+    }
+}
 
-#[cfg(test)]
+impl From<GhostkeyCertificateV2> for GhostkeyCertificate {
+    fn from(certificate: GhostkeyCertificateV2) -> Self {
+        GhostkeyCertificate::V2(certificate)
+    }
+}
+
+impl From<GhostkeyCertificateV3> for GhostkeyCertificate {
+    fn from(certificate: GhostkeyCertificateV3) -> Self {
+        GhostkeyCertificate::V3(certificate)
+    }
+}
+
+#[cfg(all(test, feature = "issue"))]
 mod tests {
     use super::*;
+    use crate::errors::GhostkeyError::ValidityPeriodError;
 
     #[test]
     fn test_ghost_key_certificate_creation_and_verification() {
@@ -91,13 +706,13 @@ mod tests {
 
         // Create a ghostkey certificate
         let (ghost_key_certificate, _ghost_key_signing_key) =
-            GhostkeyCertificateV1::new(&delegate_certificate, &delegate_signing_key);
+            GhostkeyCertificateV1::new(&delegate_certificate, &delegate_signing_key).unwrap();
 
         // Verify the ghostkey certificate
         let verified_info = ghost_key_certificate
-            .verify(&Some(master_verifying_key))
+            .verify(&TrustStore::from(master_verifying_key))
             .unwrap();
-        assert_eq!(verified_info, info);
+        assert_eq!(verified_info.info, info);
     }
 
     #[test]
@@ -113,14 +728,14 @@ mod tests {
 
         // Create a ghostkey certificate
         let (ghost_key_certificate, _ghost_key_signing_key) =
-            GhostkeyCertificateV1::new(&delegate_certificate, &delegate_signing_key);
+            GhostkeyCertificateV1::new(&delegate_certificate, &delegate_signing_key).unwrap();
 
         // Try to verify with the wrong master key
-        let result = ghost_key_certificate.verify(&Some(wrong_master_verifying_key));
+        let result = ghost_key_certificate.verify(&TrustStore::from(wrong_master_verifying_key));
         assert!(result.is_err());
         assert!(matches!(
-            result.unwrap_err().as_ref(),
-            SignatureVerificationError(_)
+            result.unwrap_err(),
+            SignatureVerificationError(SignatureKind::Delegate, _)
         ));
     }
 
@@ -136,17 +751,19 @@ mod tests {
 
         // Create a ghostkey certificate
         let (mut ghost_key_certificate, _ghost_key_signing_key) =
-            GhostkeyCertificateV1::new(&delegate_certificate, &delegate_signing_key);
+            GhostkeyCertificateV1::new(&delegate_certificate, &delegate_signing_key).unwrap();
 
         // Tamper with the delegate certificate
-        ghost_key_certificate.delegate.payload.info = "Tampered Info".to_string();
+        if let DelegatePayload::V1(payload) = &mut ghost_key_certificate.delegate.payload {
+            payload.info = "Tampered Info".to_string();
+        }
 
         // Try to verify the tampered certificate
-        let result = ghost_key_certificate.verify(&Some(master_verifying_key));
+        let result = ghost_key_certificate.verify(&TrustStore::from(master_verifying_key));
         assert!(result.is_err());
         assert!(matches!(
-            result.unwrap_err().as_ref(),
-            SignatureVerificationError(_)
+            result.unwrap_err(),
+            SignatureVerificationError(SignatureKind::Delegate, _)
         ));
     }
 
@@ -162,19 +779,287 @@ mod tests {
 
         // Create a ghostkey certificate
         let (mut ghost_key_certificate, _ghost_key_signing_key) =
-            GhostkeyCertificateV1::new(&delegate_certificate, &delegate_signing_key);
+            GhostkeyCertificateV1::new(&delegate_certificate, &delegate_signing_key).unwrap();
 
         // Tamper with the ghostkey verifying key
         let (_, tampered_verifying_key) = create_keypair(&mut OsRng).unwrap();
         ghost_key_certificate.verifying_key = VerifyingKey::from(tampered_verifying_key);
 
         // Try to verify the tampered certificate
-        let result = ghost_key_certificate.verify(&Some(master_verifying_key));
+        let result = ghost_key_certificate.verify(&TrustStore::from(master_verifying_key));
         assert!(result.is_err());
         assert!(matches!(
-            result.unwrap_err().as_ref(),
-            SignatureVerificationError(_)
+            result.unwrap_err(),
+            SignatureVerificationError(SignatureKind::GhostKey, _)
         ));
     }
-}
 
+    #[test]
+    fn test_ghost_key_certificate_expired_delegate() {
+        // Create a master key pair
+        let (master_signing_key, master_verifying_key) = create_keypair(&mut OsRng).unwrap();
+
+        // Create a delegate certificate that is only valid for a limited period
+        let info = "Test Delegate".to_string();
+        let (delegate_certificate, delegate_signing_key) =
+            DelegateCertificateV1::new_with_validity(&master_signing_key, &info, 1000, 2000)
+                .unwrap();
+
+        // Create a ghostkey certificate
+        let (ghost_key_certificate, _ghost_key_signing_key) =
+            GhostkeyCertificateV1::new(&delegate_certificate, &delegate_signing_key).unwrap();
+
+        // Valid while the delegate is valid
+        let verified_info = ghost_key_certificate
+            .verify_at(1500, &TrustStore::from(master_verifying_key))
+            .unwrap();
+        assert_eq!(verified_info.info, info);
+
+        // Rejected once the delegate has expired
+        let result = ghost_key_certificate.verify_at(2001, &TrustStore::from(master_verifying_key));
+        assert!(matches!(
+            result.unwrap_err(),
+            ValidityPeriodError(_)
+        ));
+
+        // Rejected before the delegate becomes valid
+        let result = ghost_key_certificate.verify_at(999, &TrustStore::from(master_verifying_key));
+        assert!(matches!(
+            result.unwrap_err(),
+            ValidityPeriodError(_)
+        ));
+    }
+
+    #[test]
+    fn test_ghost_key_certificate_versioned_decode() {
+        let (master_signing_key, master_verifying_key) = create_keypair(&mut OsRng).unwrap();
+
+        let info = "Test Delegate".to_string();
+        let (delegate_certificate, delegate_signing_key) =
+            DelegateCertificateV1::new(&master_signing_key, &info).unwrap();
+        let (ghost_key_certificate, _) =
+            GhostkeyCertificateV1::new(&delegate_certificate, &delegate_signing_key).unwrap();
+
+        let armored = ghost_key_certificate.to_armored_string().unwrap();
+        let decoded = GhostkeyCertificate::from_armored_string(&armored).unwrap();
+        assert_eq!(decoded.version(), 1);
+        assert_eq!(decoded.verifying_key(), &ghost_key_certificate.verifying_key);
+        assert_eq!(
+            decoded.verify(&TrustStore::from(master_verifying_key)).unwrap().info,
+            info
+        );
+
+        // Unversioned labels and raw bytes are decoded by shape
+        let unversioned = armored.replace("GHOSTKEY_CERTIFICATE_V1", "GHOSTKEY_CERTIFICATE");
+        assert!(GhostkeyCertificate::from_armored_string(&unversioned).is_ok());
+        let decoded = GhostkeyCertificate::from_base64(&ghost_key_certificate.to_base64().unwrap()).unwrap();
+        assert_eq!(decoded.to_armored_string().unwrap(), armored);
+    }
+
+    #[test]
+    fn test_ghost_key_bundle() {
+        let (master_signing_key, _) = create_keypair(&mut OsRng).unwrap();
+
+        let info = "Test Delegate".to_string();
+        let (delegate_certificate, delegate_signing_key) =
+            DelegateCertificateV1::new(&master_signing_key, &info).unwrap();
+        let (ghost_key_certificate, ghost_key_signing_key) =
+            GhostkeyCertificateV1::new(&delegate_certificate, &delegate_signing_key).unwrap();
+
+        let mut bundle = ArmoredBundle::new();
+        bundle.add(&ghost_key_certificate).unwrap();
+        bundle.add(&ghost_key_signing_key).unwrap();
+        let armored = bundle.to_armored_string().unwrap();
+
+        let bundle = ArmoredBundle::from_armored_string(&armored).unwrap();
+        assert_eq!(bundle.labels(), vec!["GHOSTKEY_CERTIFICATE_V1", "SIGNING_KEY_V1"]);
+        let decoded = GhostkeyCertificate::from_bundle(&bundle).unwrap();
+        let signing_key: SigningKey = bundle.get().unwrap();
+        assert_eq!(decoded.verifying_key(), &signing_key.verifying_key());
+    }
+
+    #[test]
+    fn test_ghost_key_certificate_v2() {
+        let (master_signing_key, master_verifying_key) = create_keypair(&mut OsRng).unwrap();
+        let trust_store = TrustStore::from(master_verifying_key);
+
+        let info = "Test Delegate".to_string();
+        let (delegate_certificate, delegate_signing_key) =
+            DelegateCertificateV2::new(&master_signing_key, &info).unwrap();
+        let (ghost_key_certificate, ghost_key_signing_key) = GhostkeyCertificate::new(
+            &DelegateCertificate::V2(delegate_certificate),
+            &delegate_signing_key,
+        ).unwrap();
+        assert_eq!(ghost_key_certificate.version(), 2);
+        assert_eq!(
+            ghost_key_certificate.verifying_key(),
+            &ghost_key_signing_key.verifying_key()
+        );
+        assert_eq!(ghost_key_certificate.verify(&trust_store).unwrap().info, info);
+
+        // Unversioned armor is recognised as V2 by its shape
+        let armored = ghost_key_certificate
+            .to_armored_string()
+            .unwrap()
+            .replace("GHOSTKEY_CERTIFICATE_V2", "GHOSTKEY_CERTIFICATE");
+        let decoded = GhostkeyCertificate::from_armored_string(&armored).unwrap();
+        assert_eq!(decoded.version(), 2);
+        assert!(decoded.verify(&trust_store).is_ok());
+    }
+
+    #[test]
+    fn test_ghost_key_certificate_upgrade() {
+        let (master_signing_key, master_verifying_key) = create_keypair(&mut OsRng).unwrap();
+        let trust_store = TrustStore::from(master_verifying_key);
+
+        let (delegate_certificate, delegate_signing_key) =
+            DelegateCertificateV1::new(&master_signing_key, "Test Delegate").unwrap();
+        let delegate_certificate = DelegateCertificate::V1(delegate_certificate);
+        let (ghost_key_certificate, ghost_key_signing_key) =
+            GhostkeyCertificate::new(&delegate_certificate, &delegate_signing_key).unwrap();
+        assert_eq!(ghost_key_certificate.version(), 1);
+
+        // The delegate certificate has to be upgraded first
+        assert!(matches!(
+            ghost_key_certificate.clone().upgrade(&delegate_certificate, &delegate_signing_key),
+            Err(GhostkeyError::InvalidInput(_))
+        ));
+
+        let upgraded_delegate = delegate_certificate.upgrade(&master_signing_key).unwrap();
+        let upgraded = ghost_key_certificate
+            .clone()
+            .upgrade(&upgraded_delegate, &delegate_signing_key)
+            .unwrap();
+        assert_eq!(upgraded.version(), 2);
+        assert_eq!(upgraded.verifying_key(), &ghost_key_signing_key.verifying_key());
+        assert_eq!(upgraded.verify(&trust_store).unwrap().info, "Test Delegate");
+
+        // The upgraded certificate survives armoring
+        let decoded = GhostkeyCertificate::from_armored_string(&upgraded.to_armored_string().unwrap()).unwrap();
+        assert_eq!(decoded.version(), 2);
+        assert!(decoded.verify(&trust_store).is_ok());
+
+        // Current certificates are returned as is
+        let armored = upgraded.to_armored_string().unwrap();
+        let again = upgraded.upgrade(&upgraded_delegate, &delegate_signing_key).unwrap();
+        assert_eq!(again.to_armored_string().unwrap(), armored);
+
+        // Another delegate can't re-issue the ghost key
+        let (other_delegate, other_signing_key) =
+            DelegateCertificateV2::new(&master_signing_key, "Test Delegate").unwrap();
+        assert!(matches!(
+            ghost_key_certificate
+                .clone()
+                .upgrade(&DelegateCertificate::V2(other_delegate), &other_signing_key),
+            Err(GhostkeyError::DelegateMismatchError(_))
+        ));
+        assert!(matches!(
+            ghost_key_certificate.upgrade(&upgraded_delegate, &other_signing_key),
+            Err(GhostkeyError::DelegateMismatchError(_))
+        ));
+    }
+
+    #[test]
+    fn test_ghost_key_certificate_v2_rejects_v1_signature() {
+        let (master_signing_key, master_verifying_key) = create_keypair(&mut OsRng).unwrap();
+
+        let info = "Test Delegate".to_string();
+        let (delegate_certificate, delegate_signing_key) =
+            DelegateCertificateV2::new(&master_signing_key, &info).unwrap();
+        let (mut ghost_key_certificate, _) =
+            GhostkeyCertificateV2::new(&delegate_certificate, &delegate_signing_key).unwrap();
+
+        // A delegate signature over the raw CBOR encoding, as used by V1, is not accepted
+        let delegate_keypair = KeyPair::new(
+            delegate_signing_key.public_key().unwrap(),
+            delegate_signing_key.clone(),
+        );
+        ghost_key_certificate.signature = unblinded_rsa_sign(
+            &mut OsRng,
+            &delegate_keypair,
+            &Armorable::to_bytes(&ghost_key_certificate.verifying_key).unwrap(),
+        )
+        .unwrap()
+        .into();
+        let result = ghost_key_certificate.verify(&TrustStore::from(master_verifying_key));
+        assert!(matches!(
+            result.unwrap_err(),
+            SignatureVerificationError(SignatureKind::GhostKey, _)
+        ));
+    }
+
+    #[test]
+    fn test_ghost_key_certificate_v3() {
+        use crate::delegate_certificate::{test_partially_blind_delegate, DelegateDetails};
+
+        let (master_signing_key, master_verifying_key) = create_keypair(&mut OsRng).unwrap();
+        let trust_store = TrustStore::from(master_verifying_key);
+        let details = DelegateDetails {
+            amount: 0,
+            currency: "USD".to_string(),
+            tier: "Any".to_string(),
+            issuer: "Freenet".to_string(),
+            created: 1_730_000_000,
+        };
+        let (delegate_certificate, delegate_signing_key) =
+            test_partially_blind_delegate(&master_signing_key, &details);
+        let metadata = GhostkeyMetadata {
+            amount: 20,
+            currency: "USD".to_string(),
+            issued: "2024-10".to_string(),
+            campaign: None,
+        };
+
+        let (ghost_key_certificate, _) =
+            GhostkeyCertificateV3::new(&delegate_certificate, &delegate_signing_key, &metadata)
+                .unwrap();
+        assert_eq!(ghost_key_certificate.verify(&trust_store).unwrap().details, Some(details));
+
+        // The version is recognised by shape, even though V2 would ignore the metadata field
+        let armored = ghost_key_certificate
+            .to_armored_string()
+            .unwrap()
+            .replace("GHOSTKEY_CERTIFICATE_V3", "GHOSTKEY_CERTIFICATE");
+        let decoded = GhostkeyCertificate::from_armored_string(&armored).unwrap();
+        assert_eq!(decoded.version(), 3);
+        assert_eq!(decoded.metadata(), Some(&metadata));
+        assert!(decoded.verify(&trust_store).is_ok());
+
+        // The signature covers the metadata
+        let mut tampered = ghost_key_certificate.clone();
+        tampered.metadata.amount = 1000;
+        assert!(matches!(
+            tampered.verify(&trust_store).unwrap_err(),
+            SignatureVerificationError(SignatureKind::GhostKey, _)
+        ));
+    }
+
+    #[test]
+    fn test_new_with_rng_is_deterministic() {
+        use crate::delegate_certificate::DEFAULT_DELEGATE_KEY_BITS;
+        use rand_chacha::rand_core::SeedableRng;
+        use rand_chacha::ChaCha20Rng;
+
+        let issue = |seed: [u8; 32]| {
+            let mut rng = ChaCha20Rng::from_seed(seed);
+            let (master_signing_key, master_verifying_key) = create_keypair(&mut rng).unwrap();
+            let (delegate_certificate, delegate_signing_key) = DelegateCertificateV2::new_with_rng(
+                &mut rng,
+                DEFAULT_DELEGATE_KEY_BITS,
+                &master_signing_key,
+                "Test Delegate",
+                None,
+                None,
+            )
+            .unwrap();
+            let (ghost_key_certificate, _) =
+                GhostkeyCertificateV2::new_with_rng(&mut rng, &delegate_certificate, &delegate_signing_key)
+                    .unwrap();
+            assert!(ghost_key_certificate.verify(&TrustStore::from(master_verifying_key)).is_ok());
+            ghost_key_certificate.to_armored_string().unwrap()
+        };
+
+        // The same seed gives the same certificate, so test vectors can be generated
+        assert_eq!(issue([7u8; 32]), issue([7u8; 32]));
+    }
+}
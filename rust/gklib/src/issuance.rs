@@ -0,0 +1,536 @@
+use super::delegate_certificate::DelegateCertificate;
+use super::delegate_key::PublicKey as RSAVerifyingKey;
+use super::errors::GhostkeyError;
+use super::fingerprint::{Fingerprint, Fingerprinted};
+use super::delegate_certificate::DelegateCertificateV2;
+use super::ghost_key_certificate::{
+    GhostkeyCertificate, GhostkeyCertificateV3, GhostkeyMetadata, GHOSTKEY_CERTIFICATE_V3_CONTEXT,
+};
+use super::partially_blind;
+use super::util::create_keypair;
+use alloc::format;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use blind_rsa_signatures::{
+    BlindSignature, BlindedMessage, Options, PublicKey as BlindRSAPublicKey, Secret,
+    SecretKey as RSASigningKey, Signature as RSASignature,
+};
+use ed25519_dalek::{SigningKey, VerifyingKey};
+use rand_core::{CryptoRng, OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+
+/// A request to a [`BlindIssuer`] to sign a blinded ghost key, created by the client with
+/// [`BlindingState::new`]. Encode it with [`Armorable`](crate::armorable::Armorable) to send it.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum IssuanceRequest {
+    V1(IssuanceRequestV1),
+    V2(IssuanceRequestV2),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct IssuanceRequestV1 {
+    /// Fingerprint of the delegate certificate the ghost key was blinded for
+    pub delegate: Fingerprint,
+    pub blinded_message: BlindedMessage,
+}
+
+/// A request for a [`GhostkeyCertificateV3`], whose signature binds public metadata, created
+/// with [`BlindingState::new_with_metadata`].
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct IssuanceRequestV2 {
+    /// Fingerprint of the delegate certificate the ghost key was blinded for
+    pub delegate: Fingerprint,
+    /// Metadata the ghost key was blinded with, the issuer signs it as is
+    pub metadata: GhostkeyMetadata,
+    pub blinded_message: BlindedMessage,
+}
+
+impl IssuanceRequest {
+    pub fn version(&self) -> u32 {
+        match self {
+            IssuanceRequest::V1(_) => 1,
+            IssuanceRequest::V2(_) => 2,
+        }
+    }
+
+    pub fn delegate(&self) -> &Fingerprint {
+        match self {
+            IssuanceRequest::V1(request) => &request.delegate,
+            IssuanceRequest::V2(request) => &request.delegate,
+        }
+    }
+
+    /// The metadata to bind into the ghost key, `None` for V1 requests.
+    pub fn metadata(&self) -> Option<&GhostkeyMetadata> {
+        match self {
+            IssuanceRequest::V1(_) => None,
+            IssuanceRequest::V2(request) => Some(&request.metadata),
+        }
+    }
+
+    pub fn blinded_message(&self) -> &BlindedMessage {
+        match self {
+            IssuanceRequest::V1(request) => &request.blinded_message,
+            IssuanceRequest::V2(request) => &request.blinded_message,
+        }
+    }
+}
+
+/// The blind signature a [`BlindIssuer`] returns for an [`IssuanceRequest`], turned into a
+/// ghost key certificate by [`BlindingState::finalize`].
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum IssuanceResponse {
+    V1(IssuanceResponseV1),
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct IssuanceResponseV1 {
+    pub blind_signature: BlindSignature,
+}
+
+impl IssuanceResponse {
+    /// Creates a response of the current version.
+    pub fn new(blind_signature: BlindSignature) -> Self {
+        IssuanceResponse::V1(IssuanceResponseV1 { blind_signature })
+    }
+
+    pub fn version(&self) -> u32 {
+        match self {
+            IssuanceResponse::V1(_) => 1,
+        }
+    }
+
+    pub fn blind_signature(&self) -> &BlindSignature {
+        match self {
+            IssuanceResponse::V1(response) => &response.blind_signature,
+        }
+    }
+}
+
+/// Client side of blind issuance, kept between sending the [`IssuanceRequest`] and receiving
+/// the [`IssuanceResponse`]. Holds the new ghost signing key and the blinding secret, neither of
+/// which may be shown to the issuer.
+pub struct BlindingState {
+    delegate_certificate: DelegateCertificate,
+    signing_key: SigningKey,
+    secret: Secret,
+    metadata: Option<GhostkeyMetadata>,
+}
+
+impl BlindingState {
+    /// Generates a ghost key and blinds it for the delegate, returning the state to keep and the
+    /// request to send to the issuer.
+    pub fn new(
+        delegate_certificate: DelegateCertificate,
+    ) -> Result<(Self, IssuanceRequest), GhostkeyError> {
+        Self::new_with_rng(&mut OsRng, delegate_certificate)
+    }
+
+    /// Like [`new`](Self::new), drawing the ghost key and the blinding factor from `rng`.
+    pub fn new_with_rng<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        delegate_certificate: DelegateCertificate,
+    ) -> Result<(Self, IssuanceRequest), GhostkeyError> {
+        Self::create(rng, delegate_certificate, None)
+    }
+
+    /// Like [`new`](Self::new), but blinds the ghost key for a [`GhostkeyCertificateV3`] whose
+    /// delegate signature binds `metadata`. The delegate certificate must be a V2 certificate
+    /// whose key was generated for [`partially_blind`] signing.
+    pub fn new_with_metadata(
+        delegate_certificate: DelegateCertificate,
+        metadata: GhostkeyMetadata,
+    ) -> Result<(Self, IssuanceRequest), GhostkeyError> {
+        Self::new_with_metadata_and_rng(&mut OsRng, delegate_certificate, metadata)
+    }
+
+    /// Like [`new_with_metadata`](Self::new_with_metadata), drawing the ghost key and the
+    /// blinding factor from `rng`.
+    pub fn new_with_metadata_and_rng<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        delegate_certificate: DelegateCertificate,
+        metadata: GhostkeyMetadata,
+    ) -> Result<(Self, IssuanceRequest), GhostkeyError> {
+        Self::create(rng, delegate_certificate, Some(metadata))
+    }
+
+    fn create<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        delegate_certificate: DelegateCertificate,
+        metadata: Option<GhostkeyMetadata>,
+    ) -> Result<(Self, IssuanceRequest), GhostkeyError> {
+        let (signing_key, verifying_key) = create_keypair(rng)?;
+        let (public_key, message) =
+            blinding_key_and_message(&delegate_certificate, metadata.as_ref(), &verifying_key)?;
+        let (blinded_message, secret) = blind(rng, &public_key, &message)?;
+
+        let delegate = delegate_certificate.fingerprint()?;
+        let request = match &metadata {
+            None => IssuanceRequest::V1(IssuanceRequestV1 {
+                delegate,
+                blinded_message,
+            }),
+            Some(metadata) => IssuanceRequest::V2(IssuanceRequestV2 {
+                delegate,
+                metadata: metadata.clone(),
+                blinded_message,
+            }),
+        };
+        let state = BlindingState {
+            delegate_certificate,
+            signing_key,
+            secret,
+            metadata,
+        };
+        Ok((state, request))
+    }
+
+    /// Reassembles the state from parts a client stored separately, such as a web page that
+    /// keeps them in JavaScript between calls. `metadata` is the metadata the ghost key was
+    /// blinded with, if any.
+    pub fn from_parts(
+        delegate_certificate: DelegateCertificate,
+        signing_key: SigningKey,
+        secret: Secret,
+        metadata: Option<GhostkeyMetadata>,
+    ) -> Self {
+        BlindingState {
+            delegate_certificate,
+            signing_key,
+            secret,
+            metadata,
+        }
+    }
+
+    pub fn delegate_certificate(&self) -> &DelegateCertificate {
+        &self.delegate_certificate
+    }
+
+    pub fn signing_key(&self) -> &SigningKey {
+        &self.signing_key
+    }
+
+    pub fn secret(&self) -> &Secret {
+        &self.secret
+    }
+
+    pub fn metadata(&self) -> Option<&GhostkeyMetadata> {
+        self.metadata.as_ref()
+    }
+
+    /// Unblinds the issuer's signature and assembles the ghost key certificate, failing if the
+    /// signature does not verify under the delegate key.
+    pub fn finalize(
+        self,
+        response: &IssuanceResponse,
+    ) -> Result<(GhostkeyCertificate, SigningKey), GhostkeyError> {
+        let verifying_key = self.signing_key.verifying_key();
+        let (public_key, message) = blinding_key_and_message(
+            &self.delegate_certificate,
+            self.metadata.as_ref(),
+            &verifying_key,
+        )?;
+        let signature = finalize(&public_key, response.blind_signature(), &self.secret, &message)?;
+
+        let certificate = match self.metadata {
+            None => GhostkeyCertificate::from_parts(
+                self.delegate_certificate,
+                verifying_key,
+                signature.into(),
+            ),
+            Some(metadata) => GhostkeyCertificate::V3(GhostkeyCertificateV3 {
+                delegate: metadata_delegate(&self.delegate_certificate)?.clone(),
+                verifying_key,
+                metadata,
+                context: GHOSTKEY_CERTIFICATE_V3_CONTEXT.to_string(),
+                signature: signature.into(),
+            }),
+        };
+        Ok((certificate, self.signing_key))
+    }
+}
+
+/// Server side of blind issuance, signs blinded ghost keys with a delegate signing key without
+/// learning the ghost keys.
+pub struct BlindIssuer {
+    delegate_certificate: DelegateCertificate,
+    delegate_signing_key: RSASigningKey,
+    delegate_fingerprint: Fingerprint,
+}
+
+impl BlindIssuer {
+    /// Fails with [`GhostkeyError::DelegateMismatchError`] if the signing key is not the one
+    /// certified by the delegate certificate.
+    pub fn new(
+        delegate_certificate: DelegateCertificate,
+        delegate_signing_key: RSASigningKey,
+    ) -> Result<Self, GhostkeyError> {
+        let delegate_verifying_key = delegate_signing_key.public_key().map_err(|e| {
+            GhostkeyError::RSAError("Failed to derive delegate verifying key".to_string(), e.into())
+        })?;
+        if RSAVerifyingKey::from(delegate_verifying_key)
+            != *delegate_certificate.payload().delegate_verifying_key()
+        {
+            return Err(GhostkeyError::DelegateMismatchError(
+                "Delegate signing key does not match delegate certificate".to_string(),
+            ));
+        }
+
+        Ok(BlindIssuer {
+            delegate_fingerprint: delegate_certificate.fingerprint()?,
+            delegate_certificate,
+            delegate_signing_key,
+        })
+    }
+
+    pub fn delegate_certificate(&self) -> &DelegateCertificate {
+        &self.delegate_certificate
+    }
+
+    pub fn delegate_fingerprint(&self) -> &Fingerprint {
+        &self.delegate_fingerprint
+    }
+
+    /// Signs the blinded ghost key in `request`, rejecting requests blinded for another
+    /// delegate certificate, whose signatures the client could not finalize. The metadata of a
+    /// V2 request is signed as given, so check that it is what the client is entitled to first.
+    pub fn sign(&self, request: &IssuanceRequest) -> Result<IssuanceResponse, GhostkeyError> {
+        self.sign_with_rng(&mut OsRng, request)
+    }
+
+    /// Like [`sign`](Self::sign), drawing the signature randomness from `rng`.
+    pub fn sign_with_rng<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        request: &IssuanceRequest,
+    ) -> Result<IssuanceResponse, GhostkeyError> {
+        if *request.delegate() != self.delegate_fingerprint {
+            return Err(GhostkeyError::DelegateMismatchError(format!(
+                "Ghost key was blinded for delegate certificate {}, this issuer signs for {}",
+                request.delegate(),
+                self.delegate_fingerprint
+            )));
+        }
+
+        let blind_signature = match request.metadata() {
+            None => self.sign_blinded(rng, request.blinded_message())?,
+            Some(metadata) => {
+                self.sign_blinded_with_metadata(rng, metadata, request.blinded_message())?
+            }
+        };
+        Ok(IssuanceResponse::new(blind_signature))
+    }
+
+    /// Signs a bare blinded message, for clients that predate [`IssuanceRequest`] and do not say
+    /// which delegate certificate they blinded for.
+    pub fn sign_blinded<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        blinded_message: &BlindedMessage,
+    ) -> Result<BlindSignature, GhostkeyError> {
+        blind_sign(rng, &self.delegate_signing_key, blinded_message)
+    }
+
+    /// Signs a bare blinded message with the key derived for `metadata`, see
+    /// [`partially_blind`].
+    pub fn sign_blinded_with_metadata<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        metadata: &GhostkeyMetadata,
+        blinded_message: &BlindedMessage,
+    ) -> Result<BlindSignature, GhostkeyError> {
+        metadata_delegate(&self.delegate_certificate)?;
+        let key_pair =
+            partially_blind::derive_key_pair(&self.delegate_signing_key, &metadata.to_bytes()?)?;
+        blind_sign(rng, &key_pair.sk, blinded_message)
+    }
+}
+
+/// The key to blind a ghost key for and the message to blind, which for a ghost key with
+/// metadata are derived from the delegate key and message as [`partially_blind`] describes.
+fn blinding_key_and_message(
+    delegate_certificate: &DelegateCertificate,
+    metadata: Option<&GhostkeyMetadata>,
+    verifying_key: &VerifyingKey,
+) -> Result<(BlindRSAPublicKey, Vec<u8>), GhostkeyError> {
+    let delegate_verifying_key = delegate_certificate.payload().delegate_verifying_key();
+    match metadata {
+        None => Ok((
+            delegate_verifying_key.to_blind_rsa(),
+            delegate_certificate.ghost_key_message(verifying_key)?,
+        )),
+        Some(metadata) => {
+            metadata_delegate(delegate_certificate)?;
+            let metadata = metadata.to_bytes()?;
+            Ok((
+                partially_blind::derive_public_key(delegate_verifying_key, &metadata)?.to_blind_rsa(),
+                partially_blind::metadata_message(
+                    &metadata,
+                    &GhostkeyCertificateV3::message(verifying_key)?,
+                ),
+            ))
+        }
+    }
+}
+
+/// The delegate certificate of a ghost key with metadata, which must be a V2 certificate.
+fn metadata_delegate(
+    delegate_certificate: &DelegateCertificate,
+) -> Result<&DelegateCertificateV2, GhostkeyError> {
+    match delegate_certificate {
+        DelegateCertificate::V2(delegate) => Ok(delegate),
+        DelegateCertificate::V1(_) => Err(GhostkeyError::InvalidInput(
+            "Ghost keys with metadata need a V2 delegate certificate".to_string(),
+        )),
+    }
+}
+
+/// Blinds `message` for the holder of `public_key`, returning the blinded message and the secret
+/// needed to finalize its signature. Messages are not randomized, as ghost key messages are
+/// unique by construction.
+pub(crate) fn blind<R: RngCore + CryptoRng>(
+    rng: &mut R,
+    public_key: &BlindRSAPublicKey,
+    message: &[u8],
+) -> Result<(BlindedMessage, Secret), GhostkeyError> {
+    let blinding_result = public_key
+        .blind(rng, message, false, &Options::default())
+        .map_err(|e| GhostkeyError::RSAError("Failed to blind message".to_string(), e.into()))?;
+    Ok((blinding_result.blind_msg, blinding_result.secret))
+}
+
+pub(crate) fn blind_sign<R: RngCore + CryptoRng>(
+    rng: &mut R,
+    signing_key: &RSASigningKey,
+    blinded_message: &BlindedMessage,
+) -> Result<BlindSignature, GhostkeyError> {
+    signing_key
+        .blind_sign(rng, blinded_message, &Options::default())
+        .map_err(|e| GhostkeyError::RSAError("Failed to sign blinded message".to_string(), e.into()))
+}
+
+/// Unblinds `blind_signature` into a signature over `message`, which fails unless it verifies
+/// under `public_key`.
+pub(crate) fn finalize(
+    public_key: &BlindRSAPublicKey,
+    blind_signature: &BlindSignature,
+    secret: &Secret,
+    message: &[u8],
+) -> Result<RSASignature, GhostkeyError> {
+    public_key
+        .finalize(blind_signature, secret, None, message, &Options::default())
+        .map_err(|e| GhostkeyError::RSAError("Failed to finalize blind signature".to_string(), e.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::armorable::Armorable;
+    use crate::delegate_certificate::{DelegateCertificateV1, DelegateCertificateV2};
+    use crate::trust_store::TrustStore;
+
+    #[test]
+    fn test_issuance_round_trip() {
+        let (master_signing_key, master_verifying_key) = create_keypair(&mut OsRng).unwrap();
+        let trust_store = TrustStore::from(master_verifying_key);
+        let info = "Test Delegate".to_string();
+
+        let (delegate_v1, delegate_v1_signing_key) =
+            DelegateCertificateV1::new(&master_signing_key, &info).unwrap();
+        let (delegate_v2, delegate_v2_signing_key) =
+            DelegateCertificateV2::new(&master_signing_key, &info).unwrap();
+
+        for (delegate_certificate, delegate_signing_key, version) in [
+            (DelegateCertificate::V1(delegate_v1), delegate_v1_signing_key, 1),
+            (DelegateCertificate::V2(delegate_v2), delegate_v2_signing_key, 2),
+        ] {
+            let issuer = BlindIssuer::new(delegate_certificate.clone(), delegate_signing_key).unwrap();
+            let (state, request) = BlindingState::new(delegate_certificate).unwrap();
+
+            // Requests and responses travel as CBOR
+            let request = IssuanceRequest::from_base64(&request.to_base64().unwrap()).unwrap();
+            assert_eq!(request.version(), 1);
+            let response = issuer.sign(&request).unwrap();
+            let response = IssuanceResponse::from_base64(&response.to_base64().unwrap()).unwrap();
+
+            let expected_verifying_key = state.signing_key().verifying_key();
+            let (ghost_key_certificate, signing_key) = state.finalize(&response).unwrap();
+            assert_eq!(ghost_key_certificate.version(), version);
+            assert_eq!(signing_key.verifying_key(), expected_verifying_key);
+            assert_eq!(ghost_key_certificate.verify(&trust_store).unwrap().info, info);
+        }
+    }
+
+    #[test]
+    fn test_issuance_with_metadata() {
+        use crate::delegate_certificate::{test_partially_blind_delegate, DelegateDetails};
+
+        let (master_signing_key, master_verifying_key) = create_keypair(&mut OsRng).unwrap();
+        let trust_store = TrustStore::from(master_verifying_key);
+        let details = DelegateDetails {
+            amount: 0,
+            currency: "USD".to_string(),
+            tier: "Any".to_string(),
+            issuer: "Freenet".to_string(),
+            created: 1_730_000_000,
+        };
+        let (delegate, delegate_signing_key) =
+            test_partially_blind_delegate(&master_signing_key, &details);
+        let delegate = DelegateCertificate::V2(delegate);
+        let metadata = GhostkeyMetadata {
+            amount: 50,
+            currency: "USD".to_string(),
+            issued: "2024-10".to_string(),
+            campaign: Some("Winter".to_string()),
+        };
+
+        // One delegate issues ghost keys for any metadata
+        let issuer = BlindIssuer::new(delegate.clone(), delegate_signing_key).unwrap();
+        let (state, request) =
+            BlindingState::new_with_metadata(delegate.clone(), metadata.clone()).unwrap();
+        let request = IssuanceRequest::from_base64(&request.to_base64().unwrap()).unwrap();
+        assert_eq!(request.version(), 2);
+        assert_eq!(request.metadata(), Some(&metadata));
+        let response = issuer.sign(&request).unwrap();
+
+        let (ghost_key_certificate, _) = state.finalize(&response).unwrap();
+        assert_eq!(ghost_key_certificate.version(), 3);
+        assert_eq!(ghost_key_certificate.metadata(), Some(&metadata));
+        assert!(ghost_key_certificate.verify(&trust_store).is_ok());
+
+        // A signature issued for other metadata does not finalize
+        let (state, request) = BlindingState::new_with_metadata(delegate, metadata.clone()).unwrap();
+        let other_metadata = GhostkeyMetadata {
+            amount: 5,
+            ..metadata
+        };
+        let response = IssuanceResponse::new(
+            issuer
+                .sign_blinded_with_metadata(&mut OsRng, &other_metadata, request.blinded_message())
+                .unwrap(),
+        );
+        assert_eq!(state.finalize(&response).err().unwrap().code(), "GK_RSA_FAILED");
+    }
+
+    #[test]
+    fn test_issuer_rejects_other_delegates() {
+        let (master_signing_key, _) = create_keypair(&mut OsRng).unwrap();
+        let info = "Test Delegate".to_string();
+        let (delegate, delegate_signing_key) =
+            DelegateCertificateV2::new(&master_signing_key, &info).unwrap();
+        let (other_delegate, other_delegate_signing_key) =
+            DelegateCertificateV2::new(&master_signing_key, &info).unwrap();
+        let delegate = DelegateCertificate::V2(delegate);
+        let other_delegate = DelegateCertificate::V2(other_delegate);
+
+        // A signing key belonging to another delegate certificate
+        let error = BlindIssuer::new(delegate.clone(), other_delegate_signing_key).err().unwrap();
+        assert_eq!(error.code(), "GK_DELEGATE_MISMATCH");
+
+        // A ghost key blinded for another delegate certificate
+        let issuer = BlindIssuer::new(delegate, delegate_signing_key).unwrap();
+        let (_, request) = BlindingState::new(other_delegate).unwrap();
+        let error = issuer.sign(&request).unwrap_err();
+        assert_eq!(error.code(), "GK_DELEGATE_MISMATCH");
+    }
+}
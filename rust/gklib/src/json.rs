@@ -0,0 +1,689 @@
+//! JSON representation of keys, certificates and signed messages, for services that don't speak
+//! CBOR. Conversion is lossless: decoding the JSON of an object gives back the same CBOR, so its
+//! signatures and fingerprint are unchanged.
+//!
+//! Keys are JSON Web Keys (RFC 7517). Ed25519 keys are `OKP` keys (RFC 8037), the ghost, master
+//! and subkey verifying keys as `{"kty": "OKP", "crv": "Ed25519", "x": ...}` and signing keys
+//! with the private key in `d` as well. Delegate verifying keys are `RSA` keys with the modulus
+//! in `n` and the exponent in `e`. As in JWK, binary values, including signatures and messages,
+//! are unpadded URL-safe base64.
+//!
+//! Certificates and signed messages are objects whose `type` is `delegate_certificate`,
+//! `ghost_key_certificate`, `subkey_certificate` or `signed_message`, with the certificate or
+//! message `version` and the fields of that version under the same names as in CBOR:
+//!
+//! ```json
+//! {
+//!   "type": "ghost_key_certificate",
+//!   "version": 2,
+//!   "delegate": {
+//!     "type": "delegate_certificate",
+//!     "version": 2,
+//!     "payload": {
+//!       "delegate_verifying_key": { "kty": "RSA", "n": "u3Bf...", "e": "AQAB" },
+//!       "info": "Test Delegate"
+//!     },
+//!     "context": "freenet.org ghostkey 2024-10 delegate certificate v2",
+//!     "signature": "Vq1G..."
+//!   },
+//!   "verifying_key": { "kty": "OKP", "crv": "Ed25519", "x": "11qY..." },
+//!   "context": "freenet.org ghostkey 2024-10 ghost key certificate v2",
+//!   "signature": "KJ3c..."
+//! }
+//! ```
+//!
+//! A delegate payload has either `info` or typed `details`, a V3 ghost key certificate also has
+//! `metadata`, and a signed message has the ghost key `certificate`, or for V3 the `subkey`
+//! certificate, and the fields of its version.
+
+use super::delegate_certificate::{
+    DelegateCertificate, DelegateCertificateV1, DelegateCertificateV2, DelegateDetails,
+    DelegatePayload, DelegatePayloadV1, DelegatePayloadV2,
+};
+use super::delegate_key::{PublicKey as RSAVerifyingKey, Signature as RSASignature};
+use super::errors::GhostkeyError;
+use super::errors::GhostkeyError::{DecodingError, SerializationError};
+use super::ghost_key_certificate::{
+    GhostkeyCertificate, GhostkeyCertificateV1, GhostkeyCertificateV2, GhostkeyCertificateV3,
+    GhostkeyMetadata,
+};
+use super::signed_message::{
+    SignedMessage, SignedMessageCertificate, SignedMessageV1, SignedMessageV2, SignedMessageV3,
+};
+use super::subkey::{SubkeyCertificateV1, SubkeyPayload};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use ed25519_dalek::{Signature, SigningKey, VerifyingKey};
+use rsa::{BigUint, PublicKeyParts, RsaPublicKey};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+/// Types with a JSON representation, see the [module documentation](self).
+pub trait JsonEncodable: Sized {
+    fn to_json(&self) -> Result<String, GhostkeyError>;
+    fn from_json(json: &str) -> Result<Self, GhostkeyError>;
+}
+
+/// Implements [`JsonEncodable`] through the serde type mirroring the JSON representation.
+macro_rules! json_encodable {
+    ($type:ty, $json:ty) => {
+        impl JsonEncodable for $type {
+            fn to_json(&self) -> Result<String, GhostkeyError> {
+                encode(&<$json>::try_from(self)?)
+            }
+
+            fn from_json(json: &str) -> Result<Self, GhostkeyError> {
+                decode::<$json>(json)?.try_into()
+            }
+        }
+    };
+}
+
+json_encodable!(VerifyingKey, OkpJwk);
+json_encodable!(SigningKey, OkpJwk);
+json_encodable!(RSAVerifyingKey, RsaJwk);
+json_encodable!(DelegateCertificate, DelegateCertificateJson);
+json_encodable!(GhostkeyCertificate, GhostkeyCertificateJson);
+json_encodable!(SubkeyCertificateV1, SubkeyCertificateJson);
+json_encodable!(SignedMessage, SignedMessageJson);
+
+impl JsonEncodable for DelegateCertificateV1 {
+    fn to_json(&self) -> Result<String, GhostkeyError> {
+        DelegateCertificate::V1(self.clone()).to_json()
+    }
+
+    fn from_json(json: &str) -> Result<Self, GhostkeyError> {
+        match DelegateCertificate::from_json(json)? {
+            DelegateCertificate::V1(certificate) => Ok(certificate),
+            certificate => Err(version_mismatch("delegate certificate", 1, certificate.version())),
+        }
+    }
+}
+
+impl JsonEncodable for GhostkeyCertificateV1 {
+    fn to_json(&self) -> Result<String, GhostkeyError> {
+        GhostkeyCertificate::V1(self.clone()).to_json()
+    }
+
+    fn from_json(json: &str) -> Result<Self, GhostkeyError> {
+        match GhostkeyCertificate::from_json(json)? {
+            GhostkeyCertificate::V1(certificate) => Ok(certificate),
+            certificate => Err(version_mismatch("ghost key certificate", 1, certificate.version())),
+        }
+    }
+}
+
+fn encode<T: Serialize>(value: &T) -> Result<String, GhostkeyError> {
+    serde_json::to_string_pretty(value)
+        .map_err(|e| SerializationError("Failed to encode JSON".to_string(), e.into()))
+}
+
+fn decode<T: DeserializeOwned>(json: &str) -> Result<T, GhostkeyError> {
+    serde_json::from_str(json).map_err(|e| DecodingError(format!("Invalid JSON: {}", e)))
+}
+
+fn version_mismatch(kind: &str, expected: u32, version: u32) -> GhostkeyError {
+    DecodingError(format!("Expected a V{} {}, got V{}", expected, kind, version))
+}
+
+fn to_base64(bytes: &[u8]) -> String {
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+fn from_base64(field: &str, encoded: &str) -> Result<Vec<u8>, GhostkeyError> {
+    URL_SAFE_NO_PAD
+        .decode(encoded)
+        .map_err(|e| DecodingError(format!("Invalid base64 in {}: {}", field, e)))
+}
+
+fn from_base64_array<const N: usize>(field: &str, encoded: &str) -> Result<[u8; N], GhostkeyError> {
+    from_base64(field, encoded)?
+        .try_into()
+        .map_err(|_| DecodingError(format!("{} must be {} bytes", field, N)))
+}
+
+fn check_type(field: &str, value: &str, expected: &str) -> Result<(), GhostkeyError> {
+    if value != expected {
+        return Err(DecodingError(format!("Expected {} \"{}\", got \"{}\"", field, expected, value)));
+    }
+    Ok(())
+}
+
+/// An Ed25519 key as an `OKP` JSON Web Key.
+#[derive(Serialize, Deserialize)]
+struct OkpJwk {
+    kty: String,
+    crv: String,
+    x: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    d: Option<String>,
+}
+
+impl OkpJwk {
+    fn verifying_key(&self) -> Result<VerifyingKey, GhostkeyError> {
+        check_type("kty", &self.kty, "OKP")?;
+        check_type("crv", &self.crv, "Ed25519")?;
+        VerifyingKey::from_bytes(&from_base64_array("x", &self.x)?)
+            .map_err(|e| DecodingError(format!("Invalid Ed25519 public key: {}", e)))
+    }
+}
+
+impl TryFrom<&VerifyingKey> for OkpJwk {
+    type Error = GhostkeyError;
+
+    fn try_from(key: &VerifyingKey) -> Result<Self, GhostkeyError> {
+        Ok(OkpJwk {
+            kty: "OKP".to_string(),
+            crv: "Ed25519".to_string(),
+            x: to_base64(key.as_bytes()),
+            d: None,
+        })
+    }
+}
+
+impl TryFrom<OkpJwk> for VerifyingKey {
+    type Error = GhostkeyError;
+
+    fn try_from(jwk: OkpJwk) -> Result<Self, GhostkeyError> {
+        jwk.verifying_key()
+    }
+}
+
+impl TryFrom<&SigningKey> for OkpJwk {
+    type Error = GhostkeyError;
+
+    fn try_from(key: &SigningKey) -> Result<Self, GhostkeyError> {
+        Ok(OkpJwk {
+            d: Some(to_base64(key.as_bytes())),
+            ..OkpJwk::try_from(&key.verifying_key())?
+        })
+    }
+}
+
+impl TryFrom<OkpJwk> for SigningKey {
+    type Error = GhostkeyError;
+
+    fn try_from(jwk: OkpJwk) -> Result<Self, GhostkeyError> {
+        let verifying_key = jwk.verifying_key()?;
+        let d = jwk
+            .d
+            .as_ref()
+            .ok_or_else(|| DecodingError("JWK has no private key".to_string()))?;
+        let signing_key = SigningKey::from_bytes(&from_base64_array("d", d)?);
+        if signing_key.verifying_key() != verifying_key {
+            return Err(DecodingError("JWK private key does not match its public key".to_string()));
+        }
+        Ok(signing_key)
+    }
+}
+
+/// A delegate verifying key as an `RSA` JSON Web Key.
+#[derive(Serialize, Deserialize)]
+struct RsaJwk {
+    kty: String,
+    n: String,
+    e: String,
+}
+
+impl TryFrom<&RSAVerifyingKey> for RsaJwk {
+    type Error = GhostkeyError;
+
+    fn try_from(key: &RSAVerifyingKey) -> Result<Self, GhostkeyError> {
+        Ok(RsaJwk {
+            kty: "RSA".to_string(),
+            n: to_base64(&key.0.n().to_bytes_be()),
+            e: to_base64(&key.0.e().to_bytes_be()),
+        })
+    }
+}
+
+impl TryFrom<RsaJwk> for RSAVerifyingKey {
+    type Error = GhostkeyError;
+
+    fn try_from(jwk: RsaJwk) -> Result<Self, GhostkeyError> {
+        check_type("kty", &jwk.kty, "RSA")?;
+        let n = BigUint::from_bytes_be(&from_base64("n", &jwk.n)?);
+        let e = BigUint::from_bytes_be(&from_base64("e", &jwk.e)?);
+        RsaPublicKey::new(n, e)
+            .map(RSAVerifyingKey)
+            .map_err(|e| DecodingError(format!("Invalid RSA public key: {}", e)))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct DelegatePayloadJson {
+    delegate_verifying_key: RsaJwk,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    info: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    details: Option<DelegateDetails>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    not_before: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    not_after: Option<u64>,
+}
+
+impl TryFrom<&DelegatePayload> for DelegatePayloadJson {
+    type Error = GhostkeyError;
+
+    fn try_from(payload: &DelegatePayload) -> Result<Self, GhostkeyError> {
+        Ok(match payload {
+            DelegatePayload::V1(payload) => DelegatePayloadJson {
+                delegate_verifying_key: (&payload.delegate_verifying_key).try_into()?,
+                info: Some(payload.info.clone()),
+                details: None,
+                not_before: payload.not_before,
+                not_after: payload.not_after,
+            },
+            DelegatePayload::V2(payload) => DelegatePayloadJson {
+                delegate_verifying_key: (&payload.delegate_verifying_key).try_into()?,
+                info: None,
+                details: Some(payload.details.clone()),
+                not_before: payload.not_before,
+                not_after: payload.not_after,
+            },
+        })
+    }
+}
+
+impl TryFrom<DelegatePayloadJson> for DelegatePayload {
+    type Error = GhostkeyError;
+
+    fn try_from(json: DelegatePayloadJson) -> Result<Self, GhostkeyError> {
+        let delegate_verifying_key = json.delegate_verifying_key.try_into()?;
+        match (json.info, json.details) {
+            (Some(info), None) => Ok(DelegatePayload::V1(DelegatePayloadV1 {
+                delegate_verifying_key,
+                info,
+                not_before: json.not_before,
+                not_after: json.not_after,
+            })),
+            (None, Some(details)) => Ok(DelegatePayload::V2(DelegatePayloadV2 {
+                delegate_verifying_key,
+                details,
+                not_before: json.not_before,
+                not_after: json.not_after,
+            })),
+            _ => Err(DecodingError(
+                "Delegate payload must have either info or details".to_string(),
+            )),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct DelegateCertificateJson {
+    #[serde(rename = "type")]
+    kind: String,
+    version: u32,
+    payload: DelegatePayloadJson,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    context: Option<String>,
+    signature: String,
+}
+
+impl TryFrom<&DelegateCertificate> for DelegateCertificateJson {
+    type Error = GhostkeyError;
+
+    fn try_from(certificate: &DelegateCertificate) -> Result<Self, GhostkeyError> {
+        let (payload, context, signature) = match certificate {
+            DelegateCertificate::V1(certificate) => (&certificate.payload, None, &certificate.signature),
+            DelegateCertificate::V2(certificate) => {
+                (&certificate.payload, Some(certificate.context.clone()), &certificate.signature)
+            }
+        };
+        Ok(DelegateCertificateJson {
+            kind: "delegate_certificate".to_string(),
+            version: certificate.version(),
+            payload: payload.try_into()?,
+            context,
+            signature: to_base64(&signature.to_bytes()),
+        })
+    }
+}
+
+impl TryFrom<DelegateCertificateJson> for DelegateCertificate {
+    type Error = GhostkeyError;
+
+    fn try_from(json: DelegateCertificateJson) -> Result<Self, GhostkeyError> {
+        check_type("type", &json.kind, "delegate_certificate")?;
+        let payload = json.payload.try_into()?;
+        let signature = Signature::from_bytes(&from_base64_array("signature", &json.signature)?);
+        match (json.version, json.context) {
+            (1, None) => Ok(DelegateCertificate::V1(DelegateCertificateV1 { payload, signature })),
+            (2, Some(context)) => Ok(DelegateCertificate::V2(DelegateCertificateV2 {
+                payload,
+                context,
+                signature,
+            })),
+            (1 | 2, _) => Err(DecodingError(
+                "Only V2 delegate certificates have a context".to_string(),
+            )),
+            (version, _) => Err(DecodingError(format!(
+                "Unsupported delegate certificate version {}",
+                version
+            ))),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct GhostkeyCertificateJson {
+    #[serde(rename = "type")]
+    kind: String,
+    version: u32,
+    delegate: DelegateCertificateJson,
+    verifying_key: OkpJwk,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    metadata: Option<GhostkeyMetadata>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    context: Option<String>,
+    signature: String,
+}
+
+impl TryFrom<&GhostkeyCertificate> for GhostkeyCertificateJson {
+    type Error = GhostkeyError;
+
+    fn try_from(certificate: &GhostkeyCertificate) -> Result<Self, GhostkeyError> {
+        let (delegate, metadata, context, signature) = match certificate {
+            GhostkeyCertificate::V1(certificate) => (
+                DelegateCertificate::V1(certificate.delegate.clone()),
+                None,
+                None,
+                &certificate.signature,
+            ),
+            GhostkeyCertificate::V2(certificate) => (
+                DelegateCertificate::V2(certificate.delegate.clone()),
+                None,
+                Some(certificate.context.clone()),
+                &certificate.signature,
+            ),
+            GhostkeyCertificate::V3(certificate) => (
+                DelegateCertificate::V2(certificate.delegate.clone()),
+                Some(certificate.metadata.clone()),
+                Some(certificate.context.clone()),
+                &certificate.signature,
+            ),
+        };
+        Ok(GhostkeyCertificateJson {
+            kind: "ghost_key_certificate".to_string(),
+            version: certificate.version(),
+            delegate: (&delegate).try_into()?,
+            verifying_key: certificate.verifying_key().try_into()?,
+            metadata,
+            context,
+            signature: to_base64(&signature.0),
+        })
+    }
+}
+
+impl TryFrom<GhostkeyCertificateJson> for GhostkeyCertificate {
+    type Error = GhostkeyError;
+
+    fn try_from(json: GhostkeyCertificateJson) -> Result<Self, GhostkeyError> {
+        check_type("type", &json.kind, "ghost_key_certificate")?;
+        let delegate = json.delegate.try_into()?;
+        let verifying_key = json.verifying_key.try_into()?;
+        let signature = RSASignature(from_base64("signature", &json.signature)?);
+        match (json.version, delegate, json.metadata, json.context) {
+            (1, DelegateCertificate::V1(delegate), None, None) => {
+                Ok(GhostkeyCertificate::V1(GhostkeyCertificateV1 {
+                    delegate,
+                    verifying_key,
+                    signature,
+                }))
+            }
+            (2, DelegateCertificate::V2(delegate), None, Some(context)) => {
+                Ok(GhostkeyCertificate::V2(GhostkeyCertificateV2 {
+                    delegate,
+                    verifying_key,
+                    context,
+                    signature,
+                }))
+            }
+            (3, DelegateCertificate::V2(delegate), Some(metadata), Some(context)) => {
+                Ok(GhostkeyCertificate::V3(GhostkeyCertificateV3 {
+                    delegate,
+                    verifying_key,
+                    metadata,
+                    context,
+                    signature,
+                }))
+            }
+            (version @ 1..=3, ..) => Err(DecodingError(format!(
+                "Fields of a V{} ghost key certificate don't match its version",
+                version
+            ))),
+            (version, ..) => Err(DecodingError(format!(
+                "Unsupported ghost key certificate version {}",
+                version
+            ))),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SubkeyPayloadJson {
+    verifying_key: OkpJwk,
+    purpose: String,
+    not_before: u64,
+    not_after: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SubkeyCertificateJson {
+    #[serde(rename = "type")]
+    kind: String,
+    version: u32,
+    ghost_key: GhostkeyCertificateJson,
+    payload: SubkeyPayloadJson,
+    signature: String,
+}
+
+impl TryFrom<&SubkeyCertificateV1> for SubkeyCertificateJson {
+    type Error = GhostkeyError;
+
+    fn try_from(certificate: &SubkeyCertificateV1) -> Result<Self, GhostkeyError> {
+        Ok(SubkeyCertificateJson {
+            kind: "subkey_certificate".to_string(),
+            version: 1,
+            ghost_key: (&certificate.ghost_key_certificate()).try_into()?,
+            payload: SubkeyPayloadJson {
+                verifying_key: (&certificate.payload.verifying_key).try_into()?,
+                purpose: certificate.payload.purpose.clone(),
+                not_before: certificate.payload.not_before,
+                not_after: certificate.payload.not_after,
+            },
+            signature: to_base64(&certificate.signature.to_bytes()),
+        })
+    }
+}
+
+impl TryFrom<SubkeyCertificateJson> for SubkeyCertificateV1 {
+    type Error = GhostkeyError;
+
+    fn try_from(json: SubkeyCertificateJson) -> Result<Self, GhostkeyError> {
+        check_type("type", &json.kind, "subkey_certificate")?;
+        if json.version != 1 {
+            return Err(version_mismatch("subkey certificate", 1, json.version));
+        }
+        let ghost_key: GhostkeyCertificate = json.ghost_key.try_into()?;
+        Ok(SubkeyCertificateV1 {
+            ghost_key: ghost_key.into(),
+            payload: SubkeyPayload {
+                verifying_key: json.payload.verifying_key.try_into()?,
+                purpose: json.payload.purpose,
+                not_before: json.payload.not_before,
+                not_after: json.payload.not_after,
+            },
+            signature: Signature::from_bytes(&from_base64_array("signature", &json.signature)?),
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SignedMessageJson {
+    #[serde(rename = "type")]
+    kind: String,
+    version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    certificate: Option<GhostkeyCertificateJson>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    subkey: Option<SubkeyCertificateJson>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    purpose: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    created: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    content_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+    signature: String,
+}
+
+impl TryFrom<&SignedMessage> for SignedMessageJson {
+    type Error = GhostkeyError;
+
+    fn try_from(signed_message: &SignedMessage) -> Result<Self, GhostkeyError> {
+        let mut json = SignedMessageJson {
+            kind: "signed_message".to_string(),
+            version: signed_message.version(),
+            certificate: None,
+            subkey: None,
+            purpose: None,
+            created: None,
+            content_type: None,
+            message: None,
+            signature: String::new(),
+        };
+        match signed_message {
+            SignedMessage::V1(signed_message) => {
+                json.certificate = Some((&GhostkeyCertificate::from(signed_message.certificate.clone())).try_into()?);
+                json.message = Some(to_base64(&signed_message.message));
+                json.signature = to_base64(&signed_message.signature.to_bytes());
+            }
+            SignedMessage::V2(signed_message) => {
+                json.certificate = Some((&GhostkeyCertificate::from(signed_message.certificate.clone())).try_into()?);
+                json.purpose = Some(signed_message.purpose.clone());
+                json.created = Some(signed_message.created);
+                json.content_type = signed_message.content_type.clone();
+                json.message = signed_message.message.as_deref().map(to_base64);
+                json.signature = to_base64(&signed_message.signature.to_bytes());
+            }
+            SignedMessage::V3(signed_message) => {
+                json.subkey = Some((&signed_message.subkey).try_into()?);
+                json.created = Some(signed_message.created);
+                json.content_type = signed_message.content_type.clone();
+                json.message = signed_message.message.as_deref().map(to_base64);
+                json.signature = to_base64(&signed_message.signature.to_bytes());
+            }
+        }
+        Ok(json)
+    }
+}
+
+impl TryFrom<SignedMessageJson> for SignedMessage {
+    type Error = GhostkeyError;
+
+    fn try_from(json: SignedMessageJson) -> Result<Self, GhostkeyError> {
+        check_type("type", &json.kind, "signed_message")?;
+        let signature = Signature::from_bytes(&from_base64_array("signature", &json.signature)?);
+        let message = json
+            .message
+            .as_deref()
+            .map(|message| from_base64("message", message))
+            .transpose()?;
+        let certificate = |certificate: Option<GhostkeyCertificateJson>| {
+            let certificate: GhostkeyCertificate = certificate
+                .ok_or_else(|| DecodingError("Signed message has no certificate".to_string()))?
+                .try_into()?;
+            Ok::<SignedMessageCertificate, GhostkeyError>(certificate.into())
+        };
+        let missing = |field: &str| DecodingError(format!("V{} signed message has no {}", json.version, field));
+        match json.version {
+            1 => Ok(SignedMessage::V1(SignedMessageV1 {
+                certificate: certificate(json.certificate)?,
+                message: message.ok_or_else(|| missing("message"))?,
+                signature,
+            })),
+            2 => Ok(SignedMessage::V2(SignedMessageV2 {
+                certificate: certificate(json.certificate)?,
+                purpose: json.purpose.ok_or_else(|| missing("purpose"))?,
+                created: json.created.ok_or_else(|| missing("created"))?,
+                content_type: json.content_type,
+                message,
+                signature,
+            })),
+            3 => Ok(SignedMessage::V3(SignedMessageV3 {
+                subkey: json.subkey.ok_or_else(|| missing("subkey"))?.try_into()?,
+                created: json.created.ok_or_else(|| missing("created"))?,
+                content_type: json.content_type,
+                message,
+                signature,
+            })),
+            version => Err(DecodingError(format!("Unsupported signed message version {}", version))),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "issue"))]
+mod tests {
+    use super::*;
+    use crate::armorable::Armorable;
+    use crate::trust_store::TrustStore;
+    use crate::util::create_keypair;
+    use rand_core::OsRng;
+
+    #[test]
+    fn test_json_round_trip() {
+        let (master_signing_key, master_verifying_key) = create_keypair(&mut OsRng).unwrap();
+        let trust_store = TrustStore::from(master_verifying_key);
+        let (delegate_certificate, delegate_signing_key) =
+            DelegateCertificateV1::new(&master_signing_key, "Test Delegate").unwrap();
+        let (ghost_key_certificate, ghost_signing_key) =
+            GhostkeyCertificateV1::new(&delegate_certificate, &delegate_signing_key).unwrap();
+
+        // Decoding the JSON gives back the same CBOR, so signatures still verify
+        let json = ghost_key_certificate.to_json().unwrap();
+        let decoded = GhostkeyCertificateV1::from_json(&json).unwrap();
+        assert_eq!(Armorable::to_bytes(&decoded).unwrap(), Armorable::to_bytes(&ghost_key_certificate).unwrap());
+        decoded.verify(&trust_store).unwrap();
+        let json = delegate_certificate.to_json().unwrap();
+        assert!(json.contains("\"kty\": \"RSA\""));
+        let decoded = DelegateCertificateV1::from_json(&json).unwrap();
+        assert_eq!(Armorable::to_bytes(&decoded).unwrap(), Armorable::to_bytes(&delegate_certificate).unwrap());
+
+        let json = ghost_signing_key.to_json().unwrap();
+        assert!(json.contains("\"crv\": \"Ed25519\""));
+        assert_eq!(SigningKey::from_json(&json).unwrap().to_bytes(), ghost_signing_key.to_bytes());
+        // The public half of a signing key JWK is its verifying key JWK
+        assert_eq!(
+            VerifyingKey::from_json(&json).unwrap(),
+            ghost_signing_key.verifying_key()
+        );
+        assert_eq!(
+            SigningKey::from_json(&ghost_signing_key.verifying_key().to_json().unwrap()).err().map(|e| e.code()),
+            Some("GK_DECODE_FAILED")
+        );
+
+        let signed_message = SignedMessage::V2(
+            SignedMessageV2::new(
+                ghost_key_certificate.into(),
+                &ghost_signing_key,
+                "test purpose",
+                Some("text/plain"),
+                b"Hello",
+            )
+            .unwrap(),
+        );
+        let decoded = SignedMessage::from_json(&signed_message.to_json().unwrap()).unwrap();
+        assert_eq!(decoded.to_bytes().unwrap(), signed_message.to_bytes().unwrap());
+
+        // A certificate is not a signed message
+        assert_eq!(SignedMessage::from_json(&json).err().map(|e| e.code()), Some("GK_DECODE_FAILED"));
+        assert_eq!(
+            DelegateCertificateV1::from_json(&decoded.certificate().to_json().unwrap()).err().map(|e| e.code()),
+            Some("GK_DECODE_FAILED")
+        );
+    }
+}
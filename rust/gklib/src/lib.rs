@@ -1,7 +1,43 @@
+//! Ghost key certificates for Freenet.
+//!
+//! With default features disabled and only `verify` enabled the crate builds without std and
+//! without a random number generator, for verifying ghost keys inside constrained WASM contracts
+//! and delegates. The `std`, `fs`, `issue` and `json` features add verification against the
+//! current time, armored file I/O, creation of keys and certificates, and JSON interchange.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
 pub mod armorable;
+pub mod batch_verifier;
+pub mod compact;
 pub mod delegate_certificate;
+pub mod delegate_key;
+#[cfg(feature = "issue")]
+pub mod encrypted_key;
+pub mod fingerprint;
 pub mod ghost_key_certificate;
 pub mod errors;
+#[cfg(feature = "issue")]
+pub mod issuance;
+#[cfg(feature = "json")]
+pub mod json;
+pub mod partially_blind;
+pub mod pseudonym;
+pub mod revocation_list;
+#[cfg(feature = "issue")]
+pub mod secret_sharing;
+#[cfg(feature = "issue")]
+pub mod seed;
+pub mod signed_message;
+pub mod ssh;
+pub mod subkey;
+#[cfg(feature = "issue")]
+pub mod threshold;
+#[cfg(all(test, feature = "issue"))]
+mod test_util;
+pub mod trust_store;
 pub mod util;
 
 pub const FREENET_MASTER_VERIFYING_KEY_BASE64: &str = "WCBinZei3Yki9ezxKPNLoCar/m6F3Q8nnSrWDaRSxLL6cw==";
@@ -0,0 +1,300 @@
+//! Partially blind RSA signatures, following draft-amjad-cfrg-partially-blind-rsa, the variant of
+//! RFC 9474 blind signatures that binds public metadata into the signature. The delegate signs
+//! with a key pair derived from its own key and the metadata, so one delegate key can issue ghost
+//! keys for any number of metadata values, and a signature issued for one value never verifies
+//! for another.
+//!
+//! Deriving the private key needs a delegate key whose primes are safe primes, which
+//! [`generate_signing_key`] creates. Verification needs only the delegate verifying key.
+
+use super::delegate_key::PublicKey as RSAVerifyingKey;
+use super::errors::GhostkeyError;
+use alloc::string::ToString;
+use alloc::vec;
+use alloc::vec::Vec;
+use hkdf::Hkdf;
+use rsa::sha2::Sha384;
+use rsa::{BigUint, PublicKeyParts, RsaPublicKey};
+#[cfg(feature = "issue")]
+use blind_rsa_signatures::{KeyPair, SecretKey as RSASigningKey};
+#[cfg(feature = "issue")]
+use num_bigint_dig::{prime::probably_prime, ModInverse, RandBigInt};
+#[cfg(feature = "issue")]
+use rand_core::{CryptoRng, RngCore};
+#[cfg(feature = "issue")]
+use rsa::RsaPrivateKey;
+
+/// HKDF info under which the public exponent for a metadata value is derived.
+const KEY_DERIVATION_INFO: &[u8] = b"PBRSA";
+
+/// Public exponent of the delegate key itself, as for keys created by blind-rsa-signatures.
+#[cfg(feature = "issue")]
+const PUBLIC_EXPONENT: u32 = 65537;
+
+/// Primes below this bound are sieved out of safe prime candidates before testing primality.
+#[cfg(feature = "issue")]
+const SIEVE_LIMIT: usize = 1 << 16;
+
+/// Derives the verifying key for `metadata` from the delegate verifying key. It shares the
+/// delegate's modulus and has a public exponent drawn from HKDF over the modulus and metadata.
+pub fn derive_public_key(
+    public_key: &RSAVerifyingKey,
+    metadata: &[u8],
+) -> Result<RSAVerifyingKey, GhostkeyError> {
+    let modulus = public_key.0.n();
+    let lambda_len = public_key.0.size() / 2;
+
+    let mut key_material = Vec::with_capacity(metadata.len() + 4);
+    key_material.extend_from_slice(b"key");
+    key_material.extend_from_slice(metadata);
+    key_material.push(0);
+
+    let mut exponent = vec![0u8; lambda_len + 16];
+    Hkdf::<Sha384>::new(Some(&modulus.to_bytes_be()), &key_material)
+        .expand(KEY_DERIVATION_INFO, &mut exponent)
+        .map_err(|_| {
+            GhostkeyError::InvalidInput("Delegate key is too large to derive a metadata key".to_string())
+        })?;
+
+    // Keep the exponent below the primes of the delegate key and make it odd
+    exponent[0] &= 0x3f;
+    exponent[lambda_len - 1] |= 0x01;
+    exponent.truncate(lambda_len);
+
+    Ok(RSAVerifyingKey(RsaPublicKey::new_unchecked(
+        modulus.clone(),
+        BigUint::from_bytes_be(&exponent),
+    )))
+}
+
+/// The message actually signed when `message` is signed with `metadata`, which prefixes it with
+/// the length-delimited metadata.
+pub fn metadata_message(metadata: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut prefixed = Vec::with_capacity(7 + metadata.len() + message.len());
+    prefixed.extend_from_slice(b"msg");
+    prefixed.extend_from_slice(&(metadata.len() as u32).to_be_bytes());
+    prefixed.extend_from_slice(metadata);
+    prefixed.extend_from_slice(message);
+    prefixed
+}
+
+/// Derives the key pair that signs with `metadata` from the delegate signing key. Fails if the
+/// key was not created by [`generate_signing_key`] and its primes happen not to allow it.
+#[cfg(feature = "issue")]
+pub fn derive_key_pair(
+    signing_key: &RSASigningKey,
+    metadata: &[u8],
+) -> Result<KeyPair, GhostkeyError> {
+    let key = &signing_key.0;
+    let public_key = derive_public_key(&RSAVerifyingKey(key.to_public_key()), metadata)?;
+
+    let (p, q) = match key.primes() {
+        [p, q] => (p, q),
+        _ => {
+            return Err(GhostkeyError::InvalidInput(
+                "Partially blind signing needs a delegate key with exactly two primes".to_string(),
+            ))
+        }
+    };
+    let exponent = public_key.0.e().clone();
+    let private_exponent = private_exponent(&exponent, p, q).ok_or_else(|| {
+        GhostkeyError::InvalidInput(
+            "Delegate key was not generated for partially blind signing".to_string(),
+        )
+    })?;
+
+    let derived_key = RsaPrivateKey::from_components(
+        key.n().clone(),
+        exponent,
+        private_exponent,
+        vec![p.clone(), q.clone()],
+    )
+    .map_err(|e| GhostkeyError::RSAError("Failed to derive metadata key".to_string(), e.into()))?;
+
+    Ok(KeyPair::new(
+        public_key.to_blind_rsa(),
+        RSASigningKey(derived_key),
+    ))
+}
+
+/// Generates a delegate signing key with a `key_bits` modulus for partially blind signing. Its
+/// primes are safe primes, so every derived public exponent has a private exponent. Finding
+/// 1024-bit safe primes is slow: a 2048-bit key typically takes minutes rather than the second or
+/// so of an ordinary delegate key, so generate it offline and never on a request path.
+#[cfg(feature = "issue")]
+pub fn generate_signing_key<R: RngCore + CryptoRng>(
+    rng: &mut R,
+    key_bits: usize,
+) -> Result<RSASigningKey, GhostkeyError> {
+    let sieve = small_primes();
+    let p = generate_safe_prime(rng, key_bits / 2, &sieve);
+    let q = loop {
+        let q = generate_safe_prime(rng, key_bits - key_bits / 2, &sieve);
+        if q != p {
+            break q;
+        }
+    };
+
+    let exponent = BigUint::from(PUBLIC_EXPONENT);
+    let private_exponent = private_exponent(&exponent, &p, &q).ok_or_else(|| {
+        GhostkeyError::KeyCreationError("Public exponent is not invertible".to_string())
+    })?;
+    let key = RsaPrivateKey::from_components(&p * &q, exponent, private_exponent, vec![p, q])
+        .map_err(|e| GhostkeyError::RSAError("Failed to generate delegate key".to_string(), e.into()))?;
+    key.validate()
+        .map_err(|e| GhostkeyError::RSAError("Failed to generate delegate key".to_string(), e.into()))?;
+
+    Ok(RSASigningKey(key))
+}
+
+#[cfg(feature = "issue")]
+fn private_exponent(exponent: &BigUint, p: &BigUint, q: &BigUint) -> Option<BigUint> {
+    let totient = (p - 1u32) * (q - 1u32);
+    exponent
+        .mod_inverse(&totient)
+        .and_then(|inverse| inverse.to_biguint())
+}
+
+/// Generates a prime `p` of exactly `bits` bits, with its top two bits set, such that
+/// `(p - 1) / 2` is prime as well.
+#[cfg(feature = "issue")]
+fn generate_safe_prime<R: RngCore + CryptoRng>(
+    rng: &mut R,
+    bits: usize,
+    sieve: &[u64],
+) -> BigUint {
+    let one = BigUint::from(1u32);
+    let two = BigUint::from(2u32);
+    loop {
+        // Search upwards from a random start for the half q of a safe prime 2q + 1
+        let start = rng.gen_biguint(bits - 1) | (BigUint::from(3u32) << (bits - 3)) | &one;
+        let residues: Vec<u64> = sieve
+            .iter()
+            .map(|prime| residue(&start, *prime))
+            .collect();
+
+        for offset in (0..1u64 << 20).step_by(2) {
+            // Skip candidates where q or 2q + 1 has a small factor
+            let sieved = sieve.iter().zip(&residues).all(|(prime, residue)| {
+                let q = (residue + offset) % prime;
+                q != 0 && (2 * q + 1) % prime != 0
+            });
+            if !sieved {
+                continue;
+            }
+
+            let half = &start + offset;
+            let candidate = &half * 2u32 + 1u32;
+            if candidate.bits() != bits {
+                break;
+            }
+            // A cheap Fermat test on the candidate rules out most composites first
+            if two.modpow(&(&candidate - 1u32), &candidate) == one
+                && probably_prime(&half, 20)
+                && probably_prime(&candidate, 20)
+            {
+                return candidate;
+            }
+        }
+    }
+}
+
+/// The odd primes below [`SIEVE_LIMIT`].
+#[cfg(feature = "issue")]
+fn small_primes() -> Vec<u64> {
+    let mut composite = vec![false; SIEVE_LIMIT];
+    let mut primes = Vec::new();
+    for n in 3..SIEVE_LIMIT {
+        if !composite[n] {
+            primes.push(n as u64);
+            for multiple in (n * n..SIEVE_LIMIT).step_by(n) {
+                composite[multiple] = true;
+            }
+        }
+    }
+    primes
+}
+
+#[cfg(feature = "issue")]
+fn residue(n: &BigUint, modulus: u64) -> u64 {
+    n.to_bytes_be()
+        .iter()
+        .fold(0, |residue, byte| (residue * 256 + *byte as u64) % modulus)
+}
+
+/// A 2048 bit delegate key made of fixed safe primes, as tests can't afford to generate one.
+#[cfg(all(test, feature = "issue"))]
+pub(crate) fn test_signing_key() -> RSASigningKey {
+    const P: &[u8] = b"FCCE7A9BB4588572D023657BF8D586F3DEF445E5823B556A960B489B9A8733D573CD78E6DCC4ECBD\
+        6D9424A6C0821842D2630365B2BBF6392766C14B35B5F8DE3BFD6EF4A847BF824859A1D72D50372DE2AFB92FDD\
+        F4A3BC6FCFE663CC84DFB152F7192DD2A3429146DF4C18274E42AB2F287BD2A8C5DFF60A99A44FEC1F7FEF";
+    const Q: &[u8] = b"CA2089FDAB70A22127963F06F4BE1904048E8050A3ECD6CEA54FDFC6C8D650480F07B6E1F81D3C38DB\
+        F51065FC39095233E7DEAD268250D573B5835A3F727C10B4771642427CCF0E548486246F16B06F85281843D240\
+        3653152912EC93591D32ADFE9B10FC0F82DE0AC8C2437DA37B4CCF8285664938517C0125950E0166BD7B";
+
+    let p = BigUint::parse_bytes(P, 16).unwrap();
+    let q = BigUint::parse_bytes(Q, 16).unwrap();
+    let exponent = BigUint::from(PUBLIC_EXPONENT);
+    let private_exponent = private_exponent(&exponent, &p, &q).unwrap();
+    RSASigningKey(RsaPrivateKey::from_components(&p * &q, exponent, private_exponent, vec![p, q]).unwrap())
+}
+
+#[cfg(all(test, feature = "issue"))]
+mod tests {
+    use super::*;
+    use crate::issuance::{blind, blind_sign, finalize};
+    use rand_core::OsRng;
+
+    #[test]
+    fn test_partially_blind_round_trip() {
+        let signing_key = test_signing_key();
+        let verifying_key = RSAVerifyingKey(signing_key.0.to_public_key());
+        let key_pair = derive_key_pair(&signing_key, b"2024-10").unwrap();
+        let message = b"ghost key";
+
+        let (blinded_message, secret) = blind(
+            &mut OsRng,
+            &key_pair.pk,
+            &metadata_message(b"2024-10", message),
+        )
+        .unwrap();
+        let blind_signature = blind_sign(&mut OsRng, &key_pair.sk, &blinded_message).unwrap();
+        let signature = finalize(
+            &derive_public_key(&verifying_key, b"2024-10").unwrap().to_blind_rsa(),
+            &blind_signature,
+            &secret,
+            &metadata_message(b"2024-10", message),
+        )
+        .unwrap()
+        .into();
+
+        assert!(verifying_key.verify_with_metadata(&signature, b"2024-10", message));
+        // The signature is bound to the metadata and is not one by the delegate key itself
+        assert!(!verifying_key.verify_with_metadata(&signature, b"2024-11", message));
+        assert!(!verifying_key.verify(&signature, message));
+    }
+
+    #[test]
+    fn test_derived_exponents() {
+        let verifying_key = RSAVerifyingKey(test_signing_key().0.to_public_key());
+        let derived = derive_public_key(&verifying_key, b"2024-10").unwrap();
+        assert_eq!(derived.0.n(), verifying_key.0.n());
+        assert_ne!(derived.0.e(), verifying_key.0.e());
+        assert_eq!(derived.0.e(), derive_public_key(&verifying_key, b"2024-10").unwrap().0.e());
+        assert_ne!(derived.0.e(), derive_public_key(&verifying_key, b"2024-11").unwrap().0.e());
+        // Below half the modulus size, so it is smaller than the primes
+        assert!(derived.0.e().bits() <= 1022);
+        assert_eq!(derived.0.e() % 2u32, BigUint::from(1u32));
+    }
+
+    #[test]
+    #[ignore = "searching for safe primes takes minutes in debug builds, run with --ignored"]
+    fn test_generate_signing_key() {
+        let signing_key = generate_signing_key(&mut OsRng, 512).unwrap();
+        assert_eq!(signing_key.0.n().bits(), 512);
+        for prime in signing_key.0.primes() {
+            assert!(probably_prime(&(prime >> 1), 20));
+        }
+        assert!(derive_key_pair(&signing_key, b"2024-10").is_ok());
+    }
+}
@@ -0,0 +1,578 @@
+use super::delegate_certificate::DelegateInfo;
+use super::errors::GhostkeyError;
+use super::errors::GhostkeyError::{DelegateMismatchError, SignatureVerificationError};
+use super::errors::SignatureKind;
+use super::fingerprint::Fingerprint;
+use super::ghost_key_certificate::GhostkeyCertificate;
+use super::signed_message::SignedMessageCertificate;
+use super::trust_store::TrustStore;
+use super::util::{context_hash, verify_with_context};
+#[cfg(feature = "std")]
+use super::util::current_unix_time;
+#[cfg(feature = "issue")]
+use super::util::sign_with_context;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use curve25519_dalek::edwards::{CompressedEdwardsY, EdwardsPoint};
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::IsIdentity;
+use ed25519_dalek::*;
+#[cfg(feature = "issue")]
+use rand_core::{CryptoRng, OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+
+/// BLAKE3 key derivation context under which a pseudonym signing key is derived from the ghost
+/// signing key and the pseudonym context.
+pub const PSEUDONYM_KEY_CONTEXT: &str = "freenet.org ghostkey 2024-10 pseudonym key v1";
+
+/// BLAKE3 key derivation context under which a pseudonym context is hashed to the curve point
+/// that ghost keys are multiplied with to give their tag in that context.
+pub const PSEUDONYM_TAG_CONTEXT: &str = "freenet.org ghostkey 2024-10 pseudonym tag v1";
+
+/// BLAKE3 key derivation context of the challenges of the ring proof in a pseudonym certificate.
+pub const PSEUDONYM_PROOF_CONTEXT: &str = "freenet.org ghostkey 2024-10 pseudonym proof v1";
+
+/// BLAKE3 key derivation context under which pseudonym keys sign the fields of a pseudonym
+/// message.
+pub const PSEUDONYM_MESSAGE_V1_CONTEXT: &str = "freenet.org ghostkey 2024-10 pseudonym message v1";
+
+/// Fewest ghost keys a pseudonym ring may hold, the holder's own included. A ring of one would
+/// name the holder's ghost key outright. Verifiers wanting a larger anonymity set can check
+/// [`PseudonymInfo::ring_size`].
+pub const MIN_PSEUDONYM_RING_SIZE: usize = 2;
+
+/// A pseudonym key for one context, such as a Freenet app or a poll, proven to belong to one of
+/// a ring of ghost keys issued by the same delegate without revealing which.
+///
+/// The proof is a linkable ring signature (LSAG) whose key image, the `tag`, is the ghost
+/// signing key times a point hashed from the context. Deriving a pseudonym in the same context
+/// always gives the same tag, so a verifier can keep one pseudonym per ghost key and context,
+/// while tags and pseudonym keys in different contexts can't be linked to each other or to the
+/// ghost key.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PseudonymCertificateV1 {
+    /// What the pseudonym is for, e.g. "freenet.org poll 2024-11"
+    pub context: String,
+    /// The ghost key certificates the pseudonym is proven to belong to one of, ordered by
+    /// ghost verifying key
+    pub ring: Vec<SignedMessageCertificate>,
+    /// The pseudonym key, which signs for the pseudonym in this context
+    pub verifying_key: VerifyingKey,
+    /// The key image of the ring proof, the same for every pseudonym a ghost key derives in
+    /// this context
+    #[serde(with = "serde_bytes")]
+    pub tag: [u8; 32],
+    /// The first challenge of the ring proof
+    #[serde(with = "serde_bytes")]
+    pub challenge: [u8; 32],
+    /// The responses of the ring proof, 32 bytes per ring member
+    #[serde(with = "serde_bytes")]
+    pub responses: Vec<u8>,
+}
+
+/// The statement the ring proof of a pseudonym certificate is made over.
+#[derive(Serialize)]
+struct ProofStatement<'a> {
+    context: &'a str,
+    ring: Vec<VerifyingKey>,
+    verifying_key: &'a VerifyingKey,
+    #[serde(with = "serde_bytes")]
+    tag: &'a [u8; 32],
+}
+
+/// The verified contents of a pseudonym certificate, returned by `verify`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PseudonymInfo {
+    /// The delegate that issued every ghost key in the ring
+    pub delegate_info: DelegateInfo,
+    pub context: String,
+    /// Identifies the pseudonym, the same for every pseudonym a ghost key derives in the context
+    pub pseudonym_id: Fingerprint,
+    /// The pseudonym key
+    pub verifying_key: VerifyingKey,
+    /// Number of ghost keys the pseudonym could belong to
+    pub ring_size: usize,
+}
+
+/// Derives the pseudonym signing key of a ghost key in `context`. The same ghost key always
+/// derives the same key in the same context.
+pub fn derive_pseudonym_key(ghost_signing_key: &SigningKey, context: &str) -> SigningKey {
+    let mut hasher = blake3::Hasher::new_derive_key(PSEUDONYM_KEY_CONTEXT);
+    hasher.update(ghost_signing_key.as_bytes());
+    hasher.update(context.as_bytes());
+    SigningKey::from_bytes(hasher.finalize().as_bytes())
+}
+
+impl PseudonymCertificateV1 {
+    /// Derives the pseudonym of the ghost key in `context` and proves it belongs to one of the
+    /// ghost keys in `ring`, to which the ghost key itself is added. The ring has to hold at least
+    /// [`MIN_PSEUDONYM_RING_SIZE`] ghost keys once it is. Returns the certificate and the
+    /// pseudonym signing key.
+    #[cfg(feature = "issue")]
+    pub fn new(
+        ghost_key_certificate: GhostkeyCertificate,
+        ghost_signing_key: &SigningKey,
+        context: &str,
+        ring: &[GhostkeyCertificate],
+    ) -> Result<(Self, SigningKey), GhostkeyError> {
+        Self::new_with_rng(&mut OsRng, ghost_key_certificate, ghost_signing_key, context, ring)
+    }
+
+    /// Like [`new`](Self::new), drawing the nonces of the ring proof from `rng`.
+    #[cfg(feature = "issue")]
+    pub fn new_with_rng<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        ghost_key_certificate: GhostkeyCertificate,
+        ghost_signing_key: &SigningKey,
+        context: &str,
+        ring: &[GhostkeyCertificate],
+    ) -> Result<(Self, SigningKey), GhostkeyError> {
+        if ghost_signing_key.verifying_key() != *ghost_key_certificate.verifying_key() {
+            return Err(GhostkeyError::InvalidInput(
+                "Ghost signing key does not match the ghost key certificate".to_string(),
+            ));
+        }
+        let ghost_verifying_key = *ghost_key_certificate.verifying_key();
+        let mut ring: Vec<GhostkeyCertificate> = ring.to_vec();
+        ring.push(ghost_key_certificate);
+        ring.sort_by(|a, b| a.verifying_key().as_bytes().cmp(b.verifying_key().as_bytes()));
+        ring.dedup_by(|a, b| a.verifying_key() == b.verifying_key());
+        if ring.len() < MIN_PSEUDONYM_RING_SIZE {
+            return Err(GhostkeyError::InvalidInput(format!(
+                "A pseudonym ring needs at least {} ghost keys, including the holder's own",
+                MIN_PSEUDONYM_RING_SIZE
+            )));
+        }
+        let delegate_key = ring[0].delegate_payload().delegate_verifying_key();
+        if ring.iter().any(|member| member.delegate_payload().delegate_verifying_key() != delegate_key) {
+            return Err(DelegateMismatchError(
+                "Ghost keys in the ring were issued by different delegates".to_string(),
+            ));
+        }
+        let keys = ring_points(&ring)?;
+        let signer = ring
+            .iter()
+            .position(|member| *member.verifying_key() == ghost_verifying_key)
+            .expect("the ghost key was added to the ring");
+
+        let pseudonym_signing_key = derive_pseudonym_key(ghost_signing_key, context);
+        let verifying_key = pseudonym_signing_key.verifying_key();
+        let secret = ghost_signing_key.to_scalar();
+        let base = tag_base(context);
+        let tag_point = secret * base;
+        let tag = tag_point.compress().to_bytes();
+        let prefix = proof_prefix(context, &ring, &verifying_key, &tag)?;
+
+        // LSAG: close the ring of challenges at the signer, the only member whose response can
+        // be computed after its challenge
+        let n = ring.len();
+        let mut challenges = alloc::vec![Scalar::ZERO; n];
+        let mut responses = alloc::vec![Scalar::ZERO; n];
+        let nonce = random_scalar(rng);
+        challenges[(signer + 1) % n] =
+            challenge(&prefix, &EdwardsPoint::mul_base(&nonce), &(nonce * base));
+        let mut i = (signer + 1) % n;
+        while i != signer {
+            responses[i] = random_scalar(rng);
+            let (l, r) = commitments(&keys[i], &base, &tag_point, &challenges[i], &responses[i]);
+            challenges[(i + 1) % n] = challenge(&prefix, &l, &r);
+            i = (i + 1) % n;
+        }
+        responses[signer] = nonce - challenges[signer] * secret;
+
+        Ok((
+            Self {
+                context: context.to_string(),
+                ring: ring.into_iter().map(SignedMessageCertificate::from).collect(),
+                verifying_key,
+                tag,
+                challenge: challenges[0].to_bytes(),
+                responses: responses.iter().flat_map(|response| response.to_bytes()).collect(),
+            },
+            pseudonym_signing_key,
+        ))
+    }
+
+    /// Identifies the pseudonym, the same for every pseudonym a ghost key derives in the
+    /// context whichever ring it is proven against.
+    pub fn pseudonym_id(&self) -> Result<Fingerprint, GhostkeyError> {
+        Fingerprint::compute("pseudonym tag", &(&self.context, serde_bytes::Bytes::new(&self.tag)))
+    }
+
+    /// The ghost key certificates of the ring.
+    pub fn ring_certificates(&self) -> Vec<GhostkeyCertificate> {
+        self.ring.iter().cloned().map(GhostkeyCertificate::from).collect()
+    }
+
+    /// Verifies every ghost key in the ring against the trust store, that they were issued by
+    /// the same delegate, that there are at least [`MIN_PSEUDONYM_RING_SIZE`] of them, and the
+    /// ring proof.
+    #[cfg(feature = "std")]
+    pub fn verify(&self, trust_store: &TrustStore) -> Result<PseudonymInfo, GhostkeyError> {
+        self.verify_at(current_unix_time(), trust_store)
+    }
+
+    /// Like [`verify`](Self::verify), checking the validity periods of the delegate against
+    /// `time` (seconds since the Unix epoch) rather than the current time.
+    pub fn verify_at(&self, time: u64, trust_store: &TrustStore) -> Result<PseudonymInfo, GhostkeyError> {
+        self.verify_with(|certificate| certificate.verify_at(time, trust_store))
+    }
+
+    /// Completes the verification given a function verifying each ghost key certificate of the
+    /// ring, so that callers can verify them themselves, e.g. against a revocation list.
+    pub(crate) fn verify_with<F>(&self, verify_certificate: F) -> Result<PseudonymInfo, GhostkeyError>
+    where
+        F: Fn(&GhostkeyCertificate) -> Result<DelegateInfo, GhostkeyError>,
+    {
+        let ring = self.ring_certificates();
+        if ring.len() < MIN_PSEUDONYM_RING_SIZE {
+            return Err(GhostkeyError::ValidationError(format!(
+                "Pseudonym ring has {} ghost keys, fewer than {}",
+                ring.len(),
+                MIN_PSEUDONYM_RING_SIZE
+            )));
+        }
+        let first = &ring[0];
+        let delegate_info = verify_certificate(first)?;
+        let delegate_key = first.delegate_payload().delegate_verifying_key();
+        for (index, member) in ring.iter().enumerate().skip(1) {
+            if member.delegate_payload().delegate_verifying_key() != delegate_key {
+                return Err(DelegateMismatchError(
+                    "Ghost keys in the ring were issued by different delegates".to_string(),
+                ));
+            }
+            if ring[index - 1].verifying_key().as_bytes() >= member.verifying_key().as_bytes() {
+                return Err(GhostkeyError::ValidationError(
+                    "Pseudonym ring is not ordered by ghost verifying key".to_string(),
+                ));
+            }
+            verify_certificate(member)?;
+        }
+        self.verify_proof(&ring)?;
+
+        Ok(PseudonymInfo {
+            delegate_info,
+            context: self.context.clone(),
+            pseudonym_id: self.pseudonym_id()?,
+            verifying_key: self.verifying_key,
+            ring_size: ring.len(),
+        })
+    }
+
+    fn verify_proof(&self, ring: &[GhostkeyCertificate]) -> Result<(), GhostkeyError> {
+        let invalid = || {
+            SignatureVerificationError(
+                SignatureKind::Pseudonym,
+                "Failed to verify pseudonym ring proof".to_string(),
+            )
+        };
+        let keys = ring_points(ring)?;
+        if self.responses.len() != keys.len() * 32 {
+            return Err(invalid());
+        }
+        let tag_point = CompressedEdwardsY(self.tag)
+            .decompress()
+            .filter(|point| point.is_torsion_free() && !point.is_identity())
+            .ok_or_else(invalid)?;
+        let first_challenge = canonical_scalar(&self.challenge).ok_or_else(invalid)?;
+        let base = tag_base(&self.context);
+        let prefix = proof_prefix(&self.context, ring, &self.verifying_key, &self.tag)?;
+
+        let mut challenge_i = first_challenge;
+        for (key, response) in keys.iter().zip(self.responses.chunks_exact(32)) {
+            let response = canonical_scalar(response.try_into().expect("chunks of 32 bytes"))
+                .ok_or_else(invalid)?;
+            let (l, r) = commitments(key, &base, &tag_point, &challenge_i, &response);
+            challenge_i = challenge(&prefix, &l, &r);
+        }
+        if challenge_i == first_challenge {
+            Ok(())
+        } else {
+            Err(invalid())
+        }
+    }
+}
+
+/// Content signed by a pseudonym key, carrying the pseudonym certificate so that verifiers can
+/// check it belongs to a valid ghost key. The signature covers the [`context_hash`] under
+/// [`PSEUDONYM_MESSAGE_V1_CONTEXT`] of the pseudonym context, creation time and a BLAKE3 hash of
+/// the message.
+///
+/// [`context_hash`]: crate::util::context_hash
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PseudonymMessageV1 {
+    pub pseudonym: PseudonymCertificateV1,
+    /// Seconds since the Unix epoch at which the message was signed, as claimed by the signer
+    pub created: u64,
+    #[serde(with = "serde_bytes")]
+    pub message: Vec<u8>,
+    pub signature: Signature,
+}
+
+/// The fields of a pseudonym message covered by its signature.
+#[derive(Serialize)]
+struct PseudonymMessageFields<'a> {
+    context: &'a str,
+    created: u64,
+    #[serde(with = "serde_bytes")]
+    message_hash: &'a [u8],
+}
+
+impl PseudonymMessageV1 {
+    /// Signs `message` with the pseudonym signing key.
+    #[cfg(feature = "issue")]
+    pub fn new(
+        pseudonym: PseudonymCertificateV1,
+        signing_key: &SigningKey,
+        message: &[u8],
+    ) -> Result<Self, GhostkeyError> {
+        if signing_key.verifying_key() != pseudonym.verifying_key {
+            return Err(GhostkeyError::InvalidInput(
+                "Pseudonym signing key does not match the pseudonym certificate".to_string(),
+            ));
+        }
+        let created = current_unix_time();
+        let message_hash = blake3::hash(message);
+        let signature = sign_with_context(
+            signing_key,
+            PSEUDONYM_MESSAGE_V1_CONTEXT,
+            &PseudonymMessageFields {
+                context: &pseudonym.context,
+                created,
+                message_hash: message_hash.as_bytes(),
+            },
+        )?;
+        Ok(Self { pseudonym, created, message: message.to_vec(), signature })
+    }
+
+    /// Verifies the pseudonym certificate against the trust store and the signature over the
+    /// message.
+    #[cfg(feature = "std")]
+    pub fn verify(&self, trust_store: &TrustStore) -> Result<PseudonymInfo, GhostkeyError> {
+        self.verify_at(current_unix_time(), trust_store)
+    }
+
+    /// Like [`verify`](Self::verify), checking the validity periods of the delegate against
+    /// `time` (seconds since the Unix epoch) rather than the current time.
+    pub fn verify_at(&self, time: u64, trust_store: &TrustStore) -> Result<PseudonymInfo, GhostkeyError> {
+        self.verify_with(|certificate| certificate.verify_at(time, trust_store))
+    }
+
+    pub(crate) fn verify_with<F>(&self, verify_certificate: F) -> Result<PseudonymInfo, GhostkeyError>
+    where
+        F: Fn(&GhostkeyCertificate) -> Result<DelegateInfo, GhostkeyError>,
+    {
+        let info = self.pseudonym.verify_with(verify_certificate)?;
+        let message_hash = blake3::hash(&self.message);
+        let fields = PseudonymMessageFields {
+            context: &self.pseudonym.context,
+            created: self.created,
+            message_hash: message_hash.as_bytes(),
+        };
+        if !verify_with_context(&self.pseudonym.verifying_key, PSEUDONYM_MESSAGE_V1_CONTEXT, &fields, &self.signature)? {
+            return Err(SignatureVerificationError(
+                SignatureKind::Pseudonym,
+                "Failed to verify pseudonym message".to_string(),
+            ));
+        }
+        Ok(info)
+    }
+}
+
+/// Hashes the pseudonym context to a point of the prime order subgroup with an unknown
+/// discrete logarithm, by trying successive outputs of the BLAKE3 XOF until one decompresses.
+fn tag_base(context: &str) -> EdwardsPoint {
+    let mut hasher = blake3::Hasher::new_derive_key(PSEUDONYM_TAG_CONTEXT);
+    hasher.update(context.as_bytes());
+    let mut output = hasher.finalize_xof();
+    loop {
+        let mut candidate = [0u8; 32];
+        output.fill(&mut candidate);
+        if let Some(point) = CompressedEdwardsY(candidate).decompress() {
+            let point = point.mul_by_cofactor();
+            if !point.is_identity() {
+                return point;
+            }
+        }
+    }
+}
+
+/// The ghost verifying keys of the ring as curve points, rejecting keys outside the prime order
+/// subgroup.
+fn ring_points(ring: &[GhostkeyCertificate]) -> Result<Vec<EdwardsPoint>, GhostkeyError> {
+    ring.iter()
+        .map(|member| {
+            // Decompressed here rather than with `VerifyingKey::to_edwards`, which needs
+            // ed25519-dalek 2.2
+            let point = CompressedEdwardsY(member.verifying_key().to_bytes())
+                .decompress()
+                .filter(|point| point.is_torsion_free() && !point.is_identity());
+            point.ok_or_else(|| {
+                GhostkeyError::ValidationError(
+                    "Ghost verifying key in the pseudonym ring is not in the prime order subgroup"
+                        .to_string(),
+                )
+            })
+        })
+        .collect()
+}
+
+fn proof_prefix(
+    context: &str,
+    ring: &[GhostkeyCertificate],
+    verifying_key: &VerifyingKey,
+    tag: &[u8; 32],
+) -> Result<[u8; 32], GhostkeyError> {
+    context_hash(
+        PSEUDONYM_PROOF_CONTEXT,
+        &ProofStatement {
+            context,
+            ring: ring.iter().map(|member| *member.verifying_key()).collect(),
+            verifying_key,
+            tag,
+        },
+    )
+}
+
+/// The commitments `s·B + c·A` and `s·H + c·I` of one ring member.
+fn commitments(
+    key: &EdwardsPoint,
+    base: &EdwardsPoint,
+    tag: &EdwardsPoint,
+    challenge: &Scalar,
+    response: &Scalar,
+) -> (EdwardsPoint, EdwardsPoint) {
+    (
+        EdwardsPoint::vartime_double_scalar_mul_basepoint(challenge, key, response),
+        response * base + challenge * tag,
+    )
+}
+
+fn challenge(prefix: &[u8; 32], l: &EdwardsPoint, r: &EdwardsPoint) -> Scalar {
+    let mut hasher = blake3::Hasher::new_derive_key(PSEUDONYM_PROOF_CONTEXT);
+    hasher.update(prefix);
+    hasher.update(l.compress().as_bytes());
+    hasher.update(r.compress().as_bytes());
+    let mut wide = [0u8; 64];
+    hasher.finalize_xof().fill(&mut wide);
+    Scalar::from_bytes_mod_order_wide(&wide)
+}
+
+fn canonical_scalar(bytes: &[u8; 32]) -> Option<Scalar> {
+    Option::from(Scalar::from_canonical_bytes(*bytes))
+}
+
+#[cfg(feature = "issue")]
+fn random_scalar<R: RngCore + CryptoRng>(rng: &mut R) -> Scalar {
+    let mut wide = [0u8; 64];
+    rng.fill_bytes(&mut wide);
+    Scalar::from_bytes_mod_order_wide(&wide)
+}
+
+#[cfg(all(test, feature = "issue"))]
+mod tests {
+    use super::*;
+    use crate::armorable::Armorable;
+    use crate::test_util::issue_ghost_keys;
+
+    #[test]
+    fn test_pseudonym_certificate() {
+        let (trust_store, ghost_keys) = issue_ghost_keys(3);
+        let ring: Vec<GhostkeyCertificate> = ghost_keys.iter().map(|(certificate, _)| certificate.clone()).collect();
+        let (certificate, signing_key) = &ghost_keys[1];
+
+        let (pseudonym, pseudonym_signing_key) =
+            PseudonymCertificateV1::new(certificate.clone(), signing_key, "test poll", &ring).unwrap();
+        let pseudonym =
+            PseudonymCertificateV1::from_armored_string(&pseudonym.to_armored_string().unwrap()).unwrap();
+        let info = pseudonym.verify(&trust_store).unwrap();
+        assert_eq!(info.delegate_info.info, "Test Delegate");
+        assert_eq!(info.ring_size, 3);
+        assert_eq!(info.verifying_key, pseudonym_signing_key.verifying_key());
+
+        // Stable within a context whatever the ring, and different in another context
+        let (again, again_signing_key) =
+            PseudonymCertificateV1::new(certificate.clone(), signing_key, "test poll", &ring[..2]).unwrap();
+        let again_info = again.verify(&trust_store).unwrap();
+        assert_eq!(again_info.ring_size, 2);
+        assert_eq!(again_info.pseudonym_id, info.pseudonym_id);
+        assert_eq!(again_signing_key.verifying_key(), info.verifying_key);
+        let (again, _) =
+            PseudonymCertificateV1::new(certificate.clone(), signing_key, "test poll", &ring[1..]).unwrap();
+        assert_eq!(again.verify(&trust_store).unwrap().pseudonym_id, info.pseudonym_id);
+        let (other, other_signing_key) =
+            PseudonymCertificateV1::new(certificate.clone(), signing_key, "other poll", &ring).unwrap();
+        assert_ne!(other.verify(&trust_store).unwrap().pseudonym_id, info.pseudonym_id);
+        assert_ne!(other_signing_key.verifying_key(), info.verifying_key);
+
+        // Another ghost key in the ring gets another pseudonym
+        let (certificate, signing_key) = &ghost_keys[2];
+        let (another, _) = PseudonymCertificateV1::new(certificate.clone(), signing_key, "test poll", &ring).unwrap();
+        assert_ne!(another.verify(&trust_store).unwrap().pseudonym_id, info.pseudonym_id);
+
+        // The proof is bound to the pseudonym key and the tag
+        let mut tampered = pseudonym.clone();
+        tampered.verifying_key = other_signing_key.verifying_key();
+        assert_eq!(tampered.verify(&trust_store).unwrap_err().code(), "GK_SIG_PSEUDONYM_INVALID");
+        let mut tampered = pseudonym.clone();
+        tampered.tag = other.tag;
+        assert_eq!(tampered.verify(&trust_store).unwrap_err().code(), "GK_SIG_PSEUDONYM_INVALID");
+
+        // Every ghost key in the ring is checked up to the master key
+        let (other_trust_store, _) = issue_ghost_keys(0);
+        assert_eq!(
+            pseudonym.verify(&other_trust_store).unwrap_err().code(),
+            "GK_SIG_DELEGATE_INVALID"
+        );
+    }
+
+    #[test]
+    fn test_pseudonym_ring_of_one_is_rejected() {
+        let (trust_store, ghost_keys) = issue_ghost_keys(2);
+        let ring: Vec<GhostkeyCertificate> = ghost_keys.iter().map(|(certificate, _)| certificate.clone()).collect();
+        let (certificate, signing_key) = &ghost_keys[0];
+
+        // The holder's own ghost key doesn't count twice
+        for ring in [&[][..], &[certificate.clone()][..]] {
+            let result = PseudonymCertificateV1::new(certificate.clone(), signing_key, "test poll", ring);
+            assert_eq!(result.err().unwrap().code(), "GK_INVALID_INPUT");
+        }
+
+        let (mut pseudonym, _) =
+            PseudonymCertificateV1::new(certificate.clone(), signing_key, "test poll", &ring).unwrap();
+        pseudonym.ring = alloc::vec![SignedMessageCertificate::from(certificate.clone())];
+        pseudonym.responses.truncate(32);
+        assert_eq!(pseudonym.verify(&trust_store).unwrap_err().code(), "GK_VALIDATION_FAILED");
+    }
+
+    #[test]
+    fn test_pseudonym_ring_from_one_delegate() {
+        let (_, ghost_keys_1) = issue_ghost_keys(1);
+        let (_, ghost_keys_2) = issue_ghost_keys(1);
+        let (certificate, signing_key) = &ghost_keys_1[0];
+        let result =
+            PseudonymCertificateV1::new(certificate.clone(), signing_key, "test poll", &[ghost_keys_2[0].0.clone()]);
+        assert_eq!(result.err().unwrap().code(), "GK_DELEGATE_MISMATCH");
+    }
+
+    #[test]
+    fn test_pseudonym_message() {
+        let (trust_store, ghost_keys) = issue_ghost_keys(2);
+        let ring: Vec<GhostkeyCertificate> = ghost_keys.iter().map(|(certificate, _)| certificate.clone()).collect();
+        let (certificate, signing_key) = &ghost_keys[0];
+        let (pseudonym, pseudonym_signing_key) =
+            PseudonymCertificateV1::new(certificate.clone(), signing_key, "test poll", &ring).unwrap();
+
+        let message = PseudonymMessageV1::new(pseudonym.clone(), &pseudonym_signing_key, b"Yes").unwrap();
+        let message = PseudonymMessageV1::from_bytes(&message.to_bytes().unwrap()).unwrap();
+        assert_eq!(message.verify(&trust_store).unwrap().context, "test poll");
+
+        let mut tampered = message.clone();
+        tampered.message = b"No".to_vec();
+        assert_eq!(tampered.verify(&trust_store).unwrap_err().code(), "GK_SIG_PSEUDONYM_INVALID");
+
+        // Only the pseudonym signing key can sign for the pseudonym
+        assert!(PseudonymMessageV1::new(pseudonym, signing_key, b"Yes").is_err());
+    }
+}
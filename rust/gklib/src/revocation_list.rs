@@ -0,0 +1,464 @@
+use super::delegate_certificate::{DelegateCertificate, DelegateInfo};
+use super::errors::{GhostkeyError, SignatureKind};
+use super::fingerprint::{Fingerprint, Fingerprinted};
+use super::errors::GhostkeyError::{DelegateRevokedError, SignatureVerificationError, ValidityPeriodError};
+use super::ghost_key_certificate::GhostkeyCertificate;
+use super::pseudonym::{PseudonymCertificateV1, PseudonymInfo, PseudonymMessageV1};
+use super::signed_message::{SignedMessage, VerifiedMessage};
+use super::subkey::{SubkeyCertificateV1, SubkeyInfo};
+use super::trust_store::TrustStore;
+use super::delegate_key::PublicKey as RSAVerifyingKey;
+use super::util::sign_with_context;
+#[cfg(feature = "std")]
+use super::util::current_unix_time;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use ed25519_dalek::*;
+use serde::{Deserialize, Serialize};
+
+/// BLAKE3 key derivation context under which revocation lists are signed.
+pub const REVOCATION_LIST_CONTEXT: &str = "freenet.org ghostkey 2024-10 delegate revocation list";
+
+/// A list of revoked delegate keys, signed by the master signing key. Ghost keys issued by a
+/// revoked delegate are rejected by [`DelegateRevocationListV1::verify_ghost_key_certificate`].
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DelegateRevocationListV1 {
+    pub payload: RevocationListPayload,
+    /// The payload signed by the master signing key with
+    /// [`sign_with_context`](crate::util::sign_with_context) under [`REVOCATION_LIST_CONTEXT`]
+    pub signature: Signature,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct RevocationListPayload {
+    pub revoked: Vec<RevokedDelegate>,
+    /// Unix time (seconds) at which the list was issued
+    pub issued_at: u64,
+    /// Unix time (seconds) by which a newer list will be issued, the list is considered stale
+    /// after this time
+    pub next_update: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RevokedDelegate {
+    /// Fingerprint of the revoked delegate verifying key
+    pub fingerprint: Fingerprint,
+    /// Unix time (seconds) at which the delegate was revoked
+    pub revoked_at: u64,
+    pub reason: String,
+}
+
+impl RevokedDelegate {
+    pub fn new(
+        delegate_certificate: &DelegateCertificate,
+        revoked_at: u64,
+        reason: &str,
+    ) -> Result<Self, GhostkeyError> {
+        Ok(RevokedDelegate {
+            fingerprint: delegate_certificate.payload().delegate_verifying_key().fingerprint()?,
+            revoked_at,
+            reason: reason.to_string(),
+        })
+    }
+}
+
+impl DelegateRevocationListV1 {
+    pub fn new(
+        master_signing_key: &SigningKey,
+        revoked: Vec<RevokedDelegate>,
+        issued_at: u64,
+        next_update: u64,
+    ) -> Result<Self, GhostkeyError> {
+        if next_update < issued_at {
+            return Err(GhostkeyError::InvalidInput(format!(
+                "next_update ({}) must not be earlier than issued_at ({})",
+                next_update, issued_at
+            )));
+        }
+
+        let payload = RevocationListPayload {
+            revoked,
+            issued_at,
+            next_update,
+        };
+
+        let signature = sign_with_context(master_signing_key, REVOCATION_LIST_CONTEXT, &payload)?;
+
+        Ok(DelegateRevocationListV1 { payload, signature })
+    }
+
+    /// Verifies the revocation list against the master verifying keys in the trust store and
+    /// checks that it is not stale.
+    #[cfg(feature = "std")]
+    pub fn verify(
+        &self,
+        trust_store: &TrustStore,
+    ) -> Result<(), GhostkeyError> {
+        self.verify_at(current_unix_time(), trust_store)
+    }
+
+    /// Like [`verify`](Self::verify), but checks staleness against `time` (seconds since the
+    /// Unix epoch) rather than the current time.
+    pub fn verify_at(
+        &self,
+        time: u64,
+        trust_store: &TrustStore,
+    ) -> Result<(), GhostkeyError> {
+        if trust_store
+            .find_signer_with_context(REVOCATION_LIST_CONTEXT, &self.payload, &self.signature)?
+            .is_none()
+        {
+            return Err(SignatureVerificationError(
+                SignatureKind::RevocationList,
+                "Failed to verify revocation list".to_string(),
+            ));
+        }
+
+        if time > self.payload.next_update {
+            return Err(ValidityPeriodError(format!(
+                "Revocation list is stale, it should have been replaced at {} (time is {})",
+                self.payload.next_update, time
+            )));
+        }
+        Ok(())
+    }
+
+    /// Returns the revocation entry for the delegate, if it has been revoked. The signature of
+    /// the list is not checked.
+    pub fn find_revoked(
+        &self,
+        delegate_certificate: &DelegateCertificate,
+    ) -> Result<Option<&RevokedDelegate>, GhostkeyError> {
+        self.find_revoked_key(delegate_certificate.payload().delegate_verifying_key())
+    }
+
+    /// Returns the revocation entry for the delegate verifying key, if it has been revoked.
+    pub fn find_revoked_key(
+        &self,
+        delegate_verifying_key: &RSAVerifyingKey,
+    ) -> Result<Option<&RevokedDelegate>, GhostkeyError> {
+        let fingerprint = delegate_verifying_key.fingerprint()?;
+        Ok(self
+            .payload
+            .revoked
+            .iter()
+            .find(|revoked| revoked.fingerprint == fingerprint))
+    }
+
+    /// Verifies the revocation list and the delegate certificate at `time`, and checks that the
+    /// delegate has not been revoked. Returns the delegate info on success.
+    pub fn verify_delegate_certificate_at(
+        &self,
+        time: u64,
+        delegate_certificate: &DelegateCertificate,
+        trust_store: &TrustStore,
+    ) -> Result<DelegateInfo, GhostkeyError> {
+        self.verify_at(time, trust_store)?;
+        let info = delegate_certificate.verify_at(time, trust_store)?;
+        self.check_not_revoked(delegate_certificate.payload().delegate_verifying_key())?;
+        Ok(info)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn verify_delegate_certificate(
+        &self,
+        delegate_certificate: &DelegateCertificate,
+        trust_store: &TrustStore,
+    ) -> Result<DelegateInfo, GhostkeyError> {
+        self.verify_delegate_certificate_at(current_unix_time(), delegate_certificate, trust_store)
+    }
+
+    /// Verifies the revocation list and the ghost key certificate chain at `time`, and checks
+    /// that the delegate that issued the ghost key has not been revoked. Returns the delegate info
+    /// on success.
+    pub fn verify_ghost_key_certificate_at(
+        &self,
+        time: u64,
+        ghost_key_certificate: &GhostkeyCertificate,
+        trust_store: &TrustStore,
+    ) -> Result<DelegateInfo, GhostkeyError> {
+        self.verify_at(time, trust_store)?;
+        let info = ghost_key_certificate.verify_at(time, trust_store)?;
+        self.check_not_revoked(ghost_key_certificate.delegate_payload().delegate_verifying_key())?;
+        Ok(info)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn verify_ghost_key_certificate(
+        &self,
+        ghost_key_certificate: &GhostkeyCertificate,
+        trust_store: &TrustStore,
+    ) -> Result<DelegateInfo, GhostkeyError> {
+        self.verify_ghost_key_certificate_at(current_unix_time(), ghost_key_certificate, trust_store)
+    }
+
+    /// Verifies the revocation list and the signed message at `time` as
+    /// [`SignedMessage::verify_at`] does, and checks that the delegate that issued the signing
+    /// ghost key has not been revoked.
+    pub fn verify_signed_message_at(
+        &self,
+        time: u64,
+        signed_message: &SignedMessage,
+        trust_store: &TrustStore,
+        message: Option<&[u8]>,
+    ) -> Result<VerifiedMessage, GhostkeyError> {
+        let certificate = signed_message.certificate();
+        let verification = self.verify_ghost_key_certificate_at(time, &certificate, trust_store);
+        signed_message.verify_with_certificate(time, verification, &certificate, message, false)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn verify_signed_message(
+        &self,
+        signed_message: &SignedMessage,
+        trust_store: &TrustStore,
+        message: Option<&[u8]>,
+    ) -> Result<VerifiedMessage, GhostkeyError> {
+        self.verify_signed_message_at(current_unix_time(), signed_message, trust_store, message)
+    }
+
+    /// Like [`verify_signed_message_at`](Self::verify_signed_message_at), also accepting V1
+    /// messages as [`SignedMessage::verify_legacy_at`] does.
+    pub fn verify_legacy_signed_message_at(
+        &self,
+        time: u64,
+        signed_message: &SignedMessage,
+        trust_store: &TrustStore,
+        message: Option<&[u8]>,
+    ) -> Result<VerifiedMessage, GhostkeyError> {
+        let certificate = signed_message.certificate();
+        let verification = self.verify_ghost_key_certificate_at(time, &certificate, trust_store);
+        signed_message.verify_with_certificate(time, verification, &certificate, message, true)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn verify_legacy_signed_message(
+        &self,
+        signed_message: &SignedMessage,
+        trust_store: &TrustStore,
+        message: Option<&[u8]>,
+    ) -> Result<VerifiedMessage, GhostkeyError> {
+        self.verify_legacy_signed_message_at(current_unix_time(), signed_message, trust_store, message)
+    }
+
+    /// Verifies the revocation list and the subkey certificate at `time` as
+    /// [`SubkeyCertificateV1::verify_at`] does, and checks that the delegate that issued the
+    /// certifying ghost key has not been revoked.
+    pub fn verify_subkey_certificate_at(
+        &self,
+        time: u64,
+        subkey_certificate: &SubkeyCertificateV1,
+        trust_store: &TrustStore,
+    ) -> Result<SubkeyInfo, GhostkeyError> {
+        let certificate = subkey_certificate.ghost_key_certificate();
+        let verification = self.verify_ghost_key_certificate_at(time, &certificate, trust_store);
+        subkey_certificate.verify_with_certificate(time, verification, &certificate)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn verify_subkey_certificate(
+        &self,
+        subkey_certificate: &SubkeyCertificateV1,
+        trust_store: &TrustStore,
+    ) -> Result<SubkeyInfo, GhostkeyError> {
+        self.verify_subkey_certificate_at(current_unix_time(), subkey_certificate, trust_store)
+    }
+
+    /// Verifies the revocation list and the pseudonym certificate at `time` as
+    /// [`PseudonymCertificateV1::verify_at`] does, and checks that the delegate that issued the
+    /// ghost keys of the ring has not been revoked.
+    pub fn verify_pseudonym_certificate_at(
+        &self,
+        time: u64,
+        pseudonym_certificate: &PseudonymCertificateV1,
+        trust_store: &TrustStore,
+    ) -> Result<PseudonymInfo, GhostkeyError> {
+        self.verify_at(time, trust_store)?;
+        pseudonym_certificate.verify_with(|certificate| self.verify_ring_member(time, certificate, trust_store))
+    }
+
+    #[cfg(feature = "std")]
+    pub fn verify_pseudonym_certificate(
+        &self,
+        pseudonym_certificate: &PseudonymCertificateV1,
+        trust_store: &TrustStore,
+    ) -> Result<PseudonymInfo, GhostkeyError> {
+        self.verify_pseudonym_certificate_at(current_unix_time(), pseudonym_certificate, trust_store)
+    }
+
+    /// Like [`verify_pseudonym_certificate_at`](Self::verify_pseudonym_certificate_at) for a
+    /// message signed by a pseudonym.
+    pub fn verify_pseudonym_message_at(
+        &self,
+        time: u64,
+        pseudonym_message: &PseudonymMessageV1,
+        trust_store: &TrustStore,
+    ) -> Result<PseudonymInfo, GhostkeyError> {
+        self.verify_at(time, trust_store)?;
+        pseudonym_message.verify_with(|certificate| self.verify_ring_member(time, certificate, trust_store))
+    }
+
+    #[cfg(feature = "std")]
+    pub fn verify_pseudonym_message(
+        &self,
+        pseudonym_message: &PseudonymMessageV1,
+        trust_store: &TrustStore,
+    ) -> Result<PseudonymInfo, GhostkeyError> {
+        self.verify_pseudonym_message_at(current_unix_time(), pseudonym_message, trust_store)
+    }
+
+    fn verify_ring_member(
+        &self,
+        time: u64,
+        ghost_key_certificate: &GhostkeyCertificate,
+        trust_store: &TrustStore,
+    ) -> Result<DelegateInfo, GhostkeyError> {
+        let info = ghost_key_certificate.verify_at(time, trust_store)?;
+        self.check_not_revoked(ghost_key_certificate.delegate_payload().delegate_verifying_key())?;
+        Ok(info)
+    }
+
+    fn check_not_revoked(
+        &self,
+        delegate_verifying_key: &RSAVerifyingKey,
+    ) -> Result<(), GhostkeyError> {
+        match self.find_revoked_key(delegate_verifying_key)? {
+            Some(revoked) => Err(DelegateRevokedError(format!(
+                "Delegate was revoked at {}: {}",
+                revoked.revoked_at, revoked.reason
+            ))),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "issue"))]
+mod tests {
+    use super::*;
+    use crate::armorable::Armorable;
+    use crate::delegate_certificate::{DelegateCertificateV1, DelegateCertificateV2};
+    use crate::util::create_keypair;
+    use rand_core::OsRng;
+
+    #[test]
+    fn test_revocation_list_creation_and_verification() {
+        let (master_signing_key, master_verifying_key) = create_keypair(&mut OsRng).unwrap();
+
+        let revocation_list =
+            DelegateRevocationListV1::new(&master_signing_key, vec![], 1000, 2000).unwrap();
+
+        assert!(revocation_list.verify_at(1500, &TrustStore::from(master_verifying_key)).is_ok());
+
+        // Stale list
+        let result = revocation_list.verify_at(2001, &TrustStore::from(master_verifying_key));
+        assert!(matches!(
+            result.unwrap_err(),
+            ValidityPeriodError(_)
+        ));
+
+        // Wrong master key
+        let (_, wrong_verifying_key) = create_keypair(&mut OsRng).unwrap();
+        let result = revocation_list.verify_at(1500, &TrustStore::from(wrong_verifying_key));
+        assert!(matches!(
+            result.unwrap_err(),
+            SignatureVerificationError(..)
+        ));
+    }
+
+    #[test]
+    fn test_revoked_delegate_rejects_ghost_key() {
+        let (master_signing_key, master_verifying_key) = create_keypair(&mut OsRng).unwrap();
+
+        let info = "Test Delegate".to_string();
+        let (revoked_delegate, revoked_signing_key) =
+            DelegateCertificateV1::new(&master_signing_key, &info).unwrap();
+        let revoked_delegate = DelegateCertificate::from(revoked_delegate);
+        let (other_delegate, other_signing_key) =
+            DelegateCertificateV2::new(&master_signing_key, &info).unwrap();
+        let other_delegate = DelegateCertificate::from(other_delegate);
+
+        let (revoked_ghost_key, _) =
+            GhostkeyCertificate::new(&revoked_delegate, &revoked_signing_key).unwrap();
+        let (other_ghost_key, _) = GhostkeyCertificate::new(&other_delegate, &other_signing_key).unwrap();
+
+        let revocation_list = DelegateRevocationListV1::new(
+            &master_signing_key,
+            vec![RevokedDelegate::new(&revoked_delegate, 1200, "Key leaked").unwrap()],
+            1000,
+            2000,
+        )
+        .unwrap();
+
+        // Entries are keyed by the fingerprint the CLI prints for the delegate key
+        assert_eq!(
+            revocation_list.find_revoked(&revoked_delegate).unwrap().unwrap().fingerprint,
+            revoked_delegate.payload().delegate_verifying_key().fingerprint().unwrap()
+        );
+        assert!(revocation_list.find_revoked(&other_delegate).unwrap().is_none());
+
+        let result = revocation_list.verify_ghost_key_certificate_at(
+            1500,
+            &revoked_ghost_key,
+            &TrustStore::from(master_verifying_key),
+        );
+        assert!(matches!(
+            result.unwrap_err(),
+            DelegateRevokedError(_)
+        ));
+
+        let result = revocation_list.verify_delegate_certificate_at(
+            1500,
+            &revoked_delegate,
+            &TrustStore::from(master_verifying_key),
+        );
+        assert!(matches!(
+            result.unwrap_err(),
+            DelegateRevokedError(_)
+        ));
+
+        let verified_info = revocation_list
+            .verify_ghost_key_certificate_at(1500, &other_ghost_key, &TrustStore::from(master_verifying_key))
+            .unwrap();
+        assert_eq!(verified_info.info, info);
+    }
+
+    #[test]
+    fn test_revocation_list_tampered() {
+        let (master_signing_key, master_verifying_key) = create_keypair(&mut OsRng).unwrap();
+
+        let info = "Test Delegate".to_string();
+        let (revoked_delegate, _) = DelegateCertificateV2::new(&master_signing_key, &info).unwrap();
+        let revoked_delegate = DelegateCertificate::from(revoked_delegate);
+
+        let mut revocation_list = DelegateRevocationListV1::new(
+            &master_signing_key,
+            vec![RevokedDelegate::new(&revoked_delegate, 1200, "Key leaked").unwrap()],
+            1000,
+            2000,
+        )
+        .unwrap();
+
+        // Removing an entry must invalidate the signature
+        revocation_list.payload.revoked.clear();
+        let result = revocation_list.verify_at(1500, &TrustStore::from(master_verifying_key));
+        assert!(matches!(
+            result.unwrap_err(),
+            SignatureVerificationError(..)
+        ));
+    }
+
+    #[test]
+    fn test_revocation_list_armor_round_trip() {
+        let (master_signing_key, master_verifying_key) = create_keypair(&mut OsRng).unwrap();
+
+        let revocation_list =
+            DelegateRevocationListV1::new(&master_signing_key, vec![], 1000, 2000).unwrap();
+
+        let armored = revocation_list.to_armored_string().unwrap();
+        assert!(armored.contains("DELEGATE_REVOCATION_LIST_V1"));
+
+        let decoded = DelegateRevocationListV1::from_armored_string(&armored).unwrap();
+        assert!(decoded.verify_at(1500, &TrustStore::from(master_verifying_key)).is_ok());
+    }
+}
@@ -0,0 +1,238 @@
+//! Backup of private keys as Shamir secret shares. An armored private key, such as the master
+//! signing key, is split into N shares kept by different custodians, any K of which rebuild it
+//! while fewer reveal nothing about it. Each byte of the key is shared over GF(2^8) with its own
+//! random polynomial of degree K - 1, as in SLIP-39 and `ssss`.
+
+use super::errors::GhostkeyError;
+use super::errors::GhostkeyError::{InvalidInput, ValidationError};
+use super::fingerprint::{private_key_fingerprint, Fingerprint};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use rand_core::{CryptoRng, OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+
+/// One share of a private key. Shares of a single key can't be told apart from random bytes,
+/// but enough of them rebuild the key, so each must be kept as carefully as the key itself.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PrivateKeyShareV1 {
+    /// The armor label of the shared key, e.g. `SIGNING_KEY_V1` or `ENCRYPTED_KEY_V1`
+    pub key_type: String,
+    /// The fingerprint of the shared key, see [`key_fingerprint`]
+    pub fingerprint: Fingerprint,
+    /// How many shares rebuild the key
+    pub threshold: u8,
+    /// How many shares the key was split into
+    pub shares: u8,
+    /// Which share this is, from 1 to `shares`
+    pub index: u8,
+    #[serde(with = "serde_bytes")]
+    pub share: Vec<u8>,
+}
+
+/// The fingerprint shares carry of the key they rebuild: that of its public key for signing
+/// keys, so it matches the one printed when the key was generated, and otherwise a fingerprint
+/// of the armored key itself.
+pub fn key_fingerprint(key_type: &str, key: &[u8]) -> Result<Fingerprint, GhostkeyError> {
+    private_key_fingerprint(key_type, key).unwrap_or_else(|| {
+        Fingerprint::compute("private key", &(key_type, serde_bytes::Bytes::new(key)))
+    })
+}
+
+impl PrivateKeyShareV1 {
+    /// Splits the armored private key with label `key_type` and contents `key` into `shares`
+    /// shares, any `threshold` of which rebuild it with [`combine`](Self::combine).
+    pub fn split(key_type: &str, key: &[u8], threshold: u8, shares: u8) -> Result<Vec<Self>, GhostkeyError> {
+        Self::split_with_rng(&mut OsRng, key_type, key, threshold, shares)
+    }
+
+    /// Like [`split`](Self::split), drawing the coefficients of the polynomials from `rng`.
+    pub fn split_with_rng<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        key_type: &str,
+        key: &[u8],
+        threshold: u8,
+        shares: u8,
+    ) -> Result<Vec<Self>, GhostkeyError> {
+        if threshold < 2 || threshold > shares {
+            return Err(InvalidInput(format!(
+                "Threshold must be at least 2 and at most the number of shares, got {} of {}",
+                threshold, shares
+            )));
+        }
+        if key.is_empty() {
+            return Err(InvalidInput("Key to split is empty".to_string()));
+        }
+        let fingerprint = key_fingerprint(key_type, key)?;
+
+        // One polynomial per byte of the key, its constant term being the byte
+        let mut coefficients = vec![0u8; key.len() * (threshold as usize - 1)];
+        rng.fill_bytes(&mut coefficients);
+        let polynomials: Vec<&[u8]> = coefficients.chunks(threshold as usize - 1).collect();
+
+        Ok((1..=shares)
+            .map(|index| {
+                let share = key
+                    .iter()
+                    .zip(&polynomials)
+                    .map(|(byte, polynomial)| {
+                        // Horner's method, highest coefficient first
+                        polynomial
+                            .iter()
+                            .rev()
+                            .chain(core::iter::once(byte))
+                            .fold(0u8, |value, coefficient| gf_mul(value, index) ^ coefficient)
+                    })
+                    .collect();
+                PrivateKeyShareV1 {
+                    key_type: key_type.to_string(),
+                    fingerprint,
+                    threshold,
+                    shares,
+                    index,
+                    share,
+                }
+            })
+            .collect())
+    }
+
+    /// Rebuilds the key from at least `threshold` shares of it, returning its armor label and
+    /// contents. Fails if the shares belong to different keys, are too few, or don't rebuild a
+    /// key with the fingerprint they carry, as when one of them is corrupted.
+    pub fn combine(shares: &[PrivateKeyShareV1]) -> Result<(String, Vec<u8>), GhostkeyError> {
+        let first = shares
+            .first()
+            .ok_or_else(|| InvalidInput("No shares to combine".to_string()))?;
+        for share in shares {
+            if share.key_type != first.key_type
+                || share.fingerprint != first.fingerprint
+                || share.threshold != first.threshold
+                || share.share.len() != first.share.len()
+            {
+                return Err(InvalidInput(format!(
+                    "Share {} is of a different key than share {}",
+                    share.index, first.index
+                )));
+            }
+            if share.index == 0 {
+                return Err(InvalidInput("Share index must not be 0".to_string()));
+            }
+        }
+        let mut indices: Vec<u8> = shares.iter().map(|share| share.index).collect();
+        indices.sort_unstable();
+        if let Some(pair) = indices.windows(2).find(|pair| pair[0] == pair[1]) {
+            return Err(InvalidInput(format!("Share {} was given more than once", pair[0])));
+        }
+        if shares.len() < first.threshold as usize {
+            return Err(InvalidInput(format!(
+                "{} shares are needed to rebuild the key, got {}",
+                first.threshold,
+                shares.len()
+            )));
+        }
+
+        // Lagrange interpolation at 0. In GF(2^8) subtraction is addition, so the basis
+        // polynomial of share i at 0 is the product of x_j / (x_j + x_i) over the other shares.
+        let mut key = vec![0u8; first.share.len()];
+        for share in shares {
+            let basis = shares
+                .iter()
+                .filter(|other| other.index != share.index)
+                .fold(1u8, |basis, other| {
+                    gf_mul(basis, gf_mul(other.index, gf_inv(other.index ^ share.index)))
+                });
+            for (byte, value) in key.iter_mut().zip(&share.share) {
+                *byte ^= gf_mul(basis, *value);
+            }
+        }
+
+        if key_fingerprint(&first.key_type, &key).ok() != Some(first.fingerprint) {
+            return Err(ValidationError(
+                "Rebuilt key does not match the fingerprint of the shares".to_string(),
+            ));
+        }
+        Ok((first.key_type.clone(), key))
+    }
+}
+
+/// Multiplication in GF(2^8) modulo the AES polynomial x^8 + x^4 + x^3 + x + 1, without
+/// branching on either operand.
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0u8;
+    for _ in 0..8 {
+        product ^= a & 0u8.wrapping_sub(b & 1);
+        let carry = a >> 7;
+        a = (a << 1) ^ (0x1b & 0u8.wrapping_sub(carry));
+        b >>= 1;
+    }
+    product
+}
+
+/// The multiplicative inverse in GF(2^8), a^254 since a^255 = 1 for nonzero a.
+fn gf_inv(a: u8) -> u8 {
+    let mut result = 1u8;
+    let mut power = a;
+    let mut exponent = 254u8;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = gf_mul(result, power);
+        }
+        power = gf_mul(power, power);
+        exponent >>= 1;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::armorable::Armorable;
+    use crate::fingerprint::Fingerprinted;
+    use crate::util::create_keypair;
+    use ed25519_dalek::SigningKey;
+
+    #[test]
+    fn test_field_arithmetic() {
+        // The example from FIPS 197, section 4.2
+        assert_eq!(gf_mul(0x57, 0x83), 0xc1);
+        for a in 1..=255u8 {
+            assert_eq!(gf_mul(a, gf_inv(a)), 1);
+        }
+    }
+
+    #[test]
+    fn test_split_and_combine() {
+        let (master_signing_key, master_verifying_key) = create_keypair(&mut OsRng).unwrap();
+        let key = <SigningKey as Armorable>::to_bytes(&master_signing_key).unwrap();
+        let key_type = SigningKey::struct_name();
+        let shares = PrivateKeyShareV1::split(&key_type, &key, 3, 5).unwrap();
+        assert_eq!(shares.len(), 5);
+        assert_eq!(shares[0].fingerprint, master_verifying_key.fingerprint().unwrap());
+
+        // Any three shares rebuild the key, in any order
+        for chosen in [[0, 1, 2], [4, 2, 0], [1, 3, 4]] {
+            let chosen: Vec<_> = chosen.iter().map(|&i| shares[i].clone()).collect();
+            assert_eq!(PrivateKeyShareV1::combine(&chosen).unwrap(), (key_type.clone(), key.clone()));
+        }
+        assert_eq!(PrivateKeyShareV1::combine(&shares).unwrap().1, key);
+
+        assert_eq!(PrivateKeyShareV1::combine(&shares[..2]).unwrap_err().code(), "GK_INVALID_INPUT");
+        let repeated = [shares[0].clone(), shares[1].clone(), shares[0].clone()];
+        assert_eq!(PrivateKeyShareV1::combine(&repeated).unwrap_err().code(), "GK_INVALID_INPUT");
+
+        // A corrupted share rebuilds the wrong key
+        let mut corrupted = shares[..3].to_vec();
+        corrupted[1].share[0] ^= 1;
+        assert_eq!(PrivateKeyShareV1::combine(&corrupted).unwrap_err().code(), "GK_VALIDATION_FAILED");
+
+        // Shares of different keys don't mix
+        let other = PrivateKeyShareV1::split("OTHER_KEY", &[1, 2, 3], 3, 5).unwrap();
+        let mixed = [shares[0].clone(), shares[1].clone(), other[2].clone()];
+        assert_eq!(PrivateKeyShareV1::combine(&mixed).unwrap_err().code(), "GK_INVALID_INPUT");
+        assert_eq!(PrivateKeyShareV1::combine(&other[2..]).unwrap(), ("OTHER_KEY".to_string(), vec![1, 2, 3]));
+
+        assert_eq!(PrivateKeyShareV1::split(&key_type, &key, 1, 5).err().map(|e| e.code()), Some("GK_INVALID_INPUT"));
+        assert_eq!(PrivateKeyShareV1::split(&key_type, &key, 4, 3).err().map(|e| e.code()), Some("GK_INVALID_INPUT"));
+    }
+}
@@ -0,0 +1,148 @@
+//! Backup of ghost keys as a recovery phrase. The website derives each ghost key from a random
+//! 32-byte seed, so writing the seed down as 24 BIP-39 words is enough to rebuild the ghost
+//! signing key if the key file or the browser storage is lost, the certificate itself being
+//! public.
+
+use super::errors::GhostkeyError;
+use super::errors::GhostkeyError::InvalidInput;
+use super::ghost_key_certificate::GhostkeyCertificate;
+use super::util::create_keypair;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use bip39::{Language, Mnemonic};
+use ed25519_dalek::SigningKey;
+use rand_chacha::rand_core::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use rand_core::{CryptoRng, OsRng, RngCore};
+
+/// Number of words in the recovery phrase of a seed.
+pub const MNEMONIC_WORDS: usize = 24;
+
+/// The seed a ghost key is derived from. The ghost signing key is the first key drawn from a
+/// ChaCha20 generator seeded with it, as in
+/// [`BlindingState::new_with_rng`](crate::issuance::BlindingState::new_with_rng). Like the
+/// signing key it must be kept secret.
+#[derive(Clone)]
+pub struct GhostkeySeed([u8; 32]);
+
+impl GhostkeySeed {
+    pub fn generate() -> Self {
+        Self::generate_with_rng(&mut OsRng)
+    }
+
+    /// Like [`generate`](Self::generate), drawing the seed from `rng`.
+    pub fn generate_with_rng<R: RngCore + CryptoRng>(rng: &mut R) -> Self {
+        let mut seed = [0u8; 32];
+        rng.fill_bytes(&mut seed);
+        GhostkeySeed(seed)
+    }
+
+    pub fn from_bytes(seed: [u8; 32]) -> Self {
+        GhostkeySeed(seed)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// The recovery phrase, 24 English BIP-39 words separated by spaces, the last of which
+    /// carries a checksum.
+    pub fn to_mnemonic(&self) -> String {
+        Mnemonic::from_entropy_in(Language::English, &self.0)
+            .expect("32 bytes is a valid entropy length")
+            .to_string()
+    }
+
+    /// Decodes a recovery phrase, ignoring case and extra whitespace. Fails if a word is not in
+    /// the word list, the phrase has the wrong number of words, or the checksum doesn't match.
+    pub fn from_mnemonic(phrase: &str) -> Result<Self, GhostkeyError> {
+        let words: Vec<String> = phrase.split_whitespace().map(|word| word.to_lowercase()).collect();
+        if words.len() != MNEMONIC_WORDS {
+            return Err(InvalidInput(format!(
+                "Recovery phrase must have {} words, got {}",
+                MNEMONIC_WORDS,
+                words.len()
+            )));
+        }
+        let mnemonic = Mnemonic::parse_in_normalized(Language::English, &words.join(" "))
+            .map_err(|e| InvalidInput(format!("Invalid recovery phrase: {}", e)))?;
+        let (entropy, length) = mnemonic.to_entropy_array();
+        let seed = entropy[..length]
+            .try_into()
+            .map_err(|_| InvalidInput("Recovery phrase must encode 32 bytes".to_string()))?;
+        Ok(GhostkeySeed(seed))
+    }
+
+    /// The generator the ghost key and its blinding are drawn from when issuing with this seed.
+    pub fn rng(&self) -> ChaCha20Rng {
+        ChaCha20Rng::from_seed(self.0)
+    }
+
+    /// The ghost signing key derived from the seed.
+    pub fn signing_key(&self) -> SigningKey {
+        let (signing_key, _) = create_keypair(&mut self.rng()).expect("key creation is infallible");
+        signing_key
+    }
+
+    /// Rebuilds the ghost signing key for `ghost_key_certificate`, failing if the seed is not
+    /// the one the certificate's ghost key was derived from.
+    pub fn restore(&self, ghost_key_certificate: &GhostkeyCertificate) -> Result<SigningKey, GhostkeyError> {
+        let signing_key = self.signing_key();
+        if signing_key.verifying_key() != *ghost_key_certificate.verifying_key() {
+            return Err(InvalidInput(
+                "Recovery phrase does not match the ghost key certificate".to_string(),
+            ));
+        }
+        Ok(signing_key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::delegate_certificate::{DelegateCertificate, DelegateCertificateV2};
+    use crate::issuance::{BlindIssuer, BlindingState};
+
+    #[test]
+    fn test_mnemonic_round_trip() {
+        let seed = GhostkeySeed::generate();
+        let phrase = seed.to_mnemonic();
+        assert_eq!(phrase.split(' ').count(), MNEMONIC_WORDS);
+        let restored = GhostkeySeed::from_mnemonic(&phrase.to_uppercase().replace(' ', "  \n")).unwrap();
+        assert_eq!(restored.as_bytes(), seed.as_bytes());
+
+        // A swapped word breaks the checksum
+        let mut words: Vec<&str> = phrase.split(' ').collect();
+        words.swap(0, 1);
+        if words[0] != words[1] {
+            assert_eq!(GhostkeySeed::from_mnemonic(&words.join(" ")).err().map(|e| e.code()), Some("GK_INVALID_INPUT"));
+        }
+        assert_eq!(GhostkeySeed::from_mnemonic(&words[..12].join(" ")).err().map(|e| e.code()), Some("GK_INVALID_INPUT"));
+    }
+
+    #[test]
+    fn test_restore_ghost_key() {
+        let (master_signing_key, _) = create_keypair(&mut OsRng).unwrap();
+        let (delegate_certificate, delegate_signing_key) =
+            DelegateCertificateV2::new(&master_signing_key, "Test Delegate").unwrap();
+        let delegate_certificate: DelegateCertificate = delegate_certificate.into();
+        let issuer = BlindIssuer::new(delegate_certificate.clone(), delegate_signing_key).unwrap();
+
+        // Issue as the website does, with a generator seeded by the seed
+        let seed = GhostkeySeed::generate();
+        let (blinding_state, request) =
+            BlindingState::new_with_rng(&mut seed.rng(), delegate_certificate).unwrap();
+        let response = issuer.sign(&request).unwrap();
+        let (ghost_key_certificate, ghost_signing_key) = blinding_state.finalize(&response).unwrap();
+
+        let restored = GhostkeySeed::from_mnemonic(&seed.to_mnemonic())
+            .unwrap()
+            .restore(&ghost_key_certificate)
+            .unwrap();
+        assert_eq!(restored.to_bytes(), ghost_signing_key.to_bytes());
+
+        let other_seed = GhostkeySeed::generate();
+        assert_eq!(other_seed.restore(&ghost_key_certificate).unwrap_err().code(), "GK_INVALID_INPUT");
+    }
+}
@@ -0,0 +1,707 @@
+use super::armorable::{read_versioned_block, Armorable};
+use super::delegate_certificate::DelegateInfo;
+use super::errors::GhostkeyError;
+use super::errors::GhostkeyError::{SignatureVerificationError, ValidityPeriodError};
+use super::errors::SignatureKind;
+use super::ghost_key_certificate::{
+    GhostkeyCertificate, GhostkeyCertificateV1, GhostkeyCertificateV2, GhostkeyCertificateV3,
+    GhostkeyMetadata,
+};
+use super::subkey::{SubkeyCertificateV1, SubkeyPayload};
+use super::trust_store::TrustStore;
+#[cfg(feature = "issue")]
+use super::util::sign_with_context;
+use super::util::verify_with_context;
+#[cfg(feature = "std")]
+use super::util::current_unix_time;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
+use base64::Engine;
+use ed25519_dalek::*;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+/// BLAKE3 key derivation context under which ghost keys sign the fields of a V2 signed message.
+pub const SIGNED_MESSAGE_V2_CONTEXT: &str = "freenet.org ghostkey 2024-10 signed message v2";
+
+/// BLAKE3 key derivation context under which subkeys sign the fields of a V3 signed message.
+pub const SIGNED_MESSAGE_V3_CONTEXT: &str = "freenet.org ghostkey 2024-10 signed message v3";
+
+/// The ghost key certificate of a signed message or subkey certificate. Untagged, so messages signed with a V1
+/// certificate encode exactly as they did before V2 certificates existed. V3 is tried first as
+/// it is the only version carrying metadata.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum SignedMessageCertificate {
+    V3(GhostkeyCertificateV3),
+    V2(GhostkeyCertificateV2),
+    V1(GhostkeyCertificateV1),
+}
+
+impl From<GhostkeyCertificate> for SignedMessageCertificate {
+    fn from(certificate: GhostkeyCertificate) -> Self {
+        match certificate {
+            GhostkeyCertificate::V1(certificate) => SignedMessageCertificate::V1(certificate),
+            GhostkeyCertificate::V2(certificate) => SignedMessageCertificate::V2(certificate),
+            GhostkeyCertificate::V3(certificate) => SignedMessageCertificate::V3(certificate),
+        }
+    }
+}
+
+impl From<SignedMessageCertificate> for GhostkeyCertificate {
+    fn from(certificate: SignedMessageCertificate) -> Self {
+        match certificate {
+            SignedMessageCertificate::V1(certificate) => GhostkeyCertificate::V1(certificate),
+            SignedMessageCertificate::V2(certificate) => GhostkeyCertificate::V2(certificate),
+            SignedMessageCertificate::V3(certificate) => GhostkeyCertificate::V3(certificate),
+        }
+    }
+}
+
+/// A message signed by a ghost key, as written by the CLI before V2 signed messages. The
+/// signature covers only the raw message bytes, so any other signature the ghost key made over
+/// the same bytes, such as an SSH signature, would pass as one. They are therefore only verified
+/// on request, with [`SignedMessage::verify_legacy_at`].
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SignedMessageV1 {
+    pub certificate: SignedMessageCertificate,
+    pub message: Vec<u8>,
+    pub signature: Signature,
+}
+
+/// A message signed by a ghost key. The signature covers the [`context_hash`] under
+/// [`SIGNED_MESSAGE_V2_CONTEXT`] of the purpose, creation time, content type and a BLAKE3 hash
+/// of the message, so it can't be replayed for another purpose and stays valid when the message
+/// is carried separately as a detached signature.
+///
+/// [`context_hash`]: crate::util::context_hash
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SignedMessageV2 {
+    pub certificate: SignedMessageCertificate,
+    /// What the message was signed for, e.g. "freenet.org forum post". Verifiers should check it
+    /// is the purpose they expect.
+    pub purpose: String,
+    /// Seconds since the Unix epoch at which the message was signed, as claimed by the signer
+    pub created: u64,
+    /// Media type of the message, e.g. "text/plain", absent if not given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+    /// The message, absent for a detached signature
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "serde_bytes")]
+    pub message: Option<Vec<u8>>,
+    pub signature: Signature,
+}
+
+/// The fields of a V2 or V3 signed message covered by its signature.
+#[derive(Serialize)]
+struct SignedFields<'a> {
+    purpose: &'a str,
+    created: u64,
+    content_type: Option<&'a str>,
+    #[serde(with = "serde_bytes")]
+    message_hash: &'a [u8],
+}
+
+impl SignedFields<'_> {
+    #[cfg(feature = "issue")]
+    fn sign(
+        signing_key: &SigningKey,
+        context: &str,
+        purpose: &str,
+        created: u64,
+        content_type: Option<&str>,
+        message: &[u8],
+    ) -> Result<Signature, GhostkeyError> {
+        let message_hash = blake3::hash(message);
+        let fields = SignedFields { purpose, created, content_type, message_hash: message_hash.as_bytes() };
+        sign_with_context(signing_key, context, &fields)
+    }
+
+    fn verify(
+        verifying_key: &VerifyingKey,
+        context: &str,
+        purpose: &str,
+        created: u64,
+        content_type: Option<&str>,
+        message: &[u8],
+        signature: &Signature,
+    ) -> Result<(), GhostkeyError> {
+        let message_hash = blake3::hash(message);
+        let fields = SignedFields { purpose, created, content_type, message_hash: message_hash.as_bytes() };
+        if verify_with_context(verifying_key, context, &fields, signature)? {
+            Ok(())
+        } else {
+            Err(SignatureVerificationError(
+                SignatureKind::Message,
+                "Failed to verify signed message".to_string(),
+            ))
+        }
+    }
+}
+
+impl SignedMessageV2 {
+    /// Signs `message` with the ghost signing key for `purpose`, embedding the message. Use
+    /// [`detach`](Self::detach) for a detached signature.
+    #[cfg(feature = "issue")]
+    pub fn new(
+        certificate: GhostkeyCertificate,
+        signing_key: &SigningKey,
+        purpose: &str,
+        content_type: Option<&str>,
+        message: &[u8],
+    ) -> Result<Self, GhostkeyError> {
+        Self::new_at(current_unix_time(), certificate, signing_key, purpose, content_type, message)
+    }
+
+    /// Like [`new`](Self::new), recording `created` (seconds since the Unix epoch) as the time
+    /// of signing rather than the current time.
+    #[cfg(feature = "issue")]
+    pub fn new_at(
+        created: u64,
+        certificate: GhostkeyCertificate,
+        signing_key: &SigningKey,
+        purpose: &str,
+        content_type: Option<&str>,
+        message: &[u8],
+    ) -> Result<Self, GhostkeyError> {
+        if signing_key.verifying_key() != *certificate.verifying_key() {
+            return Err(GhostkeyError::InvalidInput(
+                "Ghost signing key does not match the ghost key certificate".to_string(),
+            ));
+        }
+        let signature = SignedFields::sign(
+            signing_key,
+            SIGNED_MESSAGE_V2_CONTEXT,
+            purpose,
+            created,
+            content_type,
+            message,
+        )?;
+        Ok(Self {
+            certificate: certificate.into(),
+            purpose: purpose.to_string(),
+            created,
+            content_type: content_type.map(str::to_string),
+            message: Some(message.to_vec()),
+            signature,
+        })
+    }
+
+    /// Removes the embedded message, leaving a detached signature that is verified against the
+    /// message passed separately. Returns the message, `None` if it was already detached.
+    pub fn detach(&mut self) -> Option<Vec<u8>> {
+        self.message.take()
+    }
+
+    pub fn is_detached(&self) -> bool {
+        self.message.is_none()
+    }
+}
+
+/// A message signed by a [`SubkeyCertificateV1`] rather than the ghost key itself. The
+/// signature covers the same fields as a [`SignedMessageV2`] under
+/// [`SIGNED_MESSAGE_V3_CONTEXT`], with the purpose of the subkey as the purpose of the message.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SignedMessageV3 {
+    pub subkey: SubkeyCertificateV1,
+    /// Seconds since the Unix epoch at which the message was signed, as claimed by the signer,
+    /// within the validity period of the subkey
+    pub created: u64,
+    /// Media type of the message, e.g. "text/plain", absent if not given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+    /// The message, absent for a detached signature
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "serde_bytes")]
+    pub message: Option<Vec<u8>>,
+    pub signature: Signature,
+}
+
+impl SignedMessageV3 {
+    /// Signs `message` with the subkey signing key, embedding the message.
+    #[cfg(feature = "issue")]
+    pub fn new(
+        subkey: SubkeyCertificateV1,
+        signing_key: &SigningKey,
+        content_type: Option<&str>,
+        message: &[u8],
+    ) -> Result<Self, GhostkeyError> {
+        Self::new_at(current_unix_time(), subkey, signing_key, content_type, message)
+    }
+
+    /// Like [`new`](Self::new), recording `created` (seconds since the Unix epoch) as the time
+    /// of signing rather than the current time.
+    #[cfg(feature = "issue")]
+    pub fn new_at(
+        created: u64,
+        subkey: SubkeyCertificateV1,
+        signing_key: &SigningKey,
+        content_type: Option<&str>,
+        message: &[u8],
+    ) -> Result<Self, GhostkeyError> {
+        if signing_key.verifying_key() != subkey.payload.verifying_key {
+            return Err(GhostkeyError::InvalidInput(
+                "Subkey signing key does not match the subkey certificate".to_string(),
+            ));
+        }
+        let signature = SignedFields::sign(
+            signing_key,
+            SIGNED_MESSAGE_V3_CONTEXT,
+            &subkey.payload.purpose,
+            created,
+            content_type,
+            message,
+        )?;
+        Ok(Self {
+            subkey,
+            created,
+            content_type: content_type.map(str::to_string),
+            message: Some(message.to_vec()),
+            signature,
+        })
+    }
+
+    /// Removes the embedded message, see [`SignedMessageV2::detach`].
+    pub fn detach(&mut self) -> Option<Vec<u8>> {
+        self.message.take()
+    }
+
+    pub fn is_detached(&self) -> bool {
+        self.message.is_none()
+    }
+}
+
+/// The verified contents of a signed message, returned by `verify`. For V1 messages, which sign
+/// only the message, `purpose` and `created` are `None`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VerifiedMessage {
+    /// Signed message version
+    pub version: u32,
+    /// The delegate that issued the ghost key
+    pub delegate_info: DelegateInfo,
+    /// The metadata bound into a V3 ghost key certificate
+    pub metadata: Option<GhostkeyMetadata>,
+    /// The ghost key that signed the message, or certified the subkey that did
+    pub verifying_key: VerifyingKey,
+    /// The subkey that signed a V3 message
+    pub subkey: Option<SubkeyPayload>,
+    pub purpose: Option<String>,
+    pub created: Option<u64>,
+    pub content_type: Option<String>,
+    pub message: Vec<u8>,
+}
+
+/// Any supported version of a signed message. Decoding picks the version from the armor label,
+/// falling back to the CBOR shape (newest version first) for unversioned labels and raw bytes.
+#[derive(Clone)]
+#[allow(clippy::large_enum_variant)]
+pub enum SignedMessage {
+    V1(SignedMessageV1),
+    V2(SignedMessageV2),
+    V3(SignedMessageV3),
+}
+
+impl SignedMessage {
+    /// Armor label without the `_V<n>` version suffix.
+    pub const LABEL: &'static str = "SIGNED_MESSAGE";
+
+    pub fn version(&self) -> u32 {
+        match self {
+            SignedMessage::V1(_) => 1,
+            SignedMessage::V2(_) => 2,
+            SignedMessage::V3(_) => 3,
+        }
+    }
+
+    /// The certificate of the ghost key that signed the message, or certified its subkey.
+    pub fn certificate(&self) -> GhostkeyCertificate {
+        match self {
+            SignedMessage::V1(signed_message) => signed_message.certificate.clone().into(),
+            SignedMessage::V2(signed_message) => signed_message.certificate.clone().into(),
+            SignedMessage::V3(signed_message) => signed_message.subkey.ghost_key_certificate(),
+        }
+    }
+
+    /// Verifies the ghost key certificate chain against the trust store, the subkey of a V3
+    /// message, and the signature over the message. A detached signature needs the `message` it was made over, an embedded
+    /// message must match `message` if one is given.
+    #[cfg(feature = "std")]
+    pub fn verify(
+        &self,
+        trust_store: &TrustStore,
+        message: Option<&[u8]>,
+    ) -> Result<VerifiedMessage, GhostkeyError> {
+        self.verify_at(current_unix_time(), trust_store, message)
+    }
+
+    /// Like [`verify`](Self::verify), checking the delegate's validity period against `time`
+    /// (seconds since the Unix epoch) rather than the current time. V1 messages are rejected,
+    /// see [`verify_legacy_at`](Self::verify_legacy_at).
+    pub fn verify_at(
+        &self,
+        time: u64,
+        trust_store: &TrustStore,
+        message: Option<&[u8]>,
+    ) -> Result<VerifiedMessage, GhostkeyError> {
+        let certificate = self.certificate();
+        self.verify_with_certificate(time, certificate.verify_at(time, trust_store), &certificate, message, false)
+    }
+
+    /// Like [`verify`](Self::verify), also accepting V1 messages.
+    #[cfg(feature = "std")]
+    pub fn verify_legacy(
+        &self,
+        trust_store: &TrustStore,
+        message: Option<&[u8]>,
+    ) -> Result<VerifiedMessage, GhostkeyError> {
+        self.verify_legacy_at(current_unix_time(), trust_store, message)
+    }
+
+    /// Like [`verify_at`](Self::verify_at), also accepting V1 messages. Their signature is not
+    /// domain separated, see [`SignedMessageV1`], so only use this for messages known to have
+    /// been signed by earlier releases.
+    pub fn verify_legacy_at(
+        &self,
+        time: u64,
+        trust_store: &TrustStore,
+        message: Option<&[u8]>,
+    ) -> Result<VerifiedMessage, GhostkeyError> {
+        let certificate = self.certificate();
+        self.verify_with_certificate(time, certificate.verify_at(time, trust_store), &certificate, message, true)
+    }
+
+    /// Completes the verification given the result of verifying the ghost key certificate, so
+    /// that callers can verify it themselves, e.g. against a revocation list. V1 messages are
+    /// rejected unless `allow_v1` is set.
+    pub(crate) fn verify_with_certificate(
+        &self,
+        time: u64,
+        certificate_verification: Result<DelegateInfo, GhostkeyError>,
+        certificate: &GhostkeyCertificate,
+        message: Option<&[u8]>,
+        allow_v1: bool,
+    ) -> Result<VerifiedMessage, GhostkeyError> {
+        if matches!(self, SignedMessage::V1(_)) && !allow_v1 {
+            return Err(SignatureVerificationError(
+                SignatureKind::Message,
+                "V1 signed messages are not domain separated and are only verified as legacy messages"
+                    .to_string(),
+            ));
+        }
+        let (delegate_info, subkey) = match self {
+            SignedMessage::V3(signed_message) => {
+                let info = signed_message.subkey.verify_with_certificate(
+                    time,
+                    certificate_verification,
+                    certificate,
+                )?;
+                (info.delegate_info, Some(info.payload))
+            }
+            _ => (certificate_verification?, None),
+        };
+        let verifying_key = certificate.verifying_key();
+        let (embedded, purpose, created, content_type) = match self {
+            SignedMessage::V1(signed_message) => (Some(&signed_message.message), None, None, None),
+            SignedMessage::V2(signed_message) => (
+                signed_message.message.as_ref(),
+                Some(signed_message.purpose.clone()),
+                Some(signed_message.created),
+                signed_message.content_type.clone(),
+            ),
+            SignedMessage::V3(signed_message) => (
+                signed_message.message.as_ref(),
+                Some(signed_message.subkey.payload.purpose.clone()),
+                Some(signed_message.created),
+                signed_message.content_type.clone(),
+            ),
+        };
+        let message = match (embedded, message) {
+            (Some(embedded), Some(message)) if embedded.as_slice() != message => {
+                return Err(GhostkeyError::InvalidInput(
+                    "Message does not match the message embedded in the signed message".to_string(),
+                ));
+            }
+            (Some(embedded), _) => embedded.as_slice(),
+            (None, Some(message)) => message,
+            (None, None) => {
+                return Err(GhostkeyError::InvalidInput(
+                    "The signature is detached, the signed message is required to verify it".to_string(),
+                ));
+            }
+        };
+
+        match self {
+            SignedMessage::V1(signed_message) => verifying_key
+                .verify(message, &signed_message.signature)
+                .map_err(|e| SignatureVerificationError(SignatureKind::Message, e.to_string()))?,
+            SignedMessage::V2(signed_message) => SignedFields::verify(
+                verifying_key,
+                SIGNED_MESSAGE_V2_CONTEXT,
+                &signed_message.purpose,
+                signed_message.created,
+                signed_message.content_type.as_deref(),
+                message,
+                &signed_message.signature,
+            )?,
+            SignedMessage::V3(signed_message) => {
+                let payload = &signed_message.subkey.payload;
+                SignedFields::verify(
+                    &payload.verifying_key,
+                    SIGNED_MESSAGE_V3_CONTEXT,
+                    &payload.purpose,
+                    signed_message.created,
+                    signed_message.content_type.as_deref(),
+                    message,
+                    &signed_message.signature,
+                )?;
+                if signed_message.created < payload.not_before || signed_message.created > payload.not_after {
+                    return Err(ValidityPeriodError(format!(
+                        "Message was signed at {}, outside the validity period of its subkey",
+                        signed_message.created
+                    )));
+                }
+            }
+        }
+
+        Ok(VerifiedMessage {
+            version: self.version(),
+            delegate_info,
+            metadata: certificate.metadata().cloned(),
+            verifying_key: *verifying_key,
+            subkey,
+            purpose,
+            created,
+            content_type,
+            message: message.to_vec(),
+        })
+    }
+
+    /// Decodes CBOR bytes of any supported version, trying the newest version first.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, GhostkeyError> {
+        SignedMessageV3::from_bytes(bytes)
+            .map(SignedMessage::V3)
+            .or_else(|_| SignedMessageV2::from_bytes(bytes).map(SignedMessage::V2))
+            .or_else(|_| SignedMessageV1::from_bytes(bytes).map(SignedMessage::V1))
+    }
+
+    pub fn from_armored_string(armored_string: &str) -> Result<Self, GhostkeyError> {
+        let (version, bytes) = read_versioned_block(armored_string, Self::LABEL)?;
+        match version {
+            None => Self::from_bytes(&bytes),
+            Some(1) => SignedMessageV1::from_bytes(&bytes).map(SignedMessage::V1),
+            Some(2) => SignedMessageV2::from_bytes(&bytes).map(SignedMessage::V2),
+            Some(3) => SignedMessageV3::from_bytes(&bytes).map(SignedMessage::V3),
+            Some(version) => Err(GhostkeyError::DecodingError(format!(
+                "Unsupported signed message version {}",
+                version
+            ))),
+        }
+    }
+
+    #[cfg(feature = "fs")]
+    pub fn from_file(file_path: &Path) -> Result<Self, GhostkeyError> {
+        let armored_string = std::fs::read_to_string(file_path).map_err(|e| {
+            GhostkeyError::IOError(format!("Failed to read {}", file_path.display()), e)
+        })?;
+        Self::from_armored_string(&armored_string)
+    }
+
+    pub fn from_base64(encoded: &str) -> Result<Self, GhostkeyError> {
+        let bytes = BASE64_STANDARD.decode(encoded)?;
+        Self::from_bytes(&bytes)
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, GhostkeyError> {
+        match self {
+            SignedMessage::V1(signed_message) => signed_message.to_bytes(),
+            SignedMessage::V2(signed_message) => signed_message.to_bytes(),
+            SignedMessage::V3(signed_message) => signed_message.to_bytes(),
+        }
+    }
+
+    pub fn to_base64(&self) -> Result<String, GhostkeyError> {
+        Ok(BASE64_STANDARD.encode(self.to_bytes()?))
+    }
+
+    pub fn to_armored_string(&self) -> Result<String, GhostkeyError> {
+        match self {
+            SignedMessage::V1(signed_message) => signed_message.to_armored_string(),
+            SignedMessage::V2(signed_message) => signed_message.to_armored_string(),
+            SignedMessage::V3(signed_message) => signed_message.to_armored_string(),
+        }
+    }
+
+    #[cfg(feature = "fs")]
+    pub fn to_file(&self, file_path: &Path) -> Result<(), GhostkeyError> {
+        match self {
+            SignedMessage::V1(signed_message) => signed_message.to_file(file_path),
+            SignedMessage::V2(signed_message) => signed_message.to_file(file_path),
+            SignedMessage::V3(signed_message) => signed_message.to_file(file_path),
+        }
+    }
+}
+
+impl From<SignedMessageV1> for SignedMessage {
+    fn from(signed_message: SignedMessageV1) -> Self {
+        SignedMessage::V1(signed_message)
+    }
+}
+
+impl From<SignedMessageV2> for SignedMessage {
+    fn from(signed_message: SignedMessageV2) -> Self {
+        SignedMessage::V2(signed_message)
+    }
+}
+
+impl From<SignedMessageV3> for SignedMessage {
+    fn from(signed_message: SignedMessageV3) -> Self {
+        SignedMessage::V3(signed_message)
+    }
+}
+
+#[cfg(all(test, feature = "issue"))]
+mod tests {
+    use super::*;
+    use crate::test_util::issue_ghost_key;
+    use crate::util::create_keypair;
+    use rand_core::OsRng;
+
+    #[test]
+    fn test_signed_message_v2() {
+        let (trust_store, certificate, signing_key) = issue_ghost_key();
+        let signed_message = SignedMessageV2::new_at(
+            1000,
+            certificate,
+            &signing_key,
+            "test purpose",
+            Some("text/plain"),
+            b"Hello",
+        )
+        .unwrap();
+
+        let signed_message =
+            SignedMessage::from_armored_string(&SignedMessage::from(signed_message).to_armored_string().unwrap())
+                .unwrap();
+        assert_eq!(signed_message.version(), 2);
+        let verified = signed_message.verify(&trust_store, None).unwrap();
+        assert_eq!(verified.purpose.as_deref(), Some("test purpose"));
+        assert_eq!(verified.created, Some(1000));
+        assert_eq!(verified.content_type.as_deref(), Some("text/plain"));
+        assert_eq!(verified.message, b"Hello");
+
+        // Another message than the embedded one is rejected
+        assert_eq!(
+            signed_message.verify(&trust_store, Some(b"Goodbye")).unwrap_err().code(),
+            "GK_INVALID_INPUT"
+        );
+    }
+
+    #[test]
+    fn test_signed_message_v2_detached() {
+        let (trust_store, certificate, signing_key) = issue_ghost_key();
+        let mut signed_message =
+            SignedMessageV2::new(certificate, &signing_key, "test purpose", None, b"Hello").unwrap();
+        assert_eq!(signed_message.detach().unwrap(), b"Hello");
+        assert!(signed_message.is_detached());
+
+        let signed_message = SignedMessage::from_bytes(&SignedMessage::from(signed_message).to_bytes().unwrap()).unwrap();
+        assert_eq!(
+            signed_message.verify(&trust_store, None).unwrap_err().code(),
+            "GK_INVALID_INPUT"
+        );
+        assert_eq!(signed_message.verify(&trust_store, Some(b"Hello")).unwrap().message, b"Hello");
+        assert_eq!(
+            signed_message.verify(&trust_store, Some(b"Goodbye")).unwrap_err().code(),
+            "GK_SIG_MESSAGE_INVALID"
+        );
+    }
+
+    #[test]
+    fn test_signed_message_v2_covers_fields() {
+        let (trust_store, certificate, signing_key) = issue_ghost_key();
+        let signed_message =
+            SignedMessageV2::new_at(1000, certificate, &signing_key, "test purpose", None, b"Hello").unwrap();
+
+        let mut tampered = signed_message.clone();
+        tampered.purpose = "other purpose".to_string();
+        assert_eq!(
+            SignedMessage::from(tampered).verify(&trust_store, None).unwrap_err().code(),
+            "GK_SIG_MESSAGE_INVALID"
+        );
+
+        let mut tampered = signed_message.clone();
+        tampered.created = 2000;
+        assert!(SignedMessage::from(tampered).verify(&trust_store, None).is_err());
+
+        let mut tampered = signed_message;
+        tampered.content_type = Some("text/html".to_string());
+        assert!(SignedMessage::from(tampered).verify(&trust_store, None).is_err());
+    }
+
+    #[test]
+    fn test_signed_message_v1() {
+        let (trust_store, certificate, signing_key) = issue_ghost_key();
+        let signed_message = SignedMessageV1 {
+            certificate: certificate.into(),
+            message: b"Hello".to_vec(),
+            signature: signing_key.sign(b"Hello"),
+        };
+
+        // Messages signed by earlier releases still decode, but only verify when asked to
+        let armored = signed_message.to_armored_string().unwrap();
+        assert!(armored.starts_with("-----BEGIN SIGNED_MESSAGE_V1-----"));
+        let signed_message = SignedMessage::from_armored_string(&armored).unwrap();
+        assert_eq!(signed_message.version(), 1);
+        assert_eq!(
+            signed_message.verify(&trust_store, None).unwrap_err().code(),
+            "GK_SIG_MESSAGE_INVALID"
+        );
+        let verified = signed_message.verify_legacy(&trust_store, None).unwrap();
+        assert_eq!(verified.purpose, None);
+        assert_eq!(verified.message, b"Hello");
+    }
+
+    #[test]
+    fn test_signed_message_v3() {
+        let (trust_store, certificate, signing_key) = issue_ghost_key();
+        let (subkey, subkey_signing_key) =
+            SubkeyCertificateV1::new_with_rng(&mut OsRng, certificate, &signing_key, "test server", 1000, 2000)
+                .unwrap();
+        let signed_message =
+            SignedMessageV3::new_at(1500, subkey.clone(), &subkey_signing_key, None, b"Hello").unwrap();
+        let signed_message =
+            SignedMessage::from_bytes(&SignedMessage::from(signed_message).to_bytes().unwrap()).unwrap();
+        assert_eq!(signed_message.version(), 3);
+
+        // Verifies the chain from the master key to the subkey
+        let verified = signed_message.verify_at(1600, &trust_store, None).unwrap();
+        assert_eq!(verified.purpose.as_deref(), Some("test server"));
+        assert_eq!(verified.verifying_key, signing_key.verifying_key());
+        assert_eq!(verified.subkey.unwrap().verifying_key, subkey_signing_key.verifying_key());
+
+        // Not once the subkey has expired
+        assert_eq!(
+            signed_message.verify_at(2001, &trust_store, None).unwrap_err().code(),
+            "GK_VALIDITY_PERIOD"
+        );
+
+        // Nor claiming to be signed outside the validity period of the subkey
+        let backdated = SignedMessageV3::new_at(500, subkey.clone(), &subkey_signing_key, None, b"Hello").unwrap();
+        assert_eq!(
+            SignedMessage::from(backdated).verify_at(1600, &trust_store, None).unwrap_err().code(),
+            "GK_VALIDITY_PERIOD"
+        );
+
+        // The ghost signing key can't sign in place of the subkey
+        assert!(SignedMessageV3::new_at(1500, subkey, &signing_key, None, b"Hello").is_err());
+    }
+
+    #[test]
+    fn test_signed_message_rejects_mismatched_key() {
+        let (_, certificate, _) = issue_ghost_key();
+        let (other_signing_key, _) = create_keypair(&mut OsRng).unwrap();
+        assert!(SignedMessageV2::new(certificate, &other_signing_key, "test purpose", None, b"Hello").is_err());
+    }
+}
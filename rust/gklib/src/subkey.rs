@@ -0,0 +1,236 @@
+use super::delegate_certificate::DelegateInfo;
+use super::errors::GhostkeyError;
+use super::errors::GhostkeyError::{SignatureVerificationError, ValidityPeriodError};
+use super::errors::SignatureKind;
+use super::ghost_key_certificate::{GhostkeyCertificate, GhostkeyMetadata};
+use super::signed_message::SignedMessageCertificate;
+use super::trust_store::TrustStore;
+use super::util::verify_with_context;
+#[cfg(feature = "std")]
+use super::util::current_unix_time;
+#[cfg(feature = "issue")]
+use super::util::{create_keypair, sign_with_context};
+use alloc::format;
+use alloc::string::{String, ToString};
+use ed25519_dalek::*;
+#[cfg(feature = "issue")]
+use rand_core::{CryptoRng, OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+
+/// BLAKE3 key derivation context under which ghost keys sign the payload of a subkey
+/// certificate.
+pub const SUBKEY_CERTIFICATE_V1_CONTEXT: &str = "freenet.org ghostkey 2024-10 subkey certificate v1";
+
+/// The session key a ghost key certifies, and what for and for how long.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SubkeyPayload {
+    pub verifying_key: VerifyingKey,
+    /// What the subkey may be used for, e.g. "freenet.org chat server". Messages signed by the
+    /// subkey are signed for this purpose.
+    pub purpose: String,
+    /// Seconds since the Unix epoch before which the subkey is not valid
+    pub not_before: u64,
+    /// Seconds since the Unix epoch after which the subkey has expired
+    pub not_after: u64,
+}
+
+/// A short-lived Ed25519 session key signed by a ghost key, so that servers can act for a ghost
+/// key without holding its signing key. Verifying it checks the whole chain from the master key
+/// through the delegate and the ghost key to the subkey.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SubkeyCertificateV1 {
+    pub ghost_key: SignedMessageCertificate,
+    pub payload: SubkeyPayload,
+    /// The [`context_hash`](crate::util::context_hash) of the payload under
+    /// [`SUBKEY_CERTIFICATE_V1_CONTEXT`] signed by the ghost signing key
+    pub signature: Signature,
+}
+
+/// The verified contents of a subkey certificate, returned by `verify`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SubkeyInfo {
+    /// The delegate that issued the ghost key
+    pub delegate_info: DelegateInfo,
+    /// The metadata bound into a V3 ghost key certificate
+    pub metadata: Option<GhostkeyMetadata>,
+    /// The ghost key that certified the subkey
+    pub ghost_key: VerifyingKey,
+    pub payload: SubkeyPayload,
+}
+
+impl SubkeyCertificateV1 {
+    /// Creates a new subkey for `purpose` valid for `valid_seconds` from now, certified by the
+    /// ghost signing key.
+    #[cfg(feature = "issue")]
+    pub fn new(
+        ghost_key_certificate: GhostkeyCertificate,
+        ghost_signing_key: &SigningKey,
+        purpose: &str,
+        valid_seconds: u64,
+    ) -> Result<(Self, SigningKey), GhostkeyError> {
+        let not_before = current_unix_time();
+        Self::new_with_rng(
+            &mut OsRng,
+            ghost_key_certificate,
+            ghost_signing_key,
+            purpose,
+            not_before,
+            not_before.saturating_add(valid_seconds),
+        )
+    }
+
+    /// Like [`new`](Self::new) with an explicit validity period, drawing the subkey from `rng`.
+    #[cfg(feature = "issue")]
+    pub fn new_with_rng<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        ghost_key_certificate: GhostkeyCertificate,
+        ghost_signing_key: &SigningKey,
+        purpose: &str,
+        not_before: u64,
+        not_after: u64,
+    ) -> Result<(Self, SigningKey), GhostkeyError> {
+        if ghost_signing_key.verifying_key() != *ghost_key_certificate.verifying_key() {
+            return Err(GhostkeyError::InvalidInput(
+                "Ghost signing key does not match the ghost key certificate".to_string(),
+            ));
+        }
+        if not_after < not_before {
+            return Err(GhostkeyError::InvalidInput(format!(
+                "not_after ({}) must not be earlier than not_before ({})",
+                not_after, not_before
+            )));
+        }
+        let (subkey_signing_key, subkey_verifying_key) = create_keypair(rng)?;
+        let payload = SubkeyPayload {
+            verifying_key: subkey_verifying_key,
+            purpose: purpose.to_string(),
+            not_before,
+            not_after,
+        };
+        let signature = sign_with_context(ghost_signing_key, SUBKEY_CERTIFICATE_V1_CONTEXT, &payload)?;
+        Ok((
+            Self {
+                ghost_key: ghost_key_certificate.into(),
+                payload,
+                signature,
+            },
+            subkey_signing_key,
+        ))
+    }
+
+    /// The certificate of the ghost key that certified the subkey.
+    pub fn ghost_key_certificate(&self) -> GhostkeyCertificate {
+        self.ghost_key.clone().into()
+    }
+
+    /// Verifies the chain from the master verifying keys in the trust store to the subkey, and
+    /// that the subkey has not expired.
+    #[cfg(feature = "std")]
+    pub fn verify(&self, trust_store: &TrustStore) -> Result<SubkeyInfo, GhostkeyError> {
+        self.verify_at(current_unix_time(), trust_store)
+    }
+
+    /// Like [`verify`](Self::verify), checking the validity periods of the delegate and the
+    /// subkey against `time` (seconds since the Unix epoch) rather than the current time.
+    pub fn verify_at(&self, time: u64, trust_store: &TrustStore) -> Result<SubkeyInfo, GhostkeyError> {
+        let ghost_key_certificate = self.ghost_key_certificate();
+        self.verify_with_certificate(
+            time,
+            ghost_key_certificate.verify_at(time, trust_store),
+            &ghost_key_certificate,
+        )
+    }
+
+    /// Completes the verification given the result of verifying the ghost key certificate, so
+    /// that callers can verify it themselves, e.g. against a revocation list.
+    pub(crate) fn verify_with_certificate(
+        &self,
+        time: u64,
+        certificate_verification: Result<DelegateInfo, GhostkeyError>,
+        ghost_key_certificate: &GhostkeyCertificate,
+    ) -> Result<SubkeyInfo, GhostkeyError> {
+        let delegate_info = certificate_verification?;
+        let ghost_key = ghost_key_certificate.verifying_key();
+        if !verify_with_context(ghost_key, SUBKEY_CERTIFICATE_V1_CONTEXT, &self.payload, &self.signature)? {
+            return Err(SignatureVerificationError(
+                SignatureKind::Subkey,
+                "Failed to verify subkey certificate".to_string(),
+            ));
+        }
+        if time < self.payload.not_before {
+            return Err(ValidityPeriodError(format!(
+                "Subkey is not valid before {} (time is {})",
+                self.payload.not_before, time
+            )));
+        }
+        if time > self.payload.not_after {
+            return Err(ValidityPeriodError(format!(
+                "Subkey expired at {} (time is {})",
+                self.payload.not_after, time
+            )));
+        }
+        Ok(SubkeyInfo {
+            delegate_info,
+            metadata: ghost_key_certificate.metadata().cloned(),
+            ghost_key: *ghost_key,
+            payload: self.payload.clone(),
+        })
+    }
+}
+
+#[cfg(all(test, feature = "issue"))]
+mod tests {
+    use super::*;
+    use crate::armorable::Armorable;
+    use crate::test_util::issue_ghost_key;
+
+    #[test]
+    fn test_subkey_certificate() {
+        let (trust_store, ghost_key_certificate, ghost_signing_key) = issue_ghost_key();
+        let (subkey_certificate, subkey_signing_key) = SubkeyCertificateV1::new_with_rng(
+            &mut OsRng,
+            ghost_key_certificate.clone(),
+            &ghost_signing_key,
+            "test server",
+            1000,
+            2000,
+        )
+        .unwrap();
+        let subkey_certificate =
+            SubkeyCertificateV1::from_armored_string(&subkey_certificate.to_armored_string().unwrap()).unwrap();
+
+        let info = subkey_certificate.verify_at(1500, &trust_store).unwrap();
+        assert_eq!(info.delegate_info.info, "Test Delegate");
+        assert_eq!(info.ghost_key, *ghost_key_certificate.verifying_key());
+        assert_eq!(info.payload.verifying_key, subkey_signing_key.verifying_key());
+        assert_eq!(info.payload.purpose, "test server");
+
+        // Not yet valid and expired
+        for time in [999, 2001] {
+            assert_eq!(
+                subkey_certificate.verify_at(time, &trust_store).unwrap_err().code(),
+                "GK_VALIDITY_PERIOD"
+            );
+        }
+
+        // The payload is signed by the ghost key
+        let mut tampered = subkey_certificate.clone();
+        tampered.payload.not_after = 3000;
+        assert_eq!(tampered.verify_at(2500, &trust_store).unwrap_err().code(), "GK_SIG_SUBKEY_INVALID");
+
+        // The chain is checked up to the master key
+        let (other_trust_store, _, _) = issue_ghost_key();
+        assert_eq!(
+            subkey_certificate.verify_at(1500, &other_trust_store).unwrap_err().code(),
+            "GK_SIG_DELEGATE_INVALID"
+        );
+    }
+
+    #[test]
+    fn test_subkey_requires_matching_ghost_key() {
+        let (_, ghost_key_certificate, _) = issue_ghost_key();
+        let (other_signing_key, _) = create_keypair(&mut OsRng).unwrap();
+        let result = SubkeyCertificateV1::new(ghost_key_certificate, &other_signing_key, "test server", 60);
+        assert_eq!(result.err().unwrap().code(), "GK_INVALID_INPUT");
+    }
+}
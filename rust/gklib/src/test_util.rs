@@ -0,0 +1,34 @@
+//! Fixtures shared by the tests of several modules.
+
+use crate::delegate_certificate::DelegateCertificateV2;
+use crate::ghost_key_certificate::{GhostkeyCertificate, GhostkeyCertificateV2};
+use crate::trust_store::TrustStore;
+use crate::util::create_keypair;
+use alloc::vec::Vec;
+use ed25519_dalek::SigningKey;
+use rand_core::OsRng;
+
+/// Issues `count` V2 ghost keys from one new "Test Delegate" of a new master key, returned with
+/// a trust store holding that master key.
+pub(crate) fn issue_ghost_keys(
+    count: usize,
+) -> (TrustStore, Vec<(GhostkeyCertificate, SigningKey)>) {
+    let (master_signing_key, master_verifying_key) = create_keypair(&mut OsRng).unwrap();
+    let (delegate_certificate, delegate_signing_key) =
+        DelegateCertificateV2::new(&master_signing_key, "Test Delegate").unwrap();
+    let ghost_keys = (0..count)
+        .map(|_| {
+            let (certificate, signing_key) =
+                GhostkeyCertificateV2::new(&delegate_certificate, &delegate_signing_key).unwrap();
+            (certificate.into(), signing_key)
+        })
+        .collect();
+    (TrustStore::from(master_verifying_key), ghost_keys)
+}
+
+/// Issues a single ghost key, see [`issue_ghost_keys`].
+pub(crate) fn issue_ghost_key() -> (TrustStore, GhostkeyCertificate, SigningKey) {
+    let (trust_store, mut ghost_keys) = issue_ghost_keys(1);
+    let (certificate, signing_key) = ghost_keys.remove(0);
+    (trust_store, certificate, signing_key)
+}
@@ -0,0 +1,579 @@
+//! M-of-N threshold signing with the master key, following FROST(Ed25519, SHA-512) from
+//! RFC 9591. The master signing key is split into N shares by a trusted dealer, any M of which
+//! cooperate in two rounds to produce an ordinary Ed25519 signature under the master verifying
+//! key, so certificates signed this way verify exactly like those signed with the whole key.
+//!
+//! 1. Each signer creates [`SigningNoncesV1`] with their [`MasterKeyShareV1`], keeping the
+//!    nonces secret and sending the [`SigningCommitmentV1`] to the coordinator.
+//! 2. The coordinator collects at least M commitments and the message in a [`SigningPackageV1`].
+//! 3. Each signer checks the message and creates a [`SignatureShareV1`] with the package, using
+//!    up their nonces.
+//! 4. The coordinator combines the signature shares with [`aggregate`].
+
+use super::errors::GhostkeyError;
+use super::errors::GhostkeyError::{InvalidInput, SignatureVerificationError, ValidationError};
+use super::errors::SignatureKind;
+use alloc::format;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use curve25519_dalek::edwards::{CompressedEdwardsY, EdwardsPoint};
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::traits::IsIdentity;
+use ed25519_dalek::*;
+use rand_core::{CryptoRng, OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512};
+
+/// The context string of the FROST(Ed25519, SHA-512) ciphersuite.
+pub const FROST_CONTEXT: &str = "FROST-ED25519-SHA512-v1";
+
+/// One share of a threshold master signing key. Like the master signing key itself it must be
+/// kept secret by its holder.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct MasterKeyShareV1 {
+    /// Which share this is, from 1 to `max_signers`
+    pub identifier: u16,
+    /// How many shares must cooperate to sign
+    pub min_signers: u16,
+    /// How many shares the key was split into
+    pub max_signers: u16,
+    #[serde(with = "serde_bytes")]
+    pub signing_share: [u8; 32],
+    /// Commitments to the coefficients of the polynomial the key was split with, 32 bytes each,
+    /// the first being the master verifying key. The same for every share.
+    #[serde(with = "serde_bytes")]
+    pub vss_commitment: Vec<u8>,
+}
+
+/// The public commitment to a signer's nonces, sent to the coordinator in the first round.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SigningCommitmentV1 {
+    pub identifier: u16,
+    #[serde(with = "serde_bytes")]
+    pub hiding: [u8; 32],
+    #[serde(with = "serde_bytes")]
+    pub binding: [u8; 32],
+    /// The commitment of the signer's key share, from which the coordinator derives the master
+    /// verifying key and checks each signature share
+    #[serde(with = "serde_bytes")]
+    pub vss_commitment: Vec<u8>,
+}
+
+/// A signer's secret nonces for one signature. Reusing them for a second signature reveals the
+/// key share, so they are consumed by [`SignatureShareV1::new`].
+#[derive(Serialize, Deserialize)]
+pub struct SigningNoncesV1 {
+    #[serde(with = "serde_bytes")]
+    pub hiding: [u8; 32],
+    #[serde(with = "serde_bytes")]
+    pub binding: [u8; 32],
+    pub commitment: SigningCommitmentV1,
+}
+
+/// The message and the commitments of the signers taking part, sent by the coordinator to every
+/// signer in the second round.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SigningPackageV1 {
+    #[serde(with = "serde_bytes")]
+    pub message: Vec<u8>,
+    /// Ordered by identifier
+    pub commitments: Vec<SigningCommitmentV1>,
+}
+
+/// A signer's share of the signature, sent to the coordinator in the second round.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SignatureShareV1 {
+    pub identifier: u16,
+    #[serde(with = "serde_bytes")]
+    pub share: [u8; 32],
+}
+
+impl MasterKeyShareV1 {
+    /// Splits the master signing key into `max_signers` shares, any `min_signers` of which can
+    /// sign for the master verifying key. The master signing key should be destroyed afterwards.
+    pub fn split(
+        master_signing_key: &SigningKey,
+        min_signers: u16,
+        max_signers: u16,
+    ) -> Result<Vec<Self>, GhostkeyError> {
+        Self::split_with_rng(&mut OsRng, master_signing_key, min_signers, max_signers)
+    }
+
+    /// Like [`split`](Self::split), drawing the coefficients of the polynomial from `rng`.
+    pub fn split_with_rng<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        master_signing_key: &SigningKey,
+        min_signers: u16,
+        max_signers: u16,
+    ) -> Result<Vec<Self>, GhostkeyError> {
+        if min_signers < 2 || min_signers > max_signers {
+            return Err(InvalidInput(format!(
+                "Threshold must be at least 2 and at most the number of shares, got {} of {}",
+                min_signers, max_signers
+            )));
+        }
+        let mut coefficients = Vec::with_capacity(min_signers as usize);
+        coefficients.push(master_signing_key.to_scalar());
+        for _ in 1..min_signers {
+            coefficients.push(random_scalar(rng));
+        }
+        let vss_commitment: Vec<u8> = coefficients
+            .iter()
+            .flat_map(|coefficient| EdwardsPoint::mul_base(coefficient).compress().to_bytes())
+            .collect();
+        Ok((1..=max_signers)
+            .map(|identifier| {
+                // Horner's method, highest coefficient first
+                let x = Scalar::from(identifier);
+                let signing_share = coefficients
+                    .iter()
+                    .rev()
+                    .fold(Scalar::ZERO, |value, coefficient| value * x + coefficient);
+                MasterKeyShareV1 {
+                    identifier,
+                    min_signers,
+                    max_signers,
+                    signing_share: signing_share.to_bytes(),
+                    vss_commitment: vss_commitment.clone(),
+                }
+            })
+            .collect())
+    }
+
+    /// The master verifying key the share signs for.
+    pub fn group_verifying_key(&self) -> Result<VerifyingKey, GhostkeyError> {
+        group_verifying_key(&self.vss_commitment)
+    }
+
+    /// Checks that the share is consistent with the commitment to the polynomial, so a holder
+    /// can detect a share the dealer got wrong before relying on it.
+    pub fn verify(&self) -> Result<(), GhostkeyError> {
+        if self.identifier == 0 || self.identifier > self.max_signers {
+            return Err(ValidationError(format!("Invalid key share identifier {}", self.identifier)));
+        }
+        if self.vss_commitment.len() != self.min_signers as usize * 32 {
+            return Err(ValidationError("Key share commitment has the wrong length".to_string()));
+        }
+        let signing_share = scalar(&self.signing_share)?;
+        if EdwardsPoint::mul_base(&signing_share) != verifying_share(&self.vss_commitment, self.identifier)? {
+            return Err(ValidationError(format!(
+                "Key share {} does not match the commitment of the master key",
+                self.identifier
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl SigningNoncesV1 {
+    /// Creates nonces for one signature with the key share, in the first round.
+    pub fn new(key_share: &MasterKeyShareV1) -> Result<Self, GhostkeyError> {
+        Self::new_with_rng(&mut OsRng, key_share)
+    }
+
+    /// Like [`new`](Self::new), drawing the randomness mixed into the nonces from `rng`.
+    pub fn new_with_rng<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        key_share: &MasterKeyShareV1,
+    ) -> Result<Self, GhostkeyError> {
+        key_share.verify()?;
+        let hiding = nonce_generate(rng, &key_share.signing_share);
+        let binding = nonce_generate(rng, &key_share.signing_share);
+        Ok(SigningNoncesV1 {
+            hiding: hiding.to_bytes(),
+            binding: binding.to_bytes(),
+            commitment: SigningCommitmentV1 {
+                identifier: key_share.identifier,
+                hiding: EdwardsPoint::mul_base(&hiding).compress().to_bytes(),
+                binding: EdwardsPoint::mul_base(&binding).compress().to_bytes(),
+                vss_commitment: key_share.vss_commitment.clone(),
+            },
+        })
+    }
+}
+
+impl SigningPackageV1 {
+    /// Collects the commitments of the signers taking part to sign `message`. The commitments
+    /// must come from distinct shares of the same master key.
+    pub fn new(message: &[u8], mut commitments: Vec<SigningCommitmentV1>) -> Result<Self, GhostkeyError> {
+        commitments.sort_by_key(|commitment| commitment.identifier);
+        let package = SigningPackageV1 { message: message.to_vec(), commitments };
+        package.validate()?;
+        Ok(package)
+    }
+
+    /// Checks what [`new`](Self::new) ensures: the commitments are ordered by identifier, come
+    /// from distinct shares of the same master key and are enough to sign. A package read from
+    /// a file is checked again before signing or aggregating with it.
+    pub fn validate(&self) -> Result<(), GhostkeyError> {
+        let Some(first) = self.commitments.first() else {
+            return Err(InvalidInput("No signing commitments given".to_string()));
+        };
+        let min_signers = first.vss_commitment.len() / 32;
+        group_verifying_key(&first.vss_commitment)?;
+        for pair in self.commitments.windows(2) {
+            if pair[0].identifier == pair[1].identifier {
+                return Err(InvalidInput(format!(
+                    "More than one signing commitment from key share {}",
+                    pair[0].identifier
+                )));
+            }
+            if pair[0].identifier > pair[1].identifier {
+                return Err(InvalidInput("Signing commitments are not ordered by identifier".to_string()));
+            }
+        }
+        for commitment in &self.commitments {
+            if commitment.identifier == 0 {
+                return Err(InvalidInput("Invalid key share identifier 0".to_string()));
+            }
+            if commitment.vss_commitment != first.vss_commitment {
+                return Err(InvalidInput(format!(
+                    "Signing commitment from key share {} is for another master key",
+                    commitment.identifier
+                )));
+            }
+            point(&commitment.hiding)?;
+            point(&commitment.binding)?;
+        }
+        if self.commitments.len() < min_signers {
+            return Err(InvalidInput(format!(
+                "{} signing commitments given, the master key needs {}",
+                self.commitments.len(),
+                min_signers
+            )));
+        }
+        Ok(())
+    }
+
+    /// The master verifying key the signature is made for.
+    pub fn group_verifying_key(&self) -> Result<VerifyingKey, GhostkeyError> {
+        match self.commitments.first() {
+            Some(commitment) => group_verifying_key(&commitment.vss_commitment),
+            None => Err(InvalidInput("Signing package has no commitments".to_string())),
+        }
+    }
+
+    /// The identifiers of the key shares taking part.
+    pub fn signers(&self) -> Vec<u16> {
+        self.commitments.iter().map(|commitment| commitment.identifier).collect()
+    }
+
+    /// The group commitment, the challenge, and the binding factor of each signer.
+    fn compute(&self) -> Result<(EdwardsPoint, Scalar, Vec<Scalar>), GhostkeyError> {
+        let group_verifying_key = self.group_verifying_key()?;
+        let mut encoded_commitments = Vec::with_capacity(self.commitments.len() * 96);
+        for commitment in &self.commitments {
+            encoded_commitments.extend_from_slice(Scalar::from(commitment.identifier).as_bytes());
+            encoded_commitments.extend_from_slice(&commitment.hiding);
+            encoded_commitments.extend_from_slice(&commitment.binding);
+        }
+        let mut rho_input_prefix = Vec::with_capacity(160);
+        rho_input_prefix.extend_from_slice(group_verifying_key.as_bytes());
+        rho_input_prefix.extend_from_slice(&hash(&["msg", ""], &[&self.message]));
+        rho_input_prefix.extend_from_slice(&hash(&["com", ""], &[&encoded_commitments]));
+
+        let mut binding_factors = Vec::with_capacity(self.commitments.len());
+        let mut group_commitment = EdwardsPoint::default();
+        for commitment in &self.commitments {
+            let binding_factor = Scalar::from_bytes_mod_order_wide(&hash(
+                &["rho", ""],
+                &[&rho_input_prefix, Scalar::from(commitment.identifier).as_bytes()],
+            ));
+            group_commitment += point(&commitment.hiding)? + binding_factor * point(&commitment.binding)?;
+            binding_factors.push(binding_factor);
+        }
+        // The Ed25519 challenge, so the result verifies as an ordinary Ed25519 signature
+        let mut challenge_hash = Sha512::new();
+        challenge_hash.update(group_commitment.compress().as_bytes());
+        challenge_hash.update(group_verifying_key.as_bytes());
+        challenge_hash.update(&self.message);
+        let challenge = Scalar::from_bytes_mod_order_wide(&wide(&challenge_hash.finalize()));
+        Ok((group_commitment, challenge, binding_factors))
+    }
+
+    fn lagrange_coefficient(&self, identifier: u16) -> Scalar {
+        let x_i = Scalar::from(identifier);
+        let (numerator, denominator) = self
+            .commitments
+            .iter()
+            .filter(|commitment| commitment.identifier != identifier)
+            .fold((Scalar::ONE, Scalar::ONE), |(numerator, denominator), commitment| {
+                let x_j = Scalar::from(commitment.identifier);
+                (numerator * x_j, denominator * (x_j - x_i))
+            });
+        numerator * denominator.invert()
+    }
+}
+
+impl SignatureShareV1 {
+    /// Signs the message of the package with the key share and the nonces made for it in the
+    /// first round, in the second round. The package is validated, but check its message before
+    /// calling this.
+    pub fn new(
+        key_share: &MasterKeyShareV1,
+        nonces: SigningNoncesV1,
+        package: &SigningPackageV1,
+    ) -> Result<Self, GhostkeyError> {
+        key_share.verify()?;
+        package.validate()?;
+        if package.commitments[0].vss_commitment != key_share.vss_commitment {
+            return Err(InvalidInput("Signing package is for another master key".to_string()));
+        }
+        if nonces.commitment.identifier != key_share.identifier
+            || nonces.commitment.vss_commitment != key_share.vss_commitment
+        {
+            return Err(InvalidInput("Signing nonces were made with another key share".to_string()));
+        }
+        let Some(index) = package.commitments.iter().position(|commitment| *commitment == nonces.commitment)
+        else {
+            return Err(InvalidInput(
+                "Signing package does not include the commitment to these nonces".to_string(),
+            ));
+        };
+        let (_, challenge, binding_factors) = package.compute()?;
+        let share = scalar(&nonces.hiding)?
+            + scalar(&nonces.binding)? * binding_factors[index]
+            + package.lagrange_coefficient(key_share.identifier) * scalar(&key_share.signing_share)? * challenge;
+        Ok(SignatureShareV1 { identifier: key_share.identifier, share: share.to_bytes() })
+    }
+}
+
+/// Combines the signature shares of every signer in the package into an Ed25519 signature over
+/// its message, checking each share and the result against the master verifying key.
+pub fn aggregate(package: &SigningPackageV1, shares: &[SignatureShareV1]) -> Result<Signature, GhostkeyError> {
+    package.validate()?;
+    let (group_commitment, challenge, binding_factors) = package.compute()?;
+    let mut z = Scalar::ZERO;
+    for (commitment, binding_factor) in package.commitments.iter().zip(&binding_factors) {
+        let mut matching = shares.iter().filter(|share| share.identifier == commitment.identifier);
+        let (Some(share), None) = (matching.next(), matching.next()) else {
+            return Err(InvalidInput(format!(
+                "Expected one signature share from key share {}",
+                commitment.identifier
+            )));
+        };
+        let share_value = scalar(&share.share)?;
+        let expected = point(&commitment.hiding)?
+            + binding_factor * point(&commitment.binding)?
+            + (challenge * package.lagrange_coefficient(commitment.identifier))
+                * verifying_share(&commitment.vss_commitment, commitment.identifier)?;
+        if EdwardsPoint::mul_base(&share_value) != expected {
+            return Err(SignatureVerificationError(
+                SignatureKind::SignatureShare,
+                format!("Invalid signature share from key share {}", commitment.identifier),
+            ));
+        }
+        z += share_value;
+    }
+    if shares.len() != package.commitments.len() {
+        return Err(InvalidInput("Signature shares from signers outside the package".to_string()));
+    }
+
+    let mut signature_bytes = [0u8; 64];
+    signature_bytes[..32].copy_from_slice(group_commitment.compress().as_bytes());
+    signature_bytes[32..].copy_from_slice(z.as_bytes());
+    let signature = Signature::from_bytes(&signature_bytes);
+    package.group_verifying_key()?.verify(&package.message, &signature).map_err(|_| {
+        SignatureVerificationError(
+            SignatureKind::SignatureShare,
+            "Aggregated signature does not verify under the master verifying key".to_string(),
+        )
+    })?;
+    Ok(signature)
+}
+
+/// The point `Σ C_j · identifier^j`, the verifying share of the key share with `identifier`.
+fn verifying_share(vss_commitment: &[u8], identifier: u16) -> Result<EdwardsPoint, GhostkeyError> {
+    let x = Scalar::from(identifier);
+    let mut power = Scalar::ONE;
+    let mut result = EdwardsPoint::default();
+    for coefficient in vss_commitment.chunks_exact(32) {
+        result += power * point(coefficient.try_into().expect("chunks of 32 bytes"))?;
+        power *= x;
+    }
+    Ok(result)
+}
+
+fn group_verifying_key(vss_commitment: &[u8]) -> Result<VerifyingKey, GhostkeyError> {
+    if vss_commitment.is_empty() || !vss_commitment.len().is_multiple_of(32) {
+        return Err(ValidationError("Key share commitment has the wrong length".to_string()));
+    }
+    Ok(VerifyingKey::from(point(vss_commitment[..32].try_into().expect("32 bytes"))?))
+}
+
+/// Decodes a point, rejecting the identity and points outside the prime order subgroup.
+fn point(bytes: &[u8; 32]) -> Result<EdwardsPoint, GhostkeyError> {
+    CompressedEdwardsY(*bytes)
+        .decompress()
+        .filter(|point| point.is_torsion_free() && !point.is_identity())
+        .ok_or_else(|| ValidationError("Invalid curve point".to_string()))
+}
+
+fn scalar(bytes: &[u8; 32]) -> Result<Scalar, GhostkeyError> {
+    Option::from(Scalar::from_canonical_bytes(*bytes))
+        .ok_or_else(|| ValidationError("Invalid scalar".to_string()))
+}
+
+/// SHA-512 over the ciphersuite context string, the `labels` and the `inputs`, the H1, H3, H4
+/// and H5 hashes of RFC 9591.
+fn hash(labels: &[&str], inputs: &[&[u8]]) -> [u8; 64] {
+    let mut hasher = Sha512::new();
+    hasher.update(FROST_CONTEXT.as_bytes());
+    for label in labels {
+        hasher.update(label.as_bytes());
+    }
+    for input in inputs {
+        hasher.update(input);
+    }
+    wide(&hasher.finalize())
+}
+
+fn wide(digest: &[u8]) -> [u8; 64] {
+    let mut bytes = [0u8; 64];
+    bytes.copy_from_slice(digest);
+    bytes
+}
+
+/// A nonce mixing fresh randomness with the secret, so a weak random number generator alone
+/// doesn't reveal the key share.
+fn nonce_generate<R: RngCore + CryptoRng>(rng: &mut R, secret: &[u8; 32]) -> Scalar {
+    let mut random_bytes = [0u8; 32];
+    rng.fill_bytes(&mut random_bytes);
+    Scalar::from_bytes_mod_order_wide(&hash(&["nonce"], &[&random_bytes, secret]))
+}
+
+fn random_scalar<R: RngCore + CryptoRng>(rng: &mut R) -> Scalar {
+    let mut bytes = [0u8; 64];
+    rng.fill_bytes(&mut bytes);
+    Scalar::from_bytes_mod_order_wide(&bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::delegate_certificate::{DelegateCertificate, DelegateCertificateRequestV1};
+    use crate::trust_store::TrustStore;
+    use crate::util::create_keypair;
+    use alloc::vec;
+
+    /// Runs both rounds with the given shares.
+    fn sign(shares: &[&MasterKeyShareV1], message: &[u8]) -> Result<Signature, GhostkeyError> {
+        let nonces: Vec<SigningNoncesV1> =
+            shares.iter().map(|share| SigningNoncesV1::new(share).unwrap()).collect();
+        let package = SigningPackageV1::new(
+            message,
+            nonces.iter().map(|nonces| nonces.commitment.clone()).collect(),
+        )?;
+        let signature_shares: Vec<SignatureShareV1> = shares
+            .iter()
+            .zip(nonces)
+            .map(|(share, nonces)| SignatureShareV1::new(share, nonces, &package).unwrap())
+            .collect();
+        aggregate(&package, &signature_shares)
+    }
+
+    #[test]
+    fn test_threshold_signature() {
+        let (master_signing_key, master_verifying_key) = create_keypair(&mut OsRng).unwrap();
+        let shares = MasterKeyShareV1::split(&master_signing_key, 2, 3).unwrap();
+        for share in &shares {
+            share.verify().unwrap();
+            assert_eq!(share.group_verifying_key().unwrap(), master_verifying_key);
+        }
+
+        // Any two shares sign for the master verifying key
+        for signers in [[0, 1], [1, 2], [2, 0]] {
+            let signature = sign(&[&shares[signers[0]], &shares[signers[1]]], b"message").unwrap();
+            master_verifying_key.verify_strict(b"message", &signature).unwrap();
+        }
+        let signature = sign(&[&shares[0], &shares[1], &shares[2]], b"message").unwrap();
+        master_verifying_key.verify_strict(b"message", &signature).unwrap();
+
+        // One is not enough
+        assert_eq!(sign(&[&shares[0]], b"message").unwrap_err().code(), "GK_INVALID_INPUT");
+
+        // A tampered share is caught by its holder
+        let mut tampered = shares[0].clone();
+        tampered.signing_share = shares[1].signing_share;
+        assert_eq!(tampered.verify().unwrap_err().code(), "GK_VALIDATION_FAILED");
+    }
+
+    #[test]
+    fn test_threshold_rejects_bad_signature_share() {
+        let (master_signing_key, _) = create_keypair(&mut OsRng).unwrap();
+        let shares = MasterKeyShareV1::split(&master_signing_key, 2, 2).unwrap();
+        let nonces: Vec<SigningNoncesV1> =
+            shares.iter().map(|share| SigningNoncesV1::new(share).unwrap()).collect();
+        let package = SigningPackageV1::new(
+            b"message",
+            nonces.iter().map(|nonces| nonces.commitment.clone()).collect(),
+        )
+        .unwrap();
+        let mut signature_shares: Vec<SignatureShareV1> = shares
+            .iter()
+            .zip(nonces)
+            .map(|(share, nonces)| SignatureShareV1::new(share, nonces, &package).unwrap())
+            .collect();
+        signature_shares[1].share = Scalar::ONE.to_bytes();
+        assert_eq!(
+            aggregate(&package, &signature_shares).unwrap_err().code(),
+            "GK_SIG_SHARE_INVALID"
+        );
+        assert_eq!(
+            aggregate(&package, &signature_shares[..1]).unwrap_err().code(),
+            "GK_INVALID_INPUT"
+        );
+    }
+
+    #[test]
+    fn test_threshold_delegate_certificate() {
+        let (master_signing_key, master_verifying_key) = create_keypair(&mut OsRng).unwrap();
+        let shares = MasterKeyShareV1::split(&master_signing_key, 2, 3).unwrap();
+        let (request, _) = DelegateCertificateRequestV1::new("Test Delegate", None, None).unwrap();
+        let signature = sign(&[&shares[0], &shares[2]], &request.signing_message().unwrap()).unwrap();
+        let certificate = request.certify(signature).unwrap();
+        assert_eq!(certificate.version(), 1);
+        let info = certificate.verify(&TrustStore::from(master_verifying_key)).unwrap();
+        assert_eq!(info.info, "Test Delegate");
+
+        // The certificate verifies as an ordinary V1 certificate
+        let DelegateCertificate::V1(certificate) = certificate else {
+            panic!("expected a V1 certificate");
+        };
+        certificate.verify(&TrustStore::from(master_verifying_key)).unwrap();
+    }
+
+    #[test]
+    fn test_threshold_rejects_tampered_package() {
+        let (master_signing_key, _) = create_keypair(&mut OsRng).unwrap();
+        let shares = MasterKeyShareV1::split(&master_signing_key, 2, 3).unwrap();
+        let nonces = SigningNoncesV1::new(&shares[0]).unwrap();
+        let other = SigningNoncesV1::new(&shares[1]).unwrap();
+        let package = SigningPackageV1::new(
+            b"message",
+            vec![nonces.commitment.clone(), other.commitment.clone()],
+        )
+        .unwrap();
+
+        // Another commitment under the identifier of the second signer
+        let mut duplicated = package.clone();
+        duplicated.commitments.push(SigningNoncesV1::new(&shares[1]).unwrap().commitment);
+        assert_eq!(
+            SignatureShareV1::new(&shares[0], SigningNoncesV1::new(&shares[0]).unwrap(), &duplicated)
+                .unwrap_err()
+                .code(),
+            "GK_INVALID_INPUT"
+        );
+
+        // A commitment claiming another master key
+        let (other_master_signing_key, _) = create_keypair(&mut OsRng).unwrap();
+        let other_shares = MasterKeyShareV1::split(&other_master_signing_key, 2, 3).unwrap();
+        let mut mixed = package.clone();
+        mixed.commitments[1].vss_commitment = other_shares[1].vss_commitment.clone();
+        assert_eq!(
+            SignatureShareV1::new(&shares[0], nonces, &mixed).unwrap_err().code(),
+            "GK_INVALID_INPUT"
+        );
+        assert_eq!(aggregate(&mixed, &[]).unwrap_err().code(), "GK_INVALID_INPUT");
+    }
+}
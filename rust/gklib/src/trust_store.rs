@@ -0,0 +1,200 @@
+use super::errors::GhostkeyError;
+use super::util::{verify_with_context, verify_with_hash};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use ed25519_dalek::*;
+use serde::{Deserialize, Serialize};
+use crate::armorable::Armorable;
+use crate::FREENET_MASTER_VERIFYING_KEY_BASE64;
+
+/// Name of the Freenet master verifying key in [`TrustStore::freenet`].
+pub const FREENET_MASTER_KEY_NAME: &str = "current";
+
+/// A set of named master verifying keys that delegate certificates and revocation lists may be
+/// signed by, for example the `current` and `next` keys during a rotation, or a `test` key for
+/// a staging environment. Stored in armored form as a `TRUST_STORE_V1` block.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TrustStore {
+    pub keys: Vec<TrustedMasterKey>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TrustedMasterKey {
+    pub name: String,
+    pub verifying_key: VerifyingKey,
+}
+
+impl TrustStore {
+    /// Creates an empty trust store, nothing verifies against it until keys are added.
+    pub fn new() -> Self {
+        TrustStore { keys: Vec::new() }
+    }
+
+    /// Creates a trust store containing only the Freenet master verifying key.
+    pub fn freenet() -> Self {
+        let verifying_key = VerifyingKey::from_base64(FREENET_MASTER_VERIFYING_KEY_BASE64)
+            .expect("FREENET_MASTER_VERIFYING_KEY_BASE64 is a valid verifying key");
+        Self::single(FREENET_MASTER_KEY_NAME, verifying_key)
+    }
+
+    /// Creates a trust store containing a single named master verifying key.
+    pub fn single(name: &str, verifying_key: VerifyingKey) -> Self {
+        TrustStore {
+            keys: vec![TrustedMasterKey {
+                name: name.to_string(),
+                verifying_key,
+            }],
+        }
+    }
+
+    /// Adds a named master verifying key, names must be unique within the trust store.
+    pub fn add(&mut self, name: &str, verifying_key: VerifyingKey) -> Result<(), GhostkeyError> {
+        if self.get(name).is_some() {
+            return Err(GhostkeyError::InvalidInput(format!(
+                "Trust store already contains a key named '{}'",
+                name
+            )));
+        }
+        self.keys.push(TrustedMasterKey {
+            name: name.to_string(),
+            verifying_key,
+        });
+        Ok(())
+    }
+
+    /// Removes the named key, returning it if it was present.
+    pub fn remove(&mut self, name: &str) -> Option<TrustedMasterKey> {
+        let index = self.keys.iter().position(|key| key.name == name)?;
+        Some(self.keys.remove(index))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&VerifyingKey> {
+        self.keys
+            .iter()
+            .find(|key| key.name == name)
+            .map(|key| &key.verifying_key)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Returns the trusted key that produced `signature` over `data`, if any.
+    pub fn find_signer<T: Serialize + for<'de> Deserialize<'de> + 'static>(
+        &self,
+        data: &T,
+        signature: &Signature,
+    ) -> Result<Option<&TrustedMasterKey>, GhostkeyError> {
+        for key in &self.keys {
+            if verify_with_hash(&key.verifying_key, data, signature)? {
+                return Ok(Some(key));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns the trusted key that produced `signature` over `data` under `context`, see
+    /// [`verify_with_context`].
+    pub fn find_signer_with_context<T: Serialize>(
+        &self,
+        context: &str,
+        data: &T,
+        signature: &Signature,
+    ) -> Result<Option<&TrustedMasterKey>, GhostkeyError> {
+        for key in &self.keys {
+            if verify_with_context(&key.verifying_key, context, data, signature)? {
+                return Ok(Some(key));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl Default for TrustStore {
+    fn default() -> Self {
+        Self::freenet()
+    }
+}
+
+impl From<VerifyingKey> for TrustStore {
+    fn from(verifying_key: VerifyingKey) -> Self {
+        Self::single("default", verifying_key)
+    }
+}
+
+#[cfg(all(test, feature = "issue"))]
+mod tests {
+    use super::*;
+    use crate::delegate_certificate::DelegateCertificateV1;
+    use crate::util::create_keypair;
+    use rand_core::OsRng;
+
+    #[test]
+    fn test_freenet_trust_store() {
+        let trust_store = TrustStore::freenet();
+        assert!(trust_store.get(FREENET_MASTER_KEY_NAME).is_some());
+    }
+
+    #[test]
+    fn test_add_duplicate_name() {
+        let (_, verifying_key) = create_keypair(&mut OsRng).unwrap();
+        let mut trust_store = TrustStore::new();
+        trust_store.add("current", verifying_key).unwrap();
+        assert!(matches!(
+            trust_store.add("current", verifying_key),
+            Err(GhostkeyError::InvalidInput(_))
+        ));
+    }
+
+    #[test]
+    fn test_verify_with_any_trusted_key() {
+        let (current_signing_key, current_verifying_key) = create_keypair(&mut OsRng).unwrap();
+        let (next_signing_key, next_verifying_key) = create_keypair(&mut OsRng).unwrap();
+        let (untrusted_signing_key, _) = create_keypair(&mut OsRng).unwrap();
+
+        let mut trust_store = TrustStore::new();
+        trust_store.add("current", current_verifying_key).unwrap();
+        trust_store.add("next", next_verifying_key).unwrap();
+
+        let info = "Test Delegate".to_string();
+        let (current_delegate, _) = DelegateCertificateV1::new(&current_signing_key, &info).unwrap();
+        let (next_delegate, _) = DelegateCertificateV1::new(&next_signing_key, &info).unwrap();
+        let (untrusted_delegate, _) =
+            DelegateCertificateV1::new(&untrusted_signing_key, &info).unwrap();
+
+        assert_eq!(current_delegate.verify(&trust_store).unwrap().info, info);
+        assert_eq!(next_delegate.verify(&trust_store).unwrap().info, info);
+        assert!(matches!(
+            untrusted_delegate.verify(&trust_store).unwrap_err(),
+            GhostkeyError::SignatureVerificationError(..)
+        ));
+
+        let signer = trust_store
+            .find_signer(&next_delegate.payload, &next_delegate.signature)
+            .unwrap()
+            .unwrap();
+        assert_eq!(signer.name, "next");
+
+        // Retiring a key stops certificates it signed from verifying
+        trust_store.remove("current");
+        assert!(current_delegate.verify(&trust_store).is_err());
+    }
+
+    #[test]
+    fn test_trust_store_armor_round_trip() {
+        let (_, current_verifying_key) = create_keypair(&mut OsRng).unwrap();
+        let (_, test_verifying_key) = create_keypair(&mut OsRng).unwrap();
+
+        let mut trust_store = TrustStore::new();
+        trust_store.add("current", current_verifying_key).unwrap();
+        trust_store.add("test", test_verifying_key).unwrap();
+
+        let armored = trust_store.to_armored_string().unwrap();
+        assert!(armored.contains("TRUST_STORE_V1"));
+
+        let decoded = TrustStore::from_armored_string(&armored).unwrap();
+        assert_eq!(decoded, trust_store);
+    }
+}
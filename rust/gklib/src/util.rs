@@ -1,9 +1,18 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
 use ed25519_dalek::*;
-use rand_core::{CryptoRng, OsRng, RngCore};
 
 use crate::armorable::*;
 use crate::errors::GhostkeyError;
-use blind_rsa_signatures::{KeyPair as RSAKeyPair, Options, Signature as RSASignature};
+#[cfg(feature = "issue")]
+use crate::issuance;
+#[cfg(feature = "issue")]
+use blind_rsa_signatures::{KeyPair as RSAKeyPair, Signature as RSASignature};
+use ciborium::{ser::into_writer, value::Value};
+#[cfg(feature = "issue")]
+use rand_core::{CryptoRng, RngCore};
 use serde::{Deserialize, Serialize};
 
 /// Creates a new ECDSA keypair for signing and verification.
@@ -12,6 +21,7 @@ use serde::{Deserialize, Serialize};
 ///
 /// A tuple containing a `SigningKey` and its corresponding `VerifyingKey`,
 /// or a `GhostkeyError` if key creation fails.
+#[cfg(feature = "issue")]
 pub fn create_keypair<R>(rng: &mut R) -> Result<(SigningKey, VerifyingKey), GhostkeyError>
 where
     R: RngCore + CryptoRng,
@@ -34,14 +44,14 @@ where
 ///
 /// # Note
 ///
-/// This function uses blake3 to hash the data before signing.
+/// Despite the name this signs the CBOR encoding of the data directly, without hashing or
+/// domain separation. It is kept so that V1 certificates continue to verify, new formats
+/// should use [`sign_with_context`].
 pub fn sign_with_hash<T: Serialize + for<'de> Deserialize<'de> + 'static>(
     signing_key: &SigningKey,
     data: &T,
-) -> Result<Signature, Box<GhostkeyError>> {
-    let bytes = data
-        .to_bytes()
-        .map_err(|e| GhostkeyError::SerializationError(e.to_string()))?;
+) -> Result<Signature, GhostkeyError> {
+    let bytes = data.to_bytes()?;
     Ok(signing_key.sign(bytes.as_slice()))
 }
 
@@ -60,52 +70,132 @@ pub fn sign_with_hash<T: Serialize + for<'de> Deserialize<'de> + 'static>(
 ///
 /// # Note
 ///
-/// This function uses blake3 to hash the data before verification.
+/// Verifies a signature over the CBOR encoding of the data as produced by [`sign_with_hash`],
+/// see [`verify_with_context`] for the domain-separated scheme.
 pub fn verify_with_hash<T: Serialize + for<'de> Deserialize<'de> + 'static>(
     verifying_key: &VerifyingKey,
     data: &T,
     signature: &Signature,
-) -> Result<bool, Box<GhostkeyError>> {
-    let bytes = data
-        .to_bytes()
-        .map_err(|e| GhostkeyError::SerializationError(e.to_string()))?;
+) -> Result<bool, GhostkeyError> {
+    let bytes = data.to_bytes()?;
     Ok(verifying_key.verify(bytes.as_slice(), signature).is_ok())
 }
 
+/// Encodes the data as deterministic CBOR (RFC 8949, section 4.2): map keys are sorted by their
+/// encoded bytes, and ciborium already uses definite lengths and the shortest integer encodings.
+/// Two encoders that agree on the data therefore agree on the bytes that get signed.
+pub fn canonical_cbor<T: Serialize>(data: &T) -> Result<Vec<u8>, GhostkeyError> {
+    let value = Value::serialized(data).map_err(|e| {
+        GhostkeyError::SerializationError("Failed to convert to a CBOR value".to_string(), e.into())
+    })?;
+    let mut buf = Vec::new();
+    into_writer(&canonicalize(value)?, &mut buf).map_err(|e| {
+        GhostkeyError::SerializationError("Failed to encode canonical CBOR".to_string(), e.into())
+    })?;
+    Ok(buf)
+}
+
+fn canonicalize(value: Value) -> Result<Value, GhostkeyError> {
+    Ok(match value {
+        Value::Map(entries) => {
+            let mut sorted = Vec::with_capacity(entries.len());
+            for (key, value) in entries {
+                let key = canonicalize(key)?;
+                let mut key_bytes = Vec::new();
+                into_writer(&key, &mut key_bytes).map_err(|e| {
+                    GhostkeyError::SerializationError("Failed to encode map key".to_string(), e.into())
+                })?;
+                sorted.push((key_bytes, key, canonicalize(value)?));
+            }
+            sorted.sort_by(|a, b| a.0.cmp(&b.0));
+            Value::Map(sorted.into_iter().map(|(_, key, value)| (key, value)).collect())
+        }
+        Value::Array(items) => Value::Array(
+            items
+                .into_iter()
+                .map(canonicalize)
+                .collect::<Result<Vec<_>, _>>()?,
+        ),
+        Value::Tag(tag, inner) => Value::Tag(tag, Box::new(canonicalize(*inner)?)),
+        other => other,
+    })
+}
+
+/// Hashes the canonical CBOR encoding of the data with BLAKE3 in key derivation mode, using
+/// `context` to separate the domains of different signed types. A signature over the hash for
+/// one context can't be replayed as a signature over any other type.
+pub fn context_hash<T: Serialize>(context: &str, data: &T) -> Result<[u8; 32], GhostkeyError> {
+    Ok(blake3::derive_key(context, &canonical_cbor(data)?))
+}
+
+/// Signs the data with hash-then-sign over [`context_hash`].
+pub fn sign_with_context<T: Serialize>(
+    signing_key: &SigningKey,
+    context: &str,
+    data: &T,
+) -> Result<Signature, GhostkeyError> {
+    Ok(signing_key.sign(&context_hash(context, data)?))
+}
+
+/// Verifies a signature produced by [`sign_with_context`] with the same context.
+pub fn verify_with_context<T: Serialize>(
+    verifying_key: &VerifyingKey,
+    context: &str,
+    data: &T,
+    signature: &Signature,
+) -> Result<bool, GhostkeyError> {
+    Ok(verifying_key
+        .verify(&context_hash(context, data)?, signature)
+        .is_ok())
+}
+
+/// Returns the current time as seconds since the Unix epoch, used for certificate validity
+/// periods.
+#[cfg(feature = "std")]
+pub fn current_unix_time() -> u64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// The UTC month containing `time` (seconds since the Unix epoch) as `YYYY-MM`, the form of
+/// [`GhostkeyMetadata::issued`](crate::ghost_key_certificate::GhostkeyMetadata::issued).
+pub fn month_of(time: u64) -> String {
+    // Civil from days, after Howard Hinnant's date algorithms
+    let days = time / 86_400 + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    format!("{:04}-{:02}", year, month)
+}
+
 /// Signs the given data using the provided RSA signing key, uses blind signature internally
 /// to guarantee compatibility with actual blind signatures, even if it's less efficient.
-pub fn unblinded_rsa_sign(
+/// Blinding and the PSS salt draw from `rng`.
+#[cfg(feature = "issue")]
+pub fn unblinded_rsa_sign<R>(
+    rng: &mut R,
     signing_keypair: &RSAKeyPair,
     msg: &[u8],
-) -> Result<RSASignature, Box<GhostkeyError>> {
-    let options = Options::default();
-
-    let blinding_result = signing_keypair
-        .pk
-        .blind(&mut OsRng, msg, false, &options)
-        .map_err(|e| GhostkeyError::RSAError(e.to_string()))?;
-
-    let blind_sig = signing_keypair
-        .sk
-        .blind_sign(&mut OsRng, &blinding_result.blind_msg, &options)
-        .map_err(|e| GhostkeyError::RSAError(e.to_string()))?;
-
-    let sig = signing_keypair
-        .pk
-        .finalize(
-            &blind_sig,
-            &blinding_result.secret,
-            blinding_result.msg_randomizer,
-            msg,
-            &options,
-        )
-        .map_err(|e| GhostkeyError::RSAError(e.to_string()))?;
-
-    Ok(sig)
+) -> Result<RSASignature, GhostkeyError>
+where
+    R: RngCore + CryptoRng,
+{
+    let (blinded_message, secret) = issuance::blind(rng, &signing_keypair.pk, msg)?;
+    let blind_signature = issuance::blind_sign(rng, &signing_keypair.sk, &blinded_message)?;
+    issuance::finalize(&signing_keypair.pk, &blind_signature, &secret, msg)
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "issue"))]
 mod tests {
+    use rand_core::OsRng;
     use serde::{Deserialize, Serialize};
 
     use super::*;
@@ -148,11 +238,63 @@ mod tests {
         assert!(!is_valid);
     }
 
+    #[test]
+    fn test_sign_and_verify_with_context() {
+        let (signing_key, verifying_key) = create_keypair(&mut OsRng).unwrap();
+        let test_data = TestData {
+            field1: "Hello".to_string(),
+            field2: 42,
+        };
+
+        let signature = sign_with_context(&signing_key, "test context", &test_data).unwrap();
+        assert!(verify_with_context(&verifying_key, "test context", &test_data, &signature).unwrap());
+
+        // The same data under another context must not verify
+        assert!(!verify_with_context(&verifying_key, "other context", &test_data, &signature).unwrap());
+    }
+
+    #[test]
+    fn test_canonical_cbor_sorts_map_keys() {
+        use std::collections::HashMap;
+
+        let mut forward = Vec::new();
+        let mut backward = Vec::new();
+        for key in ["a", "bb", "c", "dd"] {
+            forward.push((key.to_string(), 1));
+        }
+        for key in ["dd", "c", "bb", "a"] {
+            backward.push((key.to_string(), 1));
+        }
+        let forward: HashMap<String, i32> = forward.into_iter().collect();
+        let backward: HashMap<String, i32> = backward.into_iter().collect();
+        assert_eq!(canonical_cbor(&forward).unwrap(), canonical_cbor(&backward).unwrap());
+
+        // Keys are ordered by their encoding, so shorter strings come first
+        let value: Value = ciborium::de::from_reader(canonical_cbor(&forward).unwrap().as_slice()).unwrap();
+        let keys: Vec<String> = value
+            .as_map()
+            .unwrap()
+            .iter()
+            .map(|(key, _)| key.as_text().unwrap().to_string())
+            .collect();
+        assert_eq!(keys, vec!["a", "c", "bb", "dd"]);
+    }
+
+    #[test]
+    fn test_month_of() {
+        assert_eq!(month_of(0), "1970-01");
+        // Leap day
+        assert_eq!(month_of(951_782_400), "2000-02");
+        // The last second of October 2024 and the first of November
+        assert_eq!(month_of(1_730_419_199), "2024-10");
+        assert_eq!(month_of(1_730_419_200), "2024-11");
+    }
+
     #[test]
     fn test_rsa_sign_and_verify() {
         let keypair = RSAKeyPair::generate(&mut OsRng, 2048).unwrap();
         let msg = b"test";
-        let signature = unblinded_rsa_sign(&keypair, msg).unwrap();
+        let signature = unblinded_rsa_sign(&mut OsRng, &keypair, msg).unwrap();
         let is_valid = keypair
             .pk
             .verify(&signature, None, msg, &Default::default());
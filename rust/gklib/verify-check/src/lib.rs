@@ -0,0 +1,21 @@
+//! Builds ghost key verification with only the `verify` feature of ghostkey_lib, as a WASM
+//! contract or delegate would. `cargo make check-verify-wasm` builds this crate for
+//! `wasm32-unknown-unknown` and fails if getrandom is in its dependency tree.
+
+#![no_std]
+
+use ghostkey_lib::armorable::Armorable;
+use ghostkey_lib::delegate_certificate::DelegateInfo;
+use ghostkey_lib::errors::GhostkeyError;
+use ghostkey_lib::ghost_key_certificate::GhostkeyCertificateV1;
+use ghostkey_lib::trust_store::TrustStore;
+
+/// Verifies an armored ghost key certificate against the Freenet master key at `time` (seconds
+/// since the Unix epoch).
+pub fn verify_ghost_key_certificate(
+    armored_certificate: &str,
+    time: u64,
+) -> Result<DelegateInfo, GhostkeyError> {
+    let certificate = GhostkeyCertificateV1::from_armored_string(armored_certificate)?;
+    certificate.verify_at(time, &TrustStore::freenet())
+}
@@ -1,11 +1,13 @@
 use base64::Engine;
-use rand::SeedableRng;
-use rand_chacha::ChaCha20Rng;
-use ghostkey_lib::armorable::Armorable;
-use ghostkey_lib::util::create_keypair;
-use blind_rsa_signatures::{BlindSignature, Options, Secret};
-use ghostkey_lib::delegate_certificate::DelegateCertificateV1;
-use ghostkey_lib::ghost_key_certificate::GhostkeyCertificateV1;
+use ghostkey_lib::armorable::{Armorable, ArmoredBundle};
+use blind_rsa_signatures::{BlindSignature, Secret};
+use ghostkey_lib::delegate_certificate::DelegateCertificate;
+use ghostkey_lib::ghost_key_certificate::{GhostkeyCertificate, GhostkeyMetadata};
+use ghostkey_lib::errors::GhostkeyError;
+use ghostkey_lib::issuance::{BlindingState, IssuanceResponse};
+use ghostkey_lib::seed::GhostkeySeed;
+use ghostkey_lib::signed_message::{SignedMessage, SignedMessageV2, VerifiedMessage};
+use ghostkey_lib::trust_store::TrustStore;
 use base64::prelude::*;
 
 #[cfg(target_arch = "wasm32")]
@@ -14,6 +16,49 @@ use wasm_bindgen::prelude::*;
 #[cfg(target_arch = "wasm32")]
 use js_sys::{Object, Reflect, JsString};
 
+/// An error passed to JavaScript as an `Error` whose `code` property holds the stable
+/// [`GhostkeyError::code`], so pages can react to the kind of failure without parsing messages.
+#[derive(Debug)]
+#[allow(dead_code)]
+struct CoreError {
+    code: &'static str,
+    message: String,
+}
+
+impl CoreError {
+    fn invalid_input(message: &str) -> Self {
+        CoreError {
+            code: "GK_INVALID_INPUT",
+            message: message.to_string(),
+        }
+    }
+
+    /// Wraps a library error, prefixing its message with what was being done.
+    fn context(message: &str, error: GhostkeyError) -> Self {
+        CoreError {
+            code: error.code(),
+            message: format!("{}: {}", message, error),
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl From<CoreError> for JsValue {
+    fn from(error: CoreError) -> Self {
+        let js_error = js_sys::Error::new(&error.message);
+        Reflect::set(&js_error, &JsString::from("code"), &JsString::from(error.code)).unwrap();
+        js_error.into()
+    }
+}
+
+/// Unix time (seconds) given by the page, or the current time from the JavaScript clock if
+/// undefined. `SystemTime` panics on wasm32, so the library's `verify_at` and `new_at` variants
+/// are called with this time instead of the variants that read the clock.
+#[cfg(target_arch = "wasm32")]
+fn unix_time(time: Option<f64>) -> u64 {
+    (time.unwrap_or_else(|| js_sys::Date::now() / 1000.0)) as u64
+}
+
 #[derive(Debug)]
 #[allow(dead_code)]
 struct KeypairAndBlindResult {
@@ -21,48 +66,69 @@ struct KeypairAndBlindResult {
     ec_verifying_key: String,
     blinded_signing_key: String,
     blinding_secret: String,
+    issuance_request: String,
+    mnemonic: String,
 }
 
+/// Decodes the metadata the API returned with the delegate certificate, if it returned any.
 #[allow(dead_code)]
-fn generate_keypair_and_blind_core(delegate_certificate_base64: String, seed: Vec<u8>) -> Result<KeypairAndBlindResult, String> {
-    if seed.len() != 32 {
-        return Err("Seed must be exactly 32 bytes".to_string());
-    }
+fn decode_metadata(metadata_base64: Option<String>) -> Result<Option<GhostkeyMetadata>, CoreError> {
+    metadata_base64
+        .map(|encoded| GhostkeyMetadata::from_base64(&encoded))
+        .transpose()
+        .map_err(|e| CoreError::context("Invalid ghost key metadata", e))
+}
 
-    let mut rng = ChaCha20Rng::from_seed(seed.try_into().expect("Seed must be 32 bytes"));
-    let (ec_signing_key, ec_verifying_key) = create_keypair(&mut rng).map_err(|_| "Failed to create keypair".to_string())?;
+/// Blinds a new ghost key derived from `seed` for the delegate, bound to `metadata_base64` if
+/// given so that the delegate issues a V3 certificate. The seed is returned as the recovery
+/// phrase for the user to write down.
+#[allow(dead_code)]
+fn generate_keypair_and_blind_core(delegate_certificate_base64: String, seed: Vec<u8>, metadata_base64: Option<String>) -> Result<KeypairAndBlindResult, CoreError> {
+    let seed: [u8; 32] = seed.try_into()
+        .map_err(|_| CoreError::invalid_input("Seed must be exactly 32 bytes"))?;
+    let seed = GhostkeySeed::from_bytes(seed);
 
-    let delegate_certificate = DelegateCertificateV1::from_base64(&delegate_certificate_base64)
-        .map_err(|e| format!("Invalid delegate certificate: {}", e))?;
+    let mut rng = seed.rng();
 
-    let verifying_key_bytes = Armorable::to_bytes(&ec_verifying_key)
-        .map_err(|_| "Failed to convert verifying key to bytes".to_string())?;
+    let delegate_certificate = DelegateCertificate::from_base64(&delegate_certificate_base64)
+        .map_err(|e| CoreError::context("Invalid delegate certificate", e))?;
 
-    let blinding_result = delegate_certificate.payload.delegate_verifying_key
-        .blind(&mut rng, verifying_key_bytes, false, &Options::default())
-        .map_err(|_| "Blinding operation failed".to_string())?;
+    let blinding_result = match decode_metadata(metadata_base64)? {
+        Some(metadata) => BlindingState::new_with_metadata_and_rng(&mut rng, delegate_certificate, metadata),
+        None => BlindingState::new_with_rng(&mut rng, delegate_certificate),
+    };
+    let (blinding_state, issuance_request) = blinding_result
+        .map_err(|e| CoreError::context("Blinding operation failed", e))?;
 
     Ok(KeypairAndBlindResult {
-        ec_signing_key: ec_signing_key.to_base64().unwrap(),
-        ec_verifying_key: ec_verifying_key.to_base64().unwrap(),
-        blinded_signing_key: blinding_result.blind_msg.to_base64().unwrap(),
-        blinding_secret: BASE64_STANDARD.encode(blinding_result.secret.0),
+        ec_signing_key: blinding_state.signing_key().to_base64()
+            .map_err(|e| CoreError::context("Failed to encode signing key", e))?,
+        ec_verifying_key: blinding_state.signing_key().verifying_key().to_base64()
+            .map_err(|e| CoreError::context("Failed to encode verifying key", e))?,
+        blinded_signing_key: issuance_request.blinded_message().to_base64()
+            .map_err(|e| CoreError::context("Failed to encode blinded message", e))?,
+        blinding_secret: BASE64_STANDARD.encode(&blinding_state.secret().0),
+        issuance_request: issuance_request.to_base64()
+            .map_err(|e| CoreError::context("Failed to encode issuance request", e))?,
+        mnemonic: seed.to_mnemonic(),
     })
 }
 
 #[cfg(target_arch = "wasm32")]
 #[wasm_bindgen]
-pub fn wasm_generate_keypair_and_blind(delegate_certificate_base64: String, seed: Vec<u8>) -> JsValue {
-    match generate_keypair_and_blind_core(delegate_certificate_base64, seed) {
+pub fn wasm_generate_keypair_and_blind(delegate_certificate_base64: String, seed: Vec<u8>, metadata_base64: Option<String>) -> Result<JsValue, JsValue> {
+    match generate_keypair_and_blind_core(delegate_certificate_base64, seed, metadata_base64) {
         Ok(result) => {
             let return_obj = Object::new();
             Reflect::set(&return_obj, &JsString::from("ec_signing_key"), &JsString::from(result.ec_signing_key)).unwrap();
             Reflect::set(&return_obj, &JsString::from("ec_verifying_key"), &JsString::from(result.ec_verifying_key)).unwrap();
             Reflect::set(&return_obj, &JsString::from("blinded_signing_key"), &JsString::from(result.blinded_signing_key)).unwrap();
             Reflect::set(&return_obj, &JsString::from("blinding_secret"), &JsString::from(result.blinding_secret)).unwrap();
-            return_obj.into()
+            Reflect::set(&return_obj, &JsString::from("issuance_request"), &JsString::from(result.issuance_request)).unwrap();
+            Reflect::set(&return_obj, &JsString::from("mnemonic"), &JsString::from(result.mnemonic)).unwrap();
+            Ok(return_obj.into())
         }
-        Err(err) => JsValue::from_str(&err),
+        Err(err) => Err(err.into()),
     }
 }
 
@@ -72,44 +138,38 @@ fn generate_ghost_key_certificate_core(
     blinded_signature_base64: String,
     blinding_secret_base64: String,
     ec_verifying_key_base64: String,
-    ec_signing_key_base64: String
-) -> Result<GhostKeyCertificateResult, String> {
+    ec_signing_key_base64: String,
+    metadata_base64: Option<String>,
+) -> Result<GhostKeyCertificateResult, CoreError> {
     let blind_signature = BlindSignature::from_base64(&blinded_signature_base64)
-        .map_err(|_| "Invalid blinded signature".to_string())?;
+        .map_err(|e| CoreError::context("Invalid blinded signature", e))?;
 
-    let delegate_certificate = DelegateCertificateV1::from_base64(&delegate_certificate_base64)
-        .map_err(|e| format!("Invalid delegate certificate: {}", e))?;
+    let delegate_certificate = DelegateCertificate::from_base64(&delegate_certificate_base64)
+        .map_err(|e| CoreError::context("Invalid delegate certificate", e))?;
 
-    let delegate_verifying_key = &delegate_certificate.clone().payload.delegate_verifying_key;
-    let blinding_secret = Secret(BASE64_STANDARD.decode(blinding_secret_base64).unwrap());
+    let blinding_secret = Secret(BASE64_STANDARD.decode(blinding_secret_base64)
+        .map_err(|e| CoreError::context("Invalid blinding secret", e.into()))?);
 
     let ec_verifying_key = ed25519_dalek::VerifyingKey::from_base64(&ec_verifying_key_base64)
-        .map_err(|_| "Invalid EC verifying key".to_string())?;
+        .map_err(|e| CoreError::context("Invalid EC verifying key", e))?;
 
     let ec_signing_key = ed25519_dalek::SigningKey::from_base64(&ec_signing_key_base64)
-        .map_err(|_| "Invalid EC signing key".to_string())?;
-
-    let verifying_key_bytes = Armorable::to_bytes(&ec_verifying_key)
-        .map_err(|_| "Failed to convert verifying key to bytes".to_string())?;
-
-    let unblinded_signature = delegate_verifying_key.finalize(
-        &blind_signature,
-        &blinding_secret,
-        None,
-        verifying_key_bytes,
-        &Options::default(),
-    ).map_err(|e| format!("Unblinding operation failed: {}", e))?;
-
-    let ghost_key_certificate = GhostkeyCertificateV1 {
-        delegate: delegate_certificate.clone(),
-        verifying_key: ec_verifying_key,
-        signature: unblinded_signature,
-    };
+        .map_err(|e| CoreError::context("Invalid EC signing key", e))?;
+
+    if ec_signing_key.verifying_key() != ec_verifying_key {
+        return Err(CoreError::invalid_input("EC verifying key does not match EC signing key"));
+    }
+
+    let metadata = decode_metadata(metadata_base64)?;
+
+    let blinding_state = BlindingState::from_parts(delegate_certificate, ec_signing_key, blinding_secret, metadata);
+    let (ghost_key_certificate, ec_signing_key) = blinding_state.finalize(&IssuanceResponse::new(blind_signature))
+        .map_err(|e| CoreError::context("Unblinding operation failed", e))?;
     
     let armored_certificate = ghost_key_certificate.to_armored_string()
-        .map_err(|_| "Failed to armor ghostkey certificate".to_string())?;
+        .map_err(|e| CoreError::context("Failed to armor ghostkey certificate", e))?;
     let armored_signing_key = ec_signing_key.to_armored_string()
-        .map_err(|_| "Failed to armor signing key".to_string())?;
+        .map_err(|e| CoreError::context("Failed to armor signing key", e))?;
 
     Ok(GhostKeyCertificateResult {
         armored_ghost_key_cert: armored_certificate,
@@ -124,7 +184,8 @@ pub fn wasm_generate_ghost_key_certificate(
     blinded_signature_base64: String,
     blinding_secret_base64: String,
     ec_verifying_key_base64: String,
-    ec_signing_key_base64: String
+    ec_signing_key_base64: String,
+    metadata_base64: Option<String>,
 ) -> Result<JsValue, JsValue> {
     match generate_ghost_key_certificate_core(
         delegate_certificate_base64,
@@ -132,6 +193,7 @@ pub fn wasm_generate_ghost_key_certificate(
         blinding_secret_base64,
         ec_verifying_key_base64,
         ec_signing_key_base64,
+        metadata_base64,
     ) {
         Ok(result) => {
             let return_obj = js_sys::Object::new();
@@ -139,40 +201,362 @@ pub fn wasm_generate_ghost_key_certificate(
             js_sys::Reflect::set(&return_obj, &JsValue::from_str("armored_ghost_key_signing_key"), &JsValue::from_str(&result.armored_ghost_key_signing_key)).unwrap();
             Ok(JsValue::from(return_obj))
         },
-        Err(err) => Err(JsValue::from_str(&format!("Error: {}", err))),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Rebuilds the armored ghost signing key for an armored ghost key certificate from the
+/// recovery phrase shown when the key was created.
+#[allow(dead_code)]
+fn restore_ghost_key_core(armored_ghost_key_cert: String, mnemonic: String) -> Result<String, CoreError> {
+    let ghost_key_certificate = GhostkeyCertificate::from_armored_string(&armored_ghost_key_cert)
+        .map_err(|e| CoreError::context("Invalid ghost key certificate", e))?;
+    let signing_key = GhostkeySeed::from_mnemonic(&mnemonic)
+        .and_then(|seed| seed.restore(&ghost_key_certificate))
+        .map_err(|e| CoreError::context("Failed to restore ghost key", e))?;
+    signing_key.to_armored_string()
+        .map_err(|e| CoreError::context("Failed to armor signing key", e))
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub fn wasm_restore_ghost_key(armored_ghost_key_cert: String, mnemonic: String) -> Result<JsValue, JsValue> {
+    match restore_ghost_key_core(armored_ghost_key_cert, mnemonic) {
+        Ok(armored_signing_key) => Ok(JsValue::from_str(&armored_signing_key)),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Verifies an armored ghost key certificate at `time` against an armored trust store, or the
+/// Freenet master verifying key if no trust store is given. Returns the delegate info on success.
+#[allow(dead_code)]
+fn verify_ghost_key_certificate_core(
+    armored_ghost_key_cert: String,
+    armored_trust_store: Option<String>,
+    time: u64,
+) -> Result<String, CoreError> {
+    let trust_store = match armored_trust_store {
+        Some(armored) => TrustStore::from_armored_string(&armored)
+            .map_err(|e| CoreError::context("Invalid trust store", e))?,
+        None => TrustStore::freenet(),
+    };
+
+    let ghost_key_certificate = GhostkeyCertificate::from_armored_string(&armored_ghost_key_cert)
+        .map_err(|e| CoreError::context("Invalid ghost key certificate", e))?;
+
+    ghost_key_certificate.verify_at(time, &trust_store)
+        .map(|info| info.to_string())
+        .map_err(|e| CoreError::context("Verification failed", e))
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub fn wasm_verify_ghost_key_certificate(
+    armored_ghost_key_cert: String,
+    armored_trust_store: Option<String>,
+    time: Option<f64>,
+) -> Result<JsValue, JsValue> {
+    match verify_ghost_key_certificate_core(armored_ghost_key_cert, armored_trust_store, unix_time(time)) {
+        Ok(info) => Ok(JsValue::from_str(&info)),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Signs `message` for `purpose` with an armored ghost key bundle holding the certificate and
+/// the unencrypted signing key, as shown on the donation page, recording `created` as the time
+/// of signing. Returns the armored signed message, with only the signature if `detached`.
+#[allow(dead_code)]
+fn sign_message_core(
+    armored_ghost_key: String,
+    purpose: String,
+    content_type: Option<String>,
+    message: Vec<u8>,
+    detached: bool,
+    created: u64,
+) -> Result<String, CoreError> {
+    let bundle = ArmoredBundle::from_armored_string(&armored_ghost_key)
+        .map_err(|e| CoreError::context("Invalid ghost key", e))?;
+    let ghost_key_certificate = GhostkeyCertificate::from_bundle(&bundle)
+        .map_err(|e| CoreError::context("Invalid ghost key certificate", e))?;
+    let ghost_signing_key = bundle.get::<ed25519_dalek::SigningKey>()
+        .map_err(|e| CoreError::context("Invalid ghost signing key", e))?;
+
+    let mut signed_message = SignedMessageV2::new_at(
+        created,
+        ghost_key_certificate,
+        &ghost_signing_key,
+        &purpose,
+        content_type.as_deref(),
+        &message,
+    ).map_err(|e| CoreError::context("Signing failed", e))?;
+    if detached {
+        signed_message.detach();
+    }
+    SignedMessage::from(signed_message).to_armored_string()
+        .map_err(|e| CoreError::context("Failed to armor signed message", e))
+}
+
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub fn wasm_sign_message(
+    armored_ghost_key: String,
+    purpose: String,
+    content_type: Option<String>,
+    message: Vec<u8>,
+    detached: bool,
+    time: Option<f64>,
+) -> Result<JsValue, JsValue> {
+    match sign_message_core(armored_ghost_key, purpose, content_type, message, detached, unix_time(time)) {
+        Ok(armored) => Ok(JsValue::from_str(&armored)),
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Verifies an armored signed message at `time` against an armored trust store, or the Freenet
+/// master verifying key if no trust store is given. A detached signature needs the `message`.
+#[allow(dead_code)]
+fn verify_signed_message_core(
+    armored_signed_message: String,
+    message: Option<Vec<u8>>,
+    armored_trust_store: Option<String>,
+    time: u64,
+) -> Result<VerifiedMessage, CoreError> {
+    let trust_store = match armored_trust_store {
+        Some(armored) => TrustStore::from_armored_string(&armored)
+            .map_err(|e| CoreError::context("Invalid trust store", e))?,
+        None => TrustStore::freenet(),
+    };
+
+    let signed_message = SignedMessage::from_armored_string(&armored_signed_message)
+        .map_err(|e| CoreError::context("Invalid signed message", e))?;
+
+    signed_message.verify_at(time, &trust_store, message.as_deref())
+        .map_err(|e| CoreError::context("Verification failed", e))
+}
+
+/// Returns an object with the `delegate_info`, `metadata`, `purpose`, `created` (Unix time),
+/// `content_type` and `message` (bytes) of the verified message, fields it lacks are undefined.
+/// V1 messages, whose signature is not domain separated, are rejected.
+#[cfg(target_arch = "wasm32")]
+#[wasm_bindgen]
+pub fn wasm_verify_signed_message(
+    armored_signed_message: String,
+    message: Option<Vec<u8>>,
+    armored_trust_store: Option<String>,
+    time: Option<f64>,
+) -> Result<JsValue, JsValue> {
+    match verify_signed_message_core(armored_signed_message, message, armored_trust_store, unix_time(time)) {
+        Ok(verified) => {
+            let return_obj = Object::new();
+            let set = |key: &str, value: JsValue| {
+                Reflect::set(&return_obj, &JsValue::from_str(key), &value).unwrap();
+            };
+            set("delegate_info", JsValue::from_str(&verified.delegate_info.to_string()));
+            if let Some(metadata) = &verified.metadata {
+                set("metadata", JsValue::from_str(&metadata.to_string()));
+            }
+            if let Some(purpose) = &verified.purpose {
+                set("purpose", JsValue::from_str(purpose));
+            }
+            if let Some(created) = verified.created {
+                set("created", JsValue::from_f64(created as f64));
+            }
+            if let Some(content_type) = &verified.content_type {
+                set("content_type", JsValue::from_str(content_type));
+            }
+            set("message", js_sys::Uint8Array::from(verified.message.as_slice()).into());
+            Ok(JsValue::from(return_obj))
+        },
+        Err(err) => Err(err.into()),
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use blind_rsa_signatures::SecretKey as RSASigningKey;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+    use ghostkey_lib::delegate_certificate::{DelegateCertificateV1, DelegateCertificateV2, DEFAULT_DELEGATE_KEY_BITS};
+    use ghostkey_lib::issuance::{BlindIssuer, IssuanceRequest};
+    use ghostkey_lib::util::{create_keypair, current_unix_time};
+    #[cfg(target_arch = "wasm32")]
+    use wasm_bindgen_test::wasm_bindgen_test;
 
-    #[test]
-    fn test_round_trip() {
-        let mut rng = ChaCha20Rng::from_seed([0u8; 32]);
-        let (master_signing_key, master_verifying_key) = create_keypair(&mut rng).unwrap();
-        let (delegate_certificate, delegate_signing_key) = DelegateCertificateV1::new(&master_signing_key, &"Test Delegate".to_string()).unwrap();
-
-        let delegate_certificate_base64 = delegate_certificate.to_base64().unwrap();
+    fn round_trip(
+        rng: &mut ChaCha20Rng,
+        delegate_certificate_base64: String,
+        delegate_signing_key: &RSASigningKey,
+        trust_store: &TrustStore,
+    ) -> GhostkeyCertificate {
         let seed = [0u8; 32].to_vec();
-        let result = generate_keypair_and_blind_core(delegate_certificate_base64.clone(), seed).unwrap();
+        let result = generate_keypair_and_blind_core(delegate_certificate_base64.clone(), seed, None).unwrap();
 
-        let blinded_signing_key = BlindSignature::from_base64(&result.blinded_signing_key).unwrap();
-        let blinded_signature = delegate_signing_key.blind_sign(&mut rng, blinded_signing_key, &Options::default()).unwrap();
+        let issuer = BlindIssuer::new(
+            DelegateCertificate::from_base64(&delegate_certificate_base64).unwrap(),
+            delegate_signing_key.clone(),
+        ).unwrap();
+        let issuance_request = IssuanceRequest::from_base64(&result.issuance_request).unwrap();
+        let issuance_response = issuer.sign_with_rng(rng, &issuance_request).unwrap();
 
         let generated = generate_ghost_key_certificate_core(
             delegate_certificate_base64,
-            blinded_signature.to_base64().unwrap(),
+            issuance_response.blind_signature().to_base64().unwrap(),
             result.blinding_secret,
             result.ec_verifying_key,
             result.ec_signing_key,
+            None,
         ).unwrap();
 
-        let ghost_key_certificate = GhostkeyCertificateV1::from_armored_string(&generated.armored_ghost_key_cert).unwrap();
-        let verified = ghost_key_certificate.verify(&Some(master_verifying_key));
+        let ghost_key_certificate = GhostkeyCertificate::from_armored_string(&generated.armored_ghost_key_cert).unwrap();
+        let verified = ghost_key_certificate.verify(trust_store);
+
+        // The recovery phrase restores the signing key
+        let restored = restore_ghost_key_core(generated.armored_ghost_key_cert.clone(), result.mnemonic).unwrap();
+        assert_eq!(restored, generated.armored_ghost_key_signing_key);
 
         assert!(verified.is_ok(), "Verification failed: {:?}", verified.unwrap_err());
+        assert_eq!(verified.unwrap().info, "Test Delegate");
+
+        let verified = verify_ghost_key_certificate_core(
+            generated.armored_ghost_key_cert,
+            Some(trust_store.to_armored_string().unwrap()),
+            current_unix_time(),
+        );
+        assert_eq!(verified.unwrap(), "Test Delegate");
+        ghost_key_certificate
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut rng = ChaCha20Rng::from_seed([0u8; 32]);
+        let (master_signing_key, master_verifying_key) = create_keypair(&mut rng).unwrap();
+        let trust_store = TrustStore::from(master_verifying_key);
+
+        let (delegate_certificate, delegate_signing_key) = DelegateCertificateV1::new_with_rng(&mut rng, DEFAULT_DELEGATE_KEY_BITS, &master_signing_key, "Test Delegate", None, None).unwrap();
+        let ghost_key_certificate = round_trip(&mut rng, delegate_certificate.to_base64().unwrap(), &delegate_signing_key, &trust_store);
+        assert_eq!(ghost_key_certificate.version(), 1);
+
+        let (delegate_certificate, delegate_signing_key) = DelegateCertificateV2::new_with_rng(&mut rng, DEFAULT_DELEGATE_KEY_BITS, &master_signing_key, "Test Delegate", None, None).unwrap();
+        let ghost_key_certificate = round_trip(&mut rng, delegate_certificate.to_base64().unwrap(), &delegate_signing_key, &trust_store);
+        assert_eq!(ghost_key_certificate.version(), 2);
+    }
+
+    #[test]
+    fn test_blinding_with_metadata() {
+        let mut rng = ChaCha20Rng::from_seed([0u8; 32]);
+        let (master_signing_key, _) = create_keypair(&mut rng).unwrap();
+        let (delegate_certificate, _) = DelegateCertificateV2::new_with_rng(&mut rng, DEFAULT_DELEGATE_KEY_BITS, &master_signing_key, "Test Delegate", None, None).unwrap();
+        let metadata = GhostkeyMetadata {
+            amount: 20,
+            currency: "USD".to_string(),
+            issued: "2024-10".to_string(),
+            campaign: None,
+        };
+
+        // The request carries the metadata for the API to check before signing
+        let result = generate_keypair_and_blind_core(
+            delegate_certificate.to_base64().unwrap(),
+            [0u8; 32].to_vec(),
+            Some(metadata.to_base64().unwrap()),
+        ).unwrap();
+        let issuance_request = IssuanceRequest::from_base64(&result.issuance_request).unwrap();
+        assert_eq!(issuance_request.version(), 2);
+        assert_eq!(issuance_request.metadata(), Some(&metadata));
+    }
+
+    #[test]
+    fn test_sign_and_verify_message() {
+        let mut rng = ChaCha20Rng::from_seed([0u8; 32]);
+        let (master_signing_key, master_verifying_key) = create_keypair(&mut rng).unwrap();
+        let trust_store = TrustStore::from(master_verifying_key);
+        let (delegate_certificate, delegate_signing_key) = DelegateCertificateV2::new_with_rng(&mut rng, DEFAULT_DELEGATE_KEY_BITS, &master_signing_key, "Test Delegate", None, None).unwrap();
+        let (ghost_key_certificate, ghost_signing_key) = GhostkeyCertificate::new_with_rng(&mut rng, &delegate_certificate.into(), &delegate_signing_key).unwrap();
+        let armored_ghost_key = ghost_key_certificate.to_armored_string().unwrap() + &ghost_signing_key.to_armored_string().unwrap();
+        let armored_trust_store = Some(trust_store.to_armored_string().unwrap());
+        let now = current_unix_time();
+
+        let armored = sign_message_core(armored_ghost_key.clone(), "test purpose".to_string(), Some("text/plain".to_string()), b"Hello".to_vec(), false, 1500).unwrap();
+        let verified = verify_signed_message_core(armored, None, armored_trust_store.clone(), now).unwrap();
+        assert_eq!(verified.purpose.as_deref(), Some("test purpose"));
+        assert_eq!(verified.content_type.as_deref(), Some("text/plain"));
+        assert_eq!(verified.created, Some(1500));
+        assert_eq!(verified.message, b"Hello");
+
+        let detached = sign_message_core(armored_ghost_key, "test purpose".to_string(), None, b"Hello".to_vec(), true, now).unwrap();
+        let error = verify_signed_message_core(detached.clone(), None, armored_trust_store.clone(), now).unwrap_err();
+        assert_eq!(error.code, "GK_INVALID_INPUT");
+        let verified = verify_signed_message_core(detached, Some(b"Hello".to_vec()), armored_trust_store, now).unwrap();
+        assert_eq!(verified.message, b"Hello");
+    }
+
+    #[test]
+    fn test_verify_ghost_key_certificate_validity_period() {
+        let mut rng = ChaCha20Rng::from_seed([0u8; 32]);
+        let (master_signing_key, master_verifying_key) = create_keypair(&mut rng).unwrap();
+        let armored_trust_store = Some(TrustStore::from(master_verifying_key).to_armored_string().unwrap());
+        let (delegate_certificate, delegate_signing_key) = DelegateCertificateV2::new_with_rng(&mut rng, DEFAULT_DELEGATE_KEY_BITS, &master_signing_key, "Test Delegate", Some(1000), Some(2000)).unwrap();
+        let (ghost_key_certificate, _) = GhostkeyCertificate::new_with_rng(&mut rng, &delegate_certificate.into(), &delegate_signing_key).unwrap();
+        let armored = ghost_key_certificate.to_armored_string().unwrap();
+
+        let verified = verify_ghost_key_certificate_core(armored.clone(), armored_trust_store.clone(), 1500);
         assert_eq!(verified.unwrap(), "Test Delegate");
+        let error = verify_ghost_key_certificate_core(armored, armored_trust_store, 2001).unwrap_err();
+        assert_eq!(error.code, "GK_VALIDITY_PERIOD");
+    }
+
+    /// Runs on wasm32, where reading the time through `SystemTime` would panic.
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    fn test_wasm_verify_ghost_key_certificate() {
+        let mut rng = ChaCha20Rng::from_seed([0u8; 32]);
+        let (master_signing_key, master_verifying_key) = create_keypair(&mut rng).unwrap();
+        let armored_trust_store = Some(TrustStore::from(master_verifying_key).to_armored_string().unwrap());
+        let (delegate_certificate, delegate_signing_key) = DelegateCertificateV2::new_with_rng(&mut rng, DEFAULT_DELEGATE_KEY_BITS, &master_signing_key, "Test Delegate", None, None).unwrap();
+        let (ghost_key_certificate, _) = GhostkeyCertificate::new_with_rng(&mut rng, &delegate_certificate.into(), &delegate_signing_key).unwrap();
+        let armored = ghost_key_certificate.to_armored_string().unwrap();
+
+        let verified = wasm_verify_ghost_key_certificate(armored.clone(), armored_trust_store.clone(), None).unwrap();
+        assert_eq!(verified.as_string().as_deref(), Some("Test Delegate"));
+        let verified = wasm_verify_ghost_key_certificate(armored, armored_trust_store, Some(1500.0)).unwrap();
+        assert_eq!(verified.as_string().as_deref(), Some("Test Delegate"));
+    }
+
+    /// Runs on wasm32, where reading the time through `SystemTime` would panic.
+    #[cfg(target_arch = "wasm32")]
+    #[wasm_bindgen_test]
+    fn test_wasm_sign_and_verify_message() {
+        let mut rng = ChaCha20Rng::from_seed([0u8; 32]);
+        let (master_signing_key, master_verifying_key) = create_keypair(&mut rng).unwrap();
+        let armored_trust_store = Some(TrustStore::from(master_verifying_key).to_armored_string().unwrap());
+        let (delegate_certificate, delegate_signing_key) = DelegateCertificateV2::new_with_rng(&mut rng, DEFAULT_DELEGATE_KEY_BITS, &master_signing_key, "Test Delegate", None, None).unwrap();
+        let (ghost_key_certificate, ghost_signing_key) = GhostkeyCertificate::new_with_rng(&mut rng, &delegate_certificate.into(), &delegate_signing_key).unwrap();
+        let armored_ghost_key = ghost_key_certificate.to_armored_string().unwrap() + &ghost_signing_key.to_armored_string().unwrap();
+
+        let armored = wasm_sign_message(armored_ghost_key.clone(), "test purpose".to_string(), None, b"Hello".to_vec(), false, None).unwrap();
+        let verified = wasm_verify_signed_message(armored.as_string().unwrap(), None, armored_trust_store.clone(), None).unwrap();
+        let message = Reflect::get(&verified, &JsValue::from_str("message")).unwrap();
+        assert_eq!(js_sys::Uint8Array::new(&message).to_vec(), b"Hello");
+
+        let armored = wasm_sign_message(armored_ghost_key, "test purpose".to_string(), None, b"Hello".to_vec(), false, Some(1500.0)).unwrap();
+        let verified = wasm_verify_signed_message(armored.as_string().unwrap(), None, armored_trust_store, Some(1600.0)).unwrap();
+        let created = Reflect::get(&verified, &JsValue::from_str("created")).unwrap();
+        assert_eq!(created.as_f64(), Some(1500.0));
+    }
+
+    #[test]
+    fn test_error_codes() {
+        let error = generate_keypair_and_blind_core("AAAA".to_string(), vec![0u8; 16], None).unwrap_err();
+        assert_eq!(error.code, "GK_INVALID_INPUT");
+
+        let error = generate_keypair_and_blind_core("not base64!".to_string(), vec![0u8; 32], None).unwrap_err();
+        assert_eq!(error.code, "GK_BASE64_INVALID");
+
+        let error = verify_ghost_key_certificate_core("garbage".to_string(), None, 0).unwrap_err();
+        assert_eq!(error.code, "GK_DECODE_FAILED");
+
+        let error = restore_ghost_key_core("garbage".to_string(), "abandon".to_string()).unwrap_err();
+        assert_eq!(error.code, "GK_DECODE_FAILED");
     }
 }
 
@@ -213,19 +213,28 @@ async fn wait_for_element(client: &Client, locator: Locator<'_>, timeout: Durati
 }
 
 fn analyze_validation_error(stderr: &str, stdout: &str) -> String {
-    if stderr.contains("Signature verification failed") {
-        "The ghostkey certificate signature is invalid. This could be due to tampering, use of an incorrect master key, or a mismatch between the certificate data and the signature."
-    } else if stderr.contains("Invalid certificate format") {
-        "The ghostkey certificate has an invalid format. It may be corrupted or not properly encoded."
-    } else if stderr.contains("Delegate certificate validation failed") {
-        "The delegate certificate within the ghostkey certificate is invalid. This could indicate an issue with the delegate key generation or signing process."
-    } else if stderr.contains("Invalid ghostkey verifying key") {
-        "The ghostkey verifying key in the certificate is invalid. This could be due to incorrect key generation or corruption of the certificate."
-    } else if stdout.contains("amount mismatch") {
-        "The amount in the ghostkey certificate does not match the expected value. This could indicate tampering or an error in the certificate generation process."
-    } else {
-        "An unknown error occurred during ghostkey validation. Please check the full error message for more details."
-    }.to_string()
+    let explanation = match error_code(stderr) {
+        Some("GK_SIG_DELEGATE_INVALID") => "The delegate certificate is not signed by a trusted master key. This usually means the wrong master verifying key was used, or the delegate certificate was altered.",
+        Some("GK_SIG_GHOST_KEY_INVALID") => "The ghost key signature does not verify against the delegate key. This could be due to tampering with the ghost verifying key, or a mismatch between the certificate data and the signature.",
+        Some("GK_BASE64_INVALID") | Some("GK_CBOR_DECODE_FAILED") | Some("GK_DECODE_FAILED") => "The ghostkey certificate has an invalid format. It may be corrupted or not properly encoded.",
+        Some("GK_ARMOR_INVALID") | Some("GK_ARMOR_CHECKSUM_MISMATCH") | Some("GK_ARMOR_TRUNCATED") => "The armored ghostkey certificate is damaged. It may have been cut off or altered while copying.",
+        Some("GK_VALIDITY_PERIOD") => "The delegate certificate is not valid at the current time.",
+        Some("GK_DELEGATE_REVOKED") => "The delegate that issued the ghost key has been revoked.",
+        Some(_) => "The ghostkey certificate could not be verified. Please check the full error message for more details.",
+        None if stdout.contains("amount mismatch") => "The amount in the ghostkey certificate does not match the expected value. This could indicate tampering or an error in the certificate generation process.",
+        None => "An unknown error occurred during ghostkey validation. Please check the full error message for more details.",
+    };
+    match error_code(stderr) {
+        Some(code) => format!("{} ({})", explanation, code),
+        None => explanation.to_string(),
+    }
+}
+
+/// Extracts the `[GK_...]` error code printed by the ghostkey CLI.
+fn error_code(stderr: &str) -> Option<&str> {
+    let start = stderr.find("[GK_")? + 1;
+    let length = stderr[start..].find(']')?;
+    Some(&stderr[start..start + length])
 }
 
 async fn capture_screenshot(client: &Client, dir: &Path, filename: &str) -> Result<()> {